@@ -9,19 +9,26 @@ use std::fmt::Debug;
 use std::time::Instant;
 
 use criterion::{criterion_group, criterion_main, Criterion};
+use rand::{rngs::StdRng, SeedableRng};
 
 use smtree::pad_secret::ALL_ZEROS_SECRET;
 use smtree::{
     index::TreeIndex,
     node_template::{HashNodeSmt, SumNodeSmt},
-    traits::{Mergeable, Paddable, ProofExtractable, Rand, Serializable, TypeName},
+    proof::MerkleProof,
+    traits::{
+        InclusionProvable, Mergeable, Paddable, ProofExtractable, Rand, Serializable, TypeName,
+    },
     tree::SparseMerkleTree,
 };
 
-type SMT<P> = SparseMerkleTree<P>;
+type Smt<P> = SparseMerkleTree<P>;
 
 type List<P> = Vec<(TreeIndex, P)>;
 
+// The seed of the random leaves, so that all runs measure the same trees.
+const SEED: u64 = 42;
+
 pub fn bench_build<
     P: 'static + Mergeable + Paddable + ProofExtractable + Rand + TypeName + Clone + Default + Eq,
 >(
@@ -43,12 +50,13 @@ pub fn bench_build<
                 let time = Instant::now();
                 let mut list: List<P> = Vec::new();
                 let mut set: HashSet<TreeIndex> = HashSet::new();
+                let mut rng = StdRng::seed_from_u64(SEED);
                 let mut sum = P::default();
                 for _i in 0..LEAF_NUM {
-                    sum.randomize();
+                    sum.randomize_with(&mut rng);
                     loop {
                         let mut idx = TreeIndex::zero(TREE_HEIGHT);
-                        idx.randomize();
+                        idx.randomize_with(&mut rng);
                         if !set.contains(&idx) {
                             list.push((idx, sum.clone()));
                             set.insert(idx);
@@ -59,7 +67,7 @@ pub fn bench_build<
                 println!("Finish in {:?} ms", time.elapsed().as_millis());
                 println!("Start!");
                 let time = Instant::now();
-                let mut tree = SMT::new(TREE_HEIGHT);
+                let mut tree = Smt::new(TREE_HEIGHT);
                 tree.build(&list, &ALL_ZEROS_SECRET);
                 println!("Finish in {:?} ms", time.elapsed().as_millis());
             })
@@ -88,12 +96,13 @@ pub fn bench_update<
                 let time = Instant::now();
                 let mut list: List<P> = Vec::new();
                 let mut set: HashSet<TreeIndex> = HashSet::new();
+                let mut rng = StdRng::seed_from_u64(SEED);
                 let mut sum = P::default();
                 for _i in 0..LEAF_NUM {
-                    sum.randomize();
+                    sum.randomize_with(&mut rng);
                     loop {
                         let mut idx = TreeIndex::zero(TREE_HEIGHT);
-                        idx.randomize();
+                        idx.randomize_with(&mut rng);
                         if !set.contains(&idx) {
                             list.push((idx, sum.clone()));
                             set.insert(idx);
@@ -104,7 +113,7 @@ pub fn bench_update<
                 println!("Finish in {:?} ms", time.elapsed().as_millis());
                 println!("Start!");
                 let time = Instant::now();
-                let mut tree = SMT::new(TREE_HEIGHT);
+                let mut tree = Smt::new(TREE_HEIGHT);
                 for item in list.iter() {
                     tree.update(&item.0, item.1.clone(), &ALL_ZEROS_SECRET);
                 }
@@ -114,6 +123,52 @@ pub fn bench_update<
     );
 }
 
+pub fn bench_update_and_prove<
+    P: 'static + Mergeable + Paddable + ProofExtractable + Rand + TypeName + Clone + Default,
+>(
+    c: &mut Criterion,
+) where
+    <P as ProofExtractable>::ProofNode: Debug + Clone + Default + Eq + Mergeable + Serializable,
+{
+    const LEAF_NUM: u64 = 100_000;
+    const TREE_HEIGHT: usize = 32;
+    let name = P::get_name();
+    let mut list: List<P> = Vec::new();
+    let mut set: HashSet<TreeIndex> = HashSet::new();
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let mut sum = P::default();
+    for _i in 0..LEAF_NUM {
+        sum.randomize_with(&mut rng);
+        loop {
+            let mut idx = TreeIndex::zero(TREE_HEIGHT);
+            idx.randomize_with(&mut rng);
+            if set.insert(idx) {
+                list.push((idx, sum.clone()));
+                break;
+            }
+        }
+    }
+    list.sort_by_key(|a| a.0);
+    let mut tree = Smt::new(TREE_HEIGHT);
+    tree.build(&list, &ALL_ZEROS_SECRET);
+
+    // Every update is followed by a proof, which walks the top levels in the dense index.
+    let mut items = list.iter().cycle();
+    c.bench_function(
+        &format!(
+            "Update a leaf of SMT({}) with {} leaves of {} and prove it",
+            TREE_HEIGHT, LEAF_NUM, name
+        ),
+        |b| {
+            b.iter(|| {
+                let (idx, value) = items.next().unwrap();
+                tree.update(idx, value.clone(), &ALL_ZEROS_SECRET);
+                MerkleProof::<P>::generate_inclusion_proof(&tree, &[*idx]).unwrap()
+            })
+        },
+    );
+}
+
 criterion_group! {
     name = benches;
     config = Criterion::default().sample_size(10);
@@ -122,6 +177,7 @@ criterion_group! {
               bench_build<HashNodeSmt::<blake3::Hasher>>,
               bench_build<HashNodeSmt<blake2::Blake2b>>,
               bench_build<HashNodeSmt<sha2::Sha256>>,
-              bench_build<HashNodeSmt<sha3::Sha3_256>>
+              bench_build<HashNodeSmt<sha3::Sha3_256>>,
+              bench_update_and_prove<HashNodeSmt::<blake3::Hasher>>
 }
 criterion_main!(benches);
@@ -0,0 +1,56 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! An HTTP endpoint serving the root and proofs of a tree of random leaves.
+//!
+//! Run with ```cargo run --example proof_server --features server```, then POST an encoded
+//! [ProofRequest](../smtree/server/enum.ProofRequest.html) to ```http://127.0.0.1:3000/proof```, e.g.,
+//! ```curl --data-binary @request.bin http://127.0.0.1:3000/proof```.
+
+use std::sync::Arc;
+
+use axum::{body::Bytes, extract::State, http::StatusCode, routing::post, Router};
+
+use smtree::{
+    error::ProviderError,
+    index::TreeIndex,
+    node_template::HashNodeSmt,
+    pad_secret::Secret,
+    server::{ProofProvider, TreeProofProvider},
+    tree::SparseMerkleTree,
+    utils::generate_sorted_index_value_pairs,
+};
+
+type Node = HashNodeSmt<blake3::Hasher>;
+
+const TREE_HEIGHT: usize = 32;
+const LEAF_NUM: usize = 1000;
+const ADDRESS: &str = "127.0.0.1:3000";
+
+async fn handle_proof(
+    State(provider): State<Arc<TreeProofProvider<Node>>>,
+    body: Bytes,
+) -> Result<Vec<u8>, (StatusCode, String)> {
+    provider.handle_bytes(&body).map_err(|e| match e {
+        ProviderError::BadRequest { .. } => (StatusCode::BAD_REQUEST, e.to_string()),
+        ProviderError::NotFound => (StatusCode::NOT_FOUND, e.to_string()),
+    })
+}
+
+#[tokio::main]
+async fn main() {
+    let secret = Secret::generate(&mut rand::rngs::OsRng);
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = SparseMerkleTree::new(TREE_HEIGHT);
+    tree.build(&list, &secret);
+    let provider = Arc::new(TreeProofProvider::new(tree, &secret));
+
+    let app = Router::new()
+        .route("/proof", post(handle_proof))
+        .with_state(provider);
+    let listener = tokio::net::TcpListener::bind(ADDRESS).await.unwrap();
+    println!("Serving proofs on http://{}/proof", ADDRESS);
+    axum::serve(listener, app).await.unwrap();
+}
@@ -0,0 +1,156 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! The node list of a sparse Merkle tree, stored in fixed-size pages shared between the clones of the list.
+//!
+//! Cloning the list clones the references to the pages only.
+//! Writing a node of a page shared with a clone copies that page first (copy-on-write),
+//! so a clone taken before an update keeps its nodes while the update copies only the pages it touches.
+
+use std::fmt::Debug;
+use std::ops::{Index, IndexMut};
+use std::sync::Arc;
+
+use crate::tree::TreeNode;
+
+/// The number of bits of a node reference addressing the node in its page.
+const PAGE_BITS: usize = 8;
+/// The number of nodes in a page.
+const PAGE_SIZE: usize = 1 << PAGE_BITS;
+
+/// A list of tree nodes stored in shared pages of ```PAGE_SIZE``` nodes,
+/// where all pages but the last one in use are full.
+///
+/// Cleared pages not shared with any clone are kept for reuse, as are the pages added by [reserve](#method.reserve).
+#[derive(Clone)]
+pub(crate) struct NodeArena<P> {
+    pub(crate) pages: Vec<Arc<Vec<TreeNode<P>>>>,
+    len: usize,
+}
+
+impl<P> Default for NodeArena<P> {
+    fn default() -> NodeArena<P> {
+        NodeArena {
+            pages: Vec::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<P: Debug> Debug for NodeArena<P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<P> NodeArena<P> {
+    /// Returns the number of nodes.
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether there is no node.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of nodes the allocated pages can hold.
+    pub(crate) fn capacity(&self) -> usize {
+        self.pages.len() * PAGE_SIZE
+    }
+
+    /// Returns an iterator over the nodes in the order of references.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &TreeNode<P>> + '_ {
+        self.pages.iter().flat_map(|page| page.iter())
+    }
+
+    /// Allocate pages for at least ```additional``` more nodes.
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        let pages_num = (self.len + additional).div_ceil(PAGE_SIZE);
+        while self.pages.len() < pages_num {
+            self.pages.push(Arc::new(Vec::with_capacity(PAGE_SIZE)));
+        }
+    }
+
+    /// Remove all nodes, keeping the pages not shared with any clone.
+    pub(crate) fn clear(&mut self) {
+        self.pages.retain_mut(|page| match Arc::get_mut(page) {
+            Some(nodes) => {
+                nodes.clear();
+                true
+            }
+            None => false,
+        });
+        self.len = 0;
+    }
+}
+
+impl<P: Clone> NodeArena<P> {
+    /// Returns the mutable page at the input position, copying it first if it's shared with a clone.
+    fn page_mut(&mut self, pos: usize) -> &mut Vec<TreeNode<P>> {
+        let page = &mut self.pages[pos];
+        if Arc::get_mut(page).is_none() {
+            let mut nodes = Vec::with_capacity(PAGE_SIZE);
+            nodes.extend_from_slice(page);
+            *page = Arc::new(nodes);
+        }
+        Arc::get_mut(page).unwrap()
+    }
+
+    /// Append a node at the end of the list.
+    pub(crate) fn push(&mut self, node: TreeNode<P>) {
+        let pos = self.len >> PAGE_BITS;
+        if pos == self.pages.len() {
+            self.pages.push(Arc::new(Vec::with_capacity(PAGE_SIZE)));
+        }
+        self.page_mut(pos).push(node);
+        self.len += 1;
+    }
+
+    /// Remove the last node and return it, or ```None``` if the list is empty.
+    pub(crate) fn pop(&mut self) -> Option<TreeNode<P>> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        self.page_mut(self.len >> PAGE_BITS).pop()
+    }
+
+    /// Consume the list, returning an iterator over the nodes in the order of references,
+    /// which copies the pages shared with a clone.
+    pub(crate) fn into_nodes(self) -> impl Iterator<Item = TreeNode<P>> {
+        self.pages.into_iter().flat_map(Arc::unwrap_or_clone)
+    }
+}
+
+impl<P: Clone> From<Vec<TreeNode<P>>> for NodeArena<P> {
+    fn from(nodes: Vec<TreeNode<P>>) -> NodeArena<P> {
+        let mut arena = NodeArena::default();
+        arena.extend(nodes);
+        arena
+    }
+}
+
+impl<P: Clone> Extend<TreeNode<P>> for NodeArena<P> {
+    fn extend<T: IntoIterator<Item = TreeNode<P>>>(&mut self, iter: T) {
+        for node in iter {
+            self.push(node);
+        }
+    }
+}
+
+impl<P> Index<usize> for NodeArena<P> {
+    type Output = TreeNode<P>;
+
+    fn index(&self, link: usize) -> &TreeNode<P> {
+        &self.pages[link >> PAGE_BITS][link & (PAGE_SIZE - 1)]
+    }
+}
+
+impl<P: Clone> IndexMut<usize> for NodeArena<P> {
+    fn index_mut(&mut self, link: usize) -> &mut TreeNode<P> {
+        &mut self.page_mut(link >> PAGE_BITS)[link & (PAGE_SIZE - 1)]
+    }
+}
@@ -0,0 +1,258 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! A node template of arkworks field elements, enabled by the ```ark``` feature.
+//!
+//! The nodes are elements of an ```ark-ff``` prime field, merged with an ```ark-crypto-primitives```
+//! two-to-one CRH under the parameters given by an [ArkCrhConfig],
+//! so that the root and the Merkle proofs of the SMT are those of the same hash in an arkworks constraint system.
+//! A single-leaf proof converts to an arkworks Merkle ```Path```
+//! with [to_ark_path](struct.ArkNodeSmt.html#method.to_ark_path).
+//!
+//! Padding nodes are field elements derived from the secret and the index,
+//! and the padding proof reveals their pre-image as for [HashNodeSmt].
+
+use std::marker::PhantomData;
+
+use ark_crypto_primitives::{
+    crh::{CRHScheme, TwoToOneCRHScheme},
+    merkle_tree::{Config, Path},
+};
+use ark_ff::{PrimeField, Zero};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use digest::Digest;
+use rand::Rng;
+use sha2::{Sha256, Sha512};
+
+use crate::pad_secret::Secret;
+use crate::{
+    error::DecodingError,
+    index::TreeIndex,
+    node_template::{HashNodeSmt, PADDING_STRING},
+    proof::MerkleProof,
+    traits::{
+        Mergeable, Paddable, PaddingProvable, ProofExtractable, Rand, Serializable, TypeName,
+    },
+};
+
+/// The field and the two-to-one CRH of an [ArkNodeSmt], with the parameters of the hash.
+pub trait ArkCrhConfig {
+    /// The prime field of the nodes.
+    type Field: PrimeField;
+    /// The parameters of the hash.
+    type Parameters: 'static;
+    /// The hash merging two child nodes, e.g., ```ark_crypto_primitives::crh::poseidon::TwoToOneCRH```.
+    type TwoToOneHash: TwoToOneCRHScheme<
+        Input = Self::Field,
+        Output = Self::Field,
+        Parameters = Self::Parameters,
+    >;
+
+    /// Returns the parameters of the hash, the same as those of the constraint system,
+    /// e.g., kept in a ```std::sync::OnceLock```.
+    fn get_parameters() -> &'static Self::Parameters;
+}
+
+// The pre-image of the padding node at the input index.
+fn get_padding_pre_image(idx: &TreeIndex, secret: &Secret) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(secret.as_bytes());
+    hasher.update(TreeIndex::serialize(&[*idx]));
+    hasher.finalize().to_vec()
+}
+
+// The padding node of the input pre-image, reduced from a wide hash to avoid a bias in the field.
+fn get_padding_element<F: PrimeField>(pre_image: &[u8]) -> F {
+    let mut hasher = Sha512::new();
+    hasher.update(PADDING_STRING.as_bytes());
+    hasher.update(pre_image);
+    F::from_le_bytes_mod_order(&hasher.finalize())
+}
+
+// ======================================================================================
+
+/// An SMT node carrying a field element, merged with the two-to-one CRH of the config.
+pub struct ArkNodeSmt<C: ArkCrhConfig> {
+    value: C::Field,
+    phantom: PhantomData<C>,
+}
+
+impl<C: ArkCrhConfig> ArkNodeSmt<C> {
+    pub fn new(value: C::Field) -> ArkNodeSmt<C> {
+        ArkNodeSmt {
+            value,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the field element.
+    pub fn get_value(&self) -> &C::Field {
+        &self.value
+    }
+
+    /// Returns the field element, consuming the node.
+    pub fn into_value(self) -> C::Field {
+        self.value
+    }
+
+    /// The constructor of a leaf hashing the input with the input leaf CRH,
+    /// e.g., the ```LeafHash``` of an arkworks Merkle tree config, whose output is the leaf of the SMT.
+    pub fn from_leaf_hash<H: CRHScheme<Output = C::Field>>(
+        parameters: &H::Parameters,
+        input: &H::Input,
+    ) -> Result<ArkNodeSmt<C>, ark_crypto_primitives::Error> {
+        Ok(ArkNodeSmt::new(H::evaluate(parameters, input)?))
+    }
+
+    /// Returns the arkworks Merkle path of a single-leaf proof,
+    /// for an arkworks config whose leaf and inner digests are the field elements of the nodes
+    /// and whose two-to-one hash is that of ```C```.
+    ///
+    /// The path verifies the pre-image of the leaf under the ```LeafHash``` of the arkworks config,
+    /// as in [from_leaf_hash](#method.from_leaf_hash).
+    /// Note that arkworks merges the leaf level with ```evaluate``` and the upper levels with ```compress```,
+    /// which agree for hashes taking field elements as input, e.g., Poseidon.
+    ///
+    /// Return ```None``` if the proof is not of a single leaf with one sibling per level,
+    /// or if the tree height is 0 or exceeds the number of bits of a ```usize```.
+    pub fn to_ark_path<P>(proof: &MerkleProof<ArkNodeSmt<C>>) -> Option<Path<P>>
+    where
+        P: Config<LeafDigest = C::Field, InnerDigest = C::Field>,
+    {
+        let indexes = proof.get_indexes();
+        if indexes.len() != 1 {
+            return None;
+        }
+        let height = indexes[0].get_height();
+        let siblings = proof.get_path_siblings();
+        if height == 0 || height > usize::BITS as usize || siblings.len() != height {
+            return None;
+        }
+        // The leaf index of arkworks has the bit of the top level as the most significant bit.
+        let leaf_index =
+            (0..height).fold(0usize, |acc, i| (acc << 1) | indexes[0].get_bit(i) as usize);
+        // The siblings of the proof are ordered from the top level, as the auth path of arkworks,
+        // which excludes the sibling of the leaf.
+        Some(Path {
+            leaf_sibling_hash: siblings[height - 1].value,
+            auth_path: siblings[..height - 1].iter().map(|x| x.value).collect(),
+            leaf_index,
+        })
+    }
+}
+
+impl<C: ArkCrhConfig> Clone for ArkNodeSmt<C> {
+    fn clone(&self) -> Self {
+        ArkNodeSmt::new(self.value)
+    }
+}
+
+impl<C: ArkCrhConfig> Default for ArkNodeSmt<C> {
+    fn default() -> Self {
+        ArkNodeSmt::new(C::Field::zero())
+    }
+}
+
+impl<C: ArkCrhConfig> std::fmt::Debug for ArkNodeSmt<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ArkNodeSmt").field(&self.value).finish()
+    }
+}
+
+impl<C: ArkCrhConfig> PartialEq for ArkNodeSmt<C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<C: ArkCrhConfig> Eq for ArkNodeSmt<C> {}
+
+impl<C: ArkCrhConfig> Mergeable for ArkNodeSmt<C> {
+    /// Merge the field elements with ```compress``` of the two-to-one CRH.
+    ///
+    /// Panics if the hash fails, e.g., with invalid parameters.
+    fn merge(lch: &ArkNodeSmt<C>, rch: &ArkNodeSmt<C>) -> ArkNodeSmt<C> {
+        let value = C::TwoToOneHash::compress(C::get_parameters(), lch.value, rch.value)
+            .expect("The two-to-one CRH fails to merge the nodes.");
+        ArkNodeSmt::new(value)
+    }
+}
+
+impl<C: ArkCrhConfig> Paddable for ArkNodeSmt<C> {
+    fn padding(idx: &TreeIndex, secret: &Secret) -> ArkNodeSmt<C> {
+        ArkNodeSmt::new(get_padding_element(&get_padding_pre_image(idx, secret)))
+    }
+}
+
+impl<C: ArkCrhConfig> Serializable for ArkNodeSmt<C> {
+    /// Encode the field element in the compressed form of ```ark-serialize```.
+    fn serialize(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.value.compressed_size());
+        self.value
+            .serialize_compressed(&mut bytes)
+            .expect("Serializing a field element to a vector doesn't fail.");
+        bytes
+    }
+
+    /// Decode the input bytes as a field element in the compressed form of ```ark-serialize```.
+    ///
+    /// If the element isn't canonically encoded,
+    /// return [DecodingError::ValueDecodingError](../error/enum.DecodingError.html#variant.ValueDecodingError).
+    fn deserialize_as_a_unit(bytes: &[u8], begin: &mut usize) -> Result<Self, DecodingError> {
+        let len = C::Field::zero().compressed_size();
+        if bytes.len() - *begin < len {
+            return Err(DecodingError::BytesNotEnough);
+        }
+        let value =
+            C::Field::deserialize_compressed(&bytes[*begin..*begin + len]).map_err(|e| {
+                DecodingError::ValueDecodingError {
+                    msg: format!("Invalid field element: {}", e),
+                }
+            })?;
+        *begin += len;
+        Ok(ArkNodeSmt::new(value))
+    }
+}
+
+impl<C: ArkCrhConfig> ProofExtractable for ArkNodeSmt<C> {
+    type ProofNode = ArkNodeSmt<C>;
+    fn get_proof_node(&self) -> Self::ProofNode {
+        self.clone()
+    }
+
+    fn get_proof_node_ref(&self) -> Option<&Self::ProofNode> {
+        Some(self)
+    }
+}
+
+impl<C: ArkCrhConfig> PaddingProvable for ArkNodeSmt<C> {
+    type PaddingProof = HashNodeSmt<Sha256>;
+
+    fn prove_padding_node(&self, idx: &TreeIndex, secret: &Secret) -> HashNodeSmt<Sha256> {
+        HashNodeSmt::new(get_padding_pre_image(idx, secret))
+    }
+
+    fn verify_padding_node(
+        node: &<Self as ProofExtractable>::ProofNode,
+        proof: &Self::PaddingProof,
+        _idx: &TreeIndex,
+    ) -> bool {
+        node.value == get_padding_element(proof.get_hash())
+    }
+}
+
+impl<C: ArkCrhConfig> Rand for ArkNodeSmt<C> {
+    fn randomize_with<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        let mut bytes = [0u8; 64];
+        rng.fill_bytes(&mut bytes);
+        *self = ArkNodeSmt::new(C::Field::from_le_bytes_mod_order(&bytes));
+    }
+}
+
+impl<C: ArkCrhConfig> TypeName for ArkNodeSmt<C> {
+    fn get_name() -> String {
+        format!("Ark ({}-bit field)", C::Field::MODULUS_BIT_SIZE)
+    }
+}
@@ -0,0 +1,166 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Borsh encoding of tree indexes, proofs and node templates, enabled by the ```borsh``` feature.
+//!
+//! The encodings are those of the following Borsh structs,
+//! so that they can be decoded by Borsh implementations in other languages:
+//! * ```TreeIndex { height: u16, path: Vec<u8> }```, with ```ceiling(height/8)``` path bytes.
+//! * ```MerkleProof { indexes: Vec<TreeIndex>, siblings: Vec<ProofNode> }```.
+//! * ```RandomSamplingProof { index: TreeIndex, padding_proofs: Vec<PaddingProof>, merkle_proof: MerkleProof, leaves: Vec<ProofNode> }```.
+//! * ```HashNodeSmt { hash: Vec<u8> }```, and the same for ```HashWiresNodeSmt``` and ```MTreeNodeSmt```.
+//! * ```SumNodeSmt(u64)```.
+
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use digest::Digest;
+
+use crate::{
+    index::TreeIndex,
+    node_template::{HashNodeSmt, HashWiresNodeSmt, MTreeNodeSmt, SumNodeSmt},
+    proof::{MerkleProof, RandomSamplingProof},
+    traits::{Mergeable, Paddable, PaddingProvable, ProofExtractable, Serializable},
+};
+
+fn invalid_data<E: ToString>(e: E) -> Error {
+    Error::new(ErrorKind::InvalidData, e.to_string())
+}
+
+impl BorshSerialize for TreeIndex {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        BorshSerialize::serialize(&(self.get_height() as u16), writer)?;
+        BorshSerialize::serialize(&self.get_path_bytes(), writer)
+    }
+}
+
+/// If the height exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html)
+/// or the number of path bytes doesn't match with the height, return an ```InvalidData``` error.
+impl BorshDeserialize for TreeIndex {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> Result<TreeIndex> {
+        let height = u16::deserialize_reader(reader)?;
+        let path = Vec::<u8>::deserialize_reader(reader)?;
+        TreeIndex::from_path_bytes(height as usize, &path).map_err(invalid_data)
+    }
+}
+
+impl<V: Default + Clone + Mergeable + ProofExtractable> BorshSerialize for MerkleProof<V>
+where
+    <V as ProofExtractable>::ProofNode:
+        Clone + Default + Eq + Mergeable + Serializable + BorshSerialize,
+{
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        BorshSerialize::serialize(self.get_indexes(), writer)?;
+        BorshSerialize::serialize(self.get_path_siblings(), writer)
+    }
+}
+
+impl<V: Default + Clone + Mergeable + ProofExtractable> BorshDeserialize for MerkleProof<V>
+where
+    <V as ProofExtractable>::ProofNode:
+        Clone + Default + Eq + Mergeable + Serializable + BorshDeserialize,
+{
+    fn deserialize_reader<R: Read>(reader: &mut R) -> Result<MerkleProof<V>> {
+        let indexes = Vec::<TreeIndex>::deserialize_reader(reader)?;
+        let mut proof = MerkleProof::new_batch(&indexes);
+        proof.set_siblings(Vec::deserialize_reader(reader)?);
+        Ok(proof)
+    }
+}
+
+impl<V: Clone + Default + Mergeable + Paddable + PaddingProvable + ProofExtractable> BorshSerialize
+    for RandomSamplingProof<V>
+where
+    V::ProofNode: Default + Eq + Clone + Mergeable + Serializable + BorshSerialize,
+    V::PaddingProof: Default + Eq + Clone + Serializable + BorshSerialize,
+{
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        BorshSerialize::serialize(self.get_index(), writer)?;
+        BorshSerialize::serialize(self.get_padding_proofs(), writer)?;
+        BorshSerialize::serialize(self.get_merkle_proof(), writer)?;
+        BorshSerialize::serialize(self.get_leaves(), writer)
+    }
+}
+
+impl<V: Clone + Default + Mergeable + Paddable + PaddingProvable + ProofExtractable>
+    BorshDeserialize for RandomSamplingProof<V>
+where
+    V::ProofNode: Default + Eq + Clone + Mergeable + Serializable + BorshDeserialize,
+    V::PaddingProof: Default + Eq + Clone + Serializable + BorshDeserialize,
+{
+    fn deserialize_reader<R: Read>(reader: &mut R) -> Result<RandomSamplingProof<V>> {
+        let index = TreeIndex::deserialize_reader(reader)?;
+        let padding_proofs = Vec::deserialize_reader(reader)?;
+        let merkle_proof = MerkleProof::deserialize_reader(reader)?;
+        let leaves = Vec::deserialize_reader(reader)?;
+        Ok(RandomSamplingProof::new(
+            index,
+            padding_proofs,
+            merkle_proof,
+            leaves,
+        ))
+    }
+}
+
+/// Decode a hash value, checking that its length is the output size of the digest.
+fn deserialize_hash<D: Digest, R: Read>(reader: &mut R) -> Result<Vec<u8>> {
+    let hash = Vec::<u8>::deserialize_reader(reader)?;
+    if hash.len() != D::output_size() {
+        return Err(invalid_data(format!(
+            "the hash has {} bytes instead of {}",
+            hash.len(),
+            D::output_size()
+        )));
+    }
+    Ok(hash)
+}
+
+impl<D> BorshSerialize for HashNodeSmt<D> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        BorshSerialize::serialize(self.get_hash(), writer)
+    }
+}
+
+impl<D: Digest> BorshDeserialize for HashNodeSmt<D> {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> Result<HashNodeSmt<D>> {
+        Ok(HashNodeSmt::new(deserialize_hash::<D, R>(reader)?))
+    }
+}
+
+impl<D, const VERSION: u8> BorshSerialize for HashWiresNodeSmt<D, VERSION> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        BorshSerialize::serialize(self.get_hash(), writer)
+    }
+}
+
+impl<D: Digest, const VERSION: u8> BorshDeserialize for HashWiresNodeSmt<D, VERSION> {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> Result<HashWiresNodeSmt<D, VERSION>> {
+        Ok(HashWiresNodeSmt::new(deserialize_hash::<D, R>(reader)?))
+    }
+}
+
+impl<D> BorshSerialize for MTreeNodeSmt<D> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        BorshSerialize::serialize(self.get_hash(), writer)
+    }
+}
+
+impl<D: Digest> BorshDeserialize for MTreeNodeSmt<D> {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> Result<MTreeNodeSmt<D>> {
+        Ok(MTreeNodeSmt::new(deserialize_hash::<D, R>(reader)?))
+    }
+}
+
+impl BorshSerialize for SumNodeSmt {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        BorshSerialize::serialize(&self.get_value(), writer)
+    }
+}
+
+impl BorshDeserialize for SumNodeSmt {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> Result<SumNodeSmt> {
+        Ok(SumNodeSmt::new(u64::deserialize_reader(reader)?))
+    }
+}
@@ -0,0 +1,222 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! CBOR encoding of tree indexes and proofs, enabled by the ```cbor``` feature.
+//!
+//! Each object is encoded as a CBOR map with unsigned integer keys,
+//! numbered as the fields in ```proto/smtree.proto```.
+//! The encoding is deterministic: keys are in increasing order, lengths are definite,
+//! and integers take the shortest form.
+//! Decoding only accepts this deterministic form,
+//! so every object has exactly one CBOR encoding.
+//!
+//! Tree node values are carried as byte strings in the encoding of their [Serializable] implementation.
+
+use std::convert::TryFrom;
+
+use ciborium::value::{Integer, Value};
+
+use crate::{
+    error::DecodingError,
+    index::TreeIndex,
+    proof::{MerkleProof, RandomSamplingProof},
+    traits::{Mergeable, Paddable, PaddingProvable, ProofExtractable, Serializable},
+};
+
+fn format_error(msg: &str) -> DecodingError {
+    DecodingError::FormatError {
+        msg: msg.to_owned(),
+    }
+}
+
+/// Encode a CBOR value as bytes.
+fn encode(value: &Value) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    // Writing into a vector never fails.
+    ciborium::ser::into_writer(value, &mut bytes).unwrap();
+    bytes
+}
+
+/// Decode the input bytes as a CBOR value, using up all bytes.
+fn decode(bytes: &[u8]) -> Result<Value, DecodingError> {
+    let mut reader = bytes;
+    let value: Value = ciborium::de::from_reader(&mut reader)
+        .map_err(|e| DecodingError::FormatError { msg: e.to_string() })?;
+    if !reader.is_empty() {
+        return Err(DecodingError::TooManyEncodedBytes);
+    }
+    // Reject non-deterministic encodings, e.g., with indefinite lengths or non-shortest integers.
+    if encode(&value) != bytes {
+        return Err(format_error("non-deterministic CBOR encoding"));
+    }
+    Ok(value)
+}
+
+fn uint_value(num: usize) -> Value {
+    Value::Integer(Integer::from(num as u64))
+}
+
+fn bytes_array_value<T: Serializable>(list: &[T]) -> Value {
+    Value::Array(list.iter().map(|x| Value::Bytes(x.serialize())).collect())
+}
+
+/// Returns the values of a map with exactly the input keys in increasing order.
+fn get_fields<'a>(value: &'a Value, keys: &[u64]) -> Result<Vec<&'a Value>, DecodingError> {
+    let map = value
+        .as_map()
+        .ok_or_else(|| format_error("expecting a map"))?;
+    if map.len() != keys.len() {
+        return Err(format_error("unexpected number of map entries"));
+    }
+    map.iter()
+        .zip(keys)
+        .map(|((key, value), expected)| match key.as_integer() {
+            Some(x) if x == Integer::from(*expected) => Ok(value),
+            _ => Err(format_error("unexpected map key")),
+        })
+        .collect()
+}
+
+fn get_uint(value: &Value) -> Result<usize, DecodingError> {
+    value
+        .as_integer()
+        .and_then(|x| u64::try_from(x).ok())
+        .map(|x| x as usize)
+        .ok_or_else(|| format_error("expecting an unsigned integer"))
+}
+
+fn get_bytes(value: &Value) -> Result<&[u8], DecodingError> {
+    value
+        .as_bytes()
+        .map(|x| x.as_slice())
+        .ok_or_else(|| format_error("expecting a byte string"))
+}
+
+fn get_bytes_array(value: &Value) -> Result<Vec<&[u8]>, DecodingError> {
+    value
+        .as_array()
+        .ok_or_else(|| format_error("expecting an array"))?
+        .iter()
+        .map(get_bytes)
+        .collect()
+}
+
+fn values_from_bytes_array<T: Serializable>(value: &Value) -> Result<Vec<T>, DecodingError> {
+    get_bytes_array(value)?
+        .into_iter()
+        .map(|bytes| T::deserialize(bytes))
+        .collect()
+}
+
+fn index_value(index: &TreeIndex) -> Value {
+    Value::Map(vec![
+        (uint_value(1), uint_value(index.get_height())),
+        (uint_value(2), Value::Bytes(index.get_path_bytes())),
+    ])
+}
+
+fn index_from_value(value: &Value) -> Result<TreeIndex, DecodingError> {
+    let fields = get_fields(value, &[1, 2])?;
+    TreeIndex::from_path_bytes(get_uint(fields[0])?, get_bytes(fields[1])?)
+}
+
+fn merkle_proof_value<V: Default + Clone + Mergeable + ProofExtractable>(
+    proof: &MerkleProof<V>,
+) -> Value
+where
+    <V as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    let indexes = proof.get_indexes();
+    Value::Map(vec![
+        (
+            uint_value(1),
+            uint_value(indexes.first().map_or(0, |x| x.get_height())),
+        ),
+        (
+            uint_value(2),
+            Value::Array(
+                indexes
+                    .iter()
+                    .map(|x| Value::Bytes(x.get_path_bytes()))
+                    .collect(),
+            ),
+        ),
+        (uint_value(3), bytes_array_value(proof.get_path_siblings())),
+    ])
+}
+
+fn merkle_proof_from_value<V: Default + Clone + Mergeable + ProofExtractable>(
+    value: &Value,
+) -> Result<MerkleProof<V>, DecodingError>
+where
+    <V as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    let fields = get_fields(value, &[1, 2, 3])?;
+    let height = get_uint(fields[0])?;
+    let indexes = get_bytes_array(fields[1])?
+        .into_iter()
+        .map(|path| TreeIndex::from_path_bytes(height, path))
+        .collect::<Result<Vec<TreeIndex>, DecodingError>>()?;
+    let mut proof = MerkleProof::new_batch(&indexes);
+    proof.set_siblings(values_from_bytes_array(fields[2])?);
+    Ok(proof)
+}
+
+impl TreeIndex {
+    /// Encode the tree index in deterministic CBOR as ```{1: height, 2: path}```.
+    pub fn to_cbor(&self) -> Vec<u8> {
+        encode(&index_value(self))
+    }
+
+    /// Decode a tree index from its deterministic CBOR encoding.
+    pub fn from_cbor(bytes: &[u8]) -> Result<TreeIndex, DecodingError> {
+        index_from_value(&decode(bytes)?)
+    }
+}
+
+impl<V: Default + Clone + Mergeable + ProofExtractable> MerkleProof<V>
+where
+    <V as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// Encode the proof in deterministic CBOR as ```{1: height, 2: [path], 3: [sibling]}```.
+    pub fn to_cbor(&self) -> Vec<u8> {
+        encode(&merkle_proof_value(self))
+    }
+
+    /// Decode a proof from its deterministic CBOR encoding.
+    pub fn from_cbor(bytes: &[u8]) -> Result<MerkleProof<V>, DecodingError> {
+        merkle_proof_from_value(&decode(bytes)?)
+    }
+}
+
+impl<V: Clone + Default + Mergeable + Paddable + PaddingProvable + ProofExtractable>
+    RandomSamplingProof<V>
+where
+    V::ProofNode: Default + Eq + Clone + Mergeable + Serializable,
+    V::PaddingProof: Default + Eq + Clone + Serializable,
+{
+    /// Encode the proof in deterministic CBOR as
+    /// ```{1: index, 2: [padding_proof], 3: merkle_proof, 4: [leaf]}```.
+    pub fn to_cbor(&self) -> Vec<u8> {
+        encode(&Value::Map(vec![
+            (uint_value(1), index_value(self.get_index())),
+            (uint_value(2), bytes_array_value(self.get_padding_proofs())),
+            (uint_value(3), merkle_proof_value(self.get_merkle_proof())),
+            (uint_value(4), bytes_array_value(self.get_leaves())),
+        ]))
+    }
+
+    /// Decode a proof from its deterministic CBOR encoding.
+    pub fn from_cbor(bytes: &[u8]) -> Result<RandomSamplingProof<V>, DecodingError> {
+        let value = decode(bytes)?;
+        let fields = get_fields(&value, &[1, 2, 3, 4])?;
+        Ok(RandomSamplingProof::new(
+            index_from_value(fields[0])?,
+            values_from_bytes_array(fields[1])?,
+            merkle_proof_from_value(fields[2])?,
+            values_from_bytes_array(fields[3])?,
+        ))
+    }
+}
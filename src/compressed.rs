@@ -0,0 +1,221 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! This module provides a path-compressed representation of the paddable sparse Merkle tree,
+//! in the spirit of the Jellyfish Merkle tree.
+//!
+//! In a sparse tree, most nodes lie on chains where one child is a padding node.
+//! The compressed tree collapses each such chain into an extension,
+//! keeping only the leaves, the branching nodes with two non-empty subtrees,
+//! and the tops of the extensions below the branching nodes.
+//! A tree of height 256 with ```n``` leaves thus stores ```O(n)``` nodes instead of ```O(n * height)```.
+//!
+//! The skipped levels are recomputed by hashing the implied padding nodes,
+//! so the root and the Merkle proofs are the same as those of the uncompressed
+//! [SparseMerkleTree](../tree/struct.SparseMerkleTree.html) built from the same leaves and secret.
+//! As padding nodes are not stored, the padding secret is an input of proof generation.
+
+use std::collections::BTreeMap;
+
+use crate::pad_secret::{Secret, ALL_ZEROS_SECRET};
+use crate::{
+    error::{DecodingError, TreeError},
+    index::{TreeIndex, MAX_HEIGHT},
+    proof::MerkleProof,
+    traits::{Mergeable, Paddable, PaddingContext, PaddingDomain, ProofExtractable, Serializable},
+    tree::SparseMerkleTree,
+};
+
+/// A path-compressed paddable sparse Merkle tree.
+#[derive(Debug, Clone)]
+pub struct CompressedSmt<P> {
+    height: usize,
+    root: P,
+    leaves: BTreeMap<TreeIndex, P>,
+    // The values of the branching nodes and of the tops of the extensions.
+    nodes: BTreeMap<TreeIndex, P>,
+    // The epoch and the tag of the padding nodes.
+    padding_domain: PaddingDomain,
+}
+
+impl<P: Clone + Default + Mergeable + Paddable + ProofExtractable> CompressedSmt<P>
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// The constructor.
+    ///
+    /// Panics if the height exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html).
+    pub fn new(height: usize) -> CompressedSmt<P> {
+        if height > MAX_HEIGHT {
+            panic!("{}", DecodingError::ExceedMaxHeight);
+        }
+        CompressedSmt {
+            height,
+            root: P::padding_with_context(
+                &TreeIndex::zero(0),
+                &PaddingContext::new(height, &ALL_ZEROS_SECRET),
+            ),
+            leaves: BTreeMap::new(),
+            nodes: BTreeMap::new(),
+            padding_domain: PaddingDomain::default(),
+        }
+    }
+
+    /// Returns the height of the tree.
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the number of leaves in the tree.
+    pub fn get_leaves_num(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// Returns the number of stored nodes, i.e., the leaves, the branching nodes and the tops of the extensions.
+    pub fn get_nodes_num(&self) -> usize {
+        self.leaves.len() + self.nodes.len()
+    }
+
+    /// Returns the raw data of the root.
+    pub fn get_root_raw(&self) -> &P {
+        &self.root
+    }
+
+    /// Returns the data of the root that is visible in the Merkle proof.
+    pub fn get_root(&self) -> <P as ProofExtractable>::ProofNode {
+        self.root.get_proof_node()
+    }
+
+    /// Returns the value of the leaf at the input index, or None if it is not a leaf.
+    pub fn get_leaf_by_index(&self, idx: &TreeIndex) -> Option<&P> {
+        self.leaves.get(idx)
+    }
+
+    /// Returns the epoch and the application tag of the padding nodes of the tree.
+    pub fn get_padding_domain(&self) -> &PaddingDomain {
+        &self.padding_domain
+    }
+
+    /// Set the epoch and the application tag of the padding nodes,
+    /// the same as those of the uncompressed tree, before building the tree.
+    pub fn set_padding_domain(&mut self, domain: PaddingDomain) {
+        self.padding_domain = domain;
+    }
+
+    // Returns the padding node at the input index in the context of the tree.
+    fn get_padding(&self, idx: &TreeIndex, secret: &Secret) -> P {
+        P::padding_with_context(idx, &self.padding_domain.get_context(self.height, secret))
+    }
+
+    /// Build the tree from the input list of sorted index-value pairs, index being the sorting key.
+    ///
+    /// Panics if the input list is not valid.
+    pub fn build(&mut self, list: &[(TreeIndex, P)], secret: &Secret) {
+        if let Some(x) = SparseMerkleTree::new(self.height).check_index_list_validity(list) {
+            panic!("{}", x);
+        }
+        self.leaves = list.iter().cloned().collect();
+        self.nodes = BTreeMap::new();
+        self.root = self
+            .compute_subtree(&TreeIndex::zero(0), secret)
+            .unwrap_or_else(|| self.get_padding(&TreeIndex::zero(0), secret));
+        log_debug!(
+            "Built compressed SMT of height {} with {} leaves and {} nodes",
+            self.height,
+            self.leaves.len(),
+            self.get_nodes_num()
+        );
+    }
+
+    /// Update the leaf at the input index with the input value, inserting it if it doesn't exist.
+    ///
+    /// Panics if the height of the input index doesn't match with that of the tree.
+    pub fn update(&mut self, key: &TreeIndex, value: P, secret: &Secret) {
+        if key.get_height() != self.height {
+            panic!("{}", TreeError::HeightNotMatch);
+        }
+        self.leaves.insert(*key, value);
+        // The stored nodes on the path are out of date.
+        for i in 0..=self.height {
+            self.nodes.remove(&key.get_prefix(i));
+        }
+        self.root = self
+            .compute_subtree(&TreeIndex::zero(0), secret)
+            .unwrap_or_else(|| self.get_padding(&TreeIndex::zero(0), secret));
+    }
+
+    /// Returns the Merkle proof of the leaf at the input index, with all levels of the uncompressed tree,
+    /// or None if the index is not a leaf.
+    ///
+    /// The secret must be the one the tree was built with, to recompute the padding nodes.
+    pub fn generate_proof(&self, idx: &TreeIndex, secret: &Secret) -> Option<MerkleProof<P>> {
+        self.leaves.get(idx)?;
+        let mut proof = MerkleProof::new(*idx);
+        let siblings = (1..=self.height)
+            .map(|i| {
+                let sibling = idx.get_prefix(i).get_sibling_index();
+                // A non-empty sibling is a child of a branching node, thus stored.
+                match self.nodes.get(&sibling) {
+                    Some(value) => value.get_proof_node(),
+                    None => self.get_padding(&sibling, secret).get_proof_node(),
+                }
+            })
+            .collect();
+        proof.set_siblings(siblings);
+        Some(proof)
+    }
+
+    /// Returns the first and the last leaf indexes in the subtree rooted at the input index.
+    fn get_leaf_range(&self, prefix: &TreeIndex) -> Option<(TreeIndex, TreeIndex)> {
+        let mut low = *prefix;
+        low.set_height(self.height);
+        let mut high = *prefix;
+        while high.get_height() < self.height {
+            high = high.get_rch_index();
+        }
+        let first = self.leaves.range(low..=high).next()?.0;
+        let last = self.leaves.range(low..=high).next_back()?.0;
+        Some((*first, *last))
+    }
+
+    /// Computes the value of the subtree rooted at the input index, storing the visited nodes,
+    /// or returns None if the subtree has no leaf.
+    ///
+    /// The stored value of a node is reused, so the nodes on updated paths must be removed before.
+    fn compute_subtree(&mut self, prefix: &TreeIndex, secret: &Secret) -> Option<P> {
+        if let Some(value) = self.nodes.get(prefix) {
+            return Some(value.clone());
+        }
+        let (first, last) = self.get_leaf_range(prefix)?;
+
+        // The lowest node containing all leaves of the subtree is a leaf or a branching node.
+        let bottom = first.lowest_common_ancestor(&last);
+        let mut value = if bottom.get_height() == self.height {
+            self.leaves[&first].clone()
+        } else {
+            // Both subtrees are non-empty.
+            let lch = self.compute_subtree(&bottom.get_lch_index(), secret)?;
+            let rch = self.compute_subtree(&bottom.get_rch_index(), secret)?;
+            let value = Mergeable::merge_with_index(&lch, &rch, &bottom);
+            self.nodes.insert(bottom, value.clone());
+            value
+        };
+
+        // Hash the implied padding nodes along the extension up to the input index.
+        let mut idx = bottom;
+        while idx.get_height() > prefix.get_height() {
+            let padding = self.get_padding(&idx.get_sibling_index(), secret);
+            let parent = idx.get_parent_index();
+            if idx.get_last_bit() == 0 {
+                value = Mergeable::merge_with_index(&value, &padding, &parent);
+            } else {
+                value = Mergeable::merge_with_index(&padding, &value, &parent);
+            }
+            idx = parent;
+        }
+        self.nodes.insert(*prefix, value.clone());
+        Some(value)
+    }
+}
@@ -0,0 +1,215 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! This module provides an object-safe adapter of the SMT,
+//! so that the node template of a tree can be chosen at runtime, e.g., for algorithm agility per deployment,
+//! without making the application generic over the node templates.
+//!
+//! A [DynSmt] exchanges the leaf values, the roots and the proofs as encoded bytes,
+//! and it is implemented by any [SparseMerkleTree](../tree/struct.SparseMerkleTree.html)
+//! whose nodes are [Serializable](../traits/trait.Serializable.html).
+//! A ```Box<dyn DynSmt>``` is constructed by the type name of a node template with [new_dyn_smt],
+//! or by boxing a tree of any other node template.
+
+use crate::pad_secret::Secret;
+use crate::{
+    error::{DecodingError, SmtError, TreeError},
+    index::TreeIndex,
+    node_template::{HashNodeSmt, SortedPairNodeSmt, SumNodeSmt},
+    proof::{BorrowedMerkleProof, MerkleProof},
+    traits::{Mergeable, Paddable, ProofExtractable, Serializable, TypeName},
+    tree::SparseMerkleTree,
+};
+
+/// An object-safe interface of a sparse Merkle tree, with the node values encoded as bytes.
+pub trait DynSmt {
+    /// Returns the type name of the nodes of the tree.
+    fn get_type_name(&self) -> String;
+
+    /// Returns the height of the tree.
+    fn get_height(&self) -> usize;
+
+    /// Returns the number of nodes in the tree.
+    fn get_nodes_num(&self) -> usize;
+
+    /// Returns the encoded root, as visible in the Merkle proofs.
+    fn get_root(&self) -> Vec<u8>;
+
+    /// Returns the encoded value of the leaf at the input index, or None if the leaf doesn't exist.
+    fn get_leaf(&self, idx: &TreeIndex) -> Option<Vec<u8>>;
+
+    /// Clear the tree and rebuild it from the input list of sorted index-value pairs with encoded values.
+    ///
+    /// If a value can't be decoded, return the decoding error.
+    /// If the input list is not valid, return the error of
+    /// [check_index_list_validity](../tree/struct.SparseMerkleTree.html#method.check_index_list_validity).
+    /// In both cases the tree is left unchanged.
+    fn build(&mut self, list: &[(TreeIndex, Vec<u8>)], secret: &Secret) -> Result<(), SmtError>;
+
+    /// Update the leaf at the input index with the encoded value.
+    ///
+    /// If the value can't be decoded, return the decoding error.
+    /// If the height of the index doesn't match with that of the tree,
+    /// return [TreeError::HeightNotMatch](../error/enum.TreeError.html#variant.HeightNotMatch).
+    fn update(&mut self, idx: &TreeIndex, value: &[u8], secret: &Secret) -> Result<(), SmtError>;
+
+    /// Returns the encoded Merkle proof of the leaves at the input sorted indexes.
+    ///
+    /// Return None if the list is empty, not sorted, of indexes of another height,
+    /// or if any index is not a leaf of the tree.
+    fn generate_inclusion_proof(&self, list: &[TreeIndex]) -> Option<Vec<u8>>;
+
+    /// Verify the encoded Merkle proof of the encoded leaves against the encoded root,
+    /// with the node template of the tree, independently of the content of the tree.
+    ///
+    /// If the proof, the leaves or the root can't be decoded, return the decoding error.
+    fn verify_inclusion_proof(
+        &self,
+        proof: &[u8],
+        leaves: &[Vec<u8>],
+        root: &[u8],
+    ) -> Result<bool, DecodingError>;
+}
+
+impl<P> DynSmt for SparseMerkleTree<P>
+where
+    P: Clone + Default + Mergeable + Paddable + ProofExtractable + Serializable + TypeName,
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    fn get_type_name(&self) -> String {
+        P::get_name()
+    }
+
+    fn get_height(&self) -> usize {
+        SparseMerkleTree::get_height(self)
+    }
+
+    fn get_nodes_num(&self) -> usize {
+        SparseMerkleTree::get_nodes_num(self)
+    }
+
+    fn get_root(&self) -> Vec<u8> {
+        SparseMerkleTree::get_root(self).serialize()
+    }
+
+    fn get_leaf(&self, idx: &TreeIndex) -> Option<Vec<u8>> {
+        if idx.get_height() != self.height {
+            return None;
+        }
+        self.get_leaf_by_index(idx)
+            .map(|node| node.get_value().serialize())
+    }
+
+    fn build(&mut self, list: &[(TreeIndex, Vec<u8>)], secret: &Secret) -> Result<(), SmtError> {
+        let mut decoded: Vec<(TreeIndex, P)> = Vec::with_capacity(list.len());
+        for (idx, value) in list {
+            decoded.push((*idx, P::deserialize(value)?));
+        }
+        self.rebuild(&decoded, secret)?;
+        Ok(())
+    }
+
+    fn update(&mut self, idx: &TreeIndex, value: &[u8], secret: &Secret) -> Result<(), SmtError> {
+        if idx.get_height() != self.height {
+            return Err(TreeError::HeightNotMatch.into());
+        }
+        let value = P::deserialize(value)?;
+        SparseMerkleTree::update(self, idx, value, secret);
+        Ok(())
+    }
+
+    fn generate_inclusion_proof(&self, list: &[TreeIndex]) -> Option<Vec<u8>> {
+        if list.is_empty()
+            || list.windows(2).any(|x| x[0] >= x[1])
+            || list.iter().any(|idx| idx.get_height() != self.height)
+        {
+            return None;
+        }
+        BorrowedMerkleProof::generate(self, list).map(|proof| proof.serialize())
+    }
+
+    fn verify_inclusion_proof(
+        &self,
+        proof: &[u8],
+        leaves: &[Vec<u8>],
+        root: &[u8],
+    ) -> Result<bool, DecodingError> {
+        let proof = MerkleProof::<P>::deserialize(proof)?;
+        let mut decoded: Vec<P::ProofNode> = Vec::with_capacity(leaves.len());
+        for leaf in leaves {
+            decoded.push(P::ProofNode::deserialize(leaf)?);
+        }
+        let root = P::ProofNode::deserialize(root)?;
+        if decoded.len() == 1 {
+            Ok(proof.verify(&decoded[0], &root))
+        } else {
+            Ok(proof.verify_batch(&decoded, &root))
+        }
+    }
+}
+
+// Returns an empty tree of the input height with node type ```P``` as a trait object.
+fn new_boxed<P>(height: usize) -> Box<dyn DynSmt>
+where
+    P: Clone
+        + Default
+        + Mergeable
+        + Paddable
+        + ProofExtractable
+        + Serializable
+        + TypeName
+        + 'static,
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    Box::new(SparseMerkleTree::<P>::new(height))
+}
+
+/// Returns an empty tree of the input height with the node template of the input type name,
+/// as returned by [TypeName::get_name](../traits/trait.TypeName.html#method.get_name),
+/// or None if the name isn't one of the hash, sorted pair hash and sum templates of the crate.
+///
+/// Panics if the height exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html).
+pub fn new_dyn_smt(name: &str, height: usize) -> Option<Box<dyn DynSmt>> {
+    type Constructor = fn(usize) -> Box<dyn DynSmt>;
+    let constructors: [(String, Constructor); 9] = [
+        (
+            HashNodeSmt::<blake3::Hasher>::get_name(),
+            new_boxed::<HashNodeSmt<blake3::Hasher>>,
+        ),
+        (
+            HashNodeSmt::<blake2::Blake2b>::get_name(),
+            new_boxed::<HashNodeSmt<blake2::Blake2b>>,
+        ),
+        (
+            HashNodeSmt::<sha2::Sha256>::get_name(),
+            new_boxed::<HashNodeSmt<sha2::Sha256>>,
+        ),
+        (
+            HashNodeSmt::<sha3::Sha3_256>::get_name(),
+            new_boxed::<HashNodeSmt<sha3::Sha3_256>>,
+        ),
+        (
+            SortedPairNodeSmt::<blake3::Hasher>::get_name(),
+            new_boxed::<SortedPairNodeSmt<blake3::Hasher>>,
+        ),
+        (
+            SortedPairNodeSmt::<blake2::Blake2b>::get_name(),
+            new_boxed::<SortedPairNodeSmt<blake2::Blake2b>>,
+        ),
+        (
+            SortedPairNodeSmt::<sha2::Sha256>::get_name(),
+            new_boxed::<SortedPairNodeSmt<sha2::Sha256>>,
+        ),
+        (
+            SortedPairNodeSmt::<sha3::Sha3_256>::get_name(),
+            new_boxed::<SortedPairNodeSmt<sha3::Sha3_256>>,
+        ),
+        (SumNodeSmt::get_name(), new_boxed::<SumNodeSmt>),
+    ];
+    constructors
+        .iter()
+        .find(|(x, _)| x == name)
+        .map(|(_, constructor)| constructor(height))
+}
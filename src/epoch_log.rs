@@ -0,0 +1,204 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! This module provides an append-only log of the roots published every epoch,
+//! giving a tamper-evident history of the commitments.
+//!
+//! The log is a sparse Merkle tree of its own, where the root published for epoch ```N```
+//! is the leaf at position ```N```, counting from 0 for the first appended root.
+//! An [EpochProof](type.EpochProof.html) proves that a root was committed for an epoch against the head of the log,
+//! i.e., the root of the log tree.
+//! A [ConsistencyProof](struct.ConsistencyProof.html) proves that the log of a head extends the log of an older head,
+//! i.e., that the roots committed for the older epochs haven't been changed since.
+//!
+//! The log tree is padded with [ALL_ZEROS_SECRET](../pad_secret/constant.ALL_ZEROS_SECRET.html),
+//! as the published roots are public.
+
+use crate::pad_secret::ALL_ZEROS_SECRET;
+use crate::{
+    error::{DecodingError, TreeError},
+    index::{TreeIndex, MAX_HEIGHT},
+    proof::MerkleProof,
+    traits::{Mergeable, Paddable, PaddingContext, ProofExtractable, Serializable},
+    tree::SparseMerkleTree,
+};
+
+/// The proof of the root committed for an epoch, a Merkle proof of the leaf at the epoch position.
+pub type EpochProof<P> = MerkleProof<P>;
+
+/// The proof that a log extends an older log of fewer epochs.
+///
+/// It is the root committed for the first epoch after the older log together with its [EpochProof],
+/// whose siblings on the left of the path commit to the older epochs,
+/// or empty if both logs have the same number of epochs.
+#[derive(Clone)]
+pub struct ConsistencyProof<P: Clone + Default + Mergeable + ProofExtractable>
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    appended: Option<(P, EpochProof<P>)>,
+}
+
+impl<P: Clone + Default + Mergeable + ProofExtractable> ConsistencyProof<P>
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// Returns the root committed for the first epoch after the older log and its proof,
+    /// or None if both logs have the same number of epochs.
+    pub fn get_appended(&self) -> Option<&(P, EpochProof<P>)> {
+        self.appended.as_ref()
+    }
+}
+
+/// An append-only log of the roots of the trees with node type ```P```, committed by a log tree of the same node type.
+#[derive(Debug, Clone)]
+pub struct EpochLog<P> {
+    epochs_num: u64,
+    tree: SparseMerkleTree<P>,
+}
+
+impl<P: Clone + Default + Mergeable + Paddable + ProofExtractable<ProofNode = P>> EpochLog<P>
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// The constructor of an empty log with room for ```2^height``` epochs.
+    ///
+    /// Panics if the height exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html).
+    pub fn new(height: usize) -> EpochLog<P> {
+        if height > MAX_HEIGHT {
+            panic!("{}", DecodingError::ExceedMaxHeight);
+        }
+        EpochLog {
+            epochs_num: 0,
+            tree: SparseMerkleTree::new(height),
+        }
+    }
+
+    /// Returns the height of the log tree.
+    pub fn get_height(&self) -> usize {
+        self.tree.get_height()
+    }
+
+    /// Returns the number of logged epochs.
+    pub fn get_epochs_num(&self) -> u64 {
+        self.epochs_num
+    }
+
+    /// Returns the head of the log, i.e., the root of the log tree.
+    pub fn get_head(&self) -> P {
+        self.tree.get_root()
+    }
+
+    /// Returns the root committed for the input epoch, or None if the epoch hasn't been logged.
+    pub fn get_epoch_root(&self, epoch: u64) -> Option<&P> {
+        if epoch >= self.epochs_num {
+            return None;
+        }
+        let idx = TreeIndex::from_u64(self.get_height(), epoch);
+        self.tree.get_leaf_by_index(&idx).map(|x| x.get_value())
+    }
+
+    /// Append the root published for the next epoch, and return the epoch number.
+    ///
+    /// If all epochs of the log are taken,
+    /// return [TreeError::TreeFull](../error/enum.TreeError.html#variant.TreeFull).
+    pub fn append(&mut self, root: P) -> Result<u64, TreeError> {
+        let epoch = self.epochs_num;
+        let idx =
+            TreeIndex::try_from_u64(self.get_height(), epoch).map_err(|_| TreeError::TreeFull)?;
+        self.tree.update(&idx, root, &ALL_ZEROS_SECRET);
+        self.epochs_num += 1;
+        Ok(epoch)
+    }
+
+    /// Returns the proof of the root committed for the input epoch against the current head,
+    /// or None if the epoch hasn't been logged.
+    pub fn prove_epoch(&self, epoch: u64) -> Option<EpochProof<P>> {
+        if epoch >= self.epochs_num {
+            return None;
+        }
+        self.tree.prove_position(epoch)
+    }
+
+    /// Verify that the input root was committed for the input epoch in the log of the input head,
+    /// whose log tree is of the input height.
+    pub fn verify_epoch(
+        head: &P,
+        height: usize,
+        epoch: u64,
+        root: &P,
+        proof: &EpochProof<P>,
+    ) -> bool {
+        SparseMerkleTree::verify_position(head, height, epoch, root, proof)
+    }
+
+    /// Returns the proof that the current log extends its prefix of the input number of epochs,
+    /// or None if the input number exceeds the number of logged epochs.
+    pub fn prove_consistency(&self, old_epochs_num: u64) -> Option<ConsistencyProof<P>> {
+        if old_epochs_num > self.epochs_num {
+            return None;
+        }
+        if old_epochs_num == self.epochs_num {
+            return Some(ConsistencyProof { appended: None });
+        }
+        let root = self.get_epoch_root(old_epochs_num)?.clone();
+        let proof = self.prove_epoch(old_epochs_num)?;
+        Some(ConsistencyProof {
+            appended: Some((root, proof)),
+        })
+    }
+
+    /// Verify that the log of the new head extends the log of the old head,
+    /// both of the input numbers of epochs in a log tree of the input height.
+    ///
+    /// The old head is recomputed from the siblings committing to the old epochs in the proof,
+    /// with the padding nodes of the log tree in place of the appended epochs.
+    pub fn verify_consistency(
+        old_head: &P,
+        old_epochs_num: u64,
+        new_head: &P,
+        new_epochs_num: u64,
+        height: usize,
+        proof: &ConsistencyProof<P>,
+    ) -> bool {
+        if old_epochs_num > new_epochs_num {
+            return false;
+        }
+        let (root, epoch_proof) = match &proof.appended {
+            None => return old_epochs_num == new_epochs_num && old_head == new_head,
+            Some(x) => x,
+        };
+        if old_epochs_num == new_epochs_num
+            || !Self::verify_epoch(new_head, height, old_epochs_num, root, epoch_proof)
+        {
+            return false;
+        }
+
+        // Recompute the old head from the leaf of the first appended epoch up to the root,
+        // where None stands for a subtree without old epochs, i.e., a padding node in the old log.
+        let secret = ALL_ZEROS_SECRET;
+        let ctx = PaddingContext::new(height, &secret);
+        let siblings = epoch_proof.get_path_siblings();
+        let mut idx = TreeIndex::from_u64(height, old_epochs_num);
+        let mut node: Option<P> = None;
+        for sibling in siblings.iter().rev() {
+            let parent = idx.get_parent_index();
+            node = if idx.get_last_bit() == 1 {
+                // The left sibling only covers old epochs.
+                let rch = node.unwrap_or_else(|| P::padding_with_context(&idx, &ctx));
+                Some(P::merge_with_index(sibling, &rch, &parent))
+            } else {
+                // The right sibling only covers appended epochs, thus is padding in the old log.
+                node.map(|lch| {
+                    let rch = P::padding_with_context(&idx.get_sibling_index(), &ctx);
+                    P::merge_with_index(&lch, &rch, &parent)
+                })
+            };
+            idx = parent;
+        }
+        let old_root = node.unwrap_or_else(|| P::padding_with_context(&idx, &ctx));
+        old_root == *old_head
+    }
+}
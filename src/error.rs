@@ -5,7 +5,7 @@
 
 //! This module provides definitions of errors might be returned by this library.
 
-use crate::index::MAX_HEIGHT;
+use crate::index::{TreeIndex, MAX_HEIGHT};
 
 /// Errors occur during deserialization.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -23,6 +23,27 @@ pub enum DecodingError {
         /// ```msg``` is the error message.
         msg: String,
     },
+    /// Error when decoding a text encoding, e.g., hex or base64, into bytes.
+    TextDecodingError {
+        /// ```msg``` is the error message.
+        msg: String,
+    },
+    /// Error when the input is not well-formed in an external data format, e.g., CBOR.
+    FormatError {
+        /// ```msg``` is the error message.
+        msg: String,
+    },
+    /// Error when the tree height a proof is bound to isn't the expected one.
+    HeightNotMatch,
+    /// Error when the domain tag a proof is bound to isn't the expected one.
+    DomainNotMatch,
+    /// Error when the input is a valid but non-canonical encoding, rejected by strict decoding.
+    NonCanonicalEncoding,
+    /// Error when reading the bytes to be decoded from a reader, other than reaching the end.
+    IoError {
+        /// ```msg``` is the error message.
+        msg: String,
+    },
 }
 
 impl core::fmt::Display for DecodingError {
@@ -47,6 +68,24 @@ impl core::fmt::Display for DecodingError {
             DecodingError::ValueDecodingError { msg } => {
                 write!(f, "Value decoding error: {}", msg)?;
             }
+            DecodingError::TextDecodingError { msg } => {
+                write!(f, "Text decoding error: {}", msg)?;
+            }
+            DecodingError::FormatError { msg } => {
+                write!(f, "Format error: {}", msg)?;
+            }
+            DecodingError::HeightNotMatch => {
+                write!(f, "The tree height doesn't match with the expected one.")?;
+            }
+            DecodingError::DomainNotMatch => {
+                write!(f, "The domain tag doesn't match with the expected one.")?;
+            }
+            DecodingError::NonCanonicalEncoding => {
+                write!(f, "The encoding is not canonical.")?;
+            }
+            DecodingError::IoError { msg } => {
+                write!(f, "I/O error: {}", msg)?;
+            }
         }
         Ok(())
     }
@@ -65,6 +104,62 @@ pub enum TreeError {
     IndexDuplicated,
     /// Errors related to SMTree Secret.
     SecretError,
+    /// Error when appending a leaf to a tree with all leaf positions taken.
+    TreeFull,
+    /// Error when there is no tree of the input name.
+    TreeNotFound,
+    /// Error when the tree height exceeds [MAX_HEIGHT].
+    ExceedMaxHeight,
+    /// Error when there is no leaf at the input index.
+    LeafNotFound,
+    /// Error when the index of the input entry at ```position``` doesn't match with the height of the tree.
+    HeightNotMatchAt {
+        /// ```position``` is the position of the offending entry in the input list.
+        position: usize,
+        /// ```index``` is the tree index of the offending entry.
+        index: TreeIndex,
+    },
+    /// Error when the index of the input entry at ```position``` is smaller than that of the previous entry.
+    IndexNotSortedAt {
+        /// ```position``` is the position of the offending entry in the input list.
+        position: usize,
+        /// ```index``` is the tree index of the offending entry.
+        index: TreeIndex,
+    },
+    /// Error when the index of the input entry at ```position``` appears earlier in the list.
+    IndexDuplicatedAt {
+        /// ```position``` is the position of the offending entry in the input list.
+        position: usize,
+        /// ```index``` is the tree index of the offending entry.
+        index: TreeIndex,
+    },
+    /// Error when a delta is applied to another version of the tree than the one it was exported against.
+    BaseNotMatch,
+    /// Error when the entries of a delta don't lead to the target root of the delta.
+    TargetNotMatch,
+}
+
+impl TreeError {
+    /// Returns the error without the context of the offending entry,
+    /// e.g., [TreeError::IndexNotSorted] for [TreeError::IndexNotSortedAt].
+    pub fn get_kind(&self) -> TreeError {
+        match self {
+            TreeError::HeightNotMatchAt { .. } => TreeError::HeightNotMatch,
+            TreeError::IndexNotSortedAt { .. } => TreeError::IndexNotSorted,
+            TreeError::IndexDuplicatedAt { .. } => TreeError::IndexDuplicated,
+            x => x.clone(),
+        }
+    }
+
+    /// Returns the position and the tree index of the offending entry in the input list if available.
+    pub fn get_entry(&self) -> Option<(usize, TreeIndex)> {
+        match self {
+            TreeError::HeightNotMatchAt { position, index }
+            | TreeError::IndexNotSortedAt { position, index }
+            | TreeError::IndexDuplicatedAt { position, index } => Some((*position, *index)),
+            _ => None,
+        }
+    }
 }
 
 impl core::fmt::Display for TreeError {
@@ -85,9 +180,265 @@ impl core::fmt::Display for TreeError {
             TreeError::SecretError => {
                 write!(f, "Wrong Secret size")?;
             }
+            TreeError::TreeFull => {
+                write!(f, "All leaf positions of the tree are taken.")?;
+            }
+            TreeError::TreeNotFound => {
+                write!(f, "There is no tree of the input name.")?;
+            }
+            TreeError::ExceedMaxHeight => {
+                write!(
+                    f,
+                    "The height exceeds the maximum height, {}, in an SMT.",
+                    MAX_HEIGHT
+                )?;
+            }
+            TreeError::LeafNotFound => {
+                write!(f, "There is no leaf at the input index.")?;
+            }
+            TreeError::HeightNotMatchAt { position, index } => {
+                write!(
+                    f,
+                    "The height of the index {:?} at position {} doesn't match with the height of the tree.",
+                    index, position
+                )?;
+            }
+            TreeError::IndexNotSortedAt { position, index } => {
+                write!(
+                    f,
+                    "The indexes are not sorted: index {:?} at position {} is smaller than the previous one.",
+                    index, position
+                )?;
+            }
+            TreeError::IndexDuplicatedAt { position, index } => {
+                write!(
+                    f,
+                    "There are duplicated indexes: index {:?} at position {} appears earlier in the list.",
+                    index, position
+                )?;
+            }
+            TreeError::BaseNotMatch => {
+                write!(
+                    f,
+                    "The delta doesn't apply to the current version of the tree."
+                )?;
+            }
+            TreeError::TargetNotMatch => {
+                write!(f, "The delta doesn't lead to its target root.")?;
+            }
         }
         Ok(())
     }
 }
 
 impl std::error::Error for TreeError {}
+
+/// Errors occur when persisting or loading SMT nodes through a storage backend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageError {
+    /// Error reported by the underlying storage backend.
+    BackendError {
+        /// ```msg``` is the error message.
+        msg: String,
+    },
+    /// Error when decoding a stored node or the tree metadata.
+    DecodingError(DecodingError),
+    /// Error when a node referenced by the tree is missing from the storage.
+    NodeMissing(usize),
+    /// Error when the storage doesn't contain a tree.
+    MetadataMissing,
+}
+
+impl core::fmt::Display for StorageError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            StorageError::BackendError { msg } => {
+                write!(f, "Storage backend error: {}", msg)?;
+            }
+            StorageError::DecodingError(e) => {
+                write!(f, "Stored data decoding error: {}", e)?;
+            }
+            StorageError::NodeMissing(link) => {
+                write!(
+                    f,
+                    "The node with reference {} is missing in the storage.",
+                    link
+                )?;
+            }
+            StorageError::MetadataMissing => {
+                write!(f, "There is no tree in the storage.")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<DecodingError> for StorageError {
+    fn from(e: DecodingError) -> StorageError {
+        StorageError::DecodingError(e)
+    }
+}
+
+/// Errors occur in any operation of the library, wrapping the error of the operation,
+/// so that tree operations and (de)serialization can be chained with ```?```.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SmtError {
+    /// Error when operating on the SMT.
+    Tree(TreeError),
+    /// Error during deserialization.
+    Decoding(DecodingError),
+}
+
+impl core::fmt::Display for SmtError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SmtError::Tree(e) => {
+                write!(f, "Tree error: {}", e)?;
+            }
+            SmtError::Decoding(e) => {
+                write!(f, "Decoding error: {}", e)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SmtError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SmtError::Tree(e) => Some(e),
+            SmtError::Decoding(e) => Some(e),
+        }
+    }
+}
+
+impl From<TreeError> for SmtError {
+    fn from(e: TreeError) -> SmtError {
+        SmtError::Tree(e)
+    }
+}
+
+impl From<DecodingError> for SmtError {
+    fn from(e: DecodingError) -> SmtError {
+        SmtError::Decoding(e)
+    }
+}
+
+/// Errors found when auditing the integrity of a tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntegrityError {
+    /// Error when a reference to a node, i.e., the root or a child, is out of the node list.
+    InvalidReference {
+        /// ```link``` is the invalid reference.
+        link: usize,
+    },
+    /// Error when the parent of a node doesn't refer back to the node as a child,
+    /// or when a node is reachable from the root through more than one path.
+    LinkMismatch {
+        /// ```link``` is the reference to the offending node.
+        link: usize,
+        /// ```index``` is the tree index of the offending node.
+        index: TreeIndex,
+    },
+    /// Error when the children of a node don't match with its type,
+    /// i.e., an internal node without two children, a leaf or padding node with a child,
+    /// or a leaf not at the bottom of the tree.
+    NodeTypeMismatch {
+        /// ```link``` is the reference to the offending node.
+        link: usize,
+        /// ```index``` is the tree index of the offending node.
+        index: TreeIndex,
+    },
+    /// Error when the value of an internal node isn't the merge of its children.
+    InternalMismatch {
+        /// ```link``` is the reference to the offending node.
+        link: usize,
+        /// ```index``` is the tree index of the offending node.
+        index: TreeIndex,
+    },
+    /// Error when the value of a padding node isn't the one derived from the secret.
+    PaddingMismatch {
+        /// ```link``` is the reference to the offending node.
+        link: usize,
+        /// ```index``` is the tree index of the offending node.
+        index: TreeIndex,
+    },
+}
+
+impl core::fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            IntegrityError::InvalidReference { link } => {
+                write!(f, "The reference {} is out of the node list.", link)?;
+            }
+            IntegrityError::LinkMismatch { link, index } => {
+                write!(
+                    f,
+                    "The links of node {} at {:?} are not symmetric.",
+                    link, index
+                )?;
+            }
+            IntegrityError::NodeTypeMismatch { link, index } => {
+                write!(
+                    f,
+                    "The children of node {} at {:?} don't match with its type.",
+                    link, index
+                )?;
+            }
+            IntegrityError::InternalMismatch { link, index } => {
+                write!(
+                    f,
+                    "The internal node {} at {:?} isn't the merge of its children.",
+                    link, index
+                )?;
+            }
+            IntegrityError::PaddingMismatch { link, index } => {
+                write!(
+                    f,
+                    "The padding node {} at {:?} isn't derived from the secret.",
+                    link, index
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for IntegrityError {}
+
+/// Errors occur when a proof provider serves a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProviderError {
+    /// Error when the request is not valid, e.g., it can't be decoded or its indexes don't fit to the tree.
+    BadRequest {
+        /// ```msg``` is the error message.
+        msg: String,
+    },
+    /// Error when some requested leaf doesn't exist in the tree.
+    NotFound,
+}
+
+impl core::fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ProviderError::BadRequest { msg } => {
+                write!(f, "Bad request: {}", msg)?;
+            }
+            ProviderError::NotFound => {
+                write!(f, "The requested leaf doesn't exist in the tree.")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ProviderError {}
+
+impl From<DecodingError> for ProviderError {
+    fn from(e: DecodingError) -> ProviderError {
+        ProviderError::BadRequest { msg: e.to_string() }
+    }
+}
@@ -0,0 +1,112 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! This module provides a Bloom filter over the indexes of the leaves of a tree,
+//! which answers definite absence of a leaf without walking the tree,
+//! e.g., when the tree is disk-backed and most lookups are for missing keys.
+//!
+//! The filter only supports insertion, so a leaf that no longer exists may still be reported as possibly present,
+//! but a leaf that exists is never reported as absent.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::index::TreeIndex;
+
+/// The number of bits in a word of the filter.
+const WORD_BITS: usize = 64;
+
+/// A Bloom filter over tree indexes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LeafFilter {
+    words: Vec<u64>,
+    bits_num: usize,
+    hashes_num: usize,
+    inserted_num: usize,
+}
+
+impl LeafFilter {
+    /// The constructor of an empty filter sized for the expected number of leaves
+    /// with the target false positive rate.
+    ///
+    /// Panics if the rate is not in the range ```(0, 1)```.
+    pub fn new(expected_leaves: usize, false_positive_rate: f64) -> LeafFilter {
+        if !(false_positive_rate > 0.0 && false_positive_rate < 1.0) {
+            panic!("The false positive rate is not in the range (0, 1).");
+        }
+        let ln2 = std::f64::consts::LN_2;
+        let expected = expected_leaves.max(1) as f64;
+        let bits_num = (-expected * false_positive_rate.ln() / (ln2 * ln2)).ceil() as usize;
+        let hashes_num = (bits_num as f64 / expected * ln2).round() as usize;
+        Self::with_params(bits_num, hashes_num)
+    }
+
+    /// The constructor of an empty filter with the input numbers of bits and of hash functions,
+    /// each at least 1.
+    pub fn with_params(bits_num: usize, hashes_num: usize) -> LeafFilter {
+        let bits_num = bits_num.max(1);
+        LeafFilter {
+            words: vec![0; bits_num.div_ceil(WORD_BITS)],
+            bits_num,
+            hashes_num: hashes_num.max(1),
+            inserted_num: 0,
+        }
+    }
+
+    /// Returns the number of bits of the filter.
+    pub fn get_bits_num(&self) -> usize {
+        self.bits_num
+    }
+
+    /// Returns the number of hash functions of the filter.
+    pub fn get_hashes_num(&self) -> usize {
+        self.hashes_num
+    }
+
+    /// Returns the number of insertions into the filter, counting repeated indexes.
+    pub fn get_inserted_num(&self) -> usize {
+        self.inserted_num
+    }
+
+    /// Returns the estimated false positive rate after the insertions so far.
+    pub fn get_false_positive_rate(&self) -> f64 {
+        let exponent = -(self.hashes_num as f64) * self.inserted_num as f64 / self.bits_num as f64;
+        (1.0 - exponent.exp()).powi(self.hashes_num as i32)
+    }
+
+    /// Remove all indexes from the filter, keeping its size.
+    pub fn clear(&mut self) {
+        self.words.iter_mut().for_each(|word| *word = 0);
+        self.inserted_num = 0;
+    }
+
+    /// Insert the input index into the filter.
+    pub fn insert(&mut self, idx: &TreeIndex) {
+        for bit in self.get_bit_positions(idx) {
+            self.words[bit / WORD_BITS] |= 1 << (bit % WORD_BITS);
+        }
+        self.inserted_num += 1;
+    }
+
+    /// Returns false if the input index was definitely not inserted,
+    /// and true if it may have been inserted.
+    pub fn may_contain(&self, idx: &TreeIndex) -> bool {
+        self.get_bit_positions(idx)
+            .all(|bit| self.words[bit / WORD_BITS] & (1 << (bit % WORD_BITS)) != 0)
+    }
+
+    // Returns the positions of the bits of the input index, by double hashing with two hashes of the index.
+    fn get_bit_positions(&self, idx: &TreeIndex) -> impl Iterator<Item = usize> {
+        let mut hasher = DefaultHasher::new();
+        idx.hash(&mut hasher);
+        let first = hasher.finish();
+        // Hashing one more byte gives the second hash, which is made nonzero so that the positions differ.
+        0u8.hash(&mut hasher);
+        let second = hasher.finish() | 1;
+        let bits_num = self.bits_num as u64;
+        (0..self.hashes_num as u64)
+            .map(move |i| (first.wrapping_add(i.wrapping_mul(second)) % bits_num) as usize)
+    }
+}
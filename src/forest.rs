@@ -0,0 +1,221 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! This module provides a forest of named sparse Merkle trees under one top-level tree,
+//! e.g., one tree per currency committed by a master root.
+//!
+//! All trees of a forest share the padding secret and the node template.
+//! The top-level tree has a leaf for each tree,
+//! at the tree index derived from the SHA-256 hash of the tree name and valued by the root of the tree.
+//! A [ForestProof] proves a leaf in a named tree against the root of the forest.
+
+use std::collections::BTreeMap;
+
+use digest::Digest;
+
+use crate::pad_secret::Secret;
+use crate::{
+    error::{DecodingError, TreeError},
+    index::{TreeIndex, MAX_HEIGHT},
+    proof::MerkleProof,
+    traits::{InclusionProvable, Mergeable, Paddable, ProofExtractable, Serializable},
+    tree::SparseMerkleTree,
+};
+
+/// Returns the tree index of the named tree in a top-level tree of the input height,
+/// i.e., the first ```top_height``` bits of the SHA-256 hash of the name.
+///
+/// Panics if the height exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html).
+pub fn get_tree_index(name: &str, top_height: usize) -> TreeIndex {
+    let mut path = [0u8; MAX_HEIGHT / 8];
+    path.copy_from_slice(&sha2::Sha256::digest(name.as_bytes()));
+    TreeIndex::new(MAX_HEIGHT, path).get_prefix(top_height)
+}
+
+/// A forest of named sparse Merkle trees committed by a top-level tree.
+pub struct Forest<P> {
+    height: usize,
+    secret: Secret,
+    trees: BTreeMap<String, SparseMerkleTree<P>>,
+    top: SparseMerkleTree<P>,
+}
+
+impl<P: Clone + Default + Mergeable + Paddable + ProofExtractable> Forest<P>
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// The constructor, with the height of each tree, the height of the top-level tree and the shared secret.
+    ///
+    /// Panics if either height exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html).
+    pub fn new(height: usize, top_height: usize, secret: &Secret) -> Forest<P> {
+        if height > MAX_HEIGHT {
+            panic!("{}", DecodingError::ExceedMaxHeight);
+        }
+        Forest {
+            height,
+            // The secret has been checked at its construction.
+            secret: Secret::from_bytes(secret.as_bytes()).unwrap(),
+            trees: BTreeMap::new(),
+            top: SparseMerkleTree::new_with_secret(top_height, secret),
+        }
+    }
+
+    /// Returns the height of each tree.
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the height of the top-level tree.
+    pub fn get_top_height(&self) -> usize {
+        self.top.get_height()
+    }
+
+    /// Returns the number of trees.
+    pub fn get_trees_num(&self) -> usize {
+        self.trees.len()
+    }
+
+    /// Returns the names of the trees in increasing order.
+    pub fn get_names(&self) -> Vec<&str> {
+        self.trees.keys().map(|x| x.as_str()).collect()
+    }
+
+    /// Returns the named tree, or None if it doesn't exist.
+    pub fn get_tree(&self, name: &str) -> Option<&SparseMerkleTree<P>> {
+        self.trees.get(name)
+    }
+
+    /// Returns the top-level tree.
+    pub fn get_top_tree(&self) -> &SparseMerkleTree<P> {
+        &self.top
+    }
+
+    /// Returns the root of the forest, i.e., the root of the top-level tree.
+    pub fn get_root(&self) -> <P as ProofExtractable>::ProofNode {
+        self.top.get_root()
+    }
+
+    /// Build a tree of the input name from the input list of sorted index-value pairs,
+    /// replacing the tree of the same name if it exists, and update the top-level tree.
+    ///
+    /// If the input list is not valid,
+    /// return the error of [SparseMerkleTree::check_index_list_validity](../tree/struct.SparseMerkleTree.html#method.check_index_list_validity).
+    ///
+    /// If the tree index of the name is taken by another tree,
+    /// return [TreeError::IndexDuplicated](../error/enum.TreeError.html#variant.IndexDuplicated).
+    pub fn add_tree(&mut self, name: &str, list: &[(TreeIndex, P)]) -> Result<(), TreeError> {
+        let index = get_tree_index(name, self.top.get_height());
+        if self
+            .trees
+            .keys()
+            .any(|x| x != name && get_tree_index(x, self.top.get_height()) == index)
+        {
+            return Err(TreeError::IndexDuplicated);
+        }
+        let mut tree = SparseMerkleTree::new(self.height);
+        if let Some(x) = tree.check_index_list_validity(list) {
+            return Err(x);
+        }
+        tree.build(list, &self.secret);
+        self.top
+            .update(&index, tree.get_root_raw().clone(), &self.secret);
+        self.trees.insert(name.to_owned(), tree);
+        Ok(())
+    }
+
+    /// Update the leaf of the input index in the named tree, and update the top-level tree.
+    ///
+    /// If the tree doesn't exist, return [TreeError::TreeNotFound](../error/enum.TreeError.html#variant.TreeNotFound).
+    ///
+    /// If the height of the index doesn't match with that of the trees,
+    /// return [TreeError::HeightNotMatch](../error/enum.TreeError.html#variant.HeightNotMatch).
+    pub fn update(&mut self, name: &str, key: &TreeIndex, value: P) -> Result<(), TreeError> {
+        let tree = self.trees.get_mut(name).ok_or(TreeError::TreeNotFound)?;
+        if key.get_height() != self.height {
+            return Err(TreeError::HeightNotMatch);
+        }
+        tree.update(key, value, &self.secret);
+        let root = tree.get_root_raw().clone();
+        self.top.update(
+            &get_tree_index(name, self.top.get_height()),
+            root,
+            &self.secret,
+        );
+        Ok(())
+    }
+
+    /// Returns the proof of the leaf of the input index in the named tree against the root of the forest,
+    /// or None if the tree or the leaf doesn't exist.
+    pub fn generate_proof(&self, name: &str, idx: &TreeIndex) -> Option<ForestProof<P>> {
+        let tree = self.trees.get(name)?;
+        let leaf_proof = MerkleProof::generate_inclusion_proof(tree, &[*idx])?;
+        let tree_proof = MerkleProof::generate_inclusion_proof(
+            &self.top,
+            &[get_tree_index(name, self.top.get_height())],
+        )?;
+        Some(ForestProof {
+            leaf_proof,
+            tree_root: tree.get_root(),
+            tree_proof,
+        })
+    }
+}
+
+/// A cross-tree proof of a leaf in a named tree of a forest,
+/// consisting of the proof of the leaf against the root of the tree
+/// and the proof of the root of the tree in the top-level tree.
+#[derive(Debug, Clone)]
+pub struct ForestProof<P: Clone + Default + Mergeable + ProofExtractable>
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    leaf_proof: MerkleProof<P>,
+    tree_root: P::ProofNode,
+    tree_proof: MerkleProof<P>,
+}
+
+impl<P: Clone + Default + Mergeable + ProofExtractable> ForestProof<P>
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// The constructor.
+    pub fn new(
+        leaf_proof: MerkleProof<P>,
+        tree_root: P::ProofNode,
+        tree_proof: MerkleProof<P>,
+    ) -> ForestProof<P> {
+        ForestProof {
+            leaf_proof,
+            tree_root,
+            tree_proof,
+        }
+    }
+
+    /// Returns the proof of the leaf against the root of the tree.
+    pub fn get_leaf_proof(&self) -> &MerkleProof<P> {
+        &self.leaf_proof
+    }
+
+    /// Returns the root of the tree.
+    pub fn get_tree_root(&self) -> &P::ProofNode {
+        &self.tree_root
+    }
+
+    /// Returns the proof of the root of the tree in the top-level tree.
+    pub fn get_tree_proof(&self) -> &MerkleProof<P> {
+        &self.tree_proof
+    }
+
+    /// Verify the proof of the input leaf in the named tree against the root of the forest.
+    pub fn verify(&self, name: &str, leaf: &P::ProofNode, root: &P::ProofNode) -> bool {
+        let indexes = self.tree_proof.get_indexes();
+        if indexes.len() != 1 || indexes[0] != get_tree_index(name, indexes[0].get_height()) {
+            log_debug!("Forest proof verification failed: tree index mismatch");
+            return false;
+        }
+        self.leaf_proof.verify(leaf, &self.tree_root)
+            && self.tree_proof.verify(&self.tree_root, root)
+    }
+}
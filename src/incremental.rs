@@ -0,0 +1,163 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! This module provides an append-only Merkle tree of fixed height,
+//! in the style of the Ethereum deposit contract.
+//!
+//! Leaves are appended from left to right,
+//! and the positions not yet taken are filled by a zero leaf.
+//! Instead of all nodes, the tree stores the left frontier, i.e., the latest complete left node of each level,
+//! and the cached roots of the zero subtrees of each height,
+//! so that appending a leaf and computing the root take O(height) time and the tree takes O(height) space.
+//!
+//! Note that the empty positions are zero subtrees instead of padding nodes,
+//! so the root differs from that of a [SparseMerkleTree](../tree/struct.SparseMerkleTree.html) with the same leaves.
+//!
+//! The nodes are merged with [merge_with_index](../traits/trait.Mergeable.html#method.merge_with_index)
+//! at their tree indexes, as in the Merkle proofs.
+//! The root of the zero subtree of each height is merged once at the leftmost index of its level,
+//! so a node type merging by index must only depend on the height of the parent index,
+//! e.g., hashing the level into the internal nodes, for the zero subtrees to be the same at all positions.
+
+use crate::{
+    error::{DecodingError, TreeError},
+    index::{TreeIndex, MAX_HEIGHT},
+    proof::MerkleProof,
+    traits::{Mergeable, ProofExtractable, Serializable},
+};
+
+/// An append-only Merkle tree storing only the left frontier and the zero subtrees.
+#[derive(Debug, Clone)]
+pub struct IncrementalMerkleTree<P> {
+    height: usize,
+    leaves_num: u64,
+    // The latest complete left node of each level, from the leaf level up,
+    // followed by the root once the tree is full.
+    frontier: Vec<P>,
+    // The root of the zero subtree of each height, from the zero leaf up to the empty tree.
+    zeros: Vec<P>,
+}
+
+impl<P: Clone + Default + Mergeable + ProofExtractable> IncrementalMerkleTree<P>
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// The constructor, with ```P::default()``` as the zero leaf.
+    ///
+    /// Panics if the height exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html).
+    pub fn new(height: usize) -> IncrementalMerkleTree<P> {
+        IncrementalMerkleTree::new_with_zero(height, P::default())
+    }
+
+    /// The constructor with a customized zero leaf, e.g., 32 zero bytes as in the deposit contract.
+    ///
+    /// Panics if the height exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html).
+    pub fn new_with_zero(height: usize, zero: P) -> IncrementalMerkleTree<P> {
+        if height > MAX_HEIGHT {
+            panic!("{}", DecodingError::ExceedMaxHeight);
+        }
+        let mut zeros = Vec::with_capacity(height + 1);
+        zeros.push(zero);
+        for i in 0..height {
+            let parent = TreeIndex::zero(height - i - 1);
+            zeros.push(Mergeable::merge_with_index(&zeros[i], &zeros[i], &parent));
+        }
+        IncrementalMerkleTree {
+            height,
+            leaves_num: 0,
+            frontier: zeros.clone(),
+            zeros,
+        }
+    }
+
+    /// Returns the height of the tree.
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the number of appended leaves.
+    pub fn get_leaves_num(&self) -> u64 {
+        self.leaves_num
+    }
+
+    /// Returns true if all leaf positions are taken.
+    pub fn is_full(&self) -> bool {
+        self.height < 64 && self.leaves_num == 1u64 << self.height
+    }
+
+    /// Append a leaf at the leftmost empty position, and return the tree index of the leaf.
+    ///
+    /// If all leaf positions are taken,
+    /// return [TreeError::TreeFull](../error/enum.TreeError.html#variant.TreeFull).
+    pub fn append(&mut self, leaf: P) -> Result<TreeIndex, TreeError> {
+        if self.is_full() || self.leaves_num == u64::MAX {
+            return Err(TreeError::TreeFull);
+        }
+        let position = self.leaves_num;
+        let idx = TreeIndex::from_u64(self.height, position);
+        self.leaves_num += 1;
+
+        // Merge the new leaf with the complete left nodes until it becomes a left node itself,
+        // or the root if the tree becomes full.
+        let mut node = leaf;
+        let mut size = self.leaves_num;
+        for level in 0..=self.height {
+            if size & 1 == 1 {
+                self.frontier[level] = node;
+                break;
+            }
+            let parent = idx.get_prefix(self.height - level - 1);
+            node = Mergeable::merge_with_index(&self.frontier[level], &node, &parent);
+            size >>= 1;
+        }
+        Ok(idx)
+    }
+
+    /// Returns the root of the tree that is visible in the Merkle proof.
+    pub fn get_root(&self) -> <P as ProofExtractable>::ProofNode {
+        if self.is_full() {
+            return self.frontier[self.height].get_proof_node();
+        }
+        // Merge up from the leftmost empty position.
+        let idx = TreeIndex::from_u64(self.height, self.leaves_num);
+        let mut node = self.zeros[0].clone();
+        let mut size = self.leaves_num;
+        for level in 0..self.height {
+            let parent = idx.get_prefix(self.height - level - 1);
+            if size & 1 == 1 {
+                node = Mergeable::merge_with_index(&self.frontier[level], &node, &parent);
+            } else {
+                node = Mergeable::merge_with_index(&node, &self.zeros[level], &parent);
+            }
+            size >>= 1;
+        }
+        node.get_proof_node()
+    }
+
+    /// Returns the Merkle proof of the latest appended leaf,
+    /// which can be verified by [MerkleProof::verify](../proof/struct.MerkleProof.html#method.verify).
+    ///
+    /// If no leaf has been appended, return None.
+    pub fn prove_last(&self) -> Option<MerkleProof<P>> {
+        if self.leaves_num == 0 {
+            return None;
+        }
+        let position = self.leaves_num - 1;
+        let mut proof = MerkleProof::new(TreeIndex::from_u64(self.height, position));
+        // The siblings of a proof are ordered from the root down.
+        let siblings = (0..self.height)
+            .rev()
+            .map(|level| {
+                if level < 64 && (position >> level) & 1 == 1 {
+                    self.frontier[level].get_proof_node()
+                } else {
+                    self.zeros[level].get_proof_node()
+                }
+            })
+            .collect();
+        proof.set_siblings(siblings);
+        Some(proof)
+    }
+}
@@ -7,26 +7,32 @@
 //! and operations to get the index of the parent/sibling/child/etc. of a given tree node.
 
 use std::cmp::Ordering;
+use std::io::Read;
 
 use rand::Rng;
 
 use crate::{
     error::DecodingError,
+    traits::Serializable,
     tree::ChildDir,
-    utils::{bytes_to_usize, tree_index_from_u64, usize_to_bytes},
+    utils::{bytes_to_count, read_bytes, u64_to_bytes},
 };
 
-// We store the position of each tree node in a byte array of size 32,
+// We store the position of each tree node in a byte array of size 32 by default,
 // thus the maximum height could be 8  * 32 = 256.
 const BYTE_SIZE: usize = 8;
-const BYTE_NUM: usize = 32;
+pub(crate) const BYTE_NUM: usize = 32;
 
 /// The maximum height of a SMT is 256 (not including the root node),
 /// so the maximum number of leaves is ```2^256```.
 pub const MAX_HEIGHT: usize = BYTE_SIZE * BYTE_NUM;
 
 // The number of bytes for encoding the height field.
-const HEIGHT_BYTE_NUM: usize = 2;
+pub(crate) const HEIGHT_BYTE_NUM: usize = 2;
+
+// Indexes of height up to 64 have their paths handled as a single u64.
+const FAST_PATH_MAX_HEIGHT: usize = 64;
+const FAST_PATH_BYTE_NUM: usize = FAST_PATH_MAX_HEIGHT / BYTE_SIZE;
 
 /// The index of a tree node includes the height (the root with height 0),
 /// and the path from the root to the node.
@@ -35,27 +41,56 @@ const HEIGHT_BYTE_NUM: usize = 2;
 ///
 /// The i-th bit being 0 indicates that the node at height i+1 in the path
 /// is the left child of the node at height i, and 1 indicates the right child.
-#[derive(Debug, Default, Clone, Copy, Hash, PartialEq, Eq)]
-pub struct TreeIndex {
+///
+/// The path is stored in ```N``` bytes, so the maximum height of the index is ```8 * N```.
+/// Shallow trees can use a smaller ```N``` to save memory,
+/// e.g., [TreeIndex64](type.TreeIndex64.html) takes 10 bytes while [TreeIndex](type.TreeIndex.html) takes 34 bytes.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct TreeIndexN<const N: usize> {
     // The height of the node.
-    height: usize,
+    height: u16,
 
     // The position of the node, the least significant bit indicates
     // the direction from the root node, 0 for left, 1 for right.
-    path: [u8; BYTE_NUM],
+    path: [u8; N],
+}
+
+/// The tree index supporting heights up to [MAX_HEIGHT](constant.MAX_HEIGHT.html), used by the SMT.
+pub type TreeIndex = TreeIndexN<BYTE_NUM>;
+/// The tree index supporting heights up to 16.
+pub type TreeIndex16 = TreeIndexN<2>;
+/// The tree index supporting heights up to 32.
+pub type TreeIndex32 = TreeIndexN<4>;
+/// The tree index supporting heights up to 64.
+pub type TreeIndex64 = TreeIndexN<8>;
+
+impl<const N: usize> Default for TreeIndexN<N> {
+    fn default() -> Self {
+        TreeIndexN {
+            height: 0,
+            path: [0u8; N],
+        }
+    }
 }
 
 /// If two indexes have the same height, the right-side one is greater.
 ///
 /// If two indexes have different heights, the one with smaller height is greater,
 /// i.o.w., the higher the node in the SMT, the greater its index is.
-impl Ord for TreeIndex {
+impl<const N: usize> Ord for TreeIndexN<N> {
     fn cmp(&self, other: &Self) -> Ordering {
-        match self.height.cmp(&other.get_height()) {
+        match self.get_height().cmp(&other.get_height()) {
             Ordering::Greater => Ordering::Less,
             Ordering::Less => Ordering::Greater,
+            Ordering::Equal if self.get_height() <= FAST_PATH_MAX_HEIGHT => {
+                // The first bit in the path is the most significant one for the order.
+                let mask = Self::get_fast_path_mask(self.get_height());
+                (self.get_fast_path() & mask)
+                    .reverse_bits()
+                    .cmp(&(other.get_fast_path() & mask).reverse_bits())
+            }
             Ordering::Equal => {
-                for i in 0..self.height {
+                for i in 0..self.get_height() {
                     match self.get_bit(i).cmp(&other.get_bit(i)) {
                         Ordering::Greater => {
                             return Ordering::Greater;
@@ -78,79 +113,122 @@ impl Ord for TreeIndex {
 ///
 /// If two indexes have different heights, the one with smaller height is greater,
 /// i.o.w., the higher the node in the SMT, the greater its index is.
-impl PartialOrd for TreeIndex {
+impl<const N: usize> PartialOrd for TreeIndexN<N> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
-impl TreeIndex {
+impl<const N: usize> TreeIndexN<N> {
+    /// The maximum height of the index, i.e., the number of bits in the path.
+    pub const MAX_HEIGHT: usize = BYTE_SIZE * N;
+
     /// The constructor.
     ///
-    /// Panics if the input height exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html),
-    pub fn new(height: usize, pos: [u8; BYTE_NUM]) -> TreeIndex {
-        if height > MAX_HEIGHT {
+    /// Panics if the input height exceeds the maximum height of the index,
+    pub fn new(height: usize, pos: [u8; N]) -> Self {
+        if height > Self::MAX_HEIGHT {
             panic!("{}", DecodingError::ExceedMaxHeight);
         }
-        TreeIndex { height, path: pos }
+        TreeIndexN {
+            height: height as u16,
+            path: pos,
+        }
     }
 
     /// Construct TreeIndex from a u32 leaf position.
     ///
     /// Panics if:
-    /// * `height` exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html).
+    /// * `height` exceeds the maximum height of the index.
     /// * `pos` is not a valid leaf position in the tree of the specified `height`.
-    pub fn from_u32(height: usize, pos: u32) -> TreeIndex {
-        if height > MAX_HEIGHT {
-            panic!("{}", DecodingError::ExceedMaxHeight);
-        }
-        // Check if index fits to the tree.
-        if 32 - pos.leading_zeros() > height as u32 {
-            panic!("{}", DecodingError::IndexOverflow);
-        }
-        tree_index_from_u64(height, pos as u64)
+    pub fn from_u32(height: usize, pos: u32) -> Self {
+        Self::try_from_u32(height, pos).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Construct TreeIndex from a u32 leaf position.
+    ///
+    /// If `height` exceeds the maximum height of the index,
+    /// return [DecodingError::ExceedMaxHeight](../error/enum.DecodingError.html#variant.ExceedMaxHeight).
+    /// If `pos` is not a valid leaf position in the tree of the specified `height`,
+    /// return [DecodingError::IndexOverflow](../error/enum.DecodingError.html#variant.IndexOverflow).
+    pub fn try_from_u32(height: usize, pos: u32) -> Result<Self, DecodingError> {
+        Self::try_from_u64(height, pos as u64)
     }
 
     /// Construct TreeIndex from a u64 leaf position.
     ///
     /// Panics if:
-    /// * `height` exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html).
+    /// * `height` exceeds the maximum height of the index.
     /// * `pos` is not a valid leaf position in the tree of the specified `height`.
-    pub fn from_u64(height: usize, pos: u64) -> TreeIndex {
-        if height > MAX_HEIGHT {
-            panic!("{}", DecodingError::ExceedMaxHeight);
+    pub fn from_u64(height: usize, pos: u64) -> Self {
+        Self::try_from_u64(height, pos).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Construct TreeIndex from a u64 leaf position.
+    ///
+    /// If `height` exceeds the maximum height of the index,
+    /// return [DecodingError::ExceedMaxHeight](../error/enum.DecodingError.html#variant.ExceedMaxHeight).
+    /// If `pos` is not a valid leaf position in the tree of the specified `height`,
+    /// return [DecodingError::IndexOverflow](../error/enum.DecodingError.html#variant.IndexOverflow).
+    pub fn try_from_u64(height: usize, pos: u64) -> Result<Self, DecodingError> {
+        if height > Self::MAX_HEIGHT {
+            return Err(DecodingError::ExceedMaxHeight);
         }
         // Check if index fits to the tree.
         if 64 - pos.leading_zeros() > height as u32 {
-            panic!("{}", DecodingError::IndexOverflow);
+            return Err(DecodingError::IndexOverflow);
         }
-        tree_index_from_u64(height, pos)
+        Ok(Self::from_u64_unchecked(height, pos))
+    }
+
+    // Construct a tree index from a u64 leaf position without checking the input.
+    fn from_u64_unchecked(height: usize, pos: u64) -> Self {
+        let mut path = [0u8; N];
+        let mut pos = pos;
+        for i in (0..height).rev() {
+            path[i / BYTE_SIZE] += ((pos & 1) << (i % BYTE_SIZE)) as u8;
+            pos >>= 1;
+        }
+        Self::new(height, path)
+    }
+
+    /// Convert the index to one with a path of ```M``` bytes.
+    ///
+    /// If the height of the index exceeds the maximum height of the target type, return ```None```.
+    pub fn convert<const M: usize>(&self) -> Option<TreeIndexN<M>> {
+        if self.get_height() > TreeIndexN::<M>::MAX_HEIGHT {
+            return None;
+        }
+        let mut path = [0u8; M];
+        let byte_num = Self::get_byte_num_by_bit(self.get_height());
+        path[..byte_num].copy_from_slice(&self.path[..byte_num]);
+        Some(TreeIndexN::<M>::new(self.get_height(), path))
     }
 
     /// Returns a tree index of the left-most node (all bits in the path being 0) at the given height.
     ///
-    /// Panics if the input height exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html).
-    pub fn zero(height: usize) -> TreeIndex {
-        TreeIndex::new(height, [0u8; BYTE_NUM])
+    /// Panics if the input height exceeds the maximum height of the index.
+    pub fn zero(height: usize) -> Self {
+        Self::new(height, [0u8; N])
     }
 
     /// Returns the height of a tree index.
     pub fn get_height(&self) -> usize {
-        self.height
+        self.height as usize
     }
 
     /// Set the height of a tree index.
     ///
-    /// Panics if the input height exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html).
+    /// Panics if the input height exceeds the maximum height of the index.
     pub fn set_height(&mut self, height: usize) {
-        if height > MAX_HEIGHT {
+        if height > Self::MAX_HEIGHT {
             panic!("{}", DecodingError::ExceedMaxHeight);
         }
-        self.height = height;
+        self.height = height as u16;
     }
 
     /// Returns the path of a tree index.
-    pub fn get_path(&self) -> [u8; BYTE_NUM] {
+    pub fn get_path(&self) -> [u8; N] {
         self.path
     }
 
@@ -158,33 +236,46 @@ impl TreeIndex {
     ///
     /// Panics if queried bit index is out of the range ```[0, height-1]```.
     pub fn get_bit(&self, i: usize) -> u8 {
-        if i >= self.height {
+        if i >= self.get_height() {
             panic!("The input index is out of range, thus the queried bit doesn't exist.");
         }
         (self.path[i / BYTE_SIZE] >> (i % BYTE_SIZE)) & 1
     }
 
+    /// Get the i-th bit in the path, or ```None``` if it is out of the range ```[0, height-1]```.
+    pub fn try_get_bit(&self, i: usize) -> Option<u8> {
+        if i >= self.get_height() {
+            return None;
+        }
+        Some(self.get_bit(i))
+    }
+
     /// Returns the last bit in the path of the tree index.
     ///
     /// Panics if the tree index has height 0 thus the bit doesn't exist.
     pub fn get_last_bit(self) -> u8 {
-        if self.height == 0 {
+        if self.get_height() == 0 {
             panic!("The height is 0, thus the queried bit doesn't exist.");
         }
-        self.get_bit(self.height - 1)
+        self.get_bit(self.get_height() - 1)
     }
 
     /// Returns a tree index with the input height and the path being a prefix of the self path.
     ///
     /// Panics if the input height exceeds the height of the index.
-    pub fn get_prefix(&self, height: usize) -> TreeIndex {
-        if height > self.height {
+    pub fn get_prefix(&self, height: usize) -> Self {
+        if height > self.get_height() {
             panic!("The input height exceeds the height of the tree index.");
         }
-        let mut index = TreeIndex::new(height, self.path);
+        if height <= FAST_PATH_MAX_HEIGHT {
+            let mut index = Self::zero(height);
+            index.set_fast_path(self.get_fast_path() & Self::get_fast_path_mask(height));
+            return index;
+        }
+        let mut index = Self::new(height, self.path);
         let mut len = height;
         let mut flag: u32 = (1 << 8) - 1;
-        for i in 0..BYTE_NUM {
+        for i in 0..N {
             if len < BYTE_SIZE {
                 flag = (1 << len) - 1;
                 len = 0;
@@ -196,45 +287,58 @@ impl TreeIndex {
         index
     }
 
+    /// Returns a tree index with the input height and the path being a prefix of the self path,
+    /// or ```None``` if the input height exceeds the height of the index.
+    pub fn try_get_prefix(&self, height: usize) -> Option<Self> {
+        if height > self.get_height() {
+            return None;
+        }
+        Some(self.get_prefix(height))
+    }
+
     /// Randomly samples a path.
     pub fn randomize(&mut self) {
-        let mut rng = rand::thread_rng();
-        for i in 0..BYTE_NUM {
+        self.randomize_with(&mut rand::thread_rng());
+    }
+
+    /// Randomly samples a path from the input random number generator.
+    pub fn randomize_with<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        for i in 0..N {
             self.path[i] = rng.gen();
         }
-        *self = self.get_prefix(self.height);
+        *self = self.get_prefix(self.get_height());
     }
 
     /// Returns the tree index of the left child of a node.
     ///
-    /// Panics if the height of the child node exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html).
-    pub fn get_lch_index(&self) -> TreeIndex {
-        if self.height == MAX_HEIGHT {
+    /// Panics if the height of the child node exceeds the maximum height of the index.
+    pub fn get_lch_index(&self) -> Self {
+        if self.get_height() == Self::MAX_HEIGHT {
             panic!("The index already has the maximum height.");
         }
         let mut pos = self.path;
         // Change the new bit for the left child as 0.
-        pos[self.height / BYTE_SIZE] &= u8::MAX - (1 << (self.height % BYTE_SIZE));
-        TreeIndex::new(self.height + 1, self.path)
+        pos[self.get_height() / BYTE_SIZE] &= u8::MAX - (1 << (self.get_height() % BYTE_SIZE));
+        Self::new(self.get_height() + 1, pos)
     }
 
     /// Returns the tree index of the right child of a node.
     ///
-    /// Panics if the height of the child node exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html).
-    pub fn get_rch_index(&self) -> TreeIndex {
-        if self.height == MAX_HEIGHT {
+    /// Panics if the height of the child node exceeds the maximum height of the index.
+    pub fn get_rch_index(&self) -> Self {
+        if self.get_height() == Self::MAX_HEIGHT {
             panic!("The index already has the maximum height.");
         }
         let mut pos = self.path;
         // Change the new bit for the right child as 1.
-        pos[self.height / BYTE_SIZE] |= 1 << (self.height % BYTE_SIZE);
-        TreeIndex::new(self.height + 1, pos)
+        pos[self.get_height() / BYTE_SIZE] |= 1 << (self.get_height() % BYTE_SIZE);
+        Self::new(self.get_height() + 1, pos)
     }
 
     /// Returns the tree index of the child in the input direction of a node.
     ///
-    /// Panics if the height of the child node exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html).
-    pub fn get_child_index_by_dir(&self, dir: ChildDir) -> TreeIndex {
+    /// Panics if the height of the child node exceeds the maximum height of the index.
+    pub fn get_child_index_by_dir(&self, dir: ChildDir) -> Self {
         if dir == ChildDir::Left {
             self.get_lch_index()
         } else {
@@ -242,40 +346,181 @@ impl TreeIndex {
         }
     }
 
+    /// Returns true if the bits of the path beyond the height are all zero,
+    /// i.e., the index is in its canonical form, as constructed by the library.
+    pub fn is_canonical(&self) -> bool {
+        self.get_prefix(self.get_height()) == *self
+    }
+
     /// Returns the tree index of the sibling of a node.
     ///
     /// Panics if the queried node is the root, which means that the sibling doesn't exist.
-    pub fn get_sibling_index(&self) -> TreeIndex {
-        if self.height == 0 {
+    pub fn get_sibling_index(&self) -> Self {
+        if self.get_height() == 0 {
             panic!("The root doesn't have a sibling.");
         }
         let mut pos = self.path;
         // Change the last bit as the opposite.
-        pos[(self.height - 1) / BYTE_SIZE] ^= 1 << ((self.height - 1) % BYTE_SIZE);
-        TreeIndex::new(self.height, pos)
+        pos[(self.get_height() - 1) / BYTE_SIZE] ^= 1 << ((self.get_height() - 1) % BYTE_SIZE);
+        Self::new(self.get_height(), pos)
+    }
+
+    /// Returns the tree index of the sibling of a node, or ```None``` for the root.
+    pub fn try_get_sibling_index(&self) -> Option<Self> {
+        if self.get_height() == 0 {
+            return None;
+        }
+        Some(self.get_sibling_index())
     }
 
     /// Returns the tree index of the parent of a node.
     ///
     /// Panics if the queried node is the root, which means that the parent doesn't exist.
-    pub fn get_parent_index(&self) -> TreeIndex {
-        if self.height == 0 {
+    pub fn get_parent_index(&self) -> Self {
+        if self.get_height() == 0 {
             panic!("The root doesn't have a parent.");
         }
-        self.get_prefix(self.height - 1)
+        self.get_prefix(self.get_height() - 1)
+    }
+
+    /// Returns the tree index of the parent of a node, or ```None``` for the root.
+    pub fn try_get_parent_index(&self) -> Option<Self> {
+        if self.get_height() == 0 {
+            return None;
+        }
+        Some(self.get_parent_index())
+    }
+
+    /// Returns the length of the longest common prefix of the paths of two indexes,
+    /// which is at most the smaller height of the two,
+    /// i.e., the height of the lowest common ancestor.
+    ///
+    /// The paths are compared 64 bits at a time,
+    /// locating the first differing bit by the trailing zeros of the XOR of two words.
+    pub fn common_prefix_len(&self, other: &Self) -> usize {
+        let height = self.get_height().min(other.get_height());
+        let mut offset = 0;
+        for (lhs, rhs) in self
+            .path
+            .chunks(FAST_PATH_BYTE_NUM)
+            .zip(other.path.chunks(FAST_PATH_BYTE_NUM))
+        {
+            if offset >= height {
+                break;
+            }
+            let diff = Self::get_word(lhs) ^ Self::get_word(rhs);
+            if diff != 0 {
+                return height.min(offset + diff.trailing_zeros() as usize);
+            }
+            offset += FAST_PATH_MAX_HEIGHT;
+        }
+        height
+    }
+
+    /// Returns the index of the lowest common ancestor of two nodes.
+    pub fn lowest_common_ancestor(&self, other: &Self) -> Self {
+        self.get_prefix(self.common_prefix_len(other))
+    }
+
+    /// Returns the index of the ancestor at the input height,
+    /// or None if the height exceeds that of the index.
+    ///
+    /// Unlike [get_prefix](#method.get_prefix), an out-of-range height doesn't panic.
+    pub fn ancestor_at(&self, height: usize) -> Option<Self> {
+        if height > self.get_height() {
+            None
+        } else {
+            Some(self.get_prefix(height))
+        }
+    }
+
+    /// Returns true if self is an ancestor of the other node, or the same node,
+    /// i.e., the other node is in the subtree rooted at self.
+    pub fn is_ancestor_of(&self, other: &Self) -> bool {
+        self.get_height() <= other.get_height()
+            && self.common_prefix_len(other) == self.get_height()
+    }
+
+    /// Returns true if self is a descendant of the other node, or the same node,
+    /// i.e., self is in the subtree rooted at the other node.
+    pub fn is_descendant_of(&self, other: &Self) -> bool {
+        other.is_ancestor_of(self)
+    }
+
+    // Returns up to 8 bytes of the path as a u64, the i-th bit in the bytes being the i-th least significant bit.
+    fn get_word(bytes: &[u8]) -> u64 {
+        let mut word = [0u8; FAST_PATH_BYTE_NUM];
+        word[..bytes.len()].copy_from_slice(bytes);
+        u64::from_le_bytes(word)
+    }
+
+    /// Returns the first 64 bits of the path as a u64, the i-th bit in the path being the i-th least significant bit.
+    fn get_fast_path(&self) -> u64 {
+        Self::get_word(&self.path[..N.min(FAST_PATH_BYTE_NUM)])
+    }
+
+    /// Set the first 64 bits of the path from a u64, the i-th bit in the path being the i-th least significant bit.
+    fn set_fast_path(&mut self, path: u64) {
+        let len = N.min(FAST_PATH_BYTE_NUM);
+        self.path[..len].copy_from_slice(&path.to_le_bytes()[..len]);
+    }
+
+    /// Returns the mask of the first ```height``` bits of the u64 path.
+    fn get_fast_path_mask(height: usize) -> u64 {
+        if height >= FAST_PATH_MAX_HEIGHT {
+            u64::MAX
+        } else {
+            (1u64 << height) - 1
+        }
+    }
+
+    /// Returns the bytes of the path used by the height, i.e., the first ```ceiling(height/8)``` bytes.
+    pub fn get_path_bytes(&self) -> Vec<u8> {
+        self.path[..Self::get_byte_num_by_bit(self.get_height())].to_vec()
+    }
+
+    /// Construct a tree index from the height and the bytes of the path used by the height.
+    ///
+    /// If the height exceeds the maximum height of the index,
+    /// return [DecodingError::ExceedMaxHeight](../error/enum.DecodingError.html#variant.ExceedMaxHeight).
+    ///
+    /// If the number of path bytes doesn't match with the height,
+    /// return [DecodingError::BytesNotEnough](../error/enum.DecodingError.html#variant.BytesNotEnough)
+    /// or [DecodingError::TooManyEncodedBytes](../error/enum.DecodingError.html#variant.TooManyEncodedBytes).
+    ///
+    /// If the last byte has set bits beyond the height,
+    /// return [DecodingError::NonCanonicalEncoding](../error/enum.DecodingError.html#variant.NonCanonicalEncoding).
+    pub fn from_path_bytes(height: usize, bytes: &[u8]) -> Result<Self, DecodingError> {
+        if height > Self::MAX_HEIGHT {
+            return Err(DecodingError::ExceedMaxHeight);
+        }
+        let byte_num = Self::get_byte_num_by_bit(height);
+        if bytes.len() < byte_num {
+            return Err(DecodingError::BytesNotEnough);
+        }
+        if bytes.len() > byte_num {
+            return Err(DecodingError::TooManyEncodedBytes);
+        }
+        let mut path = [0u8; N];
+        path[..byte_num].copy_from_slice(bytes);
+        let index = Self::new(height, path);
+        if !index.is_canonical() {
+            return Err(DecodingError::NonCanonicalEncoding);
+        }
+        Ok(index)
     }
 
     /// Returns the number of bytes for encoding the bit array by the number of bits.
-    fn get_byte_num_by_bit(bit_num: usize) -> usize {
+    pub(crate) fn get_byte_num_by_bit(bit_num: usize) -> usize {
         let mut byte_num = bit_num / BYTE_SIZE;
-        if bit_num % BYTE_SIZE > 0 {
+        if bit_num % BYTE_SIZE != 0 {
             byte_num += 1;
         }
         byte_num
     }
 
     /// Returns the left/right index to the input index, direction depending on the input.
-    fn get_dir_index(&self, dir: ChildDir) -> Option<TreeIndex> {
+    fn get_dir_index(&self, dir: ChildDir) -> Option<Self> {
         let mut opp_dir = ChildDir::Left;
         let mut dir_bit = 1;
         if dir == ChildDir::Left {
@@ -286,14 +531,14 @@ impl TreeIndex {
         // Gets the closest ancestor that has a dir child not on the path from the root to the input index.
         // Retrieve the dir child, which is the root of the subtree that contains the dir index.
         let mut index = *self;
-        for i in (0..self.height).rev() {
+        for i in (0..self.get_height()).rev() {
             if self.get_bit(i) == 1 - dir_bit {
                 index = index.get_prefix(i).get_child_index_by_dir(dir);
                 break;
             }
         }
         // Gets the opp_dir-most child, which is the desired index.
-        while index.get_height() < self.height {
+        while index.get_height() < self.get_height() {
             index = index.get_child_index_by_dir(opp_dir);
         }
 
@@ -307,19 +552,19 @@ impl TreeIndex {
     }
 
     /// Returns the index on the left of self.
-    pub fn get_left_index(&self) -> Option<TreeIndex> {
+    pub fn get_left_index(&self) -> Option<Self> {
         self.get_dir_index(ChildDir::Left)
     }
 
     /// Returns the index on the right of self.
-    pub fn get_right_index(&self) -> Option<TreeIndex> {
+    pub fn get_right_index(&self) -> Option<Self> {
         self.get_dir_index(ChildDir::Right)
     }
 
     /// Encode a list of tree indexes in the format: ```height || path || ... || path```.
     ///
     /// If the input list is empty, return empty vector.
-    pub fn serialize(list: &[TreeIndex]) -> Vec<u8> {
+    pub fn serialize(list: &[Self]) -> Vec<u8> {
         let mut vec: Vec<u8> = Vec::new();
         // Returns empty vector if the input list is empty.
         if list.is_empty() {
@@ -328,7 +573,7 @@ impl TreeIndex {
 
         // Encode the height.
         let height = list[0].get_height();
-        let mut height_bytes = usize_to_bytes(height, HEIGHT_BYTE_NUM);
+        let mut height_bytes = u64_to_bytes(height as u64, HEIGHT_BYTE_NUM);
         vec.append(&mut height_bytes);
 
         // Encode all the paths, each takes ceiling(height/8) bytes.
@@ -345,48 +590,207 @@ impl TreeIndex {
     /// ```num``` is the target number of tree indexes,
     /// ```begin``` is the beginning position of ```bytes```.
     ///
-    /// If the decoded height exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html),
+    /// If the decoded height exceeds the maximum height of the index,
     /// return [DecodingError::ExceedMaxHeight](../error/enum.DecodingError.html#variant.ExceedMaxHeight).
     ///
     /// If the bytes are not enough for decoding,
     /// return [DecodingError::BytesNotEnough](../error/enum.DecodingError.html#variant.BytesNotEnough).
+    ///
+    /// If the decoded height is 0 and ```num``` is more than 1,
+    /// return [DecodingError::IndexOverflow](../error/enum.DecodingError.html#variant.IndexOverflow).
     pub fn deserialize_as_a_unit(
         bytes: &[u8],
         num: usize,
         begin: &mut usize,
-    ) -> Result<Vec<TreeIndex>, DecodingError> {
+    ) -> Result<Vec<Self>, DecodingError> {
         // Return empty list if the input byte is empty.
         if bytes.len() - *begin == 0 && num == 0 {
             return Ok(Vec::new());
         }
 
         // Decode the height.
-        let height = bytes_to_usize(bytes, HEIGHT_BYTE_NUM, begin);
+        let height = bytes_to_count(bytes, HEIGHT_BYTE_NUM, begin);
         if let Err(e) = height {
             return Err(e);
         }
         let height = height.unwrap();
-        if height > MAX_HEIGHT {
+        if height > Self::MAX_HEIGHT {
             return Err(DecodingError::ExceedMaxHeight);
         }
 
+        // A tree of height 0 has a single index, which takes no bytes to encode.
+        if height == 0 && num > 1 {
+            return Err(DecodingError::IndexOverflow);
+        }
+
         // Check if the bytes are enough for the target number of indexes.
         let index_byte_num = Self::get_byte_num_by_bit(height);
-        if (bytes.len() - *begin) < index_byte_num * num {
-            return Err(DecodingError::BytesNotEnough);
+        match index_byte_num.checked_mul(num) {
+            Some(len) if len <= bytes.len() - *begin => (),
+            _ => return Err(DecodingError::BytesNotEnough),
         }
 
         // Decode each path in the indexes.
-        let mut vec: Vec<TreeIndex> = Vec::new();
+        let mut vec: Vec<Self> = Vec::new();
         for _i in 0..num {
-            let mut path = [0u8; BYTE_NUM];
+            let mut path = [0u8; N];
             for item in path.iter_mut().take(index_byte_num) {
                 *item = bytes[*begin];
                 *begin += 1;
             }
-            vec.push(TreeIndex::new(height, path));
+            vec.push(Self::new(height, path));
         }
 
         Ok(vec)
     }
+
+    /// Read ```num``` tree indexes (```height || path || ... || path```) from the reader,
+    /// reading exactly the encoded bytes.
+    ///
+    /// If ```num``` is zero, read nothing and return empty list.
+    ///
+    /// If the reader ends before the indexes are complete,
+    /// return [DecodingError::BytesNotEnough](../error/enum.DecodingError.html#variant.BytesNotEnough).
+    ///
+    /// If the decoded height is 0 and ```num``` is more than 1,
+    /// return [DecodingError::IndexOverflow](../error/enum.DecodingError.html#variant.IndexOverflow).
+    pub fn deserialize_from<R: Read + ?Sized>(
+        r: &mut R,
+        num: usize,
+    ) -> Result<Vec<Self>, DecodingError> {
+        if num == 0 {
+            return Ok(Vec::new());
+        }
+        let mut bytes = [0u8; HEIGHT_BYTE_NUM];
+        read_bytes(r, &mut bytes)?;
+        let height = bytes_to_count(&bytes, HEIGHT_BYTE_NUM, &mut 0)?;
+        if height > Self::MAX_HEIGHT {
+            return Err(DecodingError::ExceedMaxHeight);
+        }
+
+        if height == 0 && num > 1 {
+            return Err(DecodingError::IndexOverflow);
+        }
+
+        // Read the paths one by one, not trusting the number of indexes for allocation.
+        let index_byte_num = Self::get_byte_num_by_bit(height);
+        let mut vec: Vec<Self> = Vec::new();
+        for _i in 0..num {
+            let mut path = [0u8; N];
+            read_bytes(r, &mut path[..index_byte_num])?;
+            vec.push(Self::new(height, path));
+        }
+        Ok(vec)
+    }
+}
+
+impl<const N: usize> Serializable for TreeIndexN<N> {
+    /// Encode a tree index in the format: ```height || path```,
+    /// the same as the encoding of the list of the single index.
+    fn serialize(&self) -> Vec<u8> {
+        Self::serialize(&[*self])
+    }
+
+    /// Decode input bytes (```height || path```) as a tree index.
+    ///
+    /// Note that the static methods of lists of indexes take precedence over the trait methods
+    /// when called by path, so call ```<TreeIndex as Serializable>::deserialize_as_a_unit``` explicitly.
+    fn deserialize_as_a_unit(bytes: &[u8], begin: &mut usize) -> Result<Self, DecodingError> {
+        Ok(Self::deserialize_as_a_unit(bytes, 1, begin)?[0])
+    }
+}
+
+/// An iterator over all tree indexes of the same height between two bounds, both inclusive,
+/// in increasing order, or in decreasing order with ```rev```.
+///
+/// For example, ```TreeIndexRange::new(TreeIndex::from_u64(4, 2), TreeIndex::from_u64(4, 9)).step_by(3)```
+/// yields the leaf positions 2, 5 and 8 of a tree of height 4.
+#[derive(Debug, Clone)]
+pub struct TreeIndexRange<const N: usize = BYTE_NUM> {
+    // The next index from the front and from the back, None when the range is exhausted.
+    range: Option<(TreeIndexN<N>, TreeIndexN<N>)>,
+}
+
+impl<const N: usize> TreeIndexRange<N> {
+    /// The constructor of the range from ```first``` to ```last```, both inclusive.
+    /// If ```first``` is greater than ```last```, the range is empty.
+    ///
+    /// Panics if the two indexes are of different heights.
+    pub fn new(first: TreeIndexN<N>, last: TreeIndexN<N>) -> TreeIndexRange<N> {
+        if first.get_height() != last.get_height() {
+            panic!("The bounds of the range are of different heights.");
+        }
+        TreeIndexRange {
+            range: if first <= last {
+                Some((first, last))
+            } else {
+                None
+            },
+        }
+    }
+
+    /// The range of all indexes of the input height.
+    ///
+    /// Panics if the height exceeds the maximum height of the index.
+    pub fn all(height: usize) -> TreeIndexRange<N> {
+        let mut last = TreeIndexN::zero(0);
+        while last.get_height() < height {
+            last = last.get_rch_index();
+        }
+        TreeIndexRange::new(TreeIndexN::zero(height), last)
+    }
+}
+
+impl<const N: usize> Iterator for TreeIndexRange<N> {
+    type Item = TreeIndexN<N>;
+
+    fn next(&mut self) -> Option<TreeIndexN<N>> {
+        let (front, back) = self.range?;
+        self.range = if front == back {
+            None
+        } else {
+            // The front is less than the back, so there is an index on its right.
+            front.get_right_index().map(|next| (next, back))
+        };
+        Some(front)
+    }
+}
+
+impl<const N: usize> DoubleEndedIterator for TreeIndexRange<N> {
+    fn next_back(&mut self) -> Option<TreeIndexN<N>> {
+        let (front, back) = self.range?;
+        self.range = if front == back {
+            None
+        } else {
+            back.get_left_index().map(|next| (front, next))
+        };
+        Some(back)
+    }
+}
+
+/// Maps a U256, e.g., an Ethereum storage key or account hash, to the leaf position of a tree of height 256,
+/// the most significant bit giving the direction from the root.
+#[cfg(feature = "primitive-types")]
+impl From<primitive_types::U256> for TreeIndex {
+    fn from(value: primitive_types::U256) -> TreeIndex {
+        let mut path = [0u8; BYTE_NUM];
+        value.to_big_endian(&mut path);
+        for byte in path.iter_mut() {
+            *byte = byte.reverse_bits();
+        }
+        TreeIndex::new(MAX_HEIGHT, path)
+    }
+}
+
+/// Maps a tree index to its position among the nodes of its height,
+/// which is the inverse of ```From<U256>``` for indexes of height 256.
+#[cfg(feature = "primitive-types")]
+impl From<TreeIndex> for primitive_types::U256 {
+    fn from(index: TreeIndex) -> primitive_types::U256 {
+        let mut bytes = index.get_prefix(index.get_height()).get_path();
+        for byte in bytes.iter_mut() {
+            *byte = byte.reverse_bits();
+        }
+        primitive_types::U256::from_big_endian(&bytes) >> (MAX_HEIGHT - index.get_height())
+    }
 }
@@ -5,11 +5,44 @@
 
 //! A library supplying paddable sparse Merkle tree.
 
+#[macro_use]
+mod logging;
+
+mod arena;
+#[cfg(feature = "ark")]
+pub mod ark;
+#[cfg(feature = "borsh")]
+mod borsh_impls;
+#[cfg(feature = "cbor")]
+mod cbor;
+pub mod compressed;
+pub mod dynamic;
+pub mod epoch_log;
 pub mod error;
+pub mod filter;
+pub mod forest;
+pub mod incremental;
 pub mod index;
+pub mod metrics;
+pub mod nary;
 pub mod node_template;
 pub mod pad_secret;
+pub mod partial;
+#[cfg(feature = "pedersen")]
+pub mod pedersen;
 pub mod proof;
+pub mod proof_cache;
+#[cfg(feature = "proto")]
+pub mod proto;
+#[cfg(feature = "serde")]
+pub mod serde_node;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "ssz")]
+pub mod ssz;
+pub mod storage;
+#[cfg(feature = "test-vectors")]
+pub mod test_vectors;
 pub mod traits;
 pub mod tree;
 pub mod utils;
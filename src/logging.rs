@@ -0,0 +1,26 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Internal diagnostics, emitted through the ```log``` crate when the ```log``` feature is enabled
+//! and compiled away otherwise.
+
+/// Emit a debug event, e.g., on verification failures, decoding errors and build statistics.
+#[cfg(feature = "log")]
+macro_rules! log_debug {
+    ($($arg:tt)+) => {
+        log::debug!(target: "smtree", $($arg)+)
+    };
+}
+
+/// Emit a debug event, e.g., on verification failures, decoding errors and build statistics.
+#[cfg(not(feature = "log"))]
+macro_rules! log_debug {
+    ($($arg:tt)+) => {{
+        // Type-check the arguments without evaluating them.
+        if false {
+            let _ = format_args!($($arg)+);
+        }
+    }};
+}
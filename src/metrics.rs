@@ -0,0 +1,137 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! This module provides an optional hook for counting the work done by tree operations,
+//! i.e., merge (hash) invocations, allocated nodes and created padding nodes,
+//! for profiling different digests and tree heights.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// The tree operations reporting metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// Building the tree from a list of leaves.
+    Build,
+    /// Updating a single leaf.
+    Update,
+    /// Generating an inclusion proof or a random sampling proof.
+    ProofGeneration,
+}
+
+/// The work done by a single tree operation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OperationMetrics {
+    /// The number of merge (hash) invocations,
+    /// not including the hashes deriving padding nodes, which are counted by ```paddings```.
+    pub merges: u64,
+    /// The number of allocated tree nodes, or proof nodes copied from the tree for proof generation.
+    pub nodes_allocated: u64,
+    /// The number of created padding nodes, or generated padding node proofs for proof generation.
+    pub paddings: u64,
+}
+
+impl OperationMetrics {
+    /// Add up the metrics of two operations.
+    pub fn add(&mut self, other: &OperationMetrics) {
+        self.merges += other.merges;
+        self.nodes_allocated += other.nodes_allocated;
+        self.paddings += other.paddings;
+    }
+}
+
+/// Trait for receiving the metrics of tree operations.
+///
+/// The recorder is called once per operation with the totals of that operation,
+/// counted where the nodes are merged, allocated or padded,
+/// so that the cost of recording doesn't grow with the number of hashes.
+pub trait MetricsRecorder: Send + Sync {
+    /// Record the metrics of one operation.
+    fn record(&self, op: Operation, metrics: &OperationMetrics);
+}
+
+/// A recorder accumulating the metrics of each operation type in atomic counters.
+#[derive(Debug, Default)]
+pub struct CountingRecorder {
+    counters: [[AtomicU64; 4]; 3],
+}
+
+impl CountingRecorder {
+    /// The constructor.
+    pub fn new() -> CountingRecorder {
+        CountingRecorder::default()
+    }
+
+    /// Returns the accumulated metrics of the input operation type.
+    pub fn get_totals(&self, op: Operation) -> OperationMetrics {
+        let counters = &self.counters[Self::slot(op)];
+        OperationMetrics {
+            merges: counters[0].load(Ordering::Relaxed),
+            nodes_allocated: counters[1].load(Ordering::Relaxed),
+            paddings: counters[2].load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the number of recorded operations of the input operation type.
+    pub fn get_operations_num(&self, op: Operation) -> u64 {
+        self.counters[Self::slot(op)][3].load(Ordering::Relaxed)
+    }
+
+    /// Reset all counters to zero.
+    pub fn reset(&self) {
+        for counters in &self.counters {
+            for counter in counters {
+                counter.store(0, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn slot(op: Operation) -> usize {
+        match op {
+            Operation::Build => 0,
+            Operation::Update => 1,
+            Operation::ProofGeneration => 2,
+        }
+    }
+}
+
+impl MetricsRecorder for CountingRecorder {
+    fn record(&self, op: Operation, metrics: &OperationMetrics) {
+        let counters = &self.counters[Self::slot(op)];
+        counters[0].fetch_add(metrics.merges, Ordering::Relaxed);
+        counters[1].fetch_add(metrics.nodes_allocated, Ordering::Relaxed);
+        counters[2].fetch_add(metrics.paddings, Ordering::Relaxed);
+        counters[3].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// The optional metrics recorder attached to a tree.
+#[derive(Clone, Default)]
+pub(crate) struct MetricsHook(Option<Arc<dyn MetricsRecorder>>);
+
+impl MetricsHook {
+    pub(crate) fn new(recorder: Option<Arc<dyn MetricsRecorder>>) -> MetricsHook {
+        MetricsHook(recorder)
+    }
+
+    pub(crate) fn get(&self) -> Option<&Arc<dyn MetricsRecorder>> {
+        self.0.as_ref()
+    }
+
+    pub(crate) fn record(&self, op: Operation, metrics: &OperationMetrics) {
+        if let Some(recorder) = &self.0 {
+            recorder.record(op, metrics);
+        }
+    }
+}
+
+impl fmt::Debug for MetricsHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("MetricsHook")
+            .field(&self.0.as_ref().map(|_| "MetricsRecorder"))
+            .finish()
+    }
+}
@@ -0,0 +1,380 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! This module provides a paddable sparse Merkle tree of arity ```ARITY```, a power of two,
+//! together with its Merkle proofs carrying ```ARITY - 1``` siblings per level.
+//!
+//! Each level of the N-ary tree consumes ```log2(ARITY)``` bits of a [TreeIndex](../index/type.TreeIndex.html),
+//! i.e., a digit of the index,
+//! so a leaf of an N-ary tree of height ```h``` has an index of height ```h * log2(ARITY)```,
+//! and the maximum height of the N-ary tree is ```MAX_HEIGHT / log2(ARITY)```.
+//! The children of a node are merged by [Mergeable::merge_n](../traits/trait.Mergeable.html#method.merge_n),
+//! and a missing child is a padding node at the index of the child.
+//!
+//! Higher arities shorten the proofs in levels at the cost of more siblings per level,
+//! which pays off for hash functions with cheap wide inputs.
+
+use std::collections::BTreeMap;
+
+use crate::pad_secret::{Secret, ALL_ZEROS_SECRET};
+use crate::{
+    error::{DecodingError, TreeError},
+    index::{TreeIndex, MAX_HEIGHT},
+    traits::{Mergeable, Paddable, PaddingContext, PaddingDomain, ProofExtractable, Serializable},
+};
+
+/// Returns the number of index bits per level of an N-ary tree.
+///
+/// Panics if the arity is not a power of two greater than one.
+fn get_digit_bits(arity: usize) -> usize {
+    if arity < 2 || !arity.is_power_of_two() {
+        panic!(
+            "The arity {} is not a power of two greater than one.",
+            arity
+        );
+    }
+    arity.trailing_zeros() as usize
+}
+
+/// Returns the index of the ```digit```-th child of the node at the input index.
+fn get_child_index(idx: &TreeIndex, digit: usize, digit_bits: usize) -> TreeIndex {
+    let mut child = *idx;
+    for i in (0..digit_bits).rev() {
+        if (digit >> i) & 1 == 0 {
+            child = child.get_lch_index();
+        } else {
+            child = child.get_rch_index();
+        }
+    }
+    child
+}
+
+/// Returns the digit of the input index at the input level, i.e., the position of the node among its siblings.
+fn get_digit(idx: &TreeIndex, level: usize, digit_bits: usize) -> usize {
+    (0..digit_bits).fold(0, |digit, i| {
+        (digit << 1) | idx.get_bit(level * digit_bits + i) as usize
+    })
+}
+
+/// A paddable sparse Merkle tree of arity ```ARITY```.
+///
+/// The tree keeps the nodes of each level, including the padding nodes, in a map by the tree index.
+#[derive(Debug, Clone)]
+pub struct NaryMerkleTree<P, const ARITY: usize> {
+    height: usize,
+    // The nodes of each level, from the root down to the leaves.
+    levels: Vec<BTreeMap<TreeIndex, P>>,
+    // The epoch and the tag of the padding nodes.
+    padding_domain: PaddingDomain,
+}
+
+impl<P: Clone + Default + Mergeable + Paddable + ProofExtractable, const ARITY: usize>
+    NaryMerkleTree<P, ARITY>
+{
+    /// The number of index bits per level.
+    const DIGIT_BITS: usize = ARITY.trailing_zeros() as usize;
+
+    /// The constructor, with the height in levels.
+    ///
+    /// Panics if the arity is not a power of two greater than one,
+    /// or the height of the leaf indexes exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html).
+    pub fn new(height: usize) -> NaryMerkleTree<P, ARITY> {
+        if height * get_digit_bits(ARITY) > MAX_HEIGHT {
+            panic!("{}", DecodingError::ExceedMaxHeight);
+        }
+        let mut root = BTreeMap::new();
+        root.insert(
+            TreeIndex::zero(0),
+            P::padding_with_context(
+                &TreeIndex::zero(0),
+                &PaddingContext::new(height * Self::DIGIT_BITS, &ALL_ZEROS_SECRET),
+            ),
+        );
+        let mut levels = vec![BTreeMap::new(); height + 1];
+        levels[0] = root;
+        NaryMerkleTree {
+            height,
+            levels,
+            padding_domain: PaddingDomain::default(),
+        }
+    }
+
+    /// Returns the height of the tree in levels.
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the height of the leaf indexes in bits.
+    pub fn get_index_height(&self) -> usize {
+        self.height * Self::DIGIT_BITS
+    }
+
+    /// Returns the epoch and the application tag of the padding nodes of the tree.
+    pub fn get_padding_domain(&self) -> &PaddingDomain {
+        &self.padding_domain
+    }
+
+    /// Set the epoch and the application tag of the padding nodes, before building the tree.
+    ///
+    /// The padding context of the tree has the height of the leaf indexes in bits.
+    pub fn set_padding_domain(&mut self, domain: PaddingDomain) {
+        self.padding_domain = domain;
+    }
+
+    /// Returns the number of nodes in the tree, including the padding nodes.
+    pub fn get_nodes_num(&self) -> usize {
+        self.levels.iter().map(|level| level.len()).sum()
+    }
+
+    /// Returns the raw data of the root.
+    pub fn get_root_raw(&self) -> &P {
+        &self.levels[0][&TreeIndex::zero(0)]
+    }
+
+    /// Returns the data of the root that is visible in the Merkle proof.
+    pub fn get_root(&self) -> <P as ProofExtractable>::ProofNode {
+        self.get_root_raw().get_proof_node()
+    }
+
+    /// Returns the value of the leaf at the input index, or None if it is not a node of the tree.
+    ///
+    /// Note that a padding node at the leaf level is returned as well.
+    pub fn get_leaf_by_index(&self, idx: &TreeIndex) -> Option<&P> {
+        self.levels[self.height].get(idx)
+    }
+
+    /// Check if the tree indexes in the list are all valid and sorted,
+    /// as [SparseMerkleTree::check_index_list_validity_at](../tree/struct.SparseMerkleTree.html#method.check_index_list_validity_at)
+    /// does for the leaf index height of this tree, identifying the first offending entry.
+    pub fn check_index_list_validity_at(&self, list: &[(TreeIndex, P)]) -> Option<TreeError> {
+        for (i, item) in list.iter().enumerate() {
+            if item.0.get_height() != self.get_index_height() {
+                return Some(TreeError::HeightNotMatchAt {
+                    position: i,
+                    index: item.0,
+                });
+            }
+            if i > 0 {
+                if item.0 < list[i - 1].0 {
+                    return Some(TreeError::IndexNotSortedAt {
+                        position: i,
+                        index: item.0,
+                    });
+                }
+                if item.0 == list[i - 1].0 {
+                    return Some(TreeError::IndexDuplicatedAt {
+                        position: i,
+                        index: item.0,
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    /// Build the tree from the input list of sorted index-value pairs, index being the sorting key.
+    ///
+    /// Panics if the input list is not valid.
+    pub fn build(&mut self, list: &[(TreeIndex, P)], secret: &Secret) {
+        if let Some(x) = self.check_index_list_validity_at(list) {
+            panic!("{}", x);
+        }
+        let ctx = self
+            .padding_domain
+            .get_context(self.get_index_height(), secret);
+        let mut levels = vec![BTreeMap::new(); self.height + 1];
+        levels[self.height] = list.iter().cloned().collect();
+
+        // Compute the parents of each level from the bottom up,
+        // padding the missing children of each parent.
+        for level in (0..self.height).rev() {
+            let parents: Vec<TreeIndex> = {
+                let mut parents: Vec<TreeIndex> = levels[level + 1]
+                    .keys()
+                    .map(|idx| idx.get_prefix(level * Self::DIGIT_BITS))
+                    .collect();
+                parents.dedup();
+                parents
+            };
+            for parent in parents {
+                let value = Self::merge_children(&mut levels[level + 1], &parent, &ctx);
+                levels[level].insert(parent, value);
+            }
+        }
+        if levels[0].is_empty() {
+            levels[0].insert(
+                TreeIndex::zero(0),
+                P::padding_with_context(&TreeIndex::zero(0), &ctx),
+            );
+        }
+        self.levels = levels;
+        log_debug!(
+            "Built {}-ary SMT of height {} with {} leaves and {} nodes",
+            ARITY,
+            self.height,
+            list.len(),
+            self.get_nodes_num()
+        );
+    }
+
+    /// Update the leaf at the input index with the input value,
+    /// padding the missing siblings along the path and recomputing the ancestors.
+    ///
+    /// Panics if the height of the input index doesn't match with the leaf index height of the tree.
+    pub fn update(&mut self, key: &TreeIndex, value: P, secret: &Secret) {
+        if key.get_height() != self.get_index_height() {
+            panic!("{}", TreeError::HeightNotMatch);
+        }
+        let ctx = self
+            .padding_domain
+            .get_context(self.get_index_height(), secret);
+        self.levels[self.height].insert(*key, value);
+        for level in (0..self.height).rev() {
+            let parent = key.get_prefix(level * Self::DIGIT_BITS);
+            let (upper, lower) = self.levels.split_at_mut(level + 1);
+            let value = Self::merge_children(&mut lower[0], &parent, &ctx);
+            upper[level].insert(parent, value);
+        }
+    }
+
+    /// Merge the children of the parent at the input index in the child level,
+    /// inserting padding nodes in the input context for the missing children.
+    fn merge_children(
+        children: &mut BTreeMap<TreeIndex, P>,
+        parent: &TreeIndex,
+        ctx: &PaddingContext,
+    ) -> P {
+        let values: Vec<P> = (0..ARITY)
+            .map(|digit| {
+                let idx = get_child_index(parent, digit, Self::DIGIT_BITS);
+                children
+                    .entry(idx)
+                    .or_insert_with(|| P::padding_with_context(&idx, ctx))
+                    .clone()
+            })
+            .collect();
+        P::merge_n(&values)
+    }
+
+    /// Returns the Merkle proof of the leaf at the input index,
+    /// or None if the index is not a node at the leaf level of the tree.
+    pub fn generate_proof(&self, idx: &TreeIndex) -> Option<NaryMerkleProof<P, ARITY>> {
+        self.get_leaf_by_index(idx)?;
+        let mut siblings = Vec::with_capacity(self.height * (ARITY - 1));
+        for level in 0..self.height {
+            let parent = idx.get_prefix(level * Self::DIGIT_BITS);
+            let own = get_digit(idx, level, Self::DIGIT_BITS);
+            for digit in (0..ARITY).filter(|digit| *digit != own) {
+                let child = get_child_index(&parent, digit, Self::DIGIT_BITS);
+                siblings.push(self.levels[level + 1].get(&child)?.get_proof_node());
+            }
+        }
+        Some(NaryMerkleProof {
+            index: *idx,
+            siblings,
+        })
+    }
+}
+
+/// A Merkle proof of a single leaf in an N-ary tree,
+/// carrying the ```ARITY - 1``` siblings of each level from the root down,
+/// and the siblings of a level from left to right.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NaryMerkleProof<V: ProofExtractable, const ARITY: usize> {
+    index: TreeIndex,
+    siblings: Vec<V::ProofNode>,
+}
+
+impl<V: ProofExtractable, const ARITY: usize> NaryMerkleProof<V, ARITY>
+where
+    V::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// The constructor.
+    pub fn new(index: TreeIndex, siblings: Vec<V::ProofNode>) -> NaryMerkleProof<V, ARITY> {
+        NaryMerkleProof { index, siblings }
+    }
+
+    /// Returns the tree index of the proved leaf.
+    pub fn get_index(&self) -> &TreeIndex {
+        &self.index
+    }
+
+    /// Returns the siblings in the proof.
+    pub fn get_siblings(&self) -> &[V::ProofNode] {
+        &self.siblings
+    }
+
+    /// Verify the proof of the input leaf against the root.
+    pub fn verify(&self, leaf: &V::ProofNode, root: &V::ProofNode) -> bool {
+        let digit_bits = get_digit_bits(ARITY);
+        if self.index.get_height() % digit_bits != 0 {
+            log_debug!("N-ary proof verification failed: invalid index height");
+            return false;
+        }
+        let height = self.index.get_height() / digit_bits;
+        if self.siblings.len() != height * (ARITY - 1) {
+            log_debug!(
+                "N-ary proof verification failed: {} siblings for height {}",
+                self.siblings.len(),
+                height
+            );
+            return false;
+        }
+
+        // Compute the nodes along the path from the leaf up.
+        let mut value = leaf.clone();
+        for (level, siblings) in self.siblings.chunks(ARITY - 1).enumerate().rev() {
+            let own = get_digit(&self.index, level, digit_bits);
+            let mut children: Vec<V::ProofNode> = Vec::with_capacity(ARITY);
+            children.extend_from_slice(&siblings[..own]);
+            children.push(value);
+            children.extend_from_slice(&siblings[own..]);
+            value = Mergeable::merge_n(&children);
+        }
+
+        if value != *root {
+            log_debug!("N-ary proof verification failed: root mismatch");
+            return false;
+        }
+        true
+    }
+}
+
+impl<V: ProofExtractable, const ARITY: usize> Serializable for NaryMerkleProof<V, ARITY>
+where
+    V::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// Encode a proof in the format: ```tree_index || siblings```,
+    /// where the number of siblings follows from the index height and the arity.
+    fn serialize(&self) -> Vec<u8> {
+        let mut bytes = TreeIndex::serialize(&[self.index]);
+        for item in &self.siblings {
+            bytes.append(&mut item.serialize());
+        }
+        bytes
+    }
+
+    /// Decode input bytes (```tree_index || siblings```) as an N-ary Merkle proof.
+    ///
+    /// If the index height is not a multiple of the bits per level,
+    /// return [DecodingError::IndexOverflow](../error/enum.DecodingError.html#variant.IndexOverflow).
+    fn deserialize_as_a_unit(
+        bytes: &[u8],
+        begin: &mut usize,
+    ) -> Result<NaryMerkleProof<V, ARITY>, DecodingError> {
+        let index = TreeIndex::deserialize_as_a_unit(bytes, 1, begin)?;
+        let index = index[0];
+        let digit_bits = get_digit_bits(ARITY);
+        if index.get_height() % digit_bits != 0 {
+            return Err(DecodingError::IndexOverflow);
+        }
+        let sibling_num = index.get_height() / digit_bits * (ARITY - 1);
+        let mut siblings = Vec::with_capacity(sibling_num);
+        for _i in 0..sibling_num {
+            siblings.push(V::ProofNode::deserialize_as_a_unit(bytes, begin)?);
+        }
+        Ok(NaryMerkleProof { index, siblings })
+    }
+}
@@ -5,24 +5,39 @@
 
 //! This module includes various Node-type templates.
 
+use std::convert::TryInto;
 use std::marker::PhantomData;
 
-use digest::Digest;
+use digest::{generic_array::typenum::Unsigned, Digest};
+use num_traits::CheckedAdd;
 use rand::Rng;
+use subtle::{Choice, ConstantTimeEq};
 
 use crate::pad_secret::Secret;
 use crate::{
     error::DecodingError,
     index::TreeIndex,
+    proof::{MerkleProof, PROOF_LEN_BYTE_NUM},
     traits::{
-        Mergeable, Paddable, PaddingProvable, ProofExtractable, Rand, Serializable, TypeName,
+        InclusionProvable, Mergeable, MergeableEncoded, Paddable, PaddingProvable,
+        ProofExtractable, Rand, Serializable, Summable, TypeName,
     },
-    utils::{bytes_to_usize, usize_to_bytes},
+    tree::{NodeType, SparseMerkleTree},
+    utils::{bytes_to_count, bytes_to_u64, u64_to_bytes},
 };
 
 pub const PADDING_STRING: &str = "padding_node";
 
-/// ======================================================================================
+/// Compare two byte strings, e.g., two hashes, in time independent of their content.
+///
+/// The equality of the hash node templates goes through this comparison,
+/// so checking a computed root against the expected one in proof verification
+/// doesn't leak how many leading bytes of the hash match.
+fn ct_eq_bytes(a: &[u8], b: &[u8]) -> Choice {
+    a.ct_eq(b)
+}
+
+// ======================================================================================
 
 /// A Hash SMT node for the top accumulator that carries just a hash value.
 #[derive(Default, Clone, Debug)]
@@ -38,11 +53,22 @@ impl<D> HashNodeSmt<D> {
             phantom: PhantomData,
         }
     }
+
+    /// Returns the hash value.
+    pub fn get_hash(&self) -> &[u8] {
+        &self.hash
+    }
+}
+
+impl<D> ConstantTimeEq for HashNodeSmt<D> {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        ct_eq_bytes(&self.hash, &other.hash)
+    }
 }
 
 impl<D> PartialEq for HashNodeSmt<D> {
     fn eq(&self, other: &Self) -> bool {
-        self.hash == other.hash
+        self.ct_eq(other).into()
     }
 }
 
@@ -55,24 +81,52 @@ impl<D: Digest> Mergeable for HashNodeSmt<D> {
         hasher.update(&rch.hash);
         HashNodeSmt::new(hasher.finalize().to_vec())
     }
+
+    fn merge_n(children: &[HashNodeSmt<D>]) -> HashNodeSmt<D> {
+        let mut hasher = D::new();
+        for child in children {
+            hasher.update(&child.hash);
+        }
+        HashNodeSmt::new(hasher.finalize().to_vec())
+    }
+}
+
+impl<D: Digest> MergeableEncoded for HashNodeSmt<D> {
+    fn get_encoded_len() -> usize {
+        D::output_size()
+    }
+
+    fn merge_encoded_rch(lch: &HashNodeSmt<D>, rch: &[u8]) -> HashNodeSmt<D> {
+        let mut hasher = D::new();
+        hasher.update(&lch.hash);
+        hasher.update(rch);
+        HashNodeSmt::new(hasher.finalize().to_vec())
+    }
+
+    fn merge_encoded_lch(lch: &[u8], rch: &HashNodeSmt<D>) -> HashNodeSmt<D> {
+        let mut hasher = D::new();
+        hasher.update(lch);
+        hasher.update(&rch.hash);
+        HashNodeSmt::new(hasher.finalize().to_vec())
+    }
 }
 
 impl<D: Digest> Paddable for HashNodeSmt<D> {
     fn padding(idx: &TreeIndex, secret: &Secret) -> HashNodeSmt<D> {
         let mut pre_image = D::new();
         pre_image.update(secret.as_bytes());
-        pre_image.update(&TreeIndex::serialize(&[*idx]));
+        pre_image.update(TreeIndex::serialize(&[*idx]));
 
         let mut hasher = D::new();
         hasher.update(PADDING_STRING.as_bytes());
-        hasher.update(&pre_image.finalize().to_vec());
+        hasher.update(pre_image.finalize());
         HashNodeSmt::new(hasher.finalize().to_vec())
     }
 }
 
 impl<D: Digest> Serializable for HashNodeSmt<D> {
     fn serialize(&self) -> Vec<u8> {
-        (&self.hash).clone()
+        self.hash.clone()
     }
 
     fn deserialize_as_a_unit(bytes: &[u8], begin: &mut usize) -> Result<Self, DecodingError> {
@@ -90,6 +144,10 @@ impl<D: Clone> ProofExtractable for HashNodeSmt<D> {
     fn get_proof_node(&self) -> Self::ProofNode {
         self.clone()
     }
+
+    fn get_proof_node_ref(&self) -> Option<&Self::ProofNode> {
+        Some(self)
+    }
 }
 
 impl<D: Clone + Digest> PaddingProvable for HashNodeSmt<D> {
@@ -116,9 +174,8 @@ impl<D: Clone + Digest> PaddingProvable for HashNodeSmt<D> {
 }
 
 impl<D: Digest> Rand for HashNodeSmt<D> {
-    fn randomize(&mut self) {
+    fn randomize_with<R: Rng + ?Sized>(&mut self, rng: &mut R) {
         *self = HashNodeSmt::new(vec![0u8; D::output_size()]);
-        let mut rng = rand::thread_rng();
         for item in &mut self.hash {
             *item = rng.gen();
         }
@@ -131,7 +188,196 @@ impl<D: TypeName> TypeName for HashNodeSmt<D> {
     }
 }
 
-/// ======================================================================================
+// ======================================================================================
+
+/// A Hash SMT node whose children are sorted before being hashed,
+/// compatible with the sorted-pair Merkle trees of OpenZeppelin's ```MerkleProof``` library.
+///
+/// As the parent doesn't depend on the order of its children,
+/// a single-leaf proof is just the list of siblings from the leaf level up without direction bits,
+/// see [get_sorted_pair_proof](../proof/struct.MerkleProof.html#method.get_sorted_pair_proof).
+/// With ```D``` being Keccak-256, such proofs and roots are accepted by ```MerkleProof.verify``` unchanged.
+///
+/// Warning: for the same reason, the root doesn't commit to the positions of the leaves.
+/// A proof of a leaf verifies for any tree index of the same height, with the same siblings,
+/// so it authenticates that the leaf is in the tree, but not its index.
+/// Applications keyed by the index, e.g., a key-value map, must commit to the key in the leaf itself,
+/// and random sampling proofs, which rely on the positions of the neighbours, don't prove the absence of a leaf.
+#[derive(Default, Clone, Debug)]
+pub struct SortedPairNodeSmt<D> {
+    hash: Vec<u8>,
+    phantom: PhantomData<D>,
+}
+
+impl<D> SortedPairNodeSmt<D> {
+    pub fn new(hash: Vec<u8>) -> SortedPairNodeSmt<D> {
+        SortedPairNodeSmt {
+            hash,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the hash value.
+    pub fn get_hash(&self) -> &[u8] {
+        &self.hash
+    }
+}
+
+impl<D> ConstantTimeEq for SortedPairNodeSmt<D> {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        ct_eq_bytes(&self.hash, &other.hash)
+    }
+}
+
+impl<D> PartialEq for SortedPairNodeSmt<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl<D> Eq for SortedPairNodeSmt<D> {}
+
+/// Returns the input pair of hashes with the smaller one first.
+fn sort_pair<'a>(a: &'a [u8], b: &'a [u8]) -> (&'a [u8], &'a [u8]) {
+    if a <= b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+impl<D: Digest> Mergeable for SortedPairNodeSmt<D> {
+    fn merge(lch: &SortedPairNodeSmt<D>, rch: &SortedPairNodeSmt<D>) -> SortedPairNodeSmt<D> {
+        let (first, second) = sort_pair(&lch.hash, &rch.hash);
+        let mut hasher = D::new();
+        hasher.update(first);
+        hasher.update(second);
+        SortedPairNodeSmt::new(hasher.finalize().to_vec())
+    }
+}
+
+impl<D: Digest> MergeableEncoded for SortedPairNodeSmt<D> {
+    fn get_encoded_len() -> usize {
+        D::output_size()
+    }
+
+    fn merge_encoded_rch(lch: &SortedPairNodeSmt<D>, rch: &[u8]) -> SortedPairNodeSmt<D> {
+        let (first, second) = sort_pair(&lch.hash, rch);
+        let mut hasher = D::new();
+        hasher.update(first);
+        hasher.update(second);
+        SortedPairNodeSmt::new(hasher.finalize().to_vec())
+    }
+
+    fn merge_encoded_lch(lch: &[u8], rch: &SortedPairNodeSmt<D>) -> SortedPairNodeSmt<D> {
+        let (first, second) = sort_pair(lch, &rch.hash);
+        let mut hasher = D::new();
+        hasher.update(first);
+        hasher.update(second);
+        SortedPairNodeSmt::new(hasher.finalize().to_vec())
+    }
+}
+
+impl<D: Digest> Paddable for SortedPairNodeSmt<D> {
+    fn padding(idx: &TreeIndex, secret: &Secret) -> SortedPairNodeSmt<D> {
+        SortedPairNodeSmt::new(HashNodeSmt::<D>::padding(idx, secret).hash)
+    }
+}
+
+impl<D: Digest> Serializable for SortedPairNodeSmt<D> {
+    fn serialize(&self) -> Vec<u8> {
+        self.hash.clone()
+    }
+
+    fn deserialize_as_a_unit(bytes: &[u8], begin: &mut usize) -> Result<Self, DecodingError> {
+        if bytes.len() - *begin < D::output_size() {
+            return Err(DecodingError::BytesNotEnough);
+        }
+        let item = Self::new(bytes[*begin..*begin + D::output_size()].to_vec());
+        *begin += D::output_size();
+        Ok(item)
+    }
+}
+
+impl<D: Clone> ProofExtractable for SortedPairNodeSmt<D> {
+    type ProofNode = SortedPairNodeSmt<D>;
+    fn get_proof_node(&self) -> Self::ProofNode {
+        self.clone()
+    }
+
+    fn get_proof_node_ref(&self) -> Option<&Self::ProofNode> {
+        Some(self)
+    }
+}
+
+impl<D: Clone + Digest> PaddingProvable for SortedPairNodeSmt<D> {
+    type PaddingProof = SortedPairNodeSmt<D>;
+
+    fn prove_padding_node(&self, idx: &TreeIndex, secret: &Secret) -> SortedPairNodeSmt<D> {
+        let data = TreeIndex::serialize(&[*idx]);
+        let mut pre_image = D::new();
+        pre_image.update(secret.as_bytes());
+        pre_image.update(&data);
+        SortedPairNodeSmt::new(pre_image.finalize().to_vec())
+    }
+
+    fn verify_padding_node(
+        node: &<Self as ProofExtractable>::ProofNode,
+        proof: &Self::PaddingProof,
+        _idx: &TreeIndex,
+    ) -> bool {
+        let mut hasher = D::new();
+        hasher.update(PADDING_STRING.as_bytes());
+        hasher.update(&proof.hash);
+        *node == SortedPairNodeSmt::<D>::new(hasher.finalize().to_vec())
+    }
+}
+
+impl<D: Digest> Rand for SortedPairNodeSmt<D> {
+    fn randomize_with<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        *self = SortedPairNodeSmt::new(vec![0u8; D::output_size()]);
+        for item in &mut self.hash {
+            *item = rng.gen();
+        }
+    }
+}
+
+impl<D: TypeName> TypeName for SortedPairNodeSmt<D> {
+    fn get_name() -> String {
+        format!("Sorted Pair Hash ({})", D::get_name())
+    }
+}
+
+impl<D: Clone + Default + Digest> MerkleProof<SortedPairNodeSmt<D>> {
+    /// Returns the siblings of a single-leaf proof from the leaf level up, without direction bits,
+    /// i.e., the ```proof``` argument of OpenZeppelin's ```MerkleProof.verify```.
+    ///
+    /// Returns None if the proof is not a single-leaf proof.
+    pub fn get_sorted_pair_proof(&self) -> Option<Vec<SortedPairNodeSmt<D>>> {
+        if self.get_indexes().len() != 1
+            || self.get_siblings_num() != self.get_indexes()[0].get_height()
+        {
+            return None;
+        }
+        Some(self.get_path_siblings().iter().rev().cloned().collect())
+    }
+
+    /// Verify a sorted-pair proof without direction bits as OpenZeppelin's ```MerkleProof.verify``` does.
+    ///
+    /// The proof only shows that the leaf is in the tree, not at which index, see [SortedPairNodeSmt].
+    pub fn verify_sorted_pair_proof(
+        proof: &[SortedPairNodeSmt<D>],
+        leaf: &SortedPairNodeSmt<D>,
+        root: &SortedPairNodeSmt<D>,
+    ) -> bool {
+        let value = proof.iter().fold(leaf.clone(), |value, sibling| {
+            Mergeable::merge(&value, sibling)
+        });
+        value == *root
+    }
+}
+
+// ======================================================================================
 
 /// An SMT node that carries a u64 value, and merging is computed as the sum of two nodes.
 #[derive(Default, Clone, Debug)]
@@ -141,6 +387,11 @@ impl SumNodeSmt {
     pub fn new(value: u64) -> SumNodeSmt {
         SumNodeSmt(value)
     }
+
+    /// Returns the sum value.
+    pub fn get_value(&self) -> u64 {
+        self.0
+    }
 }
 
 impl PartialEq for SumNodeSmt {
@@ -155,6 +406,24 @@ impl Mergeable for SumNodeSmt {
     fn merge(lch: &SumNodeSmt, rch: &SumNodeSmt) -> SumNodeSmt {
         SumNodeSmt(lch.0 + rch.0)
     }
+
+    fn merge_n(children: &[SumNodeSmt]) -> SumNodeSmt {
+        SumNodeSmt(children.iter().map(|x| x.0).sum())
+    }
+}
+
+impl MergeableEncoded for SumNodeSmt {
+    fn get_encoded_len() -> usize {
+        8
+    }
+
+    fn merge_encoded_rch(lch: &SumNodeSmt, rch: &[u8]) -> SumNodeSmt {
+        SumNodeSmt(lch.0 + u64::from_le_bytes(rch.try_into().unwrap()))
+    }
+
+    fn merge_encoded_lch(lch: &[u8], rch: &SumNodeSmt) -> SumNodeSmt {
+        SumNodeSmt(u64::from_le_bytes(lch.try_into().unwrap()) + rch.0)
+    }
 }
 
 impl Paddable for SumNodeSmt {
@@ -165,14 +434,14 @@ impl Paddable for SumNodeSmt {
 
 impl Serializable for SumNodeSmt {
     fn serialize(&self) -> Vec<u8> {
-        usize_to_bytes(self.0 as usize, 8)
+        u64_to_bytes(self.0, 8)
     }
 
     fn deserialize_as_a_unit(bytes: &[u8], begin: &mut usize) -> Result<Self, DecodingError> {
         if bytes.len() - *begin < 8 {
             return Err(DecodingError::BytesNotEnough);
         }
-        Ok(SumNodeSmt(bytes_to_usize(bytes, 8, begin).unwrap() as u64))
+        Ok(SumNodeSmt(bytes_to_u64(bytes, 8, begin)?))
     }
 }
 
@@ -181,6 +450,10 @@ impl ProofExtractable for SumNodeSmt {
     fn get_proof_node(&self) -> Self::ProofNode {
         SumNodeSmt(self.0)
     }
+
+    fn get_proof_node_ref(&self) -> Option<&Self::ProofNode> {
+        Some(self)
+    }
 }
 
 impl PaddingProvable for SumNodeSmt {
@@ -194,198 +467,1547 @@ impl PaddingProvable for SumNodeSmt {
 }
 
 impl Rand for SumNodeSmt {
-    fn randomize(&mut self) {
-        let mut rng = rand::thread_rng();
+    fn randomize_with<R: Rng + ?Sized>(&mut self, rng: &mut R) {
         let x: u32 = rng.gen();
         self.0 = x as u64;
     }
 }
 
+impl Summable for SumNodeSmt {
+    fn get_sum(&self) -> u64 {
+        self.0
+    }
+}
+
 impl TypeName for SumNodeSmt {
     fn get_name() -> String {
         "Sum".to_owned()
     }
 }
 
-/// ======================================================================================
+// ======================================================================================
 
-/// A HashWires SMT node for the top accumulator that carries just a hash value.
-#[derive(Default, Clone, Debug)]
-pub struct HashWiresNodeSmt<D> {
-    hash: Vec<u8>,
-    phantom: PhantomData<D>,
+/// An unsigned integer type of the value of a [NumSumNode], encoded in a fixed number of bytes in little-endian.
+pub trait SumValue: Copy + Default + std::fmt::Debug + Eq + CheckedAdd {
+    /// The number of bytes of an encoded value.
+    const BYTE_NUM: usize;
+
+    /// Encode the value in [BYTE_NUM](#associatedconstant.BYTE_NUM) bytes.
+    fn to_bytes(&self) -> Vec<u8>;
+
+    /// Decode a value from exactly [BYTE_NUM](#associatedconstant.BYTE_NUM) bytes.
+    fn from_bytes(bytes: &[u8]) -> Self;
 }
 
-impl<D> HashWiresNodeSmt<D> {
-    pub fn new(hash: Vec<u8>) -> HashWiresNodeSmt<D> {
-        HashWiresNodeSmt {
-            hash,
-            phantom: PhantomData,
-        }
+macro_rules! impl_sum_value {
+    ($($t:ty),*) => {
+        $(
+            impl SumValue for $t {
+                const BYTE_NUM: usize = std::mem::size_of::<$t>();
+
+                fn to_bytes(&self) -> Vec<u8> {
+                    self.to_le_bytes().to_vec()
+                }
+
+                fn from_bytes(bytes: &[u8]) -> Self {
+                    <$t>::from_le_bytes(bytes.try_into().unwrap())
+                }
+            }
+        )*
+    };
+}
+
+impl_sum_value!(u8, u16, u32, u64, u128);
+
+/// An SMT node that carries a value of an unsigned integer type, and merging is computed as the sum of two nodes,
+/// e.g., with ```u128``` for liabilities in high-precision units whose total may overflow a ```u64```.
+///
+/// The node is encoded in the number of bytes of the value type,
+/// so ```NumSumNode<u64>``` has the same encoding as [SumNodeSmt].
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct NumSumNode<T>(T);
+
+/// A [NumSumNode] carrying a ```u128``` value.
+pub type SumNodeSmt128 = NumSumNode<u128>;
+
+impl<T: SumValue> NumSumNode<T> {
+    pub fn new(value: T) -> NumSumNode<T> {
+        NumSumNode(value)
+    }
+
+    /// Returns the sum value.
+    pub fn get_value(&self) -> T {
+        self.0
+    }
+
+    /// Returns the parent of the two nodes, or ```None``` if their sum overflows.
+    pub fn checked_merge(lch: &NumSumNode<T>, rch: &NumSumNode<T>) -> Option<NumSumNode<T>> {
+        lch.0.checked_add(&rch.0).map(NumSumNode)
     }
 }
 
-impl<D> PartialEq for HashWiresNodeSmt<D> {
-    fn eq(&self, other: &Self) -> bool {
-        self.hash == other.hash
+impl<T: SumValue> Mergeable for NumSumNode<T> {
+    /// Panics if the sum overflows the value type, see [checked_merge](struct.NumSumNode.html#method.checked_merge).
+    fn merge(lch: &NumSumNode<T>, rch: &NumSumNode<T>) -> NumSumNode<T> {
+        NumSumNode::checked_merge(lch, rch).expect("The sum of the nodes overflows.")
+    }
+
+    fn merge_n(children: &[NumSumNode<T>]) -> NumSumNode<T> {
+        children
+            .iter()
+            .try_fold(T::default(), |acc, x| acc.checked_add(&x.0))
+            .map(NumSumNode)
+            .expect("The sum of the nodes overflows.")
     }
 }
 
-impl<D> Eq for HashWiresNodeSmt<D> {}
+impl<T: SumValue> MergeableEncoded for NumSumNode<T> {
+    fn get_encoded_len() -> usize {
+        T::BYTE_NUM
+    }
 
-impl<D: Digest> Mergeable for HashWiresNodeSmt<D> {
-    fn merge(lch: &HashWiresNodeSmt<D>, rch: &HashWiresNodeSmt<D>) -> HashWiresNodeSmt<D> {
-        let mut hasher = D::new();
-        hasher.update(&lch.hash);
-        hasher.update(&rch.hash);
-        HashWiresNodeSmt::new(hasher.finalize().to_vec())
+    fn merge_encoded_rch(lch: &NumSumNode<T>, rch: &[u8]) -> NumSumNode<T> {
+        Mergeable::merge(lch, &NumSumNode(T::from_bytes(rch)))
+    }
+
+    fn merge_encoded_lch(lch: &[u8], rch: &NumSumNode<T>) -> NumSumNode<T> {
+        Mergeable::merge(&NumSumNode(T::from_bytes(lch)), rch)
     }
 }
 
-impl<D: Digest> Paddable for HashWiresNodeSmt<D> {
-    fn padding(idx: &TreeIndex, secret: &Secret) -> HashWiresNodeSmt<D> {
-        let mut hasher = D::new();
-        // TODO add some identifier hasher.update(PADDING_STRING.as_bytes());
-        hasher.update(secret.as_bytes());
-        hasher.update(&TreeIndex::serialize(&[*idx]));
-        HashWiresNodeSmt::new(hasher.finalize().to_vec())
+impl<T: SumValue> Paddable for NumSumNode<T> {
+    fn padding(_idx: &TreeIndex, _secret: &Secret) -> NumSumNode<T> {
+        NumSumNode(T::default())
     }
 }
 
-impl<D: Digest> Serializable for HashWiresNodeSmt<D> {
+impl<T: SumValue> Serializable for NumSumNode<T> {
     fn serialize(&self) -> Vec<u8> {
-        (&self.hash).clone()
+        self.0.to_bytes()
     }
 
     fn deserialize_as_a_unit(bytes: &[u8], begin: &mut usize) -> Result<Self, DecodingError> {
-        if bytes.len() - *begin < D::output_size() {
+        if bytes.len() - *begin < T::BYTE_NUM {
             return Err(DecodingError::BytesNotEnough);
         }
-        let item = Self::new(bytes[*begin..*begin + D::output_size()].to_vec());
-        *begin += D::output_size();
-        Ok(item)
+        let value = T::from_bytes(&bytes[*begin..*begin + T::BYTE_NUM]);
+        *begin += T::BYTE_NUM;
+        Ok(NumSumNode(value))
     }
 }
 
-impl<D: Clone> ProofExtractable for HashWiresNodeSmt<D> {
-    type ProofNode = HashWiresNodeSmt<D>;
+impl<T: SumValue> ProofExtractable for NumSumNode<T> {
+    type ProofNode = NumSumNode<T>;
     fn get_proof_node(&self) -> Self::ProofNode {
-        self.clone()
+        NumSumNode(self.0)
+    }
+
+    fn get_proof_node_ref(&self) -> Option<&Self::ProofNode> {
+        Some(self)
     }
 }
 
-impl<D: Digest> Rand for HashWiresNodeSmt<D> {
-    fn randomize(&mut self) {
-        *self = HashWiresNodeSmt::new(vec![0u8; D::output_size()]);
-        let mut rng = rand::thread_rng();
-        for item in &mut self.hash {
-            *item = rng.gen();
-        }
+impl<T: SumValue> PaddingProvable for NumSumNode<T> {
+    type PaddingProof = NumSumNode<T>;
+    fn prove_padding_node(&self, _idx: &TreeIndex, _secret: &Secret) -> NumSumNode<T> {
+        NumSumNode(T::default())
+    }
+    fn verify_padding_node(
+        node: &<Self as ProofExtractable>::ProofNode,
+        proof: &NumSumNode<T>,
+        _idx: &TreeIndex,
+    ) -> bool {
+        node.0 == T::default() && proof.0 == T::default()
+    }
+}
+
+impl<T: SumValue> Rand for NumSumNode<T> {
+    /// Sample a value in the lower half of the bytes of the value type, so that sums of random nodes don't overflow.
+    fn randomize_with<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        let mut bytes = vec![0u8; T::BYTE_NUM];
+        rng.fill_bytes(&mut bytes[..T::BYTE_NUM / 2]);
+        self.0 = T::from_bytes(&bytes);
+    }
+}
+
+impl<T: SumValue + Into<u64>> Summable for NumSumNode<T> {
+    fn get_sum(&self) -> u64 {
+        self.0.into()
     }
 }
 
-impl<D: TypeName> TypeName for HashWiresNodeSmt<D> {
+impl<T: SumValue> TypeName for NumSumNode<T> {
     fn get_name() -> String {
-        format!("HashWires hash ({})", D::get_name())
+        format!("Sum ({}-bit)", T::BYTE_NUM * 8)
     }
 }
 
-/// ======================================================================================
+// ======================================================================================
 
-/// A hash Merkle tree node for the top accumulator that carries just a hash value.
+/// A Hash SMT node that also carries the number of real leaves in its subtree,
+/// committed in the hash of the parent,
+/// so that a Merkle proof shows the rank of a leaf among the real leaves, see
+/// [get_rank](../proof/struct.MerkleProof.html#method.get_rank).
+///
+/// A leaf counts 1, a padding node counts 0, and the hash of a parent is ```hash(count || lch || rch)```.
 #[derive(Default, Clone, Debug)]
-pub struct MTreeNodeSmt<D> {
+pub struct CountNodeSmt<D> {
+    count: u64,
     hash: Vec<u8>,
     phantom: PhantomData<D>,
 }
 
-impl<D> MTreeNodeSmt<D> {
-    pub fn new(hash: Vec<u8>) -> MTreeNodeSmt<D> {
-        MTreeNodeSmt {
+impl<D> CountNodeSmt<D> {
+    /// The constructor of a leaf, which counts 1.
+    pub fn new(hash: Vec<u8>) -> CountNodeSmt<D> {
+        CountNodeSmt::new_with_count(1, hash)
+    }
+
+    /// The constructor of a node with the input count.
+    pub fn new_with_count(count: u64, hash: Vec<u8>) -> CountNodeSmt<D> {
+        CountNodeSmt {
+            count,
             hash,
             phantom: PhantomData,
         }
     }
+
+    /// Returns the number of real leaves in the subtree.
+    pub fn get_count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the hash value.
+    pub fn get_hash(&self) -> &[u8] {
+        &self.hash
+    }
 }
 
-impl<D> PartialEq for MTreeNodeSmt<D> {
+impl<D> ConstantTimeEq for CountNodeSmt<D> {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.count.ct_eq(&other.count) & ct_eq_bytes(&self.hash, &other.hash)
+    }
+}
+
+impl<D> PartialEq for CountNodeSmt<D> {
     fn eq(&self, other: &Self) -> bool {
-        self.hash == other.hash
+        self.ct_eq(other).into()
     }
 }
 
-impl<D> Eq for MTreeNodeSmt<D> {}
+impl<D> Eq for CountNodeSmt<D> {}
 
-impl<D: Digest> Mergeable for MTreeNodeSmt<D> {
-    fn merge(lch: &MTreeNodeSmt<D>, rch: &MTreeNodeSmt<D>) -> MTreeNodeSmt<D> {
+impl<D: Digest> Mergeable for CountNodeSmt<D> {
+    fn merge(lch: &CountNodeSmt<D>, rch: &CountNodeSmt<D>) -> CountNodeSmt<D> {
+        let count = lch.count + rch.count;
         let mut hasher = D::new();
+        hasher.update(count.to_le_bytes());
         hasher.update(&lch.hash);
         hasher.update(&rch.hash);
-        MTreeNodeSmt::new(hasher.finalize().to_vec())
+        CountNodeSmt::new_with_count(count, hasher.finalize().to_vec())
     }
 }
 
-impl<D: Digest> Paddable for MTreeNodeSmt<D> {
-    fn padding(_idx: &TreeIndex, _secret: &Secret) -> MTreeNodeSmt<D> {
-        MTreeNodeSmt::new(vec![0u8; D::output_size()])
+impl<D: Digest> Paddable for CountNodeSmt<D> {
+    fn padding(idx: &TreeIndex, secret: &Secret) -> CountNodeSmt<D> {
+        CountNodeSmt::new_with_count(0, HashNodeSmt::<D>::padding(idx, secret).hash)
     }
 }
 
-impl<D: Digest> Serializable for MTreeNodeSmt<D> {
+impl<D: Digest> Serializable for CountNodeSmt<D> {
+    /// Encode a node in the format: ```count || hash```, with the count in 8 bytes.
     fn serialize(&self) -> Vec<u8> {
-        (&self.hash).clone()
+        let mut bytes = u64_to_bytes(self.count, 8);
+        bytes.extend_from_slice(&self.hash);
+        bytes
     }
 
     fn deserialize_as_a_unit(bytes: &[u8], begin: &mut usize) -> Result<Self, DecodingError> {
-        if bytes.len() - *begin < D::output_size() {
+        if bytes.len() - *begin < 8 + D::output_size() {
             return Err(DecodingError::BytesNotEnough);
         }
-        let item = Self::new(bytes[*begin..*begin + D::output_size()].to_vec());
+        let count = bytes_to_u64(bytes, 8, begin)?;
+        let item = Self::new_with_count(count, bytes[*begin..*begin + D::output_size()].to_vec());
         *begin += D::output_size();
         Ok(item)
     }
 }
 
-impl<D: Clone> ProofExtractable for MTreeNodeSmt<D> {
-    type ProofNode = MTreeNodeSmt<D>;
+impl<D: Clone> ProofExtractable for CountNodeSmt<D> {
+    type ProofNode = CountNodeSmt<D>;
     fn get_proof_node(&self) -> Self::ProofNode {
         self.clone()
     }
+
+    fn get_proof_node_ref(&self) -> Option<&Self::ProofNode> {
+        Some(self)
+    }
 }
 
-impl<D: Clone + Digest> PaddingProvable for MTreeNodeSmt<D> {
-    type PaddingProof = MTreeNodeSmt<D>;
+impl<D: Clone + Digest> PaddingProvable for CountNodeSmt<D> {
+    type PaddingProof = HashNodeSmt<D>;
 
-    fn prove_padding_node(&self, _idx: &TreeIndex, _secret: &Secret) -> MTreeNodeSmt<D> {
-        MTreeNodeSmt::new(vec![0u8; D::output_size()])
+    fn prove_padding_node(&self, idx: &TreeIndex, secret: &Secret) -> HashNodeSmt<D> {
+        HashNodeSmt::<D>::new(Vec::new()).prove_padding_node(idx, secret)
     }
 
     fn verify_padding_node(
         node: &<Self as ProofExtractable>::ProofNode,
-        _proof: &Self::PaddingProof,
-        _idx: &TreeIndex,
+        proof: &Self::PaddingProof,
+        idx: &TreeIndex,
     ) -> bool {
-        *node == MTreeNodeSmt::new(vec![0u8; D::output_size()])
+        node.count == 0
+            && HashNodeSmt::<D>::verify_padding_node(
+                &HashNodeSmt::new(node.hash.clone()),
+                proof,
+                idx,
+            )
     }
 }
 
-impl<D: Digest> Rand for MTreeNodeSmt<D> {
-    fn randomize(&mut self) {
-        *self = MTreeNodeSmt::new(vec![0u8; D::output_size()]);
-        let mut rng = rand::thread_rng();
-        for item in &mut self.hash {
-            *item = rng.gen();
-        }
+impl<D: Digest> Rand for CountNodeSmt<D> {
+    fn randomize_with<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        let mut hash = HashNodeSmt::<D>::new(Vec::new());
+        hash.randomize_with(rng);
+        *self = CountNodeSmt::new(hash.hash);
     }
 }
 
-impl<D: TypeName> TypeName for MTreeNodeSmt<D> {
+impl<D> Summable for CountNodeSmt<D> {
+    fn get_sum(&self) -> u64 {
+        self.count
+    }
+}
+
+impl<D: TypeName> TypeName for CountNodeSmt<D> {
     fn get_name() -> String {
-        format!("Hash ({})", D::get_name())
+        format!("Count ({})", D::get_name())
+    }
+}
+
+impl<D: Clone + Default + Digest> MerkleProof<CountNodeSmt<D>> {
+    /// Verify a single-leaf proof and return the rank of the leaf among the real leaves from the left, starting from 0,
+    /// i.e., the sum of the counts of the left siblings along the path.
+    ///
+    /// Returns None if the proof is invalid or the leaf doesn't count 1.
+    pub fn get_rank(&self, leaf: &CountNodeSmt<D>, root: &CountNodeSmt<D>) -> Option<u64> {
+        if leaf.count != 1 || !self.verify(leaf, root) {
+            return None;
+        }
+        let index = &self.get_indexes()[0];
+        Some(
+            self.get_path_siblings()
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| index.get_bit(*i) == 1)
+                .map(|(_, sibling)| sibling.count)
+                .sum(),
+        )
+    }
+
+    /// Verify that the input leaf is the ```rank```-th real leaf from the left, starting from 0.
+    pub fn verify_rank(&self, leaf: &CountNodeSmt<D>, root: &CountNodeSmt<D>, rank: u64) -> bool {
+        self.get_rank(leaf, root) == Some(rank)
+    }
+}
+
+// ======================================================================================
+
+/// A Hash SMT node that also carries the latest timestamp of the real leaves in its subtree,
+/// or the earliest one with ```IS_MAX = false```, committed in the hash of the parent,
+/// so that a node shows that no leaf in its subtree is newer (older) than a bound, see
+/// [verify_timestamp_bound](../proof/struct.MerkleProof.html#method.verify_timestamp_bound),
+/// e.g., for data retention or expiry policies over a committed set.
+///
+/// The timestamp of a parent is the maximum (minimum) of those of its children,
+/// and its hash is ```hash(lch_timestamp || lch_hash || rch_timestamp || rch_hash)```.
+/// A padding node has the timestamp 0 (```u64::MAX```), which doesn't change the maximum (minimum).
+#[derive(Default, Clone, Debug)]
+pub struct TimestampNodeSmt<D, const IS_MAX: bool = true> {
+    timestamp: u64,
+    hash: Vec<u8>,
+    phantom: PhantomData<D>,
+}
+
+/// A [TimestampNodeSmt] carrying the latest timestamp of its subtree.
+pub type MaxTimestampNodeSmt<D> = TimestampNodeSmt<D, true>;
+/// A [TimestampNodeSmt] carrying the earliest timestamp of its subtree.
+pub type MinTimestampNodeSmt<D> = TimestampNodeSmt<D, false>;
+
+impl<D, const IS_MAX: bool> TimestampNodeSmt<D, IS_MAX> {
+    /// The timestamp of padding nodes.
+    pub const PADDING_TIMESTAMP: u64 = if IS_MAX { 0 } else { u64::MAX };
+
+    pub fn new(timestamp: u64, hash: Vec<u8>) -> TimestampNodeSmt<D, IS_MAX> {
+        TimestampNodeSmt {
+            timestamp,
+            hash,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the latest (earliest) timestamp of the subtree.
+    pub fn get_timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Returns the hash value.
+    pub fn get_hash(&self) -> &[u8] {
+        &self.hash
+    }
+
+    /// Returns true if no leaf in the subtree is newer than the bound, i.e., the timestamp is at most the bound,
+    /// or with ```IS_MAX = false```, if no leaf is older than the bound, i.e., the timestamp is at least the bound.
+    pub fn is_within_bound(&self, bound: u64) -> bool {
+        if IS_MAX {
+            self.timestamp <= bound
+        } else {
+            self.timestamp >= bound
+        }
+    }
+}
+
+impl<D, const IS_MAX: bool> ConstantTimeEq for TimestampNodeSmt<D, IS_MAX> {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.timestamp.ct_eq(&other.timestamp) & ct_eq_bytes(&self.hash, &other.hash)
+    }
+}
+
+impl<D, const IS_MAX: bool> PartialEq for TimestampNodeSmt<D, IS_MAX> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl<D, const IS_MAX: bool> Eq for TimestampNodeSmt<D, IS_MAX> {}
+
+impl<D: Digest, const IS_MAX: bool> Mergeable for TimestampNodeSmt<D, IS_MAX> {
+    fn merge(
+        lch: &TimestampNodeSmt<D, IS_MAX>,
+        rch: &TimestampNodeSmt<D, IS_MAX>,
+    ) -> TimestampNodeSmt<D, IS_MAX> {
+        let timestamp = if IS_MAX {
+            lch.timestamp.max(rch.timestamp)
+        } else {
+            lch.timestamp.min(rch.timestamp)
+        };
+        let mut hasher = D::new();
+        hasher.update(lch.timestamp.to_le_bytes());
+        hasher.update(&lch.hash);
+        hasher.update(rch.timestamp.to_le_bytes());
+        hasher.update(&rch.hash);
+        TimestampNodeSmt::new(timestamp, hasher.finalize().to_vec())
+    }
+}
+
+impl<D: Digest, const IS_MAX: bool> Paddable for TimestampNodeSmt<D, IS_MAX> {
+    fn padding(idx: &TreeIndex, secret: &Secret) -> TimestampNodeSmt<D, IS_MAX> {
+        TimestampNodeSmt::new(
+            Self::PADDING_TIMESTAMP,
+            HashNodeSmt::<D>::padding(idx, secret).hash,
+        )
+    }
+}
+
+impl<D: Digest, const IS_MAX: bool> Serializable for TimestampNodeSmt<D, IS_MAX> {
+    /// Encode a node in the format: ```timestamp || hash```, with the timestamp in 8 bytes.
+    fn serialize(&self) -> Vec<u8> {
+        let mut bytes = u64_to_bytes(self.timestamp, 8);
+        bytes.extend_from_slice(&self.hash);
+        bytes
+    }
+
+    fn deserialize_as_a_unit(bytes: &[u8], begin: &mut usize) -> Result<Self, DecodingError> {
+        if bytes.len() - *begin < 8 + D::output_size() {
+            return Err(DecodingError::BytesNotEnough);
+        }
+        let timestamp = bytes_to_u64(bytes, 8, begin)?;
+        let item = Self::new(timestamp, bytes[*begin..*begin + D::output_size()].to_vec());
+        *begin += D::output_size();
+        Ok(item)
+    }
+}
+
+impl<D: Clone, const IS_MAX: bool> ProofExtractable for TimestampNodeSmt<D, IS_MAX> {
+    type ProofNode = TimestampNodeSmt<D, IS_MAX>;
+    fn get_proof_node(&self) -> Self::ProofNode {
+        self.clone()
+    }
+
+    fn get_proof_node_ref(&self) -> Option<&Self::ProofNode> {
+        Some(self)
+    }
+}
+
+impl<D: Clone + Digest, const IS_MAX: bool> PaddingProvable for TimestampNodeSmt<D, IS_MAX> {
+    type PaddingProof = HashNodeSmt<D>;
+
+    fn prove_padding_node(&self, idx: &TreeIndex, secret: &Secret) -> HashNodeSmt<D> {
+        HashNodeSmt::<D>::new(Vec::new()).prove_padding_node(idx, secret)
+    }
+
+    fn verify_padding_node(
+        node: &<Self as ProofExtractable>::ProofNode,
+        proof: &Self::PaddingProof,
+        idx: &TreeIndex,
+    ) -> bool {
+        node.timestamp == Self::PADDING_TIMESTAMP
+            && HashNodeSmt::<D>::verify_padding_node(
+                &HashNodeSmt::new(node.hash.clone()),
+                proof,
+                idx,
+            )
+    }
+}
+
+impl<D: Digest, const IS_MAX: bool> Rand for TimestampNodeSmt<D, IS_MAX> {
+    fn randomize_with<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        let mut hash = HashNodeSmt::<D>::new(Vec::new());
+        hash.randomize_with(rng);
+        let timestamp: u32 = rng.gen();
+        *self = TimestampNodeSmt::new(timestamp as u64, hash.hash);
+    }
+}
+
+impl<D: TypeName, const IS_MAX: bool> TypeName for TimestampNodeSmt<D, IS_MAX> {
+    fn get_name() -> String {
+        if IS_MAX {
+            format!("Max timestamp ({})", D::get_name())
+        } else {
+            format!("Min timestamp ({})", D::get_name())
+        }
+    }
+}
+
+impl<D: Clone + Default + Digest, const IS_MAX: bool> MerkleProof<TimestampNodeSmt<D, IS_MAX>> {
+    /// Verify a single-node proof, e.g., of a leaf or of the root of a subtree
+    /// generated with [generate_anchor_proof](struct.AnchoredProof.html#method.generate_anchor_proof),
+    /// and that no leaf in the subtree of the node is newer than the bound,
+    /// or with ```IS_MAX = false```, older than the bound.
+    pub fn verify_timestamp_bound(
+        &self,
+        node: &TimestampNodeSmt<D, IS_MAX>,
+        root: &TimestampNodeSmt<D, IS_MAX>,
+        bound: u64,
+    ) -> bool {
+        node.is_within_bound(bound) && self.verify(node, root)
+    }
+}
+
+// ======================================================================================
+
+/// A HashWires SMT node for the top accumulator that carries just a hash value.
+///
+/// The padding scheme is versioned, the default being the released one:
+/// * Version 1: the padding node is ```H(secret || idx)```, as in 0.1.2,
+///   which can't be proved to be a padding node without revealing the secret.
+/// * Version 2: the padding node is ```H(H(secret || idx))```,
+///   whose pre-image is revealed to prove that a node is a padding node.
+/// * Version 3: the padding node is ```H(PADDING_STRING || H(secret || idx))```, provable as version 2
+///   and distinguishable from the merge of two children.
+///
+/// Versions 2 and 3 are opted in explicitly, e.g., ```HashWiresNodeSmt<D, 3>```,
+/// and only they implement [PaddingProvable].
+#[derive(Default, Clone, Debug)]
+pub struct HashWiresNodeSmt<D, const VERSION: u8 = 1> {
+    hash: Vec<u8>,
+    phantom: PhantomData<D>,
+}
+
+impl<D, const VERSION: u8> HashWiresNodeSmt<D, VERSION> {
+    pub fn new(hash: Vec<u8>) -> HashWiresNodeSmt<D, VERSION> {
+        HashWiresNodeSmt {
+            hash,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the hash value.
+    pub fn get_hash(&self) -> &[u8] {
+        &self.hash
+    }
+}
+
+impl<D, const VERSION: u8> ConstantTimeEq for HashWiresNodeSmt<D, VERSION> {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        ct_eq_bytes(&self.hash, &other.hash)
+    }
+}
+
+impl<D, const VERSION: u8> PartialEq for HashWiresNodeSmt<D, VERSION> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl<D, const VERSION: u8> Eq for HashWiresNodeSmt<D, VERSION> {}
+
+impl<D: Digest, const VERSION: u8> Mergeable for HashWiresNodeSmt<D, VERSION> {
+    fn merge(
+        lch: &HashWiresNodeSmt<D, VERSION>,
+        rch: &HashWiresNodeSmt<D, VERSION>,
+    ) -> HashWiresNodeSmt<D, VERSION> {
+        let mut hasher = D::new();
+        hasher.update(&lch.hash);
+        hasher.update(&rch.hash);
+        HashWiresNodeSmt::new(hasher.finalize().to_vec())
+    }
+
+    fn merge_n(children: &[HashWiresNodeSmt<D, VERSION>]) -> HashWiresNodeSmt<D, VERSION> {
+        let mut hasher = D::new();
+        for child in children {
+            hasher.update(&child.hash);
+        }
+        HashWiresNodeSmt::new(hasher.finalize().to_vec())
+    }
+}
+
+impl<D: Digest, const VERSION: u8> MergeableEncoded for HashWiresNodeSmt<D, VERSION> {
+    fn get_encoded_len() -> usize {
+        D::output_size()
+    }
+
+    fn merge_encoded_rch(
+        lch: &HashWiresNodeSmt<D, VERSION>,
+        rch: &[u8],
+    ) -> HashWiresNodeSmt<D, VERSION> {
+        let mut hasher = D::new();
+        hasher.update(&lch.hash);
+        hasher.update(rch);
+        HashWiresNodeSmt::new(hasher.finalize().to_vec())
+    }
+
+    fn merge_encoded_lch(
+        lch: &[u8],
+        rch: &HashWiresNodeSmt<D, VERSION>,
+    ) -> HashWiresNodeSmt<D, VERSION> {
+        let mut hasher = D::new();
+        hasher.update(lch);
+        hasher.update(&rch.hash);
+        HashWiresNodeSmt::new(hasher.finalize().to_vec())
+    }
+}
+
+impl<D: Digest, const VERSION: u8> HashWiresNodeSmt<D, VERSION> {
+    // The pre-image of the padding node from version 2, which is the padding node of version 1.
+    fn get_padding_pre_image(idx: &TreeIndex, secret: &Secret) -> Vec<u8> {
+        let mut hasher = D::new();
+        hasher.update(secret.as_bytes());
+        hasher.update(TreeIndex::serialize(&[*idx]));
+        hasher.finalize().to_vec()
+    }
+
+    // The padding node from version 2 of the input pre-image.
+    fn hash_padding_pre_image(pre_image: &[u8]) -> HashWiresNodeSmt<D, VERSION> {
+        let mut hasher = D::new();
+        if VERSION >= 3 {
+            hasher.update(PADDING_STRING.as_bytes());
+        }
+        hasher.update(pre_image);
+        HashWiresNodeSmt::new(hasher.finalize().to_vec())
+    }
+}
+
+impl<D: Digest, const VERSION: u8> Paddable for HashWiresNodeSmt<D, VERSION> {
+    /// The padding node is ```H(secret || idx)``` in version 1, and the hash of it from version 2,
+    /// prefixed with ```PADDING_STRING``` from version 3.
+    fn padding(idx: &TreeIndex, secret: &Secret) -> HashWiresNodeSmt<D, VERSION> {
+        let pre_image = Self::get_padding_pre_image(idx, secret);
+        if VERSION <= 1 {
+            return HashWiresNodeSmt::new(pre_image);
+        }
+        Self::hash_padding_pre_image(&pre_image)
+    }
+}
+
+impl<D: Digest, const VERSION: u8> Serializable for HashWiresNodeSmt<D, VERSION> {
+    fn serialize(&self) -> Vec<u8> {
+        self.hash.clone()
+    }
+
+    fn deserialize_as_a_unit(bytes: &[u8], begin: &mut usize) -> Result<Self, DecodingError> {
+        if bytes.len() - *begin < D::output_size() {
+            return Err(DecodingError::BytesNotEnough);
+        }
+        let item = Self::new(bytes[*begin..*begin + D::output_size()].to_vec());
+        *begin += D::output_size();
+        Ok(item)
+    }
+}
+
+impl<D: Clone, const VERSION: u8> ProofExtractable for HashWiresNodeSmt<D, VERSION> {
+    type ProofNode = HashWiresNodeSmt<D, VERSION>;
+    fn get_proof_node(&self) -> Self::ProofNode {
+        self.clone()
+    }
+
+    fn get_proof_node_ref(&self) -> Option<&Self::ProofNode> {
+        Some(self)
+    }
+}
+
+macro_rules! impl_hashwires_padding_provable {
+    ($($version:literal),*) => {
+        $(
+            impl<D: Clone + Digest> PaddingProvable for HashWiresNodeSmt<D, $version> {
+                type PaddingProof = HashWiresNodeSmt<D, $version>;
+
+                fn prove_padding_node(
+                    &self,
+                    idx: &TreeIndex,
+                    secret: &Secret,
+                ) -> HashWiresNodeSmt<D, $version> {
+                    HashWiresNodeSmt::new(Self::get_padding_pre_image(idx, secret))
+                }
+
+                fn verify_padding_node(
+                    node: &<Self as ProofExtractable>::ProofNode,
+                    proof: &Self::PaddingProof,
+                    _idx: &TreeIndex,
+                ) -> bool {
+                    *node == Self::hash_padding_pre_image(&proof.hash)
+                }
+            }
+        )*
+    };
+}
+
+// The padding node of version 1 is its own pre-image, so only versions 2 and 3 are provable.
+impl_hashwires_padding_provable!(2, 3);
+
+impl<D: Digest, const VERSION: u8> Rand for HashWiresNodeSmt<D, VERSION> {
+    fn randomize_with<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        *self = HashWiresNodeSmt::new(vec![0u8; D::output_size()]);
+        for item in &mut self.hash {
+            *item = rng.gen();
+        }
+    }
+}
+
+impl<D: TypeName, const VERSION: u8> TypeName for HashWiresNodeSmt<D, VERSION> {
+    fn get_name() -> String {
+        format!("HashWires hash ({})", D::get_name())
+    }
+}
+
+// ======================================================================================
+
+/// A hash Merkle tree node for the top accumulator that carries just a hash value.
+#[derive(Default, Clone, Debug)]
+pub struct MTreeNodeSmt<D> {
+    hash: Vec<u8>,
+    phantom: PhantomData<D>,
+}
+
+impl<D> MTreeNodeSmt<D> {
+    pub fn new(hash: Vec<u8>) -> MTreeNodeSmt<D> {
+        MTreeNodeSmt {
+            hash,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the hash value.
+    pub fn get_hash(&self) -> &[u8] {
+        &self.hash
+    }
+}
+
+impl<D> ConstantTimeEq for MTreeNodeSmt<D> {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        ct_eq_bytes(&self.hash, &other.hash)
+    }
+}
+
+impl<D> PartialEq for MTreeNodeSmt<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl<D> Eq for MTreeNodeSmt<D> {}
+
+impl<D: Digest> Mergeable for MTreeNodeSmt<D> {
+    fn merge(lch: &MTreeNodeSmt<D>, rch: &MTreeNodeSmt<D>) -> MTreeNodeSmt<D> {
+        let mut hasher = D::new();
+        hasher.update(&lch.hash);
+        hasher.update(&rch.hash);
+        MTreeNodeSmt::new(hasher.finalize().to_vec())
+    }
+
+    fn merge_n(children: &[MTreeNodeSmt<D>]) -> MTreeNodeSmt<D> {
+        let mut hasher = D::new();
+        for child in children {
+            hasher.update(&child.hash);
+        }
+        MTreeNodeSmt::new(hasher.finalize().to_vec())
+    }
+}
+
+impl<D: Digest> MergeableEncoded for MTreeNodeSmt<D> {
+    fn get_encoded_len() -> usize {
+        D::output_size()
+    }
+
+    fn merge_encoded_rch(lch: &MTreeNodeSmt<D>, rch: &[u8]) -> MTreeNodeSmt<D> {
+        let mut hasher = D::new();
+        hasher.update(&lch.hash);
+        hasher.update(rch);
+        MTreeNodeSmt::new(hasher.finalize().to_vec())
+    }
+
+    fn merge_encoded_lch(lch: &[u8], rch: &MTreeNodeSmt<D>) -> MTreeNodeSmt<D> {
+        let mut hasher = D::new();
+        hasher.update(lch);
+        hasher.update(&rch.hash);
+        MTreeNodeSmt::new(hasher.finalize().to_vec())
+    }
+}
+
+impl<D: Digest> Paddable for MTreeNodeSmt<D> {
+    fn padding(_idx: &TreeIndex, _secret: &Secret) -> MTreeNodeSmt<D> {
+        MTreeNodeSmt::new(vec![0u8; D::output_size()])
+    }
+}
+
+impl<D: Digest> Serializable for MTreeNodeSmt<D> {
+    fn serialize(&self) -> Vec<u8> {
+        self.hash.clone()
+    }
+
+    fn deserialize_as_a_unit(bytes: &[u8], begin: &mut usize) -> Result<Self, DecodingError> {
+        if bytes.len() - *begin < D::output_size() {
+            return Err(DecodingError::BytesNotEnough);
+        }
+        let item = Self::new(bytes[*begin..*begin + D::output_size()].to_vec());
+        *begin += D::output_size();
+        Ok(item)
+    }
+}
+
+impl<D: Clone> ProofExtractable for MTreeNodeSmt<D> {
+    type ProofNode = MTreeNodeSmt<D>;
+    fn get_proof_node(&self) -> Self::ProofNode {
+        self.clone()
+    }
+
+    fn get_proof_node_ref(&self) -> Option<&Self::ProofNode> {
+        Some(self)
+    }
+}
+
+impl<D: Clone + Digest> PaddingProvable for MTreeNodeSmt<D> {
+    type PaddingProof = MTreeNodeSmt<D>;
+
+    fn prove_padding_node(&self, _idx: &TreeIndex, _secret: &Secret) -> MTreeNodeSmt<D> {
+        MTreeNodeSmt::new(vec![0u8; D::output_size()])
+    }
+
+    fn verify_padding_node(
+        node: &<Self as ProofExtractable>::ProofNode,
+        _proof: &Self::PaddingProof,
+        _idx: &TreeIndex,
+    ) -> bool {
+        *node == MTreeNodeSmt::new(vec![0u8; D::output_size()])
+    }
+}
+
+impl<D: Digest> Rand for MTreeNodeSmt<D> {
+    fn randomize_with<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        *self = MTreeNodeSmt::new(vec![0u8; D::output_size()]);
+        for item in &mut self.hash {
+            *item = rng.gen();
+        }
+    }
+}
+
+impl<D: TypeName> TypeName for MTreeNodeSmt<D> {
+    fn get_name() -> String {
+        format!("Hash ({})", D::get_name())
+    }
+}
+
+// ======================================================================================
+
+/// An SMT node whose leaves store the raw user payload along with its hash,
+/// while the node visible in Merkle proofs is only the hash, i.e., a [HashNodeSmt].
+///
+/// The hash of a leaf is ```H(payload)```, and the other nodes are the same as those of [HashNodeSmt],
+/// so the tree has the same root as a [HashNodeSmt] tree built from the hashes of the payloads.
+#[derive(Default, Clone, Debug)]
+pub struct PayloadNodeSmt<D> {
+    // The payload, which is only stored in leaves.
+    payload: Option<Vec<u8>>,
+    hash: Vec<u8>,
+    phantom: PhantomData<D>,
+}
+
+impl<D: Digest> PayloadNodeSmt<D> {
+    /// The constructor of a leaf storing the input payload.
+    pub fn new(payload: Vec<u8>) -> PayloadNodeSmt<D> {
+        let hash = D::digest(&payload).to_vec();
+        PayloadNodeSmt {
+            payload: Some(payload),
+            hash,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<D> PayloadNodeSmt<D> {
+    // The constructor of a node without payload, i.e., an internal or padding node.
+    fn new_from_hash(hash: Vec<u8>) -> PayloadNodeSmt<D> {
+        PayloadNodeSmt {
+            payload: None,
+            hash,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the payload of a leaf, or None for other nodes.
+    pub fn get_payload(&self) -> Option<&[u8]> {
+        self.payload.as_deref()
+    }
+
+    /// Returns the hash value.
+    pub fn get_hash(&self) -> &[u8] {
+        &self.hash
+    }
+}
+
+impl<D> PartialEq for PayloadNodeSmt<D> {
+    fn eq(&self, other: &Self) -> bool {
+        bool::from(ct_eq_bytes(&self.hash, &other.hash)) && self.payload == other.payload
+    }
+}
+
+impl<D> Eq for PayloadNodeSmt<D> {}
+
+impl<D: Digest> Mergeable for PayloadNodeSmt<D> {
+    fn merge(lch: &PayloadNodeSmt<D>, rch: &PayloadNodeSmt<D>) -> PayloadNodeSmt<D> {
+        let mut hasher = D::new();
+        hasher.update(&lch.hash);
+        hasher.update(&rch.hash);
+        PayloadNodeSmt::new_from_hash(hasher.finalize().to_vec())
+    }
+}
+
+impl<D: Digest> Paddable for PayloadNodeSmt<D> {
+    fn padding(idx: &TreeIndex, secret: &Secret) -> PayloadNodeSmt<D> {
+        PayloadNodeSmt::new_from_hash(HashNodeSmt::<D>::padding(idx, secret).hash)
+    }
+}
+
+impl<D: Clone> ProofExtractable for PayloadNodeSmt<D> {
+    type ProofNode = HashNodeSmt<D>;
+    fn get_proof_node(&self) -> Self::ProofNode {
+        HashNodeSmt::new(self.hash.clone())
+    }
+}
+
+impl<D: Clone + Digest> PaddingProvable for PayloadNodeSmt<D> {
+    type PaddingProof = HashNodeSmt<D>;
+
+    fn prove_padding_node(&self, idx: &TreeIndex, secret: &Secret) -> HashNodeSmt<D> {
+        self.get_proof_node().prove_padding_node(idx, secret)
+    }
+
+    fn verify_padding_node(
+        node: &<Self as ProofExtractable>::ProofNode,
+        proof: &Self::PaddingProof,
+        idx: &TreeIndex,
+    ) -> bool {
+        HashNodeSmt::<D>::verify_padding_node(node, proof, idx)
+    }
+}
+
+impl<D: Digest> Rand for PayloadNodeSmt<D> {
+    fn randomize_with<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        let mut payload = vec![0u8; rng.gen_range(0..64)];
+        rng.fill_bytes(&mut payload);
+        *self = PayloadNodeSmt::new(payload);
+    }
+}
+
+impl<D: TypeName> TypeName for PayloadNodeSmt<D> {
+    fn get_name() -> String {
+        format!("Payload ({})", D::get_name())
+    }
+}
+
+// ======================================================================================
+
+/// A Hash SMT node carrying a hash value of ```N``` bytes in a fixed-size array,
+/// where ```N``` is the output size of the digest ```D```, e.g., ```FixedHashNodeSmt<blake3::Hasher, 32>```.
+///
+/// The nodes are the same as those of [HashNodeSmt], so the trees have the same roots and the same encoded proofs,
+/// but the size of the nodes is known at compile time: the nodes and the siblings in proofs are stored inline,
+/// and decoding a node copies the bytes into the array instead of allocating a vector.
+///
+/// Merging, padding or decoding nodes whose size ```N``` isn't the output size of ```D``` fails to compile:
+///
+/// ```compile_fail
+/// use smtree::{node_template::FixedHashNodeSmt, traits::Mergeable};
+///
+/// let node = FixedHashNodeSmt::<sha2::Sha256, 64>::default();
+/// FixedHashNodeSmt::merge(&node, &node);
+/// ```
+#[derive(Clone, Debug)]
+pub struct FixedHashNodeSmt<D, const N: usize> {
+    hash: [u8; N],
+    phantom: PhantomData<D>,
+}
+
+impl<D, const N: usize> FixedHashNodeSmt<D, N> {
+    /// The number of bytes of an encoded node.
+    pub const ENCODED_LEN: usize = N;
+
+    pub fn new(hash: [u8; N]) -> FixedHashNodeSmt<D, N> {
+        FixedHashNodeSmt {
+            hash,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the hash value.
+    pub fn get_hash(&self) -> &[u8; N] {
+        &self.hash
+    }
+
+    /// Returns the encoded node as an array, without allocating.
+    pub fn to_bytes(&self) -> [u8; N] {
+        self.hash
+    }
+}
+
+impl<D: Digest, const N: usize> FixedHashNodeSmt<D, N> {
+    // Fails to compile the methods referring to it if the output size of the digest isn't ```N```.
+    const OUTPUT_SIZE_CHECK: () = assert!(
+        N == <D::OutputSize as Unsigned>::USIZE,
+        "The output size of the digest doesn't match with the node size."
+    );
+
+    // The constructor of a node from an output of the digest, or the bytes of an encoded node.
+    fn from_output(output: &[u8]) -> FixedHashNodeSmt<D, N> {
+        let () = Self::OUTPUT_SIZE_CHECK;
+        let mut hash = [0u8; N];
+        hash.copy_from_slice(output);
+        FixedHashNodeSmt::new(hash)
+    }
+}
+
+impl<D, const N: usize> Default for FixedHashNodeSmt<D, N> {
+    fn default() -> FixedHashNodeSmt<D, N> {
+        FixedHashNodeSmt::new([0u8; N])
+    }
+}
+
+impl<D, const N: usize> ConstantTimeEq for FixedHashNodeSmt<D, N> {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        ct_eq_bytes(&self.hash, &other.hash)
+    }
+}
+
+impl<D, const N: usize> PartialEq for FixedHashNodeSmt<D, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl<D, const N: usize> Eq for FixedHashNodeSmt<D, N> {}
+
+impl<D: Digest, const N: usize> Mergeable for FixedHashNodeSmt<D, N> {
+    fn merge(lch: &FixedHashNodeSmt<D, N>, rch: &FixedHashNodeSmt<D, N>) -> FixedHashNodeSmt<D, N> {
+        let mut hasher = D::new();
+        hasher.update(lch.hash);
+        hasher.update(rch.hash);
+        FixedHashNodeSmt::from_output(&hasher.finalize())
+    }
+
+    fn merge_many(
+        pairs: &[(&FixedHashNodeSmt<D, N>, &FixedHashNodeSmt<D, N>)],
+    ) -> Vec<FixedHashNodeSmt<D, N>> {
+        let mut hasher = D::new();
+        pairs
+            .iter()
+            .map(|(lch, rch)| {
+                hasher.update(lch.hash);
+                hasher.update(rch.hash);
+                FixedHashNodeSmt::from_output(&hasher.finalize_reset())
+            })
+            .collect()
+    }
+
+    fn merge_n(children: &[FixedHashNodeSmt<D, N>]) -> FixedHashNodeSmt<D, N> {
+        let mut hasher = D::new();
+        for child in children {
+            hasher.update(child.hash);
+        }
+        FixedHashNodeSmt::from_output(&hasher.finalize())
+    }
+}
+
+impl<D: Digest, const N: usize> MergeableEncoded for FixedHashNodeSmt<D, N> {
+    fn get_encoded_len() -> usize {
+        N
+    }
+
+    fn merge_encoded_rch(lch: &FixedHashNodeSmt<D, N>, rch: &[u8]) -> FixedHashNodeSmt<D, N> {
+        let mut hasher = D::new();
+        hasher.update(lch.hash);
+        hasher.update(rch);
+        FixedHashNodeSmt::from_output(&hasher.finalize())
+    }
+
+    fn merge_encoded_lch(lch: &[u8], rch: &FixedHashNodeSmt<D, N>) -> FixedHashNodeSmt<D, N> {
+        let mut hasher = D::new();
+        hasher.update(lch);
+        hasher.update(rch.hash);
+        FixedHashNodeSmt::from_output(&hasher.finalize())
+    }
+}
+
+impl<D: Digest, const N: usize> Paddable for FixedHashNodeSmt<D, N> {
+    fn padding(idx: &TreeIndex, secret: &Secret) -> FixedHashNodeSmt<D, N> {
+        let mut hasher = D::new();
+        hasher.update(secret.as_bytes());
+        hasher.update(TreeIndex::serialize(&[*idx]));
+        let pre_image = hasher.finalize_reset();
+
+        hasher.update(PADDING_STRING.as_bytes());
+        hasher.update(pre_image);
+        FixedHashNodeSmt::from_output(&hasher.finalize())
+    }
+}
+
+impl<D: Digest, const N: usize> Serializable for FixedHashNodeSmt<D, N> {
+    fn serialize(&self) -> Vec<u8> {
+        self.hash.to_vec()
+    }
+
+    fn deserialize_as_a_unit(bytes: &[u8], begin: &mut usize) -> Result<Self, DecodingError> {
+        if bytes.len() - *begin < N {
+            return Err(DecodingError::BytesNotEnough);
+        }
+        let item = Self::from_output(&bytes[*begin..*begin + N]);
+        *begin += N;
+        Ok(item)
+    }
+}
+
+impl<D: Clone, const N: usize> ProofExtractable for FixedHashNodeSmt<D, N> {
+    type ProofNode = FixedHashNodeSmt<D, N>;
+    fn get_proof_node(&self) -> Self::ProofNode {
+        self.clone()
+    }
+
+    fn get_proof_node_ref(&self) -> Option<&Self::ProofNode> {
+        Some(self)
+    }
+}
+
+impl<D: Clone + Digest, const N: usize> PaddingProvable for FixedHashNodeSmt<D, N> {
+    type PaddingProof = FixedHashNodeSmt<D, N>;
+
+    fn prove_padding_node(&self, idx: &TreeIndex, secret: &Secret) -> FixedHashNodeSmt<D, N> {
+        let mut pre_image = D::new();
+        pre_image.update(secret.as_bytes());
+        pre_image.update(TreeIndex::serialize(&[*idx]));
+        FixedHashNodeSmt::from_output(&pre_image.finalize())
+    }
+
+    fn verify_padding_node(
+        node: &<Self as ProofExtractable>::ProofNode,
+        proof: &Self::PaddingProof,
+        _idx: &TreeIndex,
+    ) -> bool {
+        let mut hasher = D::new();
+        hasher.update(PADDING_STRING.as_bytes());
+        hasher.update(proof.hash);
+        *node == FixedHashNodeSmt::from_output(&hasher.finalize())
+    }
+}
+
+impl<D: Digest, const N: usize> Rand for FixedHashNodeSmt<D, N> {
+    fn randomize_with<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        rng.fill_bytes(&mut self.hash);
+    }
+}
+
+impl<D: TypeName, const N: usize> TypeName for FixedHashNodeSmt<D, N> {
+    fn get_name() -> String {
+        format!("Fixed Hash ({}, {} bytes)", D::get_name(), N)
+    }
+}
+
+// ======================================================================================
+
+/// The domain separation byte of the hash of a leaf of a [NamespaceNodeSmt].
+const NAMESPACE_LEAF_PREFIX: u8 = 0;
+/// The domain separation byte of the hash of an internal node of a [NamespaceNodeSmt].
+const NAMESPACE_INTERNAL_PREFIX: u8 = 1;
+
+/// A Hash SMT node of a namespaced Merkle tree (NMT), which also carries the minimum and maximum namespace IDs
+/// of the real leaves in its subtree, committed in the hash of the parent,
+/// e.g., for data availability or multi-tenant commitments,
+/// where a [NamespaceProof] shows all leaves of a namespace and that none is omitted.
+///
+/// A leaf has a single namespace, a padding node has the empty range,
+/// and the range of a parent covers those of its children.
+/// The hash of a parent is ```hash(1 || lch_min || lch_max || lch_hash || rch_min || rch_max || rch_hash)```.
+#[derive(Default, Clone, Debug)]
+pub struct NamespaceNodeSmt<D> {
+    min: u64,
+    max: u64,
+    hash: Vec<u8>,
+    phantom: PhantomData<D>,
+}
+
+impl<D> NamespaceNodeSmt<D> {
+    /// The constructor of a leaf of the input namespace with the input hash.
+    pub fn new(namespace: u64, hash: Vec<u8>) -> NamespaceNodeSmt<D> {
+        NamespaceNodeSmt::new_with_range(namespace, namespace, hash)
+    }
+
+    /// The constructor of a node with the input range of namespaces,
+    /// which is empty if ```min > max```.
+    pub fn new_with_range(min: u64, max: u64, hash: Vec<u8>) -> NamespaceNodeSmt<D> {
+        NamespaceNodeSmt {
+            min,
+            max,
+            hash,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the minimum namespace of the real leaves in the subtree.
+    pub fn get_min_namespace(&self) -> u64 {
+        self.min
+    }
+
+    /// Returns the maximum namespace of the real leaves in the subtree.
+    pub fn get_max_namespace(&self) -> u64 {
+        self.max
+    }
+
+    /// Returns the hash value.
+    pub fn get_hash(&self) -> &[u8] {
+        &self.hash
+    }
+
+    /// Returns true if the input namespace is in the range of the node.
+    pub fn contains_namespace(&self, namespace: u64) -> bool {
+        self.min <= namespace && namespace <= self.max
+    }
+
+    // Append the encoding of the node, i.e., ```min || max || hash```, to the hasher.
+    fn update_hasher(&self, hasher: &mut D)
+    where
+        D: Digest,
+    {
+        hasher.update(self.min.to_le_bytes());
+        hasher.update(self.max.to_le_bytes());
+        hasher.update(&self.hash);
+    }
+}
+
+impl<D: Digest> NamespaceNodeSmt<D> {
+    /// The constructor of a leaf of the input namespace, hashing the input data as ```hash(0 || namespace || data)```.
+    pub fn from_data(namespace: u64, data: &[u8]) -> NamespaceNodeSmt<D> {
+        let mut hasher = D::new();
+        hasher.update([NAMESPACE_LEAF_PREFIX]);
+        hasher.update(namespace.to_le_bytes());
+        hasher.update(data);
+        NamespaceNodeSmt::new(namespace, hasher.finalize().to_vec())
+    }
+}
+
+impl<D> ConstantTimeEq for NamespaceNodeSmt<D> {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.min.ct_eq(&other.min)
+            & self.max.ct_eq(&other.max)
+            & ct_eq_bytes(&self.hash, &other.hash)
+    }
+}
+
+impl<D> PartialEq for NamespaceNodeSmt<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl<D> Eq for NamespaceNodeSmt<D> {}
+
+impl<D: Digest> Mergeable for NamespaceNodeSmt<D> {
+    fn merge(lch: &NamespaceNodeSmt<D>, rch: &NamespaceNodeSmt<D>) -> NamespaceNodeSmt<D> {
+        let mut hasher = D::new();
+        hasher.update([NAMESPACE_INTERNAL_PREFIX]);
+        lch.update_hasher(&mut hasher);
+        rch.update_hasher(&mut hasher);
+        // The empty range of a padding node is the identity of the union.
+        NamespaceNodeSmt::new_with_range(
+            lch.min.min(rch.min),
+            lch.max.max(rch.max),
+            hasher.finalize().to_vec(),
+        )
+    }
+}
+
+impl<D: Digest> Paddable for NamespaceNodeSmt<D> {
+    fn padding(idx: &TreeIndex, secret: &Secret) -> NamespaceNodeSmt<D> {
+        NamespaceNodeSmt::new_with_range(u64::MAX, 0, HashNodeSmt::<D>::padding(idx, secret).hash)
+    }
+}
+
+impl<D: Digest> Serializable for NamespaceNodeSmt<D> {
+    /// Encode a node in the format: ```min || max || hash```, with each namespace in 8 bytes.
+    fn serialize(&self) -> Vec<u8> {
+        let mut bytes = u64_to_bytes(self.min, 8);
+        bytes.append(&mut u64_to_bytes(self.max, 8));
+        bytes.extend_from_slice(&self.hash);
+        bytes
+    }
+
+    fn deserialize_as_a_unit(bytes: &[u8], begin: &mut usize) -> Result<Self, DecodingError> {
+        if bytes.len() - *begin < 16 + D::output_size() {
+            return Err(DecodingError::BytesNotEnough);
+        }
+        let min = bytes_to_u64(bytes, 8, begin)?;
+        let max = bytes_to_u64(bytes, 8, begin)?;
+        let item =
+            Self::new_with_range(min, max, bytes[*begin..*begin + D::output_size()].to_vec());
+        *begin += D::output_size();
+        Ok(item)
+    }
+}
+
+impl<D: Clone> ProofExtractable for NamespaceNodeSmt<D> {
+    type ProofNode = NamespaceNodeSmt<D>;
+    fn get_proof_node(&self) -> Self::ProofNode {
+        self.clone()
+    }
+
+    fn get_proof_node_ref(&self) -> Option<&Self::ProofNode> {
+        Some(self)
+    }
+}
+
+impl<D: Clone + Digest> PaddingProvable for NamespaceNodeSmt<D> {
+    type PaddingProof = HashNodeSmt<D>;
+
+    fn prove_padding_node(&self, idx: &TreeIndex, secret: &Secret) -> HashNodeSmt<D> {
+        HashNodeSmt::<D>::new(Vec::new()).prove_padding_node(idx, secret)
+    }
+
+    fn verify_padding_node(
+        node: &<Self as ProofExtractable>::ProofNode,
+        proof: &Self::PaddingProof,
+        idx: &TreeIndex,
+    ) -> bool {
+        node.min == u64::MAX
+            && node.max == 0
+            && HashNodeSmt::<D>::verify_padding_node(
+                &HashNodeSmt::new(node.hash.clone()),
+                proof,
+                idx,
+            )
+    }
+}
+
+impl<D: Digest> Rand for NamespaceNodeSmt<D> {
+    fn randomize_with<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        let mut hash = HashNodeSmt::<D>::new(Vec::new());
+        hash.randomize_with(rng);
+        *self = NamespaceNodeSmt::new(rng.gen(), hash.hash);
+    }
+}
+
+impl<D: TypeName> TypeName for NamespaceNodeSmt<D> {
+    fn get_name() -> String {
+        format!("Namespace ({})", D::get_name())
+    }
+}
+
+/// A namespace-completeness proof of an NMT, showing all leaves of a namespace, and that no leaf of it is omitted.
+///
+/// It consists of the batched Merkle proof of the leaves of the namespace,
+/// together with the leaves at the boundaries of the namespace where the ranges of the nodes don't tell it apart,
+/// and these leaves themselves.
+/// Every sibling in the proof has a range excluding the namespace, so none of the other leaves is of the namespace.
+/// If the namespace has no leaf, the proof shows its absence in the same way.
+#[derive(Clone, Default)]
+pub struct NamespaceProof<D: Clone + Default + Digest> {
+    namespace: u64,
+    proof: MerkleProof<NamespaceNodeSmt<D>>,
+    leaves: Vec<NamespaceNodeSmt<D>>,
+}
+
+impl<D: Clone + Default + Digest> NamespaceProof<D> {
+    /// Generate the proof of all leaves of the input namespace in the tree.
+    ///
+    /// The leaves of the namespace are found by descending into the subtrees whose ranges contain the namespace.
+    /// Where neither child of such a node contains the namespace, a single leaf of a child is proved,
+    /// so that both children are in the proof.
+    pub fn generate(
+        tree: &SparseMerkleTree<NamespaceNodeSmt<D>>,
+        namespace: u64,
+    ) -> NamespaceProof<D> {
+        // The nodes to be visited, with a flag marking a forced descent to a single leaf.
+        let mut stack = vec![(tree.get_root_ref(), TreeIndex::zero(0), false)];
+        let mut indexes: Vec<TreeIndex> = Vec::new();
+        let mut leaves: Vec<NamespaceNodeSmt<D>> = Vec::new();
+        while let Some((link, idx, forced)) = stack.pop() {
+            let node = tree.get_node_by_ref(link);
+            if !forced && !node.get_value().contains_namespace(namespace) {
+                continue;
+            }
+            match node.get_node_type() {
+                NodeType::Leaf => {
+                    indexes.push(idx);
+                    leaves.push(node.get_value().clone());
+                }
+                NodeType::Padding => {}
+                NodeType::Internal => {
+                    let children = [
+                        (node.get_lch().unwrap(), idx.get_lch_index()),
+                        (node.get_rch().unwrap(), idx.get_rch_index()),
+                    ];
+                    let contains = |(child, _): &(usize, TreeIndex)| {
+                        tree.get_node_by_ref(*child)
+                            .get_value()
+                            .contains_namespace(namespace)
+                    };
+                    if forced || !children.iter().any(contains) {
+                        // Descend into a child with a real leaf.
+                        let &(child, child_idx) = children
+                            .iter()
+                            .find(|(child, _)| {
+                                *tree.get_node_by_ref(*child).get_node_type() != NodeType::Padding
+                            })
+                            .unwrap();
+                        stack.push((child, child_idx, true));
+                    } else {
+                        // The left child is visited first.
+                        for &(child, child_idx) in children.iter().rev() {
+                            stack.push((child, child_idx, false));
+                        }
+                    }
+                }
+            }
+        }
+        let proof = if indexes.is_empty() {
+            MerkleProof::new_batch(&indexes)
+        } else {
+            MerkleProof::generate_inclusion_proof(tree, &indexes).unwrap()
+        };
+        NamespaceProof {
+            namespace,
+            proof,
+            leaves,
+        }
+    }
+
+    /// Returns the proved namespace.
+    pub fn get_namespace(&self) -> u64 {
+        self.namespace
+    }
+
+    /// Returns the batched Merkle proof of the proved leaves.
+    pub fn get_merkle_proof(&self) -> &MerkleProof<NamespaceNodeSmt<D>> {
+        &self.proof
+    }
+
+    /// Returns the index-leaf pairs of the namespace in increasing order of index,
+    /// excluding the leaves of other namespaces at its boundaries.
+    pub fn get_leaves(&self) -> Vec<(TreeIndex, &NamespaceNodeSmt<D>)> {
+        self.proof
+            .get_indexes()
+            .iter()
+            .zip(self.leaves.iter())
+            .filter(|(_, leaf)| leaf.min == self.namespace && leaf.max == self.namespace)
+            .map(|(idx, leaf)| (*idx, leaf))
+            .collect()
+    }
+
+    /// Verify that the proved leaves are in the tree of the input root,
+    /// and that the tree has no other leaf of the namespace.
+    pub fn verify(&self, root: &NamespaceNodeSmt<D>) -> bool {
+        if self.leaves.is_empty() {
+            return self.proof.get_batch_num() == 0
+                && self.proof.get_siblings_num() == 0
+                && !root.contains_namespace(self.namespace);
+        }
+        if self
+            .proof
+            .get_path_siblings()
+            .iter()
+            .any(|sibling| sibling.contains_namespace(self.namespace))
+        {
+            log_debug!("Namespace proof verification failed: a sibling may contain the namespace");
+            return false;
+        }
+        // The proved leaves must be leaves, i.e., of a single namespace.
+        if self.leaves.iter().any(|leaf| leaf.min != leaf.max) {
+            log_debug!("Namespace proof verification failed: a proved node is not a leaf");
+            return false;
+        }
+        self.proof.verify_batch(&self.leaves, root)
+    }
+
+    /// Verify that the input leaves, in increasing order of index, are exactly the leaves of the namespace
+    /// in the tree of the input root.
+    pub fn verify_leaves(
+        &self,
+        leaves: &[NamespaceNodeSmt<D>],
+        root: &NamespaceNodeSmt<D>,
+    ) -> bool {
+        let proved = self.get_leaves();
+        self.verify(root)
+            && proved.len() == leaves.len()
+            && proved.iter().zip(leaves).all(|((_, x), y)| *x == y)
+    }
+}
+
+impl<D: Clone + Default + Digest> Serializable for NamespaceProof<D> {
+    /// Encode a namespace proof in the format:
+    /// ```namespace || proof_len || proof || leaves```, with the namespace in 8 bytes.
+    fn serialize(&self) -> Vec<u8> {
+        let mut bytes = u64_to_bytes(self.namespace, 8);
+        let mut proof = self.proof.serialize();
+        bytes.append(&mut u64_to_bytes(proof.len() as u64, PROOF_LEN_BYTE_NUM));
+        bytes.append(&mut proof);
+        for leaf in &self.leaves {
+            bytes.append(&mut leaf.serialize());
+        }
+        bytes
+    }
+
+    /// Decode input bytes (```namespace || proof_len || proof || leaves```) as a namespace proof.
+    fn deserialize_as_a_unit(
+        bytes: &[u8],
+        begin: &mut usize,
+    ) -> Result<NamespaceProof<D>, DecodingError> {
+        let namespace = bytes_to_u64(bytes, 8, begin)?;
+        let proof_len = bytes_to_count(bytes, PROOF_LEN_BYTE_NUM, begin)?;
+        if bytes.len() - *begin < proof_len {
+            return Err(DecodingError::BytesNotEnough);
+        }
+        let proof =
+            MerkleProof::<NamespaceNodeSmt<D>>::deserialize(&bytes[*begin..*begin + proof_len])?;
+        *begin += proof_len;
+        let mut leaves = Vec::with_capacity(proof.get_batch_num());
+        for _ in 0..proof.get_batch_num() {
+            leaves.push(NamespaceNodeSmt::deserialize_as_a_unit(bytes, begin)?);
+        }
+        Ok(NamespaceProof {
+            namespace,
+            proof,
+            leaves,
+        })
     }
 }
 
-/// ======================================================================================
+// ======================================================================================
 
 impl TypeName for blake3::Hasher {
     fn get_name() -> String {
@@ -5,16 +5,18 @@
 
 //! SMTree secret.
 
-#[cfg(feature = "rand")]
+use hkdf::Hkdf;
 use rand::{CryptoRng, RngCore};
+use sha2::Sha256;
 use zeroize::Zeroize;
 
 use crate::error::TreeError;
-use rand::{CryptoRng, RngCore};
 
 /// The length of an SMTree `Secret`, in bytes.
 pub const SECRET_LENGTH: usize = 32;
 pub const ALL_ZEROS_SECRET: Secret = Secret([0u8; 32]);
+/// The HKDF salt of sub-secrets, separating them from other uses of the secret.
+const SUBSECRET_SALT: &[u8] = b"smtree_subsecret";
 
 /// An SMTree secret.
 ///
@@ -48,7 +50,8 @@ impl Secret {
     /// # Example
     ///
     /// ```
-    /// # fn doctest() -> Result<SecretKey, SignatureError> {
+    /// # use smtree::error::TreeError;
+    /// # fn doctest() -> Result<Secret, TreeError> {
     /// use smtree::pad_secret::{Secret, SECRET_LENGTH};
     /// let secret_bytes: [u8; SECRET_LENGTH] = [
     ///    112, 012, 187, 211, 011, 092, 030, 001,
@@ -61,6 +64,7 @@ impl Secret {
     /// # Ok(secret)
     /// # }
     /// #
+    /// # use smtree::pad_secret::Secret;
     /// # fn main() {
     /// #     let result = doctest();
     /// #     assert!(result.is_ok());
@@ -70,7 +74,7 @@ impl Secret {
     /// # Returns
     ///
     /// A `Result` whose okay value is an SMTree `Secret` or whose error value
-    /// is an `SignatureError` wrapping the internal error that occurred.
+    /// is a `TreeError` wrapping the internal error that occurred.
     #[inline]
     pub fn from_bytes(bytes: &[u8]) -> Result<Secret, TreeError> {
         if bytes.len() != SECRET_LENGTH {
@@ -87,16 +91,11 @@ impl Secret {
     /// # Example
     ///
     /// ```
-    /// extern crate rand;
-    ///
-    /// # #[cfg(feature = "std")]
-    /// # fn main() {
-    /// #
     /// use rand::rngs::OsRng;
+    /// use smtree::pad_secret::Secret;
     ///
-    /// let mut csprng = OsRng{};
+    /// let mut csprng = OsRng {};
     /// let secret_key: Secret = Secret::generate(&mut csprng);
-    /// # }
     /// ```
     ///
     /// # Input
@@ -110,4 +109,73 @@ impl Secret {
         csprng.fill_bytes(&mut sk.0);
         sk
     }
+
+    /// Derive a `Secret` from a passphrase and a salt with the memory-hard Argon2id KDF,
+    /// with its default parameters (19 MiB of memory, 2 iterations, 1 lane).
+    /// Enabled by the `argon2` feature.
+    ///
+    /// The salt should be unique per deployment and at least 8 bytes long,
+    /// and it can be stored next to the configuration, unlike the passphrase.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smtree::pad_secret::Secret;
+    ///
+    /// let secret = Secret::derive_from_passphrase(b"correct horse battery staple", b"deployment-1").unwrap();
+    /// ```
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose okay value is an SMTree `Secret` or whose error value
+    /// is `TreeError::SecretError` if the salt or the passphrase is rejected by Argon2, e.g., a too short salt.
+    #[cfg(feature = "argon2")]
+    pub fn derive_from_passphrase(passphrase: &[u8], salt: &[u8]) -> Result<Secret, TreeError> {
+        Self::derive_from_passphrase_with_params(passphrase, salt, argon2::Params::default())
+    }
+
+    /// Derive a `Secret` from a passphrase and a salt with Argon2id under the input parameters,
+    /// e.g., with more memory or iterations than the defaults of
+    /// [derive_from_passphrase](#method.derive_from_passphrase).
+    /// Enabled by the `argon2` feature.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose okay value is an SMTree `Secret` or whose error value
+    /// is `TreeError::SecretError` if the salt or the passphrase is rejected by Argon2.
+    #[cfg(feature = "argon2")]
+    pub fn derive_from_passphrase_with_params(
+        passphrase: &[u8],
+        salt: &[u8],
+        params: argon2::Params,
+    ) -> Result<Secret, TreeError> {
+        let kdf = argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+        let mut sk: Secret = Secret([0u8; 32]);
+        kdf.hash_password_into(passphrase, salt, &mut sk.0)
+            .map_err(|_| TreeError::SecretError)?;
+        Ok(sk)
+    }
+
+    /// Derive an independent `Secret` for the input label with HKDF-SHA256,
+    /// e.g., one padding secret per tree or per epoch from a single master secret.
+    ///
+    /// The same secret and label always derive the same sub-secret,
+    /// and sub-secrets of different labels don't reveal each other or the master secret.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use smtree::pad_secret::Secret;
+    ///
+    /// let master = Secret::from_bytes(&[7u8; 32]).unwrap();
+    /// let accounts = master.derive_subsecret(b"accounts");
+    /// assert_ne!(accounts.as_bytes(), master.derive_subsecret(b"balances").as_bytes());
+    /// ```
+    pub fn derive_subsecret(&self, label: &[u8]) -> Secret {
+        let mut sk: Secret = Secret([0u8; 32]);
+        Hkdf::<Sha256>::new(Some(SUBSECRET_SALT), &self.0)
+            .expand(label, &mut sk.0)
+            .expect("The output length of HKDF-SHA256 is valid.");
+        sk
+    }
 }
@@ -0,0 +1,184 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! This module provides a partial sparse Merkle tree, a.k.a. a witness tree,
+//! which stores only the nodes needed to prove and update a chosen set of leaves.
+//!
+//! A partial tree keeps the values of the chosen leaves and the siblings of the nodes on their paths,
+//! i.e., the nodes of their batched Merkle proof.
+//! It has the same root as the full tree, and serves Merkle proofs of any subset of the chosen leaves,
+//! so a light service doesn't need the full tree to serve a handful of users.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    error::TreeError,
+    index::TreeIndex,
+    proof::MerkleProof,
+    traits::{InclusionProvable, Mergeable, Paddable, ProofExtractable, Serializable},
+    tree::SparseMerkleTree,
+};
+
+/// A partial paddable sparse Merkle tree storing the chosen leaves and the siblings on their paths.
+#[derive(Debug, Clone)]
+pub struct PartialSmt<P: ProofExtractable> {
+    height: usize,
+    root: P::ProofNode,
+    leaves: BTreeMap<TreeIndex, P>,
+    // The siblings of the nodes on the paths of the leaves.
+    siblings: BTreeMap<TreeIndex, P::ProofNode>,
+}
+
+impl<P: Clone + Default + Mergeable + Paddable + ProofExtractable> PartialSmt<P>
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// Construct the partial tree of the input leaves from a full tree.
+    ///
+    /// Return ```None``` if the list is empty, or if any input index is not a leaf of the tree.
+    pub fn from_tree(tree: &SparseMerkleTree<P>, list: &[TreeIndex]) -> Option<PartialSmt<P>> {
+        let proof = MerkleProof::<P>::generate_inclusion_proof(tree, list)?;
+        let leaves: Option<Vec<P>> = list
+            .iter()
+            .map(|idx| Some(tree.get_leaf_by_index(idx)?.get_value().clone()))
+            .collect();
+        Self::from_proof(&proof, &leaves?)
+    }
+
+    /// Construct the partial tree of the proved leaves from a (batched) Merkle proof
+    /// and the values of the leaves in the order of the proved indexes.
+    ///
+    /// The root of the partial tree is the one computed from the proof,
+    /// and should be checked against the trusted root, e.g., with [get_root](#method.get_root).
+    ///
+    /// Return ```None``` if the proof has no leaf, if the proved indexes are not valid,
+    /// or if the numbers of leaves or siblings don't match with the proved indexes.
+    pub fn from_proof(proof: &MerkleProof<P>, leaves: &[P]) -> Option<PartialSmt<P>> {
+        let indexes = proof.get_indexes();
+        if indexes.is_empty() || indexes.len() != leaves.len() {
+            return None;
+        }
+        let positions = proof.get_sibling_positions()?;
+        if positions.len() != proof.get_siblings_num() {
+            return None;
+        }
+        let mut partial = PartialSmt {
+            height: indexes[0].get_height(),
+            root: P::ProofNode::default(),
+            leaves: indexes
+                .iter()
+                .copied()
+                .zip(leaves.iter().cloned())
+                .collect(),
+            siblings: positions
+                .into_iter()
+                .zip(proof.get_path_siblings().iter().cloned())
+                .collect(),
+        };
+        partial.root = partial.compute_node(&TreeIndex::zero(0))?;
+        Some(partial)
+    }
+
+    /// Returns the height of the tree.
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the number of leaves in the partial tree.
+    pub fn get_leaves_num(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// Returns the number of stored nodes, i.e., the leaves and the siblings on their paths.
+    pub fn get_nodes_num(&self) -> usize {
+        self.leaves.len() + self.siblings.len()
+    }
+
+    /// Returns the root, the same as that of the full tree.
+    pub fn get_root(&self) -> &P::ProofNode {
+        &self.root
+    }
+
+    /// Returns the value of the leaf at the input index, or None if it is not in the partial tree.
+    pub fn get_leaf_by_index(&self, idx: &TreeIndex) -> Option<&P> {
+        self.leaves.get(idx)
+    }
+
+    /// Returns the Merkle proof of the input leaves, the same as the proof generated from the full tree.
+    ///
+    /// Return ```None``` if the list is empty, or if any input index is not a leaf of the partial tree.
+    pub fn generate_proof(&self, list: &[TreeIndex]) -> Option<MerkleProof<P>> {
+        if list.is_empty() || list.iter().any(|idx| !self.leaves.contains_key(idx)) {
+            return None;
+        }
+        let mut proof = MerkleProof::<P>::new_batch(list);
+        let siblings: Option<Vec<P::ProofNode>> = proof
+            .get_sibling_positions()?
+            .iter()
+            .map(|idx| self.compute_node(idx))
+            .collect();
+        proof.set_siblings(siblings?);
+        Some(proof)
+    }
+
+    /// Update the leaf at the input index with the input value, and recompute the root.
+    ///
+    /// If the index is not a leaf of the partial tree,
+    /// return [TreeError::LeafNotFound](../error/enum.TreeError.html#variant.LeafNotFound).
+    pub fn update(&mut self, idx: &TreeIndex, value: P) -> Result<(), TreeError> {
+        match self.leaves.get_mut(idx) {
+            Some(leaf) => *leaf = value,
+            None => return Err(TreeError::LeafNotFound),
+        }
+        // The nodes on the paths are all known, as the partial tree has been constructed.
+        self.root = self.compute_node(&TreeIndex::zero(0)).unwrap();
+        Ok(())
+    }
+
+    /// Update the leaves of the partial tree with the input index-value pairs,
+    /// and recompute the root once, merging each internal node on the paths once.
+    ///
+    /// If any index is not a leaf of the partial tree,
+    /// return [TreeError::LeafNotFound](../error/enum.TreeError.html#variant.LeafNotFound),
+    /// leaving the partial tree unchanged.
+    pub fn update_batch(&mut self, list: &[(TreeIndex, P)]) -> Result<(), TreeError> {
+        if list.iter().any(|(idx, _)| !self.leaves.contains_key(idx)) {
+            return Err(TreeError::LeafNotFound);
+        }
+        for (idx, value) in list {
+            self.leaves.insert(*idx, value.clone());
+        }
+        self.root = self.compute_node(&TreeIndex::zero(0)).unwrap();
+        Ok(())
+    }
+
+    /// Returns true if the subtree rooted at the input index has a leaf of the partial tree.
+    fn has_leaf_below(&self, prefix: &TreeIndex) -> bool {
+        let mut low = *prefix;
+        low.set_height(self.height);
+        let mut high = *prefix;
+        while high.get_height() < self.height {
+            high = high.get_rch_index();
+        }
+        self.leaves.range(low..=high).next().is_some()
+    }
+
+    /// Computes the node at the input index from the leaves and the siblings,
+    /// or returns None if the node is neither stored nor on the path of a leaf.
+    fn compute_node(&self, idx: &TreeIndex) -> Option<P::ProofNode> {
+        if let Some(node) = self.siblings.get(idx) {
+            return Some(node.clone());
+        }
+        if idx.get_height() == self.height {
+            return self.leaves.get(idx).map(|x| x.get_proof_node());
+        }
+        if !self.has_leaf_below(idx) {
+            return None;
+        }
+        let lch = self.compute_node(&idx.get_lch_index())?;
+        let rch = self.compute_node(&idx.get_rch_index())?;
+        Some(Mergeable::merge_with_index(&lch, &rch, idx))
+    }
+}
@@ -0,0 +1,329 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! A node template of Pedersen commitments for confidential sums, enabled by the ```pedersen``` feature.
+//!
+//! A leaf commits to a balance ```v``` with a blinding factor ```r``` as ```C = v * G + r * H```
+//! on the Ristretto group, where ```G``` is the base point and ```H``` is a generator of unknown discrete log.
+//! A parent commits to the sum of its children, ```C = C_l + C_r```,
+//! and its hash ```H(C || h_l || h_r)``` binds the commitment to the subtree.
+//! Padding nodes commit to a zero balance, with a blinding factor derived from the secret and the index,
+//! and the padding proof reveals this blinding factor.
+//!
+//! The proofs thus reveal neither the balances of the leaves nor the total balance,
+//! which the tree owner can open from the blinding factor of the root,
+//! as in proofs of liabilities.
+//!
+//! Note that the commitments alone don't prove the total balance to be the sum of non-negative balances:
+//! a commitment to a value close to the group order acts as a negative balance and lowers the opened sum.
+//! A proof of liabilities thus needs a range proof, e.g., a Bulletproof,
+//! that every leaf commitment opens to a value in ```[0, 2^64)```,
+//! verified together with the Merkle proof of the leaf.
+//! This module doesn't provide the range proofs.
+
+use std::marker::PhantomData;
+use std::sync::OnceLock;
+
+use curve25519_dalek::{
+    constants::RISTRETTO_BASEPOINT_POINT,
+    ristretto::{CompressedRistretto, RistrettoPoint},
+    scalar::Scalar,
+    traits::MultiscalarMul,
+};
+use digest::Digest;
+use rand::Rng;
+use sha2::Sha512;
+use subtle::ConstantTimeEq;
+
+use crate::pad_secret::Secret;
+use crate::{
+    error::DecodingError,
+    index::TreeIndex,
+    node_template::PADDING_STRING,
+    traits::{
+        Mergeable, Paddable, PaddingProvable, ProofExtractable, Rand, Serializable, TypeName,
+    },
+};
+
+/// The number of bytes of a compressed Ristretto point or a scalar.
+const POINT_BYTE_NUM: usize = 32;
+/// The input to derive the generator ```H``` of the commitments.
+const GENERATOR_STRING: &str = "smtree_pedersen_generator_h";
+
+/// Returns the generator ```H``` for blinding factors, whose discrete log with respect to ```G``` is unknown.
+pub fn get_blinding_generator() -> RistrettoPoint {
+    static GENERATOR: OnceLock<RistrettoPoint> = OnceLock::new();
+    *GENERATOR.get_or_init(|| {
+        RistrettoPoint::from_uniform_bytes(&to_wide(&Sha512::digest(GENERATOR_STRING.as_bytes())))
+    })
+}
+
+/// Returns the Pedersen commitment ```value * G + blinding * H```.
+pub fn commit(value: u64, blinding: &Scalar) -> RistrettoPoint {
+    RistrettoPoint::multiscalar_mul(
+        &[Scalar::from(value), *blinding],
+        &[RISTRETTO_BASEPOINT_POINT, get_blinding_generator()],
+    )
+}
+
+// The hash binding a leaf or padding commitment.
+fn hash_commitment<D: Digest>(commitment: &RistrettoPoint) -> Vec<u8> {
+    D::digest(commitment.compress().as_bytes()).to_vec()
+}
+
+// The blinding factor of the padding node at the input index.
+fn get_padding_blinding(idx: &TreeIndex, secret: &Secret) -> Scalar {
+    let mut hasher = Sha512::new();
+    hasher.update(PADDING_STRING.as_bytes());
+    hasher.update(secret.as_bytes());
+    hasher.update(TreeIndex::serialize(&[*idx]));
+    Scalar::from_bytes_mod_order_wide(&to_wide(&hasher.finalize()))
+}
+
+// Converts a SHA-512 output to an array.
+fn to_wide(bytes: &[u8]) -> [u8; 2 * POINT_BYTE_NUM] {
+    let mut wide = [0u8; 2 * POINT_BYTE_NUM];
+    wide.copy_from_slice(bytes);
+    wide
+}
+
+// ======================================================================================
+
+/// The node in Merkle proofs of a [PedersenNodeSmt], carrying the commitment and the hash.
+#[derive(Default, Clone, Debug)]
+pub struct PedersenProofNode<D> {
+    commitment: RistrettoPoint,
+    hash: Vec<u8>,
+    phantom: PhantomData<D>,
+}
+
+impl<D> PedersenProofNode<D> {
+    pub fn new(commitment: RistrettoPoint, hash: Vec<u8>) -> PedersenProofNode<D> {
+        PedersenProofNode {
+            commitment,
+            hash,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the commitment to the sum of the subtree.
+    pub fn get_commitment(&self) -> &RistrettoPoint {
+        &self.commitment
+    }
+
+    /// Returns the hash value.
+    pub fn get_hash(&self) -> &[u8] {
+        &self.hash
+    }
+
+    /// Verify that the commitment opens to the input value and blinding factor,
+    /// e.g., to reveal the total balance committed in the root.
+    pub fn verify_opening(&self, value: u64, blinding: &Scalar) -> bool {
+        self.commitment == commit(value, blinding)
+    }
+}
+
+impl<D> PartialEq for PedersenProofNode<D> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.commitment.ct_eq(&other.commitment) & self.hash.ct_eq(&other.hash)).into()
+    }
+}
+
+impl<D> Eq for PedersenProofNode<D> {}
+
+impl<D: Digest> Mergeable for PedersenProofNode<D> {
+    fn merge(lch: &PedersenProofNode<D>, rch: &PedersenProofNode<D>) -> PedersenProofNode<D> {
+        let commitment = lch.commitment + rch.commitment;
+        let mut hasher = D::new();
+        hasher.update(commitment.compress().as_bytes());
+        hasher.update(&lch.hash);
+        hasher.update(&rch.hash);
+        PedersenProofNode::new(commitment, hasher.finalize().to_vec())
+    }
+}
+
+impl<D: Digest> Serializable for PedersenProofNode<D> {
+    /// Encode the node as ```compressed_commitment || hash```.
+    fn serialize(&self) -> Vec<u8> {
+        let mut bytes = self.commitment.compress().to_bytes().to_vec();
+        bytes.extend_from_slice(&self.hash);
+        bytes
+    }
+
+    /// Decode the input bytes (```compressed_commitment || hash```) as a proof node.
+    ///
+    /// If the commitment isn't a valid Ristretto point,
+    /// return [DecodingError::ValueDecodingError](../error/enum.DecodingError.html#variant.ValueDecodingError).
+    fn deserialize_as_a_unit(bytes: &[u8], begin: &mut usize) -> Result<Self, DecodingError> {
+        if bytes.len() - *begin < POINT_BYTE_NUM + D::output_size() {
+            return Err(DecodingError::BytesNotEnough);
+        }
+        let commitment = CompressedRistretto::from_slice(&bytes[*begin..*begin + POINT_BYTE_NUM])
+            .ok()
+            .and_then(|x| x.decompress())
+            .ok_or_else(|| DecodingError::ValueDecodingError {
+                msg: "Invalid Ristretto point".to_string(),
+            })?;
+        *begin += POINT_BYTE_NUM;
+        let hash = bytes[*begin..*begin + D::output_size()].to_vec();
+        *begin += D::output_size();
+        Ok(PedersenProofNode::new(commitment, hash))
+    }
+}
+
+// ======================================================================================
+
+/// The proof of a padding node of a [PedersenNodeSmt], i.e., its blinding factor,
+/// showing that the padding node commits to a zero balance.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct PedersenPaddingProof(Scalar);
+
+impl PedersenPaddingProof {
+    pub fn new(blinding: Scalar) -> PedersenPaddingProof {
+        PedersenPaddingProof(blinding)
+    }
+
+    /// Returns the blinding factor.
+    pub fn get_blinding(&self) -> &Scalar {
+        &self.0
+    }
+}
+
+impl Serializable for PedersenPaddingProof {
+    fn serialize(&self) -> Vec<u8> {
+        self.0.to_bytes().to_vec()
+    }
+
+    /// Decode the input bytes as a scalar.
+    ///
+    /// If the scalar isn't canonically encoded,
+    /// return [DecodingError::ValueDecodingError](../error/enum.DecodingError.html#variant.ValueDecodingError).
+    fn deserialize_as_a_unit(bytes: &[u8], begin: &mut usize) -> Result<Self, DecodingError> {
+        if bytes.len() - *begin < POINT_BYTE_NUM {
+            return Err(DecodingError::BytesNotEnough);
+        }
+        let mut scalar = [0u8; POINT_BYTE_NUM];
+        scalar.copy_from_slice(&bytes[*begin..*begin + POINT_BYTE_NUM]);
+        let scalar: Option<Scalar> = Scalar::from_canonical_bytes(scalar).into();
+        let scalar = scalar.ok_or_else(|| DecodingError::ValueDecodingError {
+            msg: "Non-canonical scalar".to_string(),
+        })?;
+        *begin += POINT_BYTE_NUM;
+        Ok(PedersenPaddingProof(scalar))
+    }
+}
+
+// ======================================================================================
+
+/// An SMT node committing to the sum of balances in its subtree with a Pedersen commitment.
+///
+/// The tree node keeps the opening of its commitment, i.e., the sum and the blinding factor,
+/// while the node in Merkle proofs is a [PedersenProofNode] hiding them.
+#[derive(Default, Clone, Debug)]
+pub struct PedersenNodeSmt<D> {
+    value: u64,
+    blinding: Scalar,
+    node: PedersenProofNode<D>,
+}
+
+impl<D: Digest> PedersenNodeSmt<D> {
+    /// The constructor of a leaf committing to the input balance with the input blinding factor.
+    pub fn new(value: u64, blinding: Scalar) -> PedersenNodeSmt<D> {
+        let commitment = commit(value, &blinding);
+        PedersenNodeSmt {
+            value,
+            blinding,
+            node: PedersenProofNode::new(commitment, hash_commitment::<D>(&commitment)),
+        }
+    }
+
+    /// Returns the parent of the two nodes, or ```None``` if the sum of their balances overflows.
+    pub fn checked_merge(
+        lch: &PedersenNodeSmt<D>,
+        rch: &PedersenNodeSmt<D>,
+    ) -> Option<PedersenNodeSmt<D>> {
+        Some(PedersenNodeSmt {
+            value: lch.value.checked_add(rch.value)?,
+            blinding: lch.blinding + rch.blinding,
+            node: Mergeable::merge(&lch.node, &rch.node),
+        })
+    }
+}
+
+impl<D> PedersenNodeSmt<D> {
+    /// Returns the committed sum of balances.
+    pub fn get_value(&self) -> u64 {
+        self.value
+    }
+
+    /// Returns the blinding factor of the commitment.
+    pub fn get_blinding(&self) -> &Scalar {
+        &self.blinding
+    }
+}
+
+impl<D> PartialEq for PedersenNodeSmt<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value && self.blinding == other.blinding && self.node == other.node
+    }
+}
+
+impl<D> Eq for PedersenNodeSmt<D> {}
+
+impl<D: Digest> Mergeable for PedersenNodeSmt<D> {
+    /// Panics if the sum of the balances overflows, see [checked_merge](struct.PedersenNodeSmt.html#method.checked_merge).
+    fn merge(lch: &PedersenNodeSmt<D>, rch: &PedersenNodeSmt<D>) -> PedersenNodeSmt<D> {
+        PedersenNodeSmt::checked_merge(lch, rch).expect("The sum of the balances overflows.")
+    }
+}
+
+impl<D: Digest> Paddable for PedersenNodeSmt<D> {
+    fn padding(idx: &TreeIndex, secret: &Secret) -> PedersenNodeSmt<D> {
+        PedersenNodeSmt::new(0, get_padding_blinding(idx, secret))
+    }
+}
+
+impl<D: Clone> ProofExtractable for PedersenNodeSmt<D> {
+    type ProofNode = PedersenProofNode<D>;
+    fn get_proof_node(&self) -> Self::ProofNode {
+        self.node.clone()
+    }
+
+    fn get_proof_node_ref(&self) -> Option<&Self::ProofNode> {
+        Some(&self.node)
+    }
+}
+
+impl<D: Clone + Digest> PaddingProvable for PedersenNodeSmt<D> {
+    type PaddingProof = PedersenPaddingProof;
+
+    fn prove_padding_node(&self, idx: &TreeIndex, secret: &Secret) -> PedersenPaddingProof {
+        PedersenPaddingProof(get_padding_blinding(idx, secret))
+    }
+
+    fn verify_padding_node(
+        node: &<Self as ProofExtractable>::ProofNode,
+        proof: &Self::PaddingProof,
+        _idx: &TreeIndex,
+    ) -> bool {
+        let commitment = commit(0, &proof.0);
+        *node == PedersenProofNode::new(commitment, hash_commitment::<D>(&commitment))
+    }
+}
+
+impl<D: Digest> Rand for PedersenNodeSmt<D> {
+    fn randomize_with<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        let mut blinding = [0u8; 2 * POINT_BYTE_NUM];
+        rng.fill_bytes(&mut blinding);
+        let value: u32 = rng.gen();
+        *self = PedersenNodeSmt::new(value as u64, Scalar::from_bytes_mod_order_wide(&blinding));
+    }
+}
+
+impl<D: TypeName> TypeName for PedersenNodeSmt<D> {
+    fn get_name() -> String {
+        format!("Pedersen ({})", D::get_name())
+    }
+}
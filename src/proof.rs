@@ -6,18 +6,29 @@
 //! This module provides definitions of the inclusion proof (Merkle proof) of a tree node in an SMT,
 //! and proof verification.
 
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fmt::Debug;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
 
 use crate::pad_secret::{Secret, ALL_ZEROS_SECRET};
+#[cfg(feature = "base64")]
+use crate::utils::{base64_to_bytes, bytes_to_base64};
+#[cfg(feature = "hex")]
+use crate::utils::{bytes_to_hex, hex_to_bytes};
 use crate::{
     error::DecodingError,
-    index::TreeIndex,
+    index::{TreeIndex, BYTE_NUM, HEIGHT_BYTE_NUM, MAX_HEIGHT},
+    metrics::{Operation, OperationMetrics},
     traits::{
-        InclusionProvable, Mergeable, Paddable, PaddingProvable, ProofExtractable,
-        RandomSampleable, Serializable,
+        InclusionProvable, Mergeable, MergeableEncoded, Paddable, PaddingContext, PaddingProvable,
+        ProofExtractable, RandomSampleable, Serializable, Summable,
+    },
+    tree::{ChildDir, MerklePathRefs, NodeType, SparseMerkleTree},
+    utils::{
+        bytes_to_count, read_bytes, read_bytes_or_end, read_unit, u64_to_bytes, write_unit, Nil,
     },
-    tree::{ChildDir, NodeType, SparseMerkleTree},
-    utils::{bytes_to_usize, usize_to_bytes, Nil},
 };
 
 /// The number of bytes for encoding the batch num in a Merkle proof.
@@ -99,6 +110,16 @@ where
         &self.siblings[idx]
     }
 
+    /// Replace the sibling at the input index, in the same order as [get_sibling_at_idx](#method.get_sibling_at_idx).
+    ///
+    /// Panics if the input index is out of the range ```[0, siblings_num-1]```.
+    pub(crate) fn set_sibling_at_idx(&mut self, idx: usize, value: V::ProofNode) {
+        if idx >= self.siblings.len() {
+            panic!("The input index is out of range.");
+        }
+        self.siblings[idx] = value;
+    }
+
     /// Add a sibling node at the end of the proof path.
     pub fn add_sibling(&mut self, value: V::ProofNode) {
         self.siblings.push(value);
@@ -109,39 +130,219 @@ where
         self.siblings = value;
     }
 
+    /// Returns an iterator over the siblings together with their tree indexes,
+    /// in the same order as [get_path_siblings](#method.get_path_siblings).
+    ///
+    /// The tree index of each sibling is recomputed from the proved indexes.
+    /// If the proved indexes are not valid, e.g., not sorted or of different heights, the iterator is empty.
+    /// If the number of siblings doesn't match with the proved indexes,
+    /// the iterator stops at the end of the shorter one.
+    pub fn iter_siblings_with_index(&self) -> impl Iterator<Item = (TreeIndex, &V::ProofNode)> {
+        self.get_sibling_positions()
+            .unwrap_or_default()
+            .into_iter()
+            .zip(self.siblings.iter())
+    }
+
+    // Returns the tree indexes of the siblings in the proof derived from the proved indexes,
+    // or None if the proved indexes are not valid.
+    pub(crate) fn get_sibling_positions(&self) -> Option<Vec<TreeIndex>> {
+        if self.indexes.is_empty() {
+            return Some(Vec::new());
+        }
+
+        // Construct an SMT from the indexes in the proof, in which the padding nodes are the siblings.
+        let mut proof_tree: SparseMerkleTree<Nil> =
+            SparseMerkleTree::new(self.indexes[0].get_height());
+        let mut list_for_building: Vec<(TreeIndex, Nil)> = Vec::new();
+        for index in &self.indexes {
+            list_for_building.push((*index, Nil));
+        }
+        if proof_tree
+            .construct_smt_nodes(&list_for_building, &ALL_ZEROS_SECRET)
+            .is_some()
+        {
+            return None;
+        }
+
+        // The siblings follow the BFS order of padding nodes in the proof_tree.
+        Some(
+            proof_tree
+                .get_index_ref_pairs()
+                .into_iter()
+                .filter(|(_, x)| {
+                    *proof_tree.get_node_by_ref(*x).get_node_type() == NodeType::Padding
+                })
+                .map(|(index, _)| index)
+                .collect(),
+        )
+    }
+
     /// Verify a Merkle proof of a single node.
     pub fn verify(&self, leaf: &V::ProofNode, root: &V::ProofNode) -> bool {
+        let value = match self.compute_root(leaf) {
+            Some(x) => x,
+            None => return false,
+        };
+
+        // Compare the computed hash with the tree root.
+        if value != *root {
+            log_debug!("Proof verification failed: root mismatch");
+            return false;
+        }
+        true
+    }
+
+    /// Returns the root computed from the Merkle proof of a single node with the input leaf.
+    ///
+    /// Return ```None``` if the proof is not valid, i.e.,
+    /// there is more than one index, or the number of siblings doesn't match with the height of the index.
+    pub fn compute_root(&self, leaf: &V::ProofNode) -> Option<V::ProofNode> {
         // Check if there is only one index.
         if self.indexes.len() != 1 {
-            return false;
+            log_debug!(
+                "Proof verification failed: {} indexes in a single proof",
+                self.indexes.len()
+            );
+            return None;
         }
 
         // If the number of siblings doesn't match with the height of the proved node,
         // the proof is invalid.
         if self.siblings.len() != self.indexes[0].get_height() {
-            return false;
+            log_debug!(
+                "Proof verification failed: {} siblings for height {}",
+                self.siblings.len(),
+                self.indexes[0].get_height()
+            );
+            return None;
         }
 
         // Compute the hash along the Merkle path.
         let mut value = leaf.clone();
         for i in (0..self.siblings.len()).rev() {
             // H[node] = hash(H[lch] | H[rch])
+            let parent = self.indexes[0].get_prefix(i);
             if self.indexes[0].get_bit(i) == 0 {
-                value = Mergeable::merge(&value, &self.siblings[i]);
+                value = Mergeable::merge_with_index(&value, &self.siblings[i], &parent);
             } else {
-                value = Mergeable::merge(&self.siblings[i], &value);
+                value = Mergeable::merge_with_index(&self.siblings[i], &value, &parent);
             }
         }
+        Some(value)
+    }
 
-        // Compare the computed hash with the tree root.
-        value == *root
+    /// Returns the new root after changing the proved leaf from ```old_leaf``` to ```new_leaf```,
+    /// computed from the Merkle proof of the old leaf without the tree,
+    /// e.g., for a stateless writer or a verifier of updates.
+    ///
+    /// The siblings don't change with the leaf, so the same proof proves the new leaf against the new root.
+    ///
+    /// Return ```None``` if the proof of the old leaf against the input root is not valid.
+    pub fn compute_updated_root(
+        &self,
+        old_leaf: &V::ProofNode,
+        new_leaf: &V::ProofNode,
+        root: &V::ProofNode,
+    ) -> Option<V::ProofNode> {
+        if !self.verify(old_leaf, root) {
+            return None;
+        }
+        self.compute_root(new_leaf)
+    }
+
+    /// Verify many independent proofs of single nodes against the same root,
+    /// returning the verification result of each proof.
+    ///
+    /// The nodes along the path of a valid proof, and their siblings, are authenticated by the root,
+    /// so the verification of a later proof stops merging at the first node of its path that is already authenticated,
+    /// and only compares the siblings above it, i.e., the upper levels shared by the proofs are merged once
+    /// rather than once per proof.
+    pub fn verify_many(
+        proofs: &[(MerkleProof<V>, V::ProofNode)],
+        root: &V::ProofNode,
+    ) -> Vec<bool> {
+        // The authenticated nodes by their tree indexes, starting with the root.
+        let mut known: BTreeMap<TreeIndex, V::ProofNode> = BTreeMap::new();
+        known.insert(TreeIndex::zero(0), root.clone());
+        proofs
+            .iter()
+            .map(|(proof, leaf)| proof.verify_with_known(leaf, &mut known))
+            .collect()
+    }
+
+    // Verify the proof of a single node up to the first authenticated node of its path,
+    // and authenticate the nodes along the path and their siblings if the proof is valid.
+    fn verify_with_known(
+        &self,
+        leaf: &V::ProofNode,
+        known: &mut BTreeMap<TreeIndex, V::ProofNode>,
+    ) -> bool {
+        if self.indexes.len() != 1 || self.siblings.len() != self.indexes[0].get_height() {
+            log_debug!("Proof verification failed: not a valid proof of a single node");
+            return false;
+        }
+        let index = self.indexes[0];
+
+        // The nodes along the path below the first authenticated node, from the leaf up.
+        let mut path: Vec<(TreeIndex, V::ProofNode)> = Vec::new();
+        let mut value = leaf.clone();
+        // The root is authenticated, so the loop stops at the latest at depth 0.
+        for depth in (0..=index.get_height()).rev() {
+            let idx = index.get_prefix(depth);
+            if let Some(node) = known.get(&idx) {
+                // An authenticated node has only one value, unless the hash function has a collision.
+                if *node != value {
+                    log_debug!("Proof verification failed: mismatch with an authenticated node");
+                    return false;
+                }
+                // The siblings above are authenticated with the node, and must be those in the proof.
+                let upper_mismatch = (0..depth).any(|i| {
+                    let sibling_idx = index.get_prefix(i + 1).get_sibling_index();
+                    known.get(&sibling_idx) != Some(&self.siblings[i])
+                });
+                if upper_mismatch {
+                    log_debug!("Proof verification failed: mismatch with an authenticated sibling");
+                    return false;
+                }
+                break;
+            }
+            let sibling = &self.siblings[depth - 1];
+            if known
+                .get(&idx.get_sibling_index())
+                .is_some_and(|node| node != sibling)
+            {
+                log_debug!("Proof verification failed: mismatch with an authenticated sibling");
+                return false;
+            }
+            let parent = index.get_prefix(depth - 1);
+            let merged = if index.get_bit(depth - 1) == 0 {
+                Mergeable::merge_with_index(&value, sibling, &parent)
+            } else {
+                Mergeable::merge_with_index(sibling, &value, &parent)
+            };
+            path.push((idx, std::mem::replace(&mut value, merged)));
+        }
+
+        for (idx, node) in path {
+            known.insert(
+                idx.get_sibling_index(),
+                self.siblings[idx.get_height() - 1].clone(),
+            );
+            known.insert(idx, node);
+        }
+        true
     }
 
     /// Verify a batched Merkle proof.
     pub fn verify_batch(&self, leaves: &[V::ProofNode], root: &V::ProofNode) -> bool {
         // Check if the number of leaves is the same as the number of the indexes.
         if leaves.len() != self.indexes.len() {
-            println!("leaf and index len don't match");
+            log_debug!(
+                "Batched proof verification failed: {} leaves for {} indexes",
+                leaves.len(),
+                self.indexes.len()
+            );
             return false;
         }
 
@@ -150,11 +351,48 @@ where
             return if self.siblings.is_empty() {
                 true
             } else {
-                println!("sibling not empty");
+                log_debug!("Batched proof verification failed: siblings without leaves");
                 false
             };
         }
 
+        let value = match self.compute_root_batch(leaves) {
+            Some(x) => x,
+            None => return false,
+        };
+        // Checks the root value.
+        if value != *root {
+            log_debug!("Batched proof verification failed: root mismatch");
+            return false;
+        }
+        true
+    }
+
+    /// Returns the root computed from a batched Merkle proof with the input leaves,
+    /// merging each internal node on the paths once.
+    ///
+    /// Return ```None``` if there is no leaf, or if the proof is not valid, e.g.,
+    /// the numbers of leaves or siblings don't match with the proved indexes.
+    pub fn compute_root_batch(&self, leaves: &[V::ProofNode]) -> Option<V::ProofNode> {
+        self.compute_root_batch_under(leaves, &TreeIndex::zero(0))
+    }
+
+    // Returns the node at the input anchor index computed from the proof with the input leaves,
+    // where the proved indexes are relative to the anchor, so that the merges are given the absolute parent indexes.
+    fn compute_root_batch_under(
+        &self,
+        leaves: &[V::ProofNode],
+        anchor: &TreeIndex,
+    ) -> Option<V::ProofNode> {
+        if leaves.is_empty() || leaves.len() != self.indexes.len() {
+            log_debug!(
+                "Batched proof verification failed: {} leaves for {} indexes",
+                leaves.len(),
+                self.indexes.len()
+            );
+            return None;
+        }
+
         // Construct an SMT from the indexes in the proof, from which we can extract the positions of sibling nodes.
         let mut proof_tree: SparseMerkleTree<Nil> =
             SparseMerkleTree::new(self.indexes[0].get_height());
@@ -162,8 +400,9 @@ where
         for index in &self.indexes {
             list_for_building.push((*index, Nil));
         }
-        if let Some(_x) = proof_tree.construct_smt_nodes(&list_for_building, &ALL_ZEROS_SECRET) {
-            return false;
+        if let Some(x) = proof_tree.construct_smt_nodes(&list_for_building, &ALL_ZEROS_SECRET) {
+            log_debug!("Batched proof verification failed: {}", x);
+            return None;
         }
 
         // Retrieve the BFS order of nodes in the proof_tree.
@@ -180,7 +419,8 @@ where
                 NodeType::Padding => {
                     if ref_sibling == 0 {
                         // The siblings are not enough for padding nodes in the proof_tree.
-                        return false;
+                        log_debug!("Batched proof verification failed: not enough siblings");
+                        return None;
                     }
                     ref_sibling -= 1;
                     value[ref_tree] = self.siblings[ref_sibling].clone();
@@ -190,8 +430,8 @@ where
                 NodeType::Leaf => {
                     if ref_leaf == 0 {
                         // The leaves are not enough for leaf node in the proof_tree.
-                        println!("leaf not enough");
-                        return false;
+                        log_debug!("Batched proof verification failed: not enough leaves");
+                        return None;
                     }
                     ref_leaf -= 1;
                     value[ref_tree] = leaves[ref_leaf].clone();
@@ -199,9 +439,15 @@ where
                 // If the current node is an internal node in the proof_tree,
                 // the value can be computed by merging two child nodes, whose values are available.
                 NodeType::Internal => {
-                    value[ref_tree] = Mergeable::merge(
+                    let parent = if anchor.get_height() == 0 {
+                        vec[i].0
+                    } else {
+                        get_absolute_index(anchor, &vec[i].0)
+                    };
+                    value[ref_tree] = Mergeable::merge_with_index(
                         &value[proof_tree.get_node_by_ref(ref_tree).get_lch().unwrap()],
                         &value[proof_tree.get_node_by_ref(ref_tree).get_rch().unwrap()],
+                        &parent,
                     );
                 }
             }
@@ -209,191 +455,1507 @@ where
 
         // If there are left leaf nodes or sibling nodes not used in the proof_tree, the Merkle proof is invalid.
         if ref_leaf > 0 || ref_sibling > 0 {
-            return false;
+            log_debug!(
+                "Batched proof verification failed: {} leaves and {} siblings unused",
+                ref_leaf,
+                ref_sibling
+            );
+            return None;
         }
-        // Checks the root value.
-        value[vec[0].1] == *root
+        Some(value[vec[0].1].clone())
     }
-}
 
-impl<V: Default + Clone + Mergeable + ProofExtractable> Serializable for MerkleProof<V>
-where
-    <V as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
-{
-    /// Encode a proof in the format: ```batch_num || tree_indexes || sibling_num || siblings```.
+    /// Returns the new root after changing the proved leaves of a batched Merkle proof
+    /// from ```old_leaves``` to ```new_leaves```, in the order of the proved indexes,
+    /// computed from the proof without the tree and merging each internal node on the paths once,
+    /// e.g., for an aggregator applying a block of updates.
     ///
-    /// If the index list is empty, return empty vector.
-    fn serialize(&self) -> Vec<u8> {
-        // If the index list is empty, return empty vector.
-        if self.indexes.is_empty() {
-            return Vec::<u8>::new();
+    /// Return ```None``` if the proof of the old leaves against the input root is not valid,
+    /// or if the number of new leaves doesn't match with the proved indexes.
+    pub fn compute_updated_root_batch(
+        &self,
+        old_leaves: &[V::ProofNode],
+        new_leaves: &[V::ProofNode],
+        root: &V::ProofNode,
+    ) -> Option<V::ProofNode> {
+        if old_leaves.is_empty() || !self.verify_batch(old_leaves, root) {
+            return None;
         }
+        self.compute_root_batch(new_leaves)
+    }
 
-        let mut bytes: Vec<u8> = Vec::new();
-        bytes.append(&mut usize_to_bytes(self.indexes.len(), BATCH_NUM_BYTE_NUM)); // Encode the batch_num.
-        bytes.append(&mut TreeIndex::serialize(&self.indexes)); // Encode the tree indexes.
-        bytes.append(&mut usize_to_bytes(
-            self.siblings.len(),
-            SIBLING_NUM_BYTE_NUM,
-        )); // Encode the sibling_num.
-        for item in &self.siblings {
-            bytes.append(&mut V::ProofNode::serialize(&item)); // Encode the siblings.
+    /// Verify the proof of the input leaves, one or many, against several candidate roots,
+    /// e.g., the roots of the last published epochs, computing the root from the proof only once.
+    ///
+    /// Returns the position of the first candidate root the proof verifies under,
+    /// or None if the proof is not valid under any of them.
+    pub fn verify_against_any(
+        &self,
+        leaves: &[V::ProofNode],
+        roots: &[V::ProofNode],
+    ) -> Option<usize> {
+        let value = if leaves.len() == 1 && self.indexes.len() == 1 {
+            self.compute_root(&leaves[0])?
+        } else {
+            self.compute_root_batch(leaves)?
+        };
+        let position = roots.iter().position(|root| *root == value);
+        if position.is_none() {
+            log_debug!("Proof verification failed: no matching root among the candidates");
         }
-        bytes
+        position
     }
+}
 
-    /// Decode input bytes (```batch_num || tree_indexes ||  sibling_num || siblings```) as a Merkle proof.
+impl<V: Default + Clone + Mergeable + ProofExtractable> MerkleProof<V>
+where
+    <V as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// Verify an encoded Merkle proof (```batch_num || tree_indexes || sibling_num || siblings```)
+    /// of the input leaves against the root, without decoding it into a [MerkleProof].
     ///
-    /// If there are bytes left, not used for decoding, or ```*begin != bytes.len()``` at the end of the execution,
-    /// return [DecodingError::TooManyEncodedBytes](../error/enum.DecodingError.html#variant.TooManyEncodedBytes).
-    fn deserialize_as_a_unit(
+    /// The verification is a single pass over the encoding, decoding each sibling once.
+    /// The siblings are encoded level by level from the top of the tree, and from left to right in a level,
+    /// so they are pushed onto a stack in that order.
+    /// The leaves are then merged level by level from the bottom of the tree,
+    /// walking each level from right to left and popping a sibling for every node whose sibling isn't computed,
+    /// without building a tree of the proved indexes.
+    ///
+    /// If the input bytes are not a valid encoding of a Merkle proof, return the decoding error.
+    /// Otherwise, return whether the proof is valid.
+    pub fn verify_serialized(
         bytes: &[u8],
-        begin: &mut usize,
-    ) -> Result<MerkleProof<V>, DecodingError> {
-        // Return empty proof if the input byte is empty.
-        if bytes.len() - *begin == 0 {
-            return Ok(MerkleProof::new_batch(&[] as &[TreeIndex]));
+        leaves: &[V::ProofNode],
+        root: &V::ProofNode,
+    ) -> Result<bool, DecodingError> {
+        // An empty encoding is an empty proof.
+        if bytes.is_empty() {
+            return Ok(leaves.is_empty());
         }
-        // Decode the batch_num.
-        let num = bytes_to_usize(bytes, BATCH_NUM_BYTE_NUM, begin);
-        if let Err(e) = num {
-            return Err(e);
+        let mut begin = 0usize;
+        let num = bytes_to_count(bytes, BATCH_NUM_BYTE_NUM, &mut begin)?;
+        let indexes = TreeIndex::deserialize_as_a_unit(bytes, num, &mut begin)?;
+        let sibling_num = bytes_to_count(bytes, SIBLING_NUM_BYTE_NUM, &mut begin)?;
+        let mut siblings: Vec<V::ProofNode> = Vec::with_capacity(sibling_num.min(bytes.len()));
+        for _i in 0..sibling_num {
+            siblings.push(V::ProofNode::deserialize_as_a_unit(bytes, &mut begin)?);
         }
-        let num = num.unwrap();
-
-        // Decode the tree indexes.
-        let index = TreeIndex::deserialize_as_a_unit(bytes, num, begin);
-        if let Err(e) = index {
-            return Err(e);
+        // Check if all input bytes are used for decoding.
+        if begin != bytes.len() {
+            return Err(DecodingError::TooManyEncodedBytes);
         }
-        let index = index.unwrap();
-        let mut proof: MerkleProof<V> = MerkleProof::new_batch(&index);
 
-        // Decode the sibling_num.
-        let sibling_num = bytes_to_usize(bytes, SIBLING_NUM_BYTE_NUM, begin);
-        if let Err(e) = sibling_num {
-            return Err(e);
+        // Check if the number of leaves is the same as the number of the indexes.
+        if leaves.len() != indexes.len() {
+            log_debug!(
+                "Proof verification failed: {} leaves for {} indexes",
+                leaves.len(),
+                indexes.len()
+            );
+            return Ok(false);
+        }
+        // If there isn't any leaf node, the siblings list must also be empty.
+        if leaves.is_empty() {
+            return Ok(sibling_num == 0);
+        }
+        // The indexes must be of the same height, sorted and distinct.
+        let height = indexes[0].get_height();
+        if indexes.iter().any(|x| x.get_height() != height)
+            || indexes.windows(2).any(|pair| pair[0] >= pair[1])
+        {
+            log_debug!("Batched proof verification failed: invalid list of indexes");
+            return Ok(false);
         }
-        let sibling_num = sibling_num.unwrap();
 
-        // Decode the siblings.
-        let mut siblings: Vec<V::ProofNode> = Vec::new();
-        for _i in 0..sibling_num {
-            let sibling = V::ProofNode::deserialize_as_a_unit(bytes, begin);
-            if let Err(e) = sibling {
-                return Err(e);
+        // Merge the nodes of each level from right to left, so that the siblings are popped in the reverse order.
+        let mut layer: Vec<(TreeIndex, V::ProofNode)> =
+            indexes.into_iter().zip(leaves.iter().cloned()).collect();
+        for _i in 0..height {
+            let mut parents: Vec<(TreeIndex, V::ProofNode)> = Vec::with_capacity(layer.len());
+            while let Some((idx, value)) = layer.pop() {
+                // The left sibling of a right child is either computed, or the next sibling on the stack.
+                let sibling = if idx.get_last_bit() == 1
+                    && layer.last().map(|x| x.0) == Some(idx.get_sibling_index())
+                {
+                    layer.pop().unwrap().1
+                } else {
+                    match siblings.pop() {
+                        Some(x) => x,
+                        None => {
+                            log_debug!("Batched proof verification failed: not enough siblings");
+                            return Ok(false);
+                        }
+                    }
+                };
+                let parent = idx.get_parent_index();
+                let value = if idx.get_last_bit() == 0 {
+                    Mergeable::merge_with_index(&value, &sibling, &parent)
+                } else {
+                    Mergeable::merge_with_index(&sibling, &value, &parent)
+                };
+                parents.push((parent, value));
             }
-            siblings.push(sibling.unwrap());
+            parents.reverse();
+            layer = parents;
         }
 
-        proof.set_siblings(siblings);
-        Ok(proof)
+        // If there are sibling nodes not used in the merging, the Merkle proof is invalid.
+        if !siblings.is_empty() {
+            log_debug!(
+                "Batched proof verification failed: {} siblings unused",
+                siblings.len()
+            );
+            return Ok(false);
+        }
+        Ok(layer[0].1 == *root)
     }
 }
 
-impl<P: Clone + Default + Mergeable + Paddable + ProofExtractable> InclusionProvable
-    for MerkleProof<P>
-where
-    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
-{
-    type ProofNodeType = <P as ProofExtractable>::ProofNode;
-    type TreeStruct = SparseMerkleTree<P>;
+/// A borrowed view of an encoded Merkle proof (```batch_num || tree_indexes || sibling_num || siblings```),
+/// whose tree indexes and siblings are read from the input bytes only when they are used.
+///
+/// The siblings are merged in their encoded form instead of being decoded into a list of proof nodes,
+/// so a verifier receiving proofs over the network can verify and discard them with little allocation.
+#[derive(Debug)]
+pub struct MerkleProofRef<'a, V> {
+    bytes: &'a [u8],
+    batch_num: usize,
+    height: usize,
+    // The position of the path of the first proved index.
+    indexes_begin: usize,
+    sibling_num: usize,
+    // The position of the first sibling.
+    siblings_begin: usize,
+    phantom: PhantomData<V>,
+}
 
-    /// Generate Merkle proof for a given list of nodes.
-    ///
-    /// Return ```None``` if any of the input node doesn't exist in the tree.
-    fn generate_inclusion_proof(tree: &Self::TreeStruct, list: &[TreeIndex]) -> Option<Self> {
-        if list.len() == 1 {
-            // Get the references to the input leaf and siblings of nodes long the Merkle path from the root to the leaves.
-            let refs = tree.get_merkle_path_ref(&list[0]);
-            refs.as_ref()?;
-            let refs = refs.unwrap();
-            // Construct the Merkle proof given the references to all sibling nodes in the proof.
-            let mut proof = MerkleProof::<P>::new(list[0]);
-            proof.set_siblings(tree.get_node_proof_by_refs(&refs[1..]));
-            Some(proof)
-        } else {
-            // Get the references to the input leaves and siblings of nodes long the batched Merkle paths from the root to the leaves.
-            let refs = tree.get_merkle_path_ref_batch(list);
-            refs.as_ref()?;
-            let refs = refs.unwrap();
-            // Construct the batched Merkle proof given the references to all sibling nodes in the proof.
-            let mut proof = MerkleProof::<P>::new_batch(list);
-            proof.set_siblings(tree.get_node_proof_by_refs(&refs[list.len()..]));
-            Some(proof)
-        }
-    }
+// A node in the reconstruction of the root from a borrowed proof.
+enum RefNode<'a, 'b, T> {
+    Encoded(&'a [u8]),
+    Leaf(&'b T),
+    Computed(T),
+}
 
-    fn verify_inclusion_proof(
-        &self,
-        leaves: &[Self::ProofNodeType],
-        root: &Self::ProofNodeType,
-    ) -> bool {
-        if leaves.len() == 1 {
-            self.verify(&leaves[0], root)
-        } else {
-            self.verify_batch(leaves, root)
+impl<'a, 'b, T> RefNode<'a, 'b, T> {
+    // Returns the decoded node, or None if it is an encoded sibling.
+    fn get_node(&self) -> Option<&T> {
+        match self {
+            RefNode::Encoded(_) => None,
+            RefNode::Leaf(x) => Some(x),
+            RefNode::Computed(x) => Some(x),
         }
     }
 }
 
-/// A random sampling proof proves that the result of random sampling is valid.
-///
-/// It consists of the tree index of the proved node, and the proofs of certain padding nodes, and a standard Merkle proof.
-///
-/// If the sampled index exists as a real leaf node (non-padding) in the tree,
-/// no padding nodes will be proved but just a standard Merkle proof for the sampled index.
-///
-/// If the sampled index doesn't exist as a real leaf node (non-padding) in the tree,
-/// proofs of necessary padding nodes between the two closest neighbours of the sampled index are included in the proof,
-/// and the Merkle proof proves inclusion of the closest neighbours.
-#[derive(Default)]
-pub struct RandomSamplingProof<
-    V: Clone + Default + Mergeable + ProofExtractable + Paddable + PaddingProvable,
-> where
-    V::ProofNode: Default + Eq + Clone + Mergeable + Serializable,
-    V::PaddingProof: Default + Eq + Clone + Serializable,
-{
-    index: TreeIndex, // The tree index of teh proved node.
-    padding_proofs: Vec<<V as PaddingProvable>::PaddingProof>, // The proofs of necessary padding nodes.
-    merkle_proof: MerkleProof<V>,                              // The Merkle proof.
-    leaves: Vec<V::ProofNode>,                                 // The leaf nodes in the proof.
-}
-
-impl<V: Clone + Default + Mergeable + Paddable + PaddingProvable + ProofExtractable>
-    RandomSamplingProof<V>
+impl<'a, V: Default + Clone + Mergeable + ProofExtractable> MerkleProofRef<'a, V>
 where
-    V::ProofNode: Default + Eq + Clone + Mergeable + Serializable,
-    V::PaddingProof: Default + Eq + Clone + Serializable,
+    <V as ProofExtractable>::ProofNode:
+        Clone + Default + Eq + Mergeable + MergeableEncoded + Serializable,
 {
-    /// The constructor.
-    pub fn new(
-        index: TreeIndex,
-        padding_proofs: Vec<V::PaddingProof>,
-        merkle_proof: MerkleProof<V>,
-        leaves: Vec<V::ProofNode>,
-    ) -> RandomSamplingProof<V> {
-        RandomSamplingProof {
-            index,
-            padding_proofs,
-            merkle_proof,
-            leaves,
+    /// Parse the header of an encoded Merkle proof, checking that the input bytes have the length it implies.
+    ///
+    /// If the input bytes are too short for the proof,
+    /// return [DecodingError::BytesNotEnough](../error/enum.DecodingError.html#variant.BytesNotEnough).
+    /// If there are bytes left,
+    /// return [DecodingError::TooManyEncodedBytes](../error/enum.DecodingError.html#variant.TooManyEncodedBytes).
+    pub fn parse(bytes: &'a [u8]) -> Result<MerkleProofRef<'a, V>, DecodingError> {
+        let mut proof = MerkleProofRef {
+            bytes,
+            batch_num: 0,
+            height: 0,
+            indexes_begin: 0,
+            sibling_num: 0,
+            siblings_begin: 0,
+            phantom: PhantomData,
+        };
+        // An empty encoding is an empty proof.
+        if bytes.is_empty() {
+            return Ok(proof);
+        }
+        let mut begin = 0usize;
+        proof.batch_num = bytes_to_count(bytes, BATCH_NUM_BYTE_NUM, &mut begin)?;
+
+        // The tree indexes share the same height, followed by the paths.
+        if proof.batch_num > 0 || begin < bytes.len() {
+            proof.height = bytes_to_count(bytes, HEIGHT_BYTE_NUM, &mut begin)?;
+            if proof.height > MAX_HEIGHT {
+                return Err(DecodingError::ExceedMaxHeight);
+            }
+        }
+        proof.indexes_begin = begin;
+        let indexes_len = TreeIndex::get_byte_num_by_bit(proof.height)
+            .checked_mul(proof.batch_num)
+            .ok_or(DecodingError::BytesNotEnough)?;
+        if bytes.len() - begin < indexes_len {
+            return Err(DecodingError::BytesNotEnough);
+        }
+        begin += indexes_len;
+
+        proof.sibling_num = bytes_to_count(bytes, SIBLING_NUM_BYTE_NUM, &mut begin)?;
+        proof.siblings_begin = begin;
+        let siblings_len = V::ProofNode::get_encoded_len()
+            .checked_mul(proof.sibling_num)
+            .ok_or(DecodingError::BytesNotEnough)?;
+        match (bytes.len() - begin).cmp(&siblings_len) {
+            std::cmp::Ordering::Less => Err(DecodingError::BytesNotEnough),
+            std::cmp::Ordering::Greater => Err(DecodingError::TooManyEncodedBytes),
+            std::cmp::Ordering::Equal => Ok(proof),
         }
     }
 
-    /// Returns the Merkle proof.
-    pub fn get_merkle_proof(&self) -> &MerkleProof<V> {
-        &self.merkle_proof
+    /// Returns the number of proved indexes.
+    pub fn get_batch_num(&self) -> usize {
+        self.batch_num
     }
 
-    /// Returns the index of the proof.
-    pub fn get_index(&self) -> &TreeIndex {
-        &self.index
+    /// Returns the number of siblings.
+    pub fn get_siblings_num(&self) -> usize {
+        self.sibling_num
     }
 
-    /// Returns the leaf nodes.
-    pub fn get_leaves(&self) -> &[V::ProofNode] {
-        &self.leaves
+    /// Decodes the proved index at the input position, or returns None if the position is out of range.
+    pub fn get_index(&self, i: usize) -> Option<TreeIndex> {
+        if i >= self.batch_num {
+            return None;
+        }
+        let byte_num = TreeIndex::get_byte_num_by_bit(self.height);
+        let begin = self.indexes_begin + i * byte_num;
+        let mut path = [0u8; BYTE_NUM];
+        path[..byte_num].copy_from_slice(&self.bytes[begin..begin + byte_num]);
+        Some(TreeIndex::new(self.height, path))
+    }
+
+    /// Returns the encoding of the sibling at the input position, in the order of
+    /// [MerkleProof::get_path_siblings](struct.MerkleProof.html#method.get_path_siblings),
+    /// or None if the position is out of range.
+    pub fn get_sibling_bytes(&self, i: usize) -> Option<&'a [u8]> {
+        if i >= self.sibling_num {
+            return None;
+        }
+        let len = V::ProofNode::get_encoded_len();
+        let begin = self.siblings_begin + i * len;
+        Some(&self.bytes[begin..begin + len])
+    }
+
+    /// Decodes the whole proof as a [MerkleProof].
+    pub fn to_proof(&self) -> Result<MerkleProof<V>, DecodingError> {
+        let indexes: Vec<TreeIndex> = (0..self.batch_num)
+            .filter_map(|i| self.get_index(i))
+            .collect();
+        let mut proof = MerkleProof::new_batch(&indexes);
+        let mut siblings = Vec::with_capacity(self.sibling_num);
+        for i in 0..self.sibling_num {
+            siblings.push(V::ProofNode::deserialize(
+                self.get_sibling_bytes(i).unwrap(),
+            )?);
+        }
+        proof.set_siblings(siblings);
+        Ok(proof)
+    }
+
+    /// Verify the proof of a single node.
+    pub fn verify(&self, leaf: &V::ProofNode, root: &V::ProofNode) -> bool {
+        if self.batch_num != 1 {
+            log_debug!(
+                "Proof verification failed: {} indexes in a single proof",
+                self.batch_num
+            );
+            return false;
+        }
+        if self.sibling_num != self.height {
+            log_debug!(
+                "Proof verification failed: {} siblings for height {}",
+                self.sibling_num,
+                self.height
+            );
+            return false;
+        }
+
+        // Compute the hash along the Merkle path, merging the encoded siblings.
+        let index = self.get_index(0).unwrap();
+        let mut value: Option<V::ProofNode> = None;
+        for i in (0..self.sibling_num).rev() {
+            let node = value.as_ref().unwrap_or(leaf);
+            let sibling = self.get_sibling_bytes(i).unwrap();
+            let parent = if index.get_bit(i) == 0 {
+                MergeableEncoded::merge_encoded_rch(node, sibling)
+            } else {
+                MergeableEncoded::merge_encoded_lch(sibling, node)
+            };
+            value = Some(parent);
+        }
+        if *value.as_ref().unwrap_or(leaf) != *root {
+            log_debug!("Proof verification failed: root mismatch");
+            return false;
+        }
+        true
+    }
+
+    /// Verify the batched proof of the input leaves.
+    pub fn verify_batch(&self, leaves: &[V::ProofNode], root: &V::ProofNode) -> bool {
+        if leaves.len() != self.batch_num {
+            log_debug!(
+                "Batched proof verification failed: {} leaves for {} indexes",
+                leaves.len(),
+                self.batch_num
+            );
+            return false;
+        }
+        // If there isn't any leaf node, the siblings list must also be empty.
+        if leaves.is_empty() {
+            return self.sibling_num == 0;
+        }
+
+        // Construct an SMT from the indexes in the proof, from which we can extract the positions of sibling nodes.
+        let mut proof_tree: SparseMerkleTree<Nil> = SparseMerkleTree::new(self.height);
+        let list_for_building: Vec<(TreeIndex, Nil)> = (0..self.batch_num)
+            .map(|i| (self.get_index(i).unwrap(), Nil))
+            .collect();
+        if let Some(x) = proof_tree.construct_smt_nodes(&list_for_building, &ALL_ZEROS_SECRET) {
+            log_debug!("Batched proof verification failed: {}", x);
+            return false;
+        }
+
+        // Compute hashes in the reverse order of the BFS list,
+        // dropping the values of child nodes once their parent is computed.
+        let vec = proof_tree.get_index_ref_pairs();
+        let mut value: Vec<Option<RefNode<V::ProofNode>>> = (0..vec.len()).map(|_| None).collect();
+        let mut ref_sibling = self.sibling_num;
+        let mut ref_leaf = leaves.len();
+        for (idx, ref_tree) in vec.iter().rev() {
+            let node = proof_tree.get_node_by_ref(*ref_tree);
+            value[*ref_tree] = Some(match node.get_node_type() {
+                NodeType::Padding => {
+                    if ref_sibling == 0 {
+                        log_debug!("Batched proof verification failed: not enough siblings");
+                        return false;
+                    }
+                    ref_sibling -= 1;
+                    RefNode::Encoded(self.get_sibling_bytes(ref_sibling).unwrap())
+                }
+                NodeType::Leaf => {
+                    ref_leaf -= 1;
+                    RefNode::Leaf(&leaves[ref_leaf])
+                }
+                NodeType::Internal => {
+                    let lch = value[node.get_lch().unwrap()].take().unwrap();
+                    let rch = value[node.get_rch().unwrap()].take().unwrap();
+                    match Self::merge_ref_nodes(lch, rch, idx) {
+                        Some(parent) => RefNode::Computed(parent),
+                        None => return false,
+                    }
+                }
+            });
+        }
+
+        // If there are sibling nodes not used in the proof_tree, the Merkle proof is invalid.
+        if ref_sibling > 0 {
+            log_debug!(
+                "Batched proof verification failed: {} siblings unused",
+                ref_sibling
+            );
+            return false;
+        }
+        value[vec[0].1].as_ref().and_then(RefNode::get_node) == Some(root)
+    }
+
+    // Merges two child nodes, or returns None if both are siblings, which doesn't happen in a valid proof tree.
+    fn merge_ref_nodes(
+        lch: RefNode<V::ProofNode>,
+        rch: RefNode<V::ProofNode>,
+        parent: &TreeIndex,
+    ) -> Option<V::ProofNode> {
+        match (&lch, &rch) {
+            (RefNode::Encoded(_), RefNode::Encoded(_)) => None,
+            (RefNode::Encoded(x), _) => {
+                Some(MergeableEncoded::merge_encoded_lch(x, rch.get_node()?))
+            }
+            (_, RefNode::Encoded(x)) => {
+                Some(MergeableEncoded::merge_encoded_rch(lch.get_node()?, x))
+            }
+            _ => Some(Mergeable::merge_with_index(
+                lch.get_node()?,
+                rch.get_node()?,
+                parent,
+            )),
+        }
+    }
+}
+
+/// The number of top levels of the proof tree whose subtrees are reconstructed in parallel.
+#[cfg(feature = "rayon")]
+const PARALLEL_DEPTH: usize = 8;
+
+#[cfg(feature = "rayon")]
+impl<V: Default + Clone + Mergeable + ProofExtractable> MerkleProof<V>
+where
+    <V as ProofExtractable>::ProofNode:
+        Clone + Default + Eq + Mergeable + Serializable + Send + Sync,
+{
+    /// Verify a batched Merkle proof as [verify_batch](#method.verify_batch) does,
+    /// reconstructing independent subtrees concurrently on the current rayon thread pool.
+    pub fn verify_batch_parallel(&self, leaves: &[V::ProofNode], root: &V::ProofNode) -> bool {
+        // Check if the number of leaves is the same as the number of the indexes.
+        if leaves.len() != self.indexes.len() {
+            log_debug!(
+                "Batched proof verification failed: {} leaves for {} indexes",
+                leaves.len(),
+                self.indexes.len()
+            );
+            return false;
+        }
+
+        // If there isn't any leaf node, the siblings list must also be empty.
+        if leaves.is_empty() {
+            return self.siblings.is_empty();
+        }
+
+        // Construct an SMT from the indexes in the proof, from which we can extract the positions of sibling nodes.
+        let mut proof_tree: SparseMerkleTree<Nil> =
+            SparseMerkleTree::new(self.indexes[0].get_height());
+        let mut list_for_building: Vec<(TreeIndex, Nil)> = Vec::new();
+        for index in &self.indexes {
+            list_for_building.push((*index, Nil));
+        }
+        if let Some(x) = proof_tree.construct_smt_nodes(&list_for_building, &ALL_ZEROS_SECRET) {
+            log_debug!("Batched proof verification failed: {}", x);
+            return false;
+        }
+
+        // Assign leaves and siblings to the leaf and padding nodes of the proof_tree in the BFS order.
+        let mut slots: Vec<Option<&V::ProofNode>> = vec![None; proof_tree.get_nodes_num()];
+        let mut ref_sibling = 0;
+        let mut ref_leaf = 0;
+        for (_, ref_tree) in proof_tree.get_index_ref_pairs() {
+            match proof_tree.get_node_by_ref(ref_tree).get_node_type() {
+                NodeType::Padding => {
+                    if ref_sibling == self.siblings.len() {
+                        log_debug!("Batched proof verification failed: not enough siblings");
+                        return false;
+                    }
+                    slots[ref_tree] = Some(&self.siblings[ref_sibling]);
+                    ref_sibling += 1;
+                }
+                NodeType::Leaf => {
+                    slots[ref_tree] = Some(&leaves[ref_leaf]);
+                    ref_leaf += 1;
+                }
+                NodeType::Internal => {}
+            }
+        }
+        // If there are sibling nodes not used in the proof_tree, the Merkle proof is invalid.
+        if ref_sibling < self.siblings.len() {
+            log_debug!(
+                "Batched proof verification failed: {} siblings unused",
+                self.siblings.len() - ref_sibling
+            );
+            return false;
+        }
+
+        // Checks the root value.
+        let value = Self::compute_subtree_parallel(
+            &proof_tree,
+            &slots,
+            proof_tree.get_root_ref(),
+            &TreeIndex::zero(0),
+        );
+        if value != *root {
+            log_debug!("Batched proof verification failed: root mismatch");
+            return false;
+        }
+        true
+    }
+
+    /// Verify many independent proofs, each with its proved leaves, against the same root
+    /// on the current rayon thread pool, returning the verification result of each proof.
+    ///
+    /// To use a dedicated thread pool, call this method inside ```rayon::ThreadPool::install```.
+    pub fn verify_many_parallel(
+        proofs: &[(MerkleProof<V>, Vec<V::ProofNode>)],
+        root: &V::ProofNode,
+    ) -> Vec<bool> {
+        use rayon::prelude::*;
+
+        proofs
+            .par_iter()
+            .map(|(proof, leaves)| {
+                if leaves.len() == 1 {
+                    proof.verify(&leaves[0], root)
+                } else {
+                    proof.verify_batch(leaves, root)
+                }
+            })
+            .collect()
+    }
+
+    // Compute the value of the subtree rooted at the input node of the proof_tree at the input index,
+    // merging the two child subtrees concurrently in the top levels.
+    fn compute_subtree_parallel(
+        proof_tree: &SparseMerkleTree<Nil>,
+        slots: &[Option<&V::ProofNode>],
+        link: usize,
+        idx: &TreeIndex,
+    ) -> V::ProofNode {
+        let node = proof_tree.get_node_by_ref(link);
+        match (node.get_lch(), node.get_rch()) {
+            (Some(lch), Some(rch)) => {
+                let lidx = idx.get_lch_index();
+                let ridx = idx.get_rch_index();
+                let (lvalue, rvalue) = if idx.get_height() < PARALLEL_DEPTH {
+                    rayon::join(
+                        || Self::compute_subtree_parallel(proof_tree, slots, lch, &lidx),
+                        || Self::compute_subtree_parallel(proof_tree, slots, rch, &ridx),
+                    )
+                } else {
+                    (
+                        Self::compute_subtree_parallel(proof_tree, slots, lch, &lidx),
+                        Self::compute_subtree_parallel(proof_tree, slots, rch, &ridx),
+                    )
+                };
+                Mergeable::merge_with_index(&lvalue, &rvalue, idx)
+            }
+            // Leaf and padding nodes of the proof_tree all have an assigned value.
+            _ => slots[link].unwrap().clone(),
+        }
+    }
+}
+
+/// The minimum number of requests handled by a task when generating proofs in parallel,
+/// so that cheap proofs are not split into tasks finer than the cost of scheduling them,
+/// while the idle threads can still steal the remaining chunks of a busy thread.
+#[cfg(feature = "rayon")]
+const PROOF_REQUESTS_PER_TASK: usize = 16;
+
+#[cfg(feature = "rayon")]
+impl<P: Clone + Default + Mergeable + Paddable + ProofExtractable + Send + Sync> MerkleProof<P>
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable + Send,
+{
+    /// Generate the Merkle proofs of many independent requests, each a list of sorted leaves,
+    /// over the shared tree on the current rayon thread pool, returning the proof of each request in order,
+    /// or None for a request where any leaf doesn't exist.
+    ///
+    /// To use a dedicated thread pool, call this method inside ```rayon::ThreadPool::install```.
+    ///
+    /// Panics if the indexes of a request don't match with the height of the tree.
+    pub fn generate_inclusion_proofs_parallel(
+        tree: &SparseMerkleTree<P>,
+        requests: &[Vec<TreeIndex>],
+    ) -> Vec<Option<MerkleProof<P>>> {
+        use rayon::prelude::*;
+
+        requests
+            .par_iter()
+            .with_min_len(PROOF_REQUESTS_PER_TASK)
+            .map(|list| Self::generate_inclusion_proof(tree, list))
+            .collect()
+    }
+}
+
+impl<V: Default + Clone + Mergeable + ProofExtractable> Serializable for MerkleProof<V>
+where
+    <V as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// Encode a proof in the format: ```batch_num || tree_indexes || sibling_num || siblings```.
+    ///
+    /// The encoded indexes carry the tree height, which the decoder doesn't check against an expected one;
+    /// [serialize_bound](struct.MerkleProof.html#method.serialize_bound) encodes a proof for a given height.
+    ///
+    /// If the index list is empty, return empty vector.
+    fn serialize(&self) -> Vec<u8> {
+        // If the index list is empty, return empty vector.
+        if self.indexes.is_empty() {
+            return Vec::<u8>::new();
+        }
+
+        let mut bytes: Vec<u8> = Vec::new();
+        bytes.append(&mut u64_to_bytes(
+            self.indexes.len() as u64,
+            BATCH_NUM_BYTE_NUM,
+        )); // Encode the batch_num.
+        bytes.append(&mut TreeIndex::serialize(&self.indexes)); // Encode the tree indexes.
+        bytes.append(&mut u64_to_bytes(
+            self.siblings.len() as u64,
+            SIBLING_NUM_BYTE_NUM,
+        )); // Encode the sibling_num.
+        for item in &self.siblings {
+            bytes.append(&mut V::ProofNode::serialize(item)); // Encode the siblings.
+        }
+        bytes
+    }
+
+    /// Decode input bytes (```batch_num || tree_indexes ||  sibling_num || siblings```) as a Merkle proof.
+    ///
+    /// If there are bytes left, not used for decoding, or ```*begin != bytes.len()``` at the end of the execution,
+    /// return [DecodingError::TooManyEncodedBytes](../error/enum.DecodingError.html#variant.TooManyEncodedBytes).
+    fn deserialize_as_a_unit(
+        bytes: &[u8],
+        begin: &mut usize,
+    ) -> Result<MerkleProof<V>, DecodingError> {
+        // Return empty proof if the input byte is empty.
+        if bytes.len() - *begin == 0 {
+            return Ok(MerkleProof::new_batch(&[] as &[TreeIndex]));
+        }
+        // Decode the batch_num.
+        let num = bytes_to_count(bytes, BATCH_NUM_BYTE_NUM, begin);
+        if let Err(e) = num {
+            return Err(e);
+        }
+        let num = num.unwrap();
+
+        // Decode the tree indexes.
+        let index = TreeIndex::deserialize_as_a_unit(bytes, num, begin);
+        if let Err(e) = index {
+            return Err(e);
+        }
+        let index = index.unwrap();
+        let mut proof: MerkleProof<V> = MerkleProof::new_batch(&index);
+
+        // Decode the sibling_num.
+        let sibling_num = bytes_to_count(bytes, SIBLING_NUM_BYTE_NUM, begin);
+        if let Err(e) = sibling_num {
+            return Err(e);
+        }
+        let sibling_num = sibling_num.unwrap();
+
+        // Decode the siblings.
+        let mut siblings: Vec<V::ProofNode> = Vec::new();
+        for _i in 0..sibling_num {
+            let sibling = V::ProofNode::deserialize_as_a_unit(bytes, begin);
+            if let Err(e) = sibling {
+                return Err(e);
+            }
+            siblings.push(sibling.unwrap());
+        }
+
+        proof.set_siblings(siblings);
+        Ok(proof)
+    }
+}
+
+/// The number of bytes for encoding the tree height in a bound Merkle proof.
+const TREE_HEIGHT_BYTE_NUM: usize = 2;
+/// The number of bytes for encoding the length of the domain tag in a bound Merkle proof.
+const DOMAIN_LEN_BYTE_NUM: usize = 2;
+
+impl<V: Default + Clone + Mergeable + ProofExtractable> MerkleProof<V>
+where
+    <V as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// Encode a proof bound to a tree height and an application domain tag, possibly empty, in the format:
+    /// ```tree_height || domain_len || domain || proof```, ```proof``` being the output of ```serialize```.
+    ///
+    /// The height is authenticated by the root, as the proof of an index has one sibling per level.
+    /// The domain tag isn't: it is a label in the clear, not merged into the verified root,
+    /// so anyone holding a proof can encode it with another tag.
+    /// The tag thus only keeps a verifier from accepting a proof meant for another application by mistake;
+    /// separating the roots of applications requires the leaves to commit to the domain,
+    /// e.g., by hashing the domain tag into each leaf.
+    ///
+    /// Panics if the domain tag is longer than ```u16::MAX``` bytes.
+    pub fn serialize_bound(&self, height: usize, domain: &[u8]) -> Vec<u8> {
+        let mut bytes = u64_to_bytes(height as u64, TREE_HEIGHT_BYTE_NUM);
+        bytes.append(&mut u64_to_bytes(domain.len() as u64, DOMAIN_LEN_BYTE_NUM));
+        bytes.extend_from_slice(domain);
+        bytes.append(&mut self.serialize());
+        bytes
+    }
+
+    /// Decode input bytes (```tree_height || domain_len || domain || proof```) as a proof
+    /// for a tree of the input height in the input domain.
+    ///
+    /// If the encoded height, or the height of a proved index, isn't the input height,
+    /// return [DecodingError::HeightNotMatch](../error/enum.DecodingError.html#variant.HeightNotMatch).
+    /// If the encoded domain tag isn't the input one,
+    /// return [DecodingError::DomainNotMatch](../error/enum.DecodingError.html#variant.DomainNotMatch).
+    pub fn deserialize_bound(
+        bytes: &[u8],
+        height: usize,
+        domain: &[u8],
+    ) -> Result<MerkleProof<V>, DecodingError> {
+        let mut begin = 0usize;
+        if bytes_to_count(bytes, TREE_HEIGHT_BYTE_NUM, &mut begin)? != height {
+            return Err(DecodingError::HeightNotMatch);
+        }
+        let domain_len = bytes_to_count(bytes, DOMAIN_LEN_BYTE_NUM, &mut begin)?;
+        if bytes.len() - begin < domain_len {
+            return Err(DecodingError::BytesNotEnough);
+        }
+        if bytes[begin..begin + domain_len] != *domain {
+            return Err(DecodingError::DomainNotMatch);
+        }
+        begin += domain_len;
+
+        let proof = MerkleProof::<V>::deserialize(&bytes[begin..])?;
+        if proof.indexes.iter().any(|x| x.get_height() != height) {
+            return Err(DecodingError::HeightNotMatch);
+        }
+        Ok(proof)
+    }
+
+    /// Verify an encoded proof bound to a tree height and a domain tag
+    /// against the expected height and domain, and then against the input leaves and root.
+    ///
+    /// If the input bytes are not a valid encoding for the expected height and domain, return the decoding error.
+    /// Otherwise, return whether the proof is valid.
+    ///
+    /// Note that the domain tag isn't authenticated,
+    /// see [serialize_bound](struct.MerkleProof.html#method.serialize_bound).
+    pub fn verify_bound(
+        bytes: &[u8],
+        height: usize,
+        domain: &[u8],
+        leaves: &[V::ProofNode],
+        root: &V::ProofNode,
+    ) -> Result<bool, DecodingError> {
+        let proof = MerkleProof::<V>::deserialize_bound(bytes, height, domain)?;
+        Ok(if leaves.len() == 1 {
+            proof.verify(&leaves[0], root)
+        } else {
+            proof.verify_batch(leaves, root)
+        })
+    }
+
+    /// Write the proof into the input writer in the streaming format:
+    /// ```batch_num || tree_indexes || sibling_num || siblings```,
+    /// which differs from the output of ```serialize``` in that each sibling is prefixed with its length
+    /// as by [write_unit](../utils/fn.write_unit.html), so that the reader reads exactly the bytes of each sibling.
+    /// The siblings are written one by one without building the whole encoding in memory.
+    ///
+    /// If the writer fails, return the I/O error, with the proof possibly partially written.
+    pub fn serialize_into<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        // If the index list is empty, write nothing.
+        if self.indexes.is_empty() {
+            return Ok(());
+        }
+        w.write_all(&u64_to_bytes(self.indexes.len() as u64, BATCH_NUM_BYTE_NUM))?;
+        w.write_all(&TreeIndex::serialize(&self.indexes))?;
+        w.write_all(&u64_to_bytes(
+            self.siblings.len() as u64,
+            SIBLING_NUM_BYTE_NUM,
+        ))?;
+        for item in &self.siblings {
+            write_unit(w, &item.serialize())?;
+        }
+        Ok(())
+    }
+
+    /// Read a proof written by [serialize_into](#method.serialize_into) from the reader,
+    /// reading exactly the encoded bytes, so that concatenated proofs can be read one after another.
+    ///
+    /// As [deserialize](#method.deserialize) does for empty bytes,
+    /// return empty proof if the reader ends before the first byte.
+    ///
+    /// If the reader ends in the middle of the proof,
+    /// return [DecodingError::BytesNotEnough](../error/enum.DecodingError.html#variant.BytesNotEnough).
+    /// If the reader fails otherwise,
+    /// return [DecodingError::IoError](../error/enum.DecodingError.html#variant.IoError).
+    pub fn deserialize_from<R: Read + ?Sized>(r: &mut R) -> Result<MerkleProof<V>, DecodingError> {
+        // Read the batch_num.
+        let mut bytes = [0u8; BATCH_NUM_BYTE_NUM];
+        if !read_bytes_or_end(r, &mut bytes)? {
+            return Ok(MerkleProof::new_batch(&[] as &[TreeIndex]));
+        }
+        let num = bytes_to_count(&bytes, BATCH_NUM_BYTE_NUM, &mut 0)?;
+
+        // Read the tree indexes.
+        let mut proof: MerkleProof<V> =
+            MerkleProof::new_batch(&TreeIndex::deserialize_from(r, num)?);
+
+        // Read the sibling_num and the siblings, not trusting the number of siblings for allocation.
+        let mut bytes = [0u8; SIBLING_NUM_BYTE_NUM];
+        read_bytes(r, &mut bytes)?;
+        let sibling_num = bytes_to_count(&bytes, SIBLING_NUM_BYTE_NUM, &mut 0)?;
+        let mut siblings: Vec<V::ProofNode> = Vec::new();
+        for _i in 0..sibling_num {
+            siblings.push(read_unit(r, V::ProofNode::deserialize_as_a_unit)?);
+        }
+        proof.set_siblings(siblings);
+        Ok(proof)
+    }
+
+    /// Returns the canonical encoding of the proof, i.e., the output of ```serialize```
+    /// with the bits of the proved indexes beyond their height cleared.
+    ///
+    /// Proofs equal in verification have the same canonical bytes,
+    /// so the canonical bytes can be used as cache keys or signed.
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        let mut proof = self.clone();
+        for index in proof.indexes.iter_mut() {
+            *index = index.get_prefix(index.get_height());
+        }
+        proof.serialize()
+    }
+
+    /// Decode input bytes (```batch_num || tree_indexes || sibling_num || siblings```) as a Merkle proof
+    /// as [deserialize](#method.deserialize) does, and if ```strict``` is set,
+    /// also check that the input bytes are the canonical encoding of the proof.
+    ///
+    /// The canonical encoding of a proof is its [canonical_bytes](#method.canonical_bytes),
+    /// with the proved indexes strictly increasing and exactly the siblings needed by the indexes.
+    /// If ```strict``` is set and the input is not canonical,
+    /// return [DecodingError::NonCanonicalEncoding](../error/enum.DecodingError.html#variant.NonCanonicalEncoding).
+    pub fn deserialize_checked(
+        bytes: &[u8],
+        strict: bool,
+    ) -> Result<MerkleProof<V>, DecodingError> {
+        let proof = MerkleProof::<V>::deserialize(bytes)?;
+        if strict {
+            let sibling_num = proof.get_sibling_positions().map(|x| x.len());
+            if sibling_num != Some(proof.siblings.len()) || proof.canonical_bytes() != bytes {
+                log_debug!("Strict decoding failed: the proof encoding is not canonical");
+                return Err(DecodingError::NonCanonicalEncoding);
+            }
+        }
+        Ok(proof)
+    }
+}
+
+impl<V: Default + Clone + Mergeable + ProofExtractable> MerkleProof<V>
+where
+    <V as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable + Summable,
+{
+    /// Verify that the input leaf covers the target offset when the sums of the leaves are laid out from left to right,
+    /// as selected by [SparseMerkleTree::select_by_cumulative_sum](../tree/struct.SparseMerkleTree.html#method.select_by_cumulative_sum).
+    ///
+    /// The offset of the leaf is the total of the left siblings along the path,
+    /// and it must hold that ```offset <= target < offset + sum```.
+    pub fn verify_cumulative_sum(
+        &self,
+        leaf: &V::ProofNode,
+        root: &V::ProofNode,
+        target: u64,
+    ) -> bool {
+        if !self.verify(leaf, root) {
+            return false;
+        }
+        let index = &self.indexes[0];
+        let offset: u64 = self
+            .siblings
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| index.get_bit(*i) == 1)
+            .map(|(_, sibling)| sibling.get_sum())
+            .sum();
+        if target < offset || target - offset >= leaf.get_sum() {
+            log_debug!("Cumulative sum verification failed: target out of the leaf range");
+            return false;
+        }
+        true
+    }
+}
+
+/// The number of bytes in a limb of a [CircuitWitness].
+pub const LIMB_BYTE_NUM: usize = 8;
+
+/// The witness of a single-leaf Merkle proof for a zk-circuit, returned by
+/// [to_circuit_witness](struct.MerkleProof.html#method.to_circuit_witness),
+/// in fixed-width arrays from the leaf level up to the root, i.e., in the order a circuit merges the nodes.
+///
+/// Each sibling is its [serialization](../traits/trait.Serializable.html#tymethod.serialize)
+/// split into ```u64``` limbs of [LIMB_BYTE_NUM] bytes, each limb read in little-endian and the first bytes in the first limb,
+/// zero-padded to the same number of limbs for all siblings, so that every limb fits in a field element.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CircuitWitness {
+    /// The direction bits of the nodes on the path, from the leaf up to the child of the root,
+    /// 1 if the node is a right child, i.e., the sibling is merged on the left.
+    pub path_bits: Vec<u8>,
+    /// The limbs of the siblings, in the same order as the path bits.
+    pub siblings: Vec<Vec<u64>>,
+    /// The number of limbs of each sibling.
+    pub limbs_num: usize,
+}
+
+impl CircuitWitness {
+    /// Returns the input bytes as the input number of ```u64``` limbs, encoded as the siblings of a witness,
+    /// e.g., for encoding the leaf and the root in the same way.
+    ///
+    /// Bytes beyond the limbs are ignored.
+    pub fn to_limbs(bytes: &[u8], limbs_num: usize) -> Vec<u64> {
+        let mut limbs = vec![0u64; limbs_num];
+        for (limb, chunk) in limbs.iter_mut().zip(bytes.chunks(LIMB_BYTE_NUM)) {
+            let mut word = [0u8; LIMB_BYTE_NUM];
+            word[..chunk.len()].copy_from_slice(chunk);
+            *limb = u64::from_le_bytes(word);
+        }
+        limbs
+    }
+}
+
+impl<V: Default + Clone + Mergeable + ProofExtractable> MerkleProof<V>
+where
+    <V as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// Returns the witness of a single-leaf proof for a zk-circuit,
+    /// with one path bit and one sibling per level of the tree.
+    ///
+    /// Return ```None``` if the proof is not valid, i.e.,
+    /// there is more than one index, or the number of siblings doesn't match with the height of the index.
+    pub fn to_circuit_witness(&self) -> Option<CircuitWitness> {
+        if self.indexes.len() != 1 || self.siblings.len() != self.indexes[0].get_height() {
+            return None;
+        }
+        let index = &self.indexes[0];
+        let encoded: Vec<Vec<u8>> = self.siblings.iter().rev().map(|x| x.serialize()).collect();
+        let limbs_num = encoded
+            .iter()
+            .map(|x| x.len().div_ceil(LIMB_BYTE_NUM))
+            .max()
+            .unwrap_or(0);
+        Some(CircuitWitness {
+            path_bits: (0..index.get_height())
+                .rev()
+                .map(|i| index.get_bit(i))
+                .collect(),
+            siblings: encoded
+                .iter()
+                .map(|x| CircuitWitness::to_limbs(x, limbs_num))
+                .collect(),
+            limbs_num,
+        })
+    }
+}
+
+#[cfg(feature = "hex")]
+impl<V: Default + Clone + Mergeable + ProofExtractable> MerkleProof<V>
+where
+    <V as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// Encode the proof as a lowercase hex string of its [serialization](#method.serialize).
+    pub fn to_hex(&self) -> String {
+        bytes_to_hex(&self.serialize())
+    }
+
+    /// Decode a proof from a lowercase hex string of its [serialization](#method.serialize).
+    ///
+    /// If the input is not a valid hex string,
+    /// return [DecodingError::TextDecodingError](../error/enum.DecodingError.html#variant.TextDecodingError).
+    pub fn from_hex(text: &str) -> Result<Self, DecodingError> {
+        Self::deserialize(&hex_to_bytes(text)?)
+    }
+}
+
+#[cfg(feature = "base64")]
+impl<V: Default + Clone + Mergeable + ProofExtractable> MerkleProof<V>
+where
+    <V as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// Encode the proof as a standard base64 string of its [serialization](#method.serialize).
+    pub fn to_base64(&self) -> String {
+        bytes_to_base64(&self.serialize())
+    }
+
+    /// Decode a proof from a standard base64 string of its [serialization](#method.serialize).
+    ///
+    /// If the input is not a valid base64 string,
+    /// return [DecodingError::TextDecodingError](../error/enum.DecodingError.html#variant.TextDecodingError).
+    pub fn from_base64(text: &str) -> Result<Self, DecodingError> {
+        Self::deserialize(&base64_to_bytes(text)?)
+    }
+}
+
+impl<P: Clone + Default + Mergeable + Paddable + ProofExtractable> InclusionProvable
+    for MerkleProof<P>
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    type ProofNodeType = <P as ProofExtractable>::ProofNode;
+    type TreeStruct = SparseMerkleTree<P>;
+
+    /// Generate Merkle proof for a given list of nodes.
+    ///
+    /// Return ```None``` if any of the input node doesn't exist in the tree.
+    fn generate_inclusion_proof(tree: &Self::TreeStruct, list: &[TreeIndex]) -> Option<Self> {
+        if list.len() == 1 {
+            // Get the references to the input leaf and siblings of nodes long the Merkle path from the root to the leaves.
+            let refs = tree.get_merkle_path_ref(&list[0])?;
+            // Construct the Merkle proof given the references to all sibling nodes in the proof.
+            let mut proof = MerkleProof::<P>::new(list[0]);
+            proof.set_siblings(tree.get_node_proof_by_refs(refs.siblings()));
+            tree.record_metrics(
+                Operation::ProofGeneration,
+                &OperationMetrics {
+                    nodes_allocated: proof.get_siblings_num() as u64,
+                    ..OperationMetrics::default()
+                },
+            );
+            Some(proof)
+        } else {
+            // Get the references to the input leaves and siblings of nodes long the batched Merkle paths from the root to the leaves.
+            let refs = tree.get_merkle_path_ref_batch(list)?;
+            // Construct the batched Merkle proof given the references to all sibling nodes in the proof.
+            let mut proof = MerkleProof::<P>::new_batch(list);
+            proof.set_siblings(tree.get_node_proof_by_refs(refs.siblings()));
+            tree.record_metrics(
+                Operation::ProofGeneration,
+                &OperationMetrics {
+                    nodes_allocated: proof.get_siblings_num() as u64,
+                    ..OperationMetrics::default()
+                },
+            );
+            Some(proof)
+        }
+    }
+
+    fn verify_inclusion_proof(
+        &self,
+        leaves: &[Self::ProofNodeType],
+        root: &Self::ProofNodeType,
+    ) -> bool {
+        if leaves.len() == 1 {
+            self.verify(&leaves[0], root)
+        } else {
+            self.verify_batch(leaves, root)
+        }
+    }
+}
+
+/// A Merkle proof borrowing its siblings from the tree it is generated from,
+/// so that serving many proofs doesn't clone the proof nodes of the tree,
+/// see [get_proof_node_ref](../traits/trait.ProofExtractable.html#method.get_proof_node_ref).
+///
+/// The proof is encoded in the same format as a [MerkleProof], or converted into one for verification.
+#[derive(Debug, Clone)]
+pub struct BorrowedMerkleProof<'a, V: Clone + ProofExtractable>
+where
+    <V as ProofExtractable>::ProofNode: Clone,
+{
+    // The tree indexes of the proved leaves.
+    indexes: Vec<TreeIndex>,
+    // The siblings in the same order as in a MerkleProof.
+    siblings: Vec<Cow<'a, V::ProofNode>>,
+}
+
+impl<'a, P: Clone + Default + Mergeable + Paddable + ProofExtractable> BorrowedMerkleProof<'a, P>
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// Generate the Merkle proof of the input sorted leaves, borrowing the siblings from the tree.
+    ///
+    /// Return ```None``` if any of the input node doesn't exist in the tree.
+    pub fn generate(
+        tree: &'a SparseMerkleTree<P>,
+        list: &[TreeIndex],
+    ) -> Option<BorrowedMerkleProof<'a, P>> {
+        let refs = if list.len() == 1 {
+            tree.get_merkle_path_ref(&list[0])?
+        } else {
+            tree.get_merkle_path_ref_batch(list)?
+        };
+        let siblings = tree.get_node_proof_cow_by_refs(refs.siblings());
+        // Only the siblings not lent by the tree are allocated.
+        tree.record_metrics(
+            Operation::ProofGeneration,
+            &OperationMetrics {
+                nodes_allocated: siblings
+                    .iter()
+                    .filter(|x| matches!(x, Cow::Owned(_)))
+                    .count() as u64,
+                ..OperationMetrics::default()
+            },
+        );
+        Some(BorrowedMerkleProof {
+            indexes: list.to_vec(),
+            siblings,
+        })
+    }
+
+    /// Returns the indexes of the proof.
+    pub fn get_indexes(&self) -> &[TreeIndex] {
+        &self.indexes
+    }
+
+    /// Returns the siblings of nodes along the path, in the same order as in a [MerkleProof].
+    pub fn get_path_siblings(&self) -> &[Cow<'a, P::ProofNode>] {
+        &self.siblings
+    }
+
+    /// Encode the proof in the same format as [MerkleProof::serialize](struct.MerkleProof.html#method.serialize).
+    pub fn serialize(&self) -> Vec<u8> {
+        if self.indexes.is_empty() {
+            return Vec::<u8>::new();
+        }
+        let mut bytes = u64_to_bytes(self.indexes.len() as u64, BATCH_NUM_BYTE_NUM);
+        bytes.append(&mut TreeIndex::serialize(&self.indexes));
+        bytes.append(&mut u64_to_bytes(
+            self.siblings.len() as u64,
+            SIBLING_NUM_BYTE_NUM,
+        ));
+        for item in &self.siblings {
+            bytes.append(&mut item.serialize());
+        }
+        bytes
+    }
+
+    /// Returns the owned Merkle proof, cloning the borrowed siblings.
+    pub fn into_owned(self) -> MerkleProof<P> {
+        let mut proof = MerkleProof::<P>::new_batch(&self.indexes);
+        proof.set_siblings(self.siblings.into_iter().map(Cow::into_owned).collect());
+        proof
+    }
+}
+
+/// The number of bytes for encoding the length of the Merkle proof in a self-contained proof.
+pub(crate) const PROOF_LEN_BYTE_NUM: usize = 8;
+
+/// A self-contained proof carries the proved leaves, and optionally the root, along with the Merkle proof,
+/// so that the leaves can't be transmitted out of line with the proved indexes.
+#[derive(Debug, Clone, Default)]
+pub struct SelfContainedProof<V: Clone + Default + Mergeable + ProofExtractable>
+where
+    <V as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    merkle_proof: MerkleProof<V>,
+    // The proved leaves, in the order of the indexes in the Merkle proof.
+    leaves: Vec<V::ProofNode>,
+    root: Option<V::ProofNode>,
+}
+
+impl<V: Clone + Default + Mergeable + ProofExtractable> SelfContainedProof<V>
+where
+    <V as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// The constructor, with the leaves in the order of the indexes in the Merkle proof and without root.
+    pub fn new(merkle_proof: MerkleProof<V>, leaves: Vec<V::ProofNode>) -> SelfContainedProof<V> {
+        SelfContainedProof {
+            merkle_proof,
+            leaves,
+            root: None,
+        }
+    }
+
+    /// Returns the Merkle proof.
+    pub fn get_merkle_proof(&self) -> &MerkleProof<V> {
+        &self.merkle_proof
+    }
+
+    /// Returns the proved leaves.
+    pub fn get_leaves(&self) -> &[V::ProofNode] {
+        &self.leaves
+    }
+
+    /// Returns the embedded root, if any.
+    pub fn get_root(&self) -> Option<&V::ProofNode> {
+        self.root.as_ref()
+    }
+
+    /// Embeds the input root in the proof.
+    pub fn set_root(&mut self, root: V::ProofNode) {
+        self.root = Some(root);
+    }
+
+    /// Verify the proof against the embedded root.
+    ///
+    /// Return false if there isn't any embedded root.
+    pub fn verify_self(&self) -> bool {
+        match &self.root {
+            Some(root) => self.merkle_proof.verify_batch(&self.leaves, root),
+            None => {
+                log_debug!("Proof verification failed: no embedded root");
+                false
+            }
+        }
+    }
+
+    /// Verify the proof against the input root,
+    /// which must also be the embedded root if there is one.
+    pub fn verify_against(&self, root: &V::ProofNode) -> bool {
+        if self.root.as_ref().is_some_and(|x| x != root) {
+            log_debug!("Proof verification failed: embedded root mismatch");
+            return false;
+        }
+        self.merkle_proof.verify_batch(&self.leaves, root)
+    }
+}
+
+impl<P: Clone + Default + Mergeable + Paddable + ProofExtractable> SelfContainedProof<P>
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// Generate the self-contained proof of the input list of leaves, embedding the root of the tree.
+    ///
+    /// Return ```None``` if any of the input node isn't a leaf of the tree.
+    pub fn generate(tree: &SparseMerkleTree<P>, list: &[TreeIndex]) -> Option<Self> {
+        let mut leaves = Vec::with_capacity(list.len());
+        for idx in list {
+            leaves.push(tree.get_leaf_by_index(idx)?.get_value().get_proof_node());
+        }
+        let merkle_proof = MerkleProof::<P>::generate_inclusion_proof(tree, list)?;
+        let mut proof = SelfContainedProof::new(merkle_proof, leaves);
+        proof.set_root(tree.get_root());
+        Some(proof)
+    }
+}
+
+impl<V: Clone + Default + Mergeable + ProofExtractable> Serializable for SelfContainedProof<V>
+where
+    <V as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// Encode a self-contained proof in the format:
+    /// ```proof_len || merkle_proof || leaves || has_root || root```,
+    /// where ```has_root``` is one byte and the root is only encoded if it is 1.
+    fn serialize(&self) -> Vec<u8> {
+        let mut proof = self.merkle_proof.serialize();
+        let mut bytes = u64_to_bytes(proof.len() as u64, PROOF_LEN_BYTE_NUM);
+        bytes.append(&mut proof);
+        for item in &self.leaves {
+            bytes.append(&mut item.serialize());
+        }
+        match &self.root {
+            Some(root) => {
+                bytes.push(1);
+                bytes.append(&mut root.serialize());
+            }
+            None => bytes.push(0),
+        }
+        bytes
+    }
+
+    /// Decode input bytes (```proof_len || merkle_proof || leaves || has_root || root```) as a self-contained proof,
+    /// with as many leaves as indexes in the Merkle proof.
+    ///
+    /// If ```has_root``` is neither 0 nor 1,
+    /// return [DecodingError::ValueDecodingError](../error/enum.DecodingError.html#variant.ValueDecodingError).
+    fn deserialize_as_a_unit(
+        bytes: &[u8],
+        begin: &mut usize,
+    ) -> Result<SelfContainedProof<V>, DecodingError> {
+        let proof_len = bytes_to_count(bytes, PROOF_LEN_BYTE_NUM, begin)?;
+        if bytes.len() - *begin < proof_len {
+            return Err(DecodingError::BytesNotEnough);
+        }
+        let merkle_proof = MerkleProof::<V>::deserialize(&bytes[*begin..*begin + proof_len])?;
+        *begin += proof_len;
+
+        let mut leaves = Vec::with_capacity(merkle_proof.get_batch_num());
+        for _i in 0..merkle_proof.get_batch_num() {
+            leaves.push(V::ProofNode::deserialize_as_a_unit(bytes, begin)?);
+        }
+        let mut proof = SelfContainedProof::new(merkle_proof, leaves);
+
+        if *begin >= bytes.len() {
+            return Err(DecodingError::BytesNotEnough);
+        }
+        *begin += 1;
+        match bytes[*begin - 1] {
+            0 => {}
+            1 => proof.set_root(V::ProofNode::deserialize_as_a_unit(bytes, begin)?),
+            x => {
+                return Err(DecodingError::ValueDecodingError {
+                    msg: format!("Invalid root flag {}", x),
+                })
+            }
+        }
+        Ok(proof)
+    }
+}
+
+/// A nested proof of leaves in an inner tree whose root is committed by a leaf of an outer tree,
+/// e.g., a tree of trees as the top accumulator in HashWires.
+///
+/// It consists of the Merkle proof of the leaves against the root of the inner tree,
+/// the root of the inner tree, and the Merkle proof of the root in the outer tree,
+/// where the leaf of the outer tree is the root of the inner tree converted by [Into].
+#[derive(Clone, Default)]
+pub struct NestedProof<I, O>
+where
+    I: Clone + Default + Mergeable + ProofExtractable,
+    <I as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+    O: Clone + Default + Mergeable + ProofExtractable,
+    <O as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    inner_proof: MerkleProof<I>,
+    inner_root: I::ProofNode,
+    outer_proof: MerkleProof<O>,
+}
+
+impl<I, O> NestedProof<I, O>
+where
+    I: Clone + Default + Mergeable + ProofExtractable,
+    <I as ProofExtractable>::ProofNode:
+        Clone + Default + Eq + Mergeable + Serializable + Into<O::ProofNode>,
+    O: Clone + Default + Mergeable + ProofExtractable,
+    <O as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// The constructor.
+    pub fn new(
+        inner_proof: MerkleProof<I>,
+        inner_root: I::ProofNode,
+        outer_proof: MerkleProof<O>,
+    ) -> NestedProof<I, O> {
+        NestedProof {
+            inner_proof,
+            inner_root,
+            outer_proof,
+        }
+    }
+
+    /// Returns the proof of the leaves against the root of the inner tree.
+    pub fn get_inner_proof(&self) -> &MerkleProof<I> {
+        &self.inner_proof
+    }
+
+    /// Returns the root of the inner tree.
+    pub fn get_inner_root(&self) -> &I::ProofNode {
+        &self.inner_root
+    }
+
+    /// Returns the proof of the root of the inner tree in the outer tree.
+    pub fn get_outer_proof(&self) -> &MerkleProof<O> {
+        &self.outer_proof
+    }
+
+    /// Verify the proof of the input leaves, in the order of the indexes in the inner proof,
+    /// against the root of the outer tree.
+    pub fn verify(&self, leaves: &[I::ProofNode], root: &O::ProofNode) -> bool {
+        if self.outer_proof.get_batch_num() != 1 {
+            log_debug!("Nested proof verification failed: not a single outer leaf");
+            return false;
+        }
+        self.inner_proof.verify_batch(leaves, &self.inner_root)
+            && self
+                .outer_proof
+                .verify(&self.inner_root.clone().into(), root)
+    }
+}
+
+impl<I, O> NestedProof<I, O>
+where
+    I: Clone + Default + Mergeable + Paddable + ProofExtractable,
+    <I as ProofExtractable>::ProofNode:
+        Clone + Default + Eq + Mergeable + Serializable + Into<O::ProofNode>,
+    O: Clone + Default + Mergeable + Paddable + ProofExtractable,
+    <O as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// Generate the nested proof of the input leaves of the inner tree,
+    /// whose root is committed by the leaf at ```outer_idx``` of the outer tree.
+    ///
+    /// Return ```None``` if any of the input nodes isn't a leaf of the inner tree,
+    /// if ```outer_idx``` isn't a leaf of the outer tree,
+    /// or if the leaf of the outer tree doesn't commit to the root of the inner tree.
+    pub fn generate(
+        inner: &SparseMerkleTree<I>,
+        list: &[TreeIndex],
+        outer: &SparseMerkleTree<O>,
+        outer_idx: &TreeIndex,
+    ) -> Option<Self> {
+        let inner_root = inner.get_root();
+        let outer_leaf = outer
+            .get_leaf_by_index(outer_idx)?
+            .get_value()
+            .get_proof_node();
+        if outer_leaf != inner_root.clone().into() {
+            return None;
+        }
+        Some(NestedProof {
+            inner_proof: MerkleProof::<I>::generate_inclusion_proof(inner, list)?,
+            inner_root,
+            outer_proof: MerkleProof::<O>::generate_inclusion_proof(outer, &[*outer_idx])?,
+        })
+    }
+}
+
+impl<I, O> Serializable for NestedProof<I, O>
+where
+    I: Clone + Default + Mergeable + ProofExtractable,
+    <I as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+    O: Clone + Default + Mergeable + ProofExtractable,
+    <O as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// Encode a nested proof in the format:
+    /// ```inner_proof_len || inner_proof || inner_root || outer_proof```.
+    fn serialize(&self) -> Vec<u8> {
+        let mut proof = self.inner_proof.serialize();
+        let mut bytes = u64_to_bytes(proof.len() as u64, PROOF_LEN_BYTE_NUM);
+        bytes.append(&mut proof);
+        bytes.append(&mut self.inner_root.serialize());
+        bytes.append(&mut self.outer_proof.serialize());
+        bytes
+    }
+
+    /// Decode input bytes (```inner_proof_len || inner_proof || inner_root || outer_proof```) as a nested proof.
+    fn deserialize_as_a_unit(
+        bytes: &[u8],
+        begin: &mut usize,
+    ) -> Result<NestedProof<I, O>, DecodingError> {
+        let proof_len = bytes_to_count(bytes, PROOF_LEN_BYTE_NUM, begin)?;
+        if bytes.len() - *begin < proof_len {
+            return Err(DecodingError::BytesNotEnough);
+        }
+        let inner_proof = MerkleProof::<I>::deserialize(&bytes[*begin..*begin + proof_len])?;
+        *begin += proof_len;
+        let inner_root = I::ProofNode::deserialize_as_a_unit(bytes, begin)?;
+        let outer_proof = MerkleProof::<O>::deserialize_as_a_unit(bytes, begin)?;
+        Ok(NestedProof {
+            inner_proof,
+            inner_root,
+            outer_proof,
+        })
+    }
+}
+
+/// A random sampling proof proves that the result of random sampling is valid.
+///
+/// It consists of the tree index of the proved node, and the proofs of certain padding nodes, and a standard Merkle proof.
+///
+/// If the sampled index exists as a real leaf node (non-padding) in the tree,
+/// no padding nodes will be proved but just a standard Merkle proof for the sampled index.
+///
+/// If the sampled index doesn't exist as a real leaf node (non-padding) in the tree,
+/// proofs of necessary padding nodes between the two closest neighbours of the sampled index are included in the proof,
+/// and the Merkle proof proves inclusion of the closest neighbours.
+#[derive(Default)]
+pub struct RandomSamplingProof<
+    V: Clone + Default + Mergeable + ProofExtractable + Paddable + PaddingProvable,
+> where
+    V::ProofNode: Default + Eq + Clone + Mergeable + Serializable,
+    V::PaddingProof: Default + Eq + Clone + Serializable,
+{
+    index: TreeIndex, // The tree index of teh proved node.
+    padding_proofs: Vec<<V as PaddingProvable>::PaddingProof>, // The proofs of necessary padding nodes.
+    merkle_proof: MerkleProof<V>,                              // The Merkle proof.
+    leaves: Vec<V::ProofNode>,                                 // The leaf nodes in the proof.
+}
+
+impl<V: Clone + Default + Mergeable + Paddable + PaddingProvable + ProofExtractable>
+    RandomSamplingProof<V>
+where
+    V::ProofNode: Default + Eq + Clone + Mergeable + Serializable,
+    V::PaddingProof: Default + Eq + Clone + Serializable,
+{
+    /// The constructor.
+    pub fn new(
+        index: TreeIndex,
+        padding_proofs: Vec<V::PaddingProof>,
+        merkle_proof: MerkleProof<V>,
+        leaves: Vec<V::ProofNode>,
+    ) -> RandomSamplingProof<V> {
+        RandomSamplingProof {
+            index,
+            padding_proofs,
+            merkle_proof,
+            leaves,
+        }
+    }
+
+    /// Returns the Merkle proof.
+    pub fn get_merkle_proof(&self) -> &MerkleProof<V> {
+        &self.merkle_proof
+    }
+
+    /// Returns the index of the proof.
+    pub fn get_index(&self) -> &TreeIndex {
+        &self.index
+    }
+
+    /// Returns the leaf nodes.
+    pub fn get_leaves(&self) -> &[V::ProofNode] {
+        &self.leaves
+    }
+
+    /// Returns the proofs of padding nodes.
+    pub fn get_padding_proofs(&self) -> &[V::PaddingProof] {
+        &self.padding_proofs
     }
 
     /// Set the leaf node in the proof of a single node.
@@ -416,9 +1978,77 @@ where
         self.padding_proofs.push(proof);
     }
 
-    /// Set the padding proofs as the input.
-    pub fn set_padding_proofs(&mut self, proofs: Vec<V::PaddingProof>) {
-        self.padding_proofs = proofs;
+    /// Set the padding proofs as the input.
+    pub fn set_padding_proofs(&mut self, proofs: Vec<V::PaddingProof>) {
+        self.padding_proofs = proofs;
+    }
+
+    /// Write the proof into the input writer in the streaming format:
+    /// ```tree_index || padding_num || padding_proofs || merkle_proof || leaves```,
+    /// which differs from the output of ```serialize``` in that the padding proofs and the leaves
+    /// are prefixed with their lengths as by [write_unit](../utils/fn.write_unit.html),
+    /// and the Merkle proof is in the streaming format of
+    /// [MerkleProof::serialize_into](struct.MerkleProof.html#method.serialize_into).
+    /// The padding proofs, the Merkle proof and the leaves are written one by one.
+    ///
+    /// If the writer fails, return the I/O error, with the proof possibly partially written.
+    ///
+    /// Panics if the number of leaves doesn't match with the number of indexes in the Merkle proof.
+    pub fn serialize_into<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        // Check if the number of leaves is the same as the number of indexes.
+        if self.merkle_proof.indexes.len() != self.leaves.len() {
+            panic!("The number of indexes doesn't match with the number of leaves");
+        }
+        w.write_all(&TreeIndex::serialize(&[self.index]))?;
+        w.write_all(&u64_to_bytes(
+            self.padding_proofs.len() as u64,
+            PADDING_NUM_BYTE_NUM,
+        ))?;
+        for item in &self.padding_proofs {
+            write_unit(w, &item.serialize())?;
+        }
+        self.merkle_proof.serialize_into(w)?;
+        for item in &self.leaves {
+            write_unit(w, &item.serialize())?;
+        }
+        Ok(())
+    }
+
+    /// Read a proof written by [serialize_into](#method.serialize_into) from the reader,
+    /// reading exactly the encoded bytes, so that concatenated proofs can be read one after another.
+    ///
+    /// If the reader ends in the middle of the proof,
+    /// return [DecodingError::BytesNotEnough](../error/enum.DecodingError.html#variant.BytesNotEnough).
+    /// If the reader fails otherwise,
+    /// return [DecodingError::IoError](../error/enum.DecodingError.html#variant.IoError).
+    pub fn deserialize_from<R: Read + ?Sized>(
+        r: &mut R,
+    ) -> Result<RandomSamplingProof<V>, DecodingError> {
+        // Read the tree index.
+        let index = TreeIndex::deserialize_from(r, 1)?;
+
+        // Read the padding_num and the padding proofs.
+        let mut bytes = [0u8; PADDING_NUM_BYTE_NUM];
+        read_bytes(r, &mut bytes)?;
+        let num = bytes_to_count(&bytes, PADDING_NUM_BYTE_NUM, &mut 0)?;
+        let mut padding_proofs: Vec<V::PaddingProof> = Vec::new();
+        for _i in 0..num {
+            padding_proofs.push(read_unit(r, V::PaddingProof::deserialize_as_a_unit)?);
+        }
+
+        // Read the Merkle proof and the leaves.
+        let merkle_proof = MerkleProof::<V>::deserialize_from(r)?;
+        let mut leaves: Vec<V::ProofNode> = Vec::new();
+        for _i in 0..merkle_proof.get_batch_num() {
+            leaves.push(read_unit(r, V::ProofNode::deserialize_as_a_unit)?);
+        }
+
+        Ok(RandomSamplingProof::<V>::new(
+            index[0],
+            padding_proofs,
+            merkle_proof,
+            leaves,
+        ))
     }
 }
 
@@ -437,16 +2067,16 @@ where
 
         let mut bytes: Vec<u8> = Vec::new();
         bytes.append(&mut TreeIndex::serialize(&[self.index])); // Encode the tree indexes.
-        bytes.append(&mut usize_to_bytes(
-            self.padding_proofs.len(),
+        bytes.append(&mut u64_to_bytes(
+            self.padding_proofs.len() as u64,
             PADDING_NUM_BYTE_NUM,
         )); // Encode the padding_num.
         for item in &self.padding_proofs {
-            bytes.append(&mut V::PaddingProof::serialize(&item)); // Encode the padding proofs.
+            bytes.append(&mut V::PaddingProof::serialize(item)); // Encode the padding proofs.
         }
         bytes.append(&mut self.merkle_proof.serialize()); // Encode the Merkle proof.
         for item in &self.leaves {
-            bytes.append(&mut V::ProofNode::serialize(&item)); // Encode the leaves.
+            bytes.append(&mut V::ProofNode::serialize(item)); // Encode the leaves.
         }
         bytes
     }
@@ -464,7 +2094,7 @@ where
         let index = index.unwrap();
 
         // Decode the padding_num.
-        let num = bytes_to_usize(bytes, PADDING_NUM_BYTE_NUM, begin);
+        let num = bytes_to_count(bytes, PADDING_NUM_BYTE_NUM, begin);
         if let Err(e) = num {
             return Err(e);
         }
@@ -505,6 +2135,48 @@ where
     }
 }
 
+#[cfg(feature = "hex")]
+impl<V: Clone + Default + Mergeable + Paddable + PaddingProvable + ProofExtractable>
+    RandomSamplingProof<V>
+where
+    V::ProofNode: Default + Eq + Clone + Mergeable + Serializable,
+    V::PaddingProof: Default + Eq + Clone + Serializable,
+{
+    /// Encode the proof as a lowercase hex string of its [serialization](#method.serialize).
+    pub fn to_hex(&self) -> String {
+        bytes_to_hex(&self.serialize())
+    }
+
+    /// Decode a proof from a lowercase hex string of its [serialization](#method.serialize).
+    ///
+    /// If the input is not a valid hex string,
+    /// return [DecodingError::TextDecodingError](../error/enum.DecodingError.html#variant.TextDecodingError).
+    pub fn from_hex(text: &str) -> Result<Self, DecodingError> {
+        Self::deserialize(&hex_to_bytes(text)?)
+    }
+}
+
+#[cfg(feature = "base64")]
+impl<V: Clone + Default + Mergeable + Paddable + PaddingProvable + ProofExtractable>
+    RandomSamplingProof<V>
+where
+    V::ProofNode: Default + Eq + Clone + Mergeable + Serializable,
+    V::PaddingProof: Default + Eq + Clone + Serializable,
+{
+    /// Encode the proof as a standard base64 string of its [serialization](#method.serialize).
+    pub fn to_base64(&self) -> String {
+        bytes_to_base64(&self.serialize())
+    }
+
+    /// Decode a proof from a standard base64 string of its [serialization](#method.serialize).
+    ///
+    /// If the input is not a valid base64 string,
+    /// return [DecodingError::TextDecodingError](../error/enum.DecodingError.html#variant.TextDecodingError).
+    pub fn from_base64(text: &str) -> Result<Self, DecodingError> {
+        Self::deserialize(&base64_to_bytes(text)?)
+    }
+}
+
 impl<V: Clone + Default + Mergeable + Paddable + PaddingProvable + ProofExtractable>
     RandomSampleable for RandomSamplingProof<V>
 where
@@ -515,6 +2187,8 @@ where
     type TreeStruct = SparseMerkleTree<V>;
 
     fn random_sampling(tree: &Self::TreeStruct, idx: &TreeIndex, secret: &Secret) -> Self {
+        // The padding nodes are proved with the secret separated by the padding domain of the tree.
+        let secret = &tree.get_padding_context(secret).get_domain_secret();
         // Fetch the lowest ancestor of the sampled index in the tree.
         let (ancestor, ancestor_idx) = tree.get_closest_ancestor_ref_index(idx);
 
@@ -527,12 +2201,19 @@ where
             let refs = tree.get_merkle_path_ref(idx).unwrap();
             // Construct the Merkle proof given the references to all sibling nodes in the proof.
             let mut proof = MerkleProof::<V>::new(*idx);
-            proof.set_siblings(tree.get_node_proof_by_refs(&refs[1..]));
+            proof.set_siblings(tree.get_node_proof_by_refs(refs.siblings()));
+            tree.record_metrics(
+                Operation::ProofGeneration,
+                &OperationMetrics {
+                    nodes_allocated: proof.get_siblings_num() as u64 + 1,
+                    ..OperationMetrics::default()
+                },
+            );
             return RandomSamplingProof::new(
                 *idx,
                 Vec::new(),
                 proof,
-                tree.get_node_proof_by_refs(&refs[0..1]),
+                tree.get_node_proof_by_refs(refs.leaves()),
             );
         }
 
@@ -567,21 +2248,20 @@ where
                 // Get the references to the input leaf and siblings of nodes long the Merkle path from the root to the leaves.
                 let refs = tree.get_merkle_path_ref(&list[0]).unwrap();
                 // Construct the Merkle proof given the references to all sibling nodes in the proof.
-                merkle_proof.set_siblings(tree.get_node_proof_by_refs(&refs[1..]));
-                leaves = tree.get_node_proof_by_refs(&refs[0..1]);
-                let padding_refs;
+                merkle_proof.set_siblings(tree.get_node_proof_by_refs(refs.siblings()));
+                leaves = tree.get_node_proof_by_refs(refs.leaves());
                 // Fetch the reference (offset to the end of the sibling list) to the necessary padding nodes by neighbour direction.
-                if list[0] < *idx {
-                    padding_refs = SparseMerkleTree::<V>::get_padding_proof_by_dir_index_ref_pairs(
+                let padding_refs = if list[0] < *idx {
+                    SparseMerkleTree::<V>::get_padding_proof_by_dir_index_ref_pairs(
                         &list[0],
                         ChildDir::Left,
-                    );
+                    )
                 } else {
-                    padding_refs = SparseMerkleTree::<V>::get_padding_proof_by_dir_index_ref_pairs(
+                    SparseMerkleTree::<V>::get_padding_proof_by_dir_index_ref_pairs(
                         &list[0],
                         ChildDir::Right,
-                    );
-                }
+                    )
+                };
                 // Add the proofs of the necessary padding nodes.
                 <RandomSamplingProof<V>>::add_padding_proofs(
                     tree,
@@ -596,8 +2276,8 @@ where
                 // Get the references to the input leaves and siblings of nodes long the batched Merkle paths from the root to the leaves.
                 let refs = tree.get_merkle_path_ref_batch(&list).unwrap();
                 // Construct the Merkle proof given the references to all sibling nodes in the proof.
-                merkle_proof.set_siblings(tree.get_node_proof_by_refs(&refs[2..]));
-                leaves = tree.get_node_proof_by_refs(&refs[0..2]);
+                merkle_proof.set_siblings(tree.get_node_proof_by_refs(refs.siblings()));
+                leaves = tree.get_node_proof_by_refs(refs.leaves());
                 // Fetch the reference (offset to the end of the sibling list) to the necessary padding nodes.
                 let padding_refs = SparseMerkleTree::<V>::get_padding_proof_batch_index_ref_pairs(
                     &list[0], &list[1],
@@ -612,6 +2292,14 @@ where
                 )
             }
         }
+        tree.record_metrics(
+            Operation::ProofGeneration,
+            &OperationMetrics {
+                nodes_allocated: (merkle_proof.get_siblings_num() + leaves.len()) as u64,
+                paddings: padding_proofs.len() as u64,
+                ..OperationMetrics::default()
+            },
+        );
         RandomSamplingProof::new(*idx, padding_proofs, merkle_proof, leaves)
     }
 
@@ -647,24 +2335,21 @@ where
                     // When the sampled index doesn't exist as a real leaf node in the tree,
                     // and the neighbour on one side doesn't exist,
                     // there is only one neighbour proved in the Merkle proof.
-                    let padding_refs;
-                    if list[0] < self.index {
+                    let padding_refs = if list[0] < self.index {
                         // Only the left neighbour exists.
                         // Get references to padding nodes that prove the left neighbour is the right-most node in the tree.
-                        padding_refs =
-                            SparseMerkleTree::<V>::get_padding_proof_by_dir_index_ref_pairs(
-                                &list[0],
-                                ChildDir::Left,
-                            );
+                        SparseMerkleTree::<V>::get_padding_proof_by_dir_index_ref_pairs(
+                            &list[0],
+                            ChildDir::Left,
+                        )
                     } else {
                         // Only the right neighbour exists.
                         // Get references to padding nodes that prove the right neighbour is the left-most node in the tree.
-                        padding_refs =
-                            SparseMerkleTree::<V>::get_padding_proof_by_dir_index_ref_pairs(
-                                &list[0],
-                                ChildDir::Right,
-                            );
-                    }
+                        SparseMerkleTree::<V>::get_padding_proof_by_dir_index_ref_pairs(
+                            &list[0],
+                            ChildDir::Right,
+                        )
+                    };
 
                     // If the number of necessary padding nodes doesn't match, the proof is invalid.
                     if padding_refs.len() != self.padding_proofs.len() {
@@ -732,16 +2417,650 @@ where
     fn add_padding_proofs(
         tree: &SparseMerkleTree<V>,
         padding_proofs: &mut Vec<<V as PaddingProvable>::PaddingProof>,
-        refs: Vec<usize>,
+        refs: MerklePathRefs,
         padding_refs: Vec<(TreeIndex, usize)>,
         secret: &Secret,
     ) {
+        let siblings = refs.siblings();
         for (index, item) in padding_refs {
             padding_proofs.push(
-                tree.get_node_by_ref(refs[refs.len() - 1 - item])
+                tree.get_node_by_ref(siblings[siblings.len() - 1 - item])
                     .get_value()
                     .prove_padding_node(&index, secret),
             );
         }
     }
 }
+
+/// Returns the index of the node at the input relative index under the subtree rooted at ```root```.
+fn get_absolute_index(root: &TreeIndex, relative: &TreeIndex) -> TreeIndex {
+    let mut index = *root;
+    for i in 0..relative.get_height() {
+        index = index.get_child_index_by_dir(if relative.get_bit(i) == 0 {
+            ChildDir::Left
+        } else {
+            ChildDir::Right
+        });
+    }
+    index
+}
+
+/// Returns the index of the input node relative to its ancestor of the input height.
+fn get_relative_index(index: &TreeIndex, height: usize) -> TreeIndex {
+    let mut relative = TreeIndex::zero(0);
+    for i in height..index.get_height() {
+        relative = relative.get_child_index_by_dir(if index.get_bit(i) == 0 {
+            ChildDir::Left
+        } else {
+            ChildDir::Right
+        });
+    }
+    relative
+}
+
+// Returns the Merkle proof of the node at the input index, of any height, against the root of the tree,
+// and the reference to the node, or None if the index is not a node in the tree,
+// e.g., a descendant of a padding node, or its height exceeds that of the tree.
+fn generate_node_proof<P: Clone + Default + Mergeable + Paddable + ProofExtractable>(
+    tree: &SparseMerkleTree<P>,
+    idx: &TreeIndex,
+) -> Option<(MerkleProof<P>, usize)>
+where
+    P::ProofNode: Default + Eq + Clone + Mergeable + Serializable,
+{
+    if idx.get_height() > tree.get_height() {
+        return None;
+    }
+    // Walk down from the root to the node, collecting the siblings along the path.
+    let mut link = tree.get_root_ref();
+    let mut siblings = Vec::with_capacity(idx.get_height());
+    for i in 0..idx.get_height() {
+        let node = tree.get_node_by_ref(link);
+        let (next, sibling) = if idx.get_bit(i) == 0 {
+            (node.get_lch()?, node.get_rch()?)
+        } else {
+            (node.get_rch()?, node.get_lch()?)
+        };
+        siblings.push(tree.get_node_by_ref(sibling).get_value().get_proof_node());
+        link = next;
+    }
+    let mut proof = MerkleProof::new(*idx);
+    proof.set_siblings(siblings);
+    Some((proof, link))
+}
+
+/// A proof that an internal node of the tree commits exactly to a list of leaves,
+/// i.e., all other positions in the subtree rooted at the node are padding.
+///
+/// It consists of the Merkle proof of the subtree root against the tree root,
+/// the batched Merkle proof of the leaves against the subtree root with indexes relative to the subtree root,
+/// whose siblings are all padding nodes in the subtree,
+/// and the proofs of these padding nodes in the BFS order.
+/// If the subtree has no leaf, the subtree root itself is a padding node with a proof.
+#[derive(Debug, Clone)]
+pub struct SubtreeCompletenessProof<
+    V: Clone + Default + Mergeable + ProofExtractable + Paddable + PaddingProvable,
+> where
+    V::ProofNode: Default + Eq + Clone + Mergeable + Serializable,
+    V::PaddingProof: Default + Eq + Clone + Serializable,
+{
+    subtree_proof: MerkleProof<V>,
+    subtree_root: V::ProofNode,
+    leaves_proof: MerkleProof<V>,
+    padding_proofs: Vec<V::PaddingProof>,
+}
+
+impl<V: Clone + Default + Mergeable + Paddable + PaddingProvable + ProofExtractable>
+    SubtreeCompletenessProof<V>
+where
+    V::ProofNode: Default + Eq + Clone + Mergeable + Serializable,
+    V::PaddingProof: Default + Eq + Clone + Serializable,
+{
+    /// Generate the proof for the subtree rooted at the input index,
+    /// which is verified against the leaves of the subtree in increasing order of index.
+    ///
+    /// Returns None if the input index is not a node in the tree, e.g., a descendant of a padding node,
+    /// or its height exceeds that of the tree.
+    pub fn generate(
+        tree: &SparseMerkleTree<V>,
+        idx: &TreeIndex,
+        secret: &Secret,
+    ) -> Option<SubtreeCompletenessProof<V>> {
+        // The padding nodes are proved with the secret separated by the padding domain of the tree.
+        let secret = &tree.get_padding_context(secret).get_domain_secret();
+        let (subtree_proof, link) = generate_node_proof(tree, idx)?;
+        let subtree_node = tree.get_node_by_ref(link);
+
+        // Collect the indexes of the leaves in the subtree from left to right.
+        let mut leaves: Vec<TreeIndex> = Vec::new();
+        let mut stack = vec![(link, *idx)];
+        while let Some((link, index)) = stack.pop() {
+            let node = tree.get_node_by_ref(link);
+            match node.get_node_type() {
+                NodeType::Leaf => leaves.push(index),
+                NodeType::Padding => (),
+                NodeType::Internal => {
+                    stack.push((node.get_rch()?, index.get_rch_index()));
+                    stack.push((node.get_lch()?, index.get_lch_index()));
+                }
+            }
+        }
+
+        let relative: Vec<TreeIndex> = leaves
+            .iter()
+            .map(|index| get_relative_index(index, idx.get_height()))
+            .collect();
+        let mut leaves_proof = MerkleProof::new_batch(&relative);
+        let mut padding_proofs = Vec::new();
+        if leaves.is_empty() {
+            padding_proofs.push(subtree_node.get_value().prove_padding_node(idx, secret));
+        } else if idx.get_height() < tree.get_height() {
+            // The siblings of the leaves in the subtree are padding nodes.
+            let mut paddings = Vec::new();
+            for position in leaves_proof.get_sibling_positions()? {
+                let index = get_absolute_index(idx, &position);
+                let mut link = link;
+                for i in idx.get_height()..index.get_height() {
+                    let node = tree.get_node_by_ref(link);
+                    link = if index.get_bit(i) == 0 {
+                        node.get_lch()?
+                    } else {
+                        node.get_rch()?
+                    };
+                }
+                let value = tree.get_node_by_ref(link).get_value();
+                paddings.push(value.get_proof_node());
+                padding_proofs.push(value.prove_padding_node(&index, secret));
+            }
+            leaves_proof.set_siblings(paddings);
+        }
+        // The proof nodes of the subtree root and of the siblings are copied from the tree.
+        tree.record_metrics(
+            Operation::ProofGeneration,
+            &OperationMetrics {
+                nodes_allocated: (subtree_proof.get_siblings_num()
+                    + leaves_proof.get_siblings_num()
+                    + 1) as u64,
+                paddings: padding_proofs.len() as u64,
+                ..Default::default()
+            },
+        );
+
+        Some(SubtreeCompletenessProof {
+            subtree_proof,
+            subtree_root: subtree_node.get_value().get_proof_node(),
+            leaves_proof,
+            padding_proofs,
+        })
+    }
+
+    /// Returns the index of the subtree root.
+    pub fn get_index(&self) -> &TreeIndex {
+        &self.subtree_proof.get_indexes()[0]
+    }
+
+    /// Returns the subtree root.
+    pub fn get_subtree_root(&self) -> &V::ProofNode {
+        &self.subtree_root
+    }
+
+    /// Verify that the subtree commits exactly to the input leaves, given in increasing order of index,
+    /// and that the subtree is in the tree of the input root.
+    pub fn verify(&self, leaves: &[(TreeIndex, V::ProofNode)], root: &V::ProofNode) -> bool {
+        if self.subtree_proof.get_indexes().len() != 1
+            || !self.subtree_proof.verify(&self.subtree_root, root)
+        {
+            log_debug!("Subtree completeness verification failed: invalid subtree proof");
+            return false;
+        }
+        let idx = *self.get_index();
+
+        if leaves.is_empty() {
+            return self.leaves_proof.get_batch_num() == 0
+                && self.padding_proofs.len() == 1
+                && V::verify_padding_node(&self.subtree_root, &self.padding_proofs[0], &idx);
+        }
+
+        // The leaves must be in the subtree, and the proved indexes must be theirs.
+        if leaves
+            .iter()
+            .any(|(index, _)| !index.is_descendant_of(&idx))
+        {
+            log_debug!("Subtree completeness verification failed: leaf out of the subtree");
+            return false;
+        }
+        let relative: Vec<TreeIndex> = leaves
+            .iter()
+            .map(|(index, _)| get_relative_index(index, idx.get_height()))
+            .collect();
+        if relative != self.leaves_proof.get_indexes() {
+            log_debug!("Subtree completeness verification failed: leaf indexes mismatch");
+            return false;
+        }
+        // The subtree root is a leaf itself.
+        if relative[0].get_height() == 0 {
+            return leaves.len() == 1
+                && leaves[0].1 == self.subtree_root
+                && self.padding_proofs.is_empty();
+        }
+        // The leaves are merged with the absolute indexes of their ancestors in the tree.
+        let values: Vec<V::ProofNode> = leaves.iter().map(|(_, value)| value.clone()).collect();
+        if self.leaves_proof.compute_root_batch_under(&values, &idx)
+            != Some(self.subtree_root.clone())
+        {
+            log_debug!("Subtree completeness verification failed: subtree root mismatch");
+            return false;
+        }
+
+        // All siblings of the leaves in the subtree must be padding nodes.
+        let positions = match self.leaves_proof.get_sibling_positions() {
+            Some(x) => x,
+            None => return false,
+        };
+        if positions.len() != self.padding_proofs.len() {
+            log_debug!("Subtree completeness verification failed: padding proofs mismatch");
+            return false;
+        }
+        positions
+            .iter()
+            .zip(self.leaves_proof.get_path_siblings())
+            .zip(&self.padding_proofs)
+            .all(|((position, node), proof)| {
+                V::verify_padding_node(node, proof, &get_absolute_index(&idx, position))
+            })
+    }
+}
+
+/// A Merkle proof of leaves against one of their ancestors instead of the root,
+/// e.g., a node at a level whose nodes are cached by the verifier,
+/// so that the proof only carries the siblings below the ancestor, called the anchor.
+///
+/// It consists of the index of the anchor,
+/// and the batched Merkle proof of the leaves against the anchor with indexes relative to the anchor.
+/// When the anchor is not trusted by the verifier, the proof is composed with the Merkle proof of the anchor
+/// against the root, see [generate_anchor_proof](#method.generate_anchor_proof) and [compose](#method.compose).
+#[derive(Clone, Default)]
+pub struct AnchoredProof<V: Clone + Default + Mergeable + ProofExtractable>
+where
+    V::ProofNode: Default + Eq + Clone + Mergeable + Serializable,
+{
+    anchor: TreeIndex,
+    proof: MerkleProof<V>,
+}
+
+impl<V: Clone + Default + Mergeable + ProofExtractable> AnchoredProof<V>
+where
+    V::ProofNode: Default + Eq + Clone + Mergeable + Serializable,
+{
+    /// Returns the index of the anchor.
+    pub fn get_anchor(&self) -> &TreeIndex {
+        &self.anchor
+    }
+
+    /// Returns the proof of the leaves against the anchor, with indexes relative to the anchor.
+    pub fn get_relative_proof(&self) -> &MerkleProof<V> {
+        &self.proof
+    }
+
+    /// Returns the indexes of the proved leaves in the tree.
+    pub fn get_indexes(&self) -> Vec<TreeIndex> {
+        self.proof
+            .get_indexes()
+            .iter()
+            .map(|index| get_absolute_index(&self.anchor, index))
+            .collect()
+    }
+
+    /// Returns the anchor computed from the proof with the input leaves, in increasing order of index.
+    ///
+    /// Return ```None``` if the proof is not valid, e.g.,
+    /// the numbers of leaves or siblings don't match with the proved indexes.
+    pub fn compute_anchor(&self, leaves: &[V::ProofNode]) -> Option<V::ProofNode> {
+        let indexes = self.proof.get_indexes();
+        if leaves.len() != indexes.len() || leaves.is_empty() {
+            log_debug!("Anchored proof verification failed: leaves mismatch");
+            return None;
+        }
+        // The anchor is the proved leaf itself.
+        if indexes[0].get_height() == 0 {
+            if leaves.len() != 1 || self.proof.get_siblings_num() != 0 {
+                log_debug!("Anchored proof verification failed: the anchor is a leaf");
+                return None;
+            }
+            return Some(leaves[0].clone());
+        }
+        self.proof.compute_root_batch_under(leaves, &self.anchor)
+    }
+
+    /// Verify the proof of the input leaves, in increasing order of index, against the trusted anchor node.
+    pub fn verify(&self, leaves: &[V::ProofNode], anchor: &V::ProofNode) -> bool {
+        match self.compute_anchor(leaves) {
+            Some(value) if value == *anchor => true,
+            Some(_) => {
+                log_debug!("Anchored proof verification failed: anchor mismatch");
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Verify the proof of the input leaves, in increasing order of index, against the root of the tree,
+    /// given the Merkle proof of the anchor against the root.
+    pub fn verify_with_anchor_proof(
+        &self,
+        leaves: &[V::ProofNode],
+        anchor_proof: &MerkleProof<V>,
+        root: &V::ProofNode,
+    ) -> bool {
+        if anchor_proof.get_indexes() != [self.anchor] {
+            log_debug!("Anchored proof verification failed: anchor index mismatch");
+            return false;
+        }
+        match self.compute_anchor(leaves) {
+            Some(value) => anchor_proof.verify(&value, root),
+            None => false,
+        }
+    }
+
+    /// Returns the Merkle proof of the leaves against the root of the tree,
+    /// composed of this proof and the input Merkle proof of the anchor against the root.
+    ///
+    /// The siblings above the anchor precede those below it in the BFS order,
+    /// so the composed proof is the same as the one generated from the tree.
+    ///
+    /// Return ```None``` if the input proof isn't a proof of the anchor.
+    pub fn compose(&self, anchor_proof: &MerkleProof<V>) -> Option<MerkleProof<V>> {
+        if anchor_proof.get_indexes() != [self.anchor]
+            || anchor_proof.get_siblings_num() != self.anchor.get_height()
+        {
+            return None;
+        }
+        let mut proof = MerkleProof::new_batch(&self.get_indexes());
+        let mut siblings = anchor_proof.get_path_siblings().to_vec();
+        siblings.extend_from_slice(self.proof.get_path_siblings());
+        proof.set_siblings(siblings);
+        Some(proof)
+    }
+}
+
+impl<P: Clone + Default + Mergeable + Paddable + ProofExtractable> AnchoredProof<P>
+where
+    P::ProofNode: Default + Eq + Clone + Mergeable + Serializable,
+{
+    /// Generate the proof of the leaves at the input sorted indexes against their ancestor at the anchor index.
+    ///
+    /// Return ```None``` if the list is empty or not sorted, if any of the input nodes isn't a leaf of the tree,
+    /// or if the anchor is not an ancestor of all of them.
+    pub fn generate(
+        tree: &SparseMerkleTree<P>,
+        anchor: &TreeIndex,
+        list: &[TreeIndex],
+    ) -> Option<AnchoredProof<P>> {
+        if list.is_empty()
+            || list.windows(2).any(|x| x[0] >= x[1])
+            || list
+                .iter()
+                .any(|idx| idx.get_height() != tree.get_height() || !idx.is_descendant_of(anchor))
+        {
+            return None;
+        }
+        let full = MerkleProof::<P>::generate_inclusion_proof(tree, list)?;
+        // Only keep the siblings below the anchor, i.e., those not on the path from the root to the anchor.
+        let siblings = full
+            .get_sibling_positions()?
+            .iter()
+            .zip(full.get_path_siblings())
+            .filter(|(position, _)| position.get_height() > anchor.get_height())
+            .map(|(_, sibling)| sibling.clone())
+            .collect();
+        let relative: Vec<TreeIndex> = list
+            .iter()
+            .map(|idx| get_relative_index(idx, anchor.get_height()))
+            .collect();
+        let mut proof = MerkleProof::new_batch(&relative);
+        proof.set_siblings(siblings);
+        Some(AnchoredProof {
+            anchor: *anchor,
+            proof,
+        })
+    }
+
+    /// Generate the Merkle proof of the node at the anchor index against the root of the tree,
+    /// to be composed with the proofs anchored at it.
+    ///
+    /// Return ```None``` if the anchor is not a node in the tree, e.g., a descendant of a padding node,
+    /// or its height exceeds that of the tree.
+    pub fn generate_anchor_proof(
+        tree: &SparseMerkleTree<P>,
+        anchor: &TreeIndex,
+    ) -> Option<MerkleProof<P>> {
+        generate_node_proof(tree, anchor).map(|(proof, _)| proof)
+    }
+}
+
+impl<V: Clone + Default + Mergeable + ProofExtractable> Serializable for AnchoredProof<V>
+where
+    V::ProofNode: Default + Eq + Clone + Mergeable + Serializable,
+{
+    /// Encode an anchored proof in the format: ```anchor || relative_proof```.
+    fn serialize(&self) -> Vec<u8> {
+        let mut bytes = TreeIndex::serialize(&[self.anchor]);
+        bytes.append(&mut self.proof.serialize());
+        bytes
+    }
+
+    /// Decode input bytes (```anchor || relative_proof```) as an anchored proof.
+    fn deserialize_as_a_unit(
+        bytes: &[u8],
+        begin: &mut usize,
+    ) -> Result<AnchoredProof<V>, DecodingError> {
+        let anchor = TreeIndex::deserialize_as_a_unit(bytes, 1, begin)?[0];
+        let proof = MerkleProof::<V>::deserialize_as_a_unit(bytes, begin)?;
+        if proof
+            .get_indexes()
+            .iter()
+            .any(|index| anchor.get_height() + index.get_height() > MAX_HEIGHT)
+        {
+            return Err(DecodingError::ExceedMaxHeight);
+        }
+        Ok(AnchoredProof { anchor, proof })
+    }
+}
+
+// ======================================================================================
+
+/// A Merkle proof omitting the siblings that are padding nodes, for verifiers holding the padding secret,
+/// e.g., internal auditors, who recompute the omitted siblings from the secret and their tree indexes.
+///
+/// In a very sparse tree most siblings along a path are padding nodes,
+/// so the proof shrinks to the non-padding siblings and one bit per sibling marking the omitted ones.
+/// Note that a verifier without the secret can't verify the proof,
+/// and a holder of the secret can distinguish padding nodes from leaves.
+pub struct PaddingOmittedProof<V: Clone + Default + Mergeable + ProofExtractable>
+where
+    V::ProofNode: Default + Eq + Clone + Mergeable + Serializable,
+{
+    // The proof of the leaves with only the non-padding siblings.
+    proof: MerkleProof<V>,
+    // Whether each sibling of the full proof, in the same order, is an omitted padding node.
+    omitted: Vec<bool>,
+}
+
+impl<V: Clone + Default + Mergeable + ProofExtractable> PaddingOmittedProof<V>
+where
+    V::ProofNode: Default + Eq + Clone + Mergeable + Serializable,
+{
+    /// Returns the indexes of the proved leaves.
+    pub fn get_indexes(&self) -> &[TreeIndex] {
+        self.proof.get_indexes()
+    }
+
+    /// Returns the siblings kept in the proof, i.e., those that are not padding nodes.
+    pub fn get_kept_siblings(&self) -> &[V::ProofNode] {
+        self.proof.get_path_siblings()
+    }
+
+    /// Returns the number of omitted padding siblings.
+    pub fn get_omitted_num(&self) -> usize {
+        self.omitted.iter().filter(|x| **x).count()
+    }
+}
+
+impl<V: Clone + Default + Mergeable + Paddable + ProofExtractable> PaddingOmittedProof<V>
+where
+    V::ProofNode: Default + Eq + Clone + Mergeable + Serializable,
+{
+    /// Generate the proof of the input list of leaves, omitting the padding siblings under the input secret,
+    /// which is the padding secret of the tree.
+    ///
+    /// For a tree with a [padding domain](../tree/struct.SparseMerkleTree.html#method.set_padding_domain),
+    /// the padding siblings are those under the
+    /// [domain secret](../traits/struct.PaddingContext.html#method.get_domain_secret) of the tree,
+    /// which is the secret the verifier needs.
+    ///
+    /// Return ```None``` if any of the input leaves doesn't exist in the tree.
+    pub fn generate(
+        tree: &SparseMerkleTree<V>,
+        list: &[TreeIndex],
+        secret: &Secret,
+    ) -> Option<PaddingOmittedProof<V>> {
+        let proof = MerkleProof::<V>::generate_inclusion_proof(tree, list)?;
+        Self::from_merkle_proof(
+            &proof,
+            &tree.get_padding_context(secret).get_domain_secret(),
+        )
+    }
+
+    /// Convert a Merkle proof by omitting the siblings that are the padding nodes under the input secret.
+    ///
+    /// Return ```None``` if the proved indexes are not valid, e.g., not sorted or of different heights,
+    /// or the number of siblings doesn't match with them.
+    pub fn from_merkle_proof(
+        proof: &MerkleProof<V>,
+        secret: &Secret,
+    ) -> Option<PaddingOmittedProof<V>> {
+        let positions = proof.get_sibling_positions()?;
+        if positions.len() != proof.siblings.len() {
+            return None;
+        }
+        let height = proof.indexes.first().map_or(0, |x| x.get_height());
+        let ctx = PaddingContext::new(height, secret);
+        let omitted: Vec<bool> = positions
+            .iter()
+            .zip(proof.siblings.iter())
+            .map(|(idx, sibling)| V::padding_with_context(idx, &ctx).get_proof_node() == *sibling)
+            .collect();
+        let mut kept = MerkleProof::new_batch(&proof.indexes);
+        kept.set_siblings(
+            proof
+                .siblings
+                .iter()
+                .zip(omitted.iter())
+                .filter(|(_, x)| !**x)
+                .map(|(sibling, _)| sibling.clone())
+                .collect(),
+        );
+        Some(PaddingOmittedProof {
+            proof: kept,
+            omitted,
+        })
+    }
+
+    /// Returns the full Merkle proof, with the omitted siblings recomputed as the padding nodes
+    /// under the input secret.
+    ///
+    /// Return ```None``` if the proof is not valid, i.e., the proved indexes are not valid,
+    /// or the number of siblings doesn't match with them.
+    pub fn to_merkle_proof(&self, secret: &Secret) -> Option<MerkleProof<V>> {
+        let positions = self.proof.get_sibling_positions()?;
+        if positions.len() != self.omitted.len()
+            || self.omitted.len() - self.get_omitted_num() != self.proof.siblings.len()
+        {
+            return None;
+        }
+        let height = self.proof.indexes.first().map_or(0, |x| x.get_height());
+        let ctx = PaddingContext::new(height, secret);
+        let mut kept = self.proof.siblings.iter();
+        let siblings = positions
+            .iter()
+            .zip(self.omitted.iter())
+            .map(|(idx, omitted)| {
+                if *omitted {
+                    V::padding_with_context(idx, &ctx).get_proof_node()
+                } else {
+                    kept.next().unwrap().clone()
+                }
+            })
+            .collect();
+        let mut proof = MerkleProof::new_batch(&self.proof.indexes);
+        proof.set_siblings(siblings);
+        Some(proof)
+    }
+
+    /// Verify the proof of a single leaf, recomputing the omitted siblings under the input secret.
+    pub fn verify(&self, leaf: &V::ProofNode, root: &V::ProofNode, secret: &Secret) -> bool {
+        match self.to_merkle_proof(secret) {
+            Some(proof) => proof.verify(leaf, root),
+            None => false,
+        }
+    }
+
+    /// Verify the proof of a batch of leaves, recomputing the omitted siblings under the input secret.
+    pub fn verify_batch(
+        &self,
+        leaves: &[V::ProofNode],
+        root: &V::ProofNode,
+        secret: &Secret,
+    ) -> bool {
+        match self.to_merkle_proof(secret) {
+            Some(proof) => proof.verify_batch(leaves, root),
+            None => false,
+        }
+    }
+}
+
+impl<V: Clone + Default + Mergeable + ProofExtractable> Serializable for PaddingOmittedProof<V>
+where
+    V::ProofNode: Default + Eq + Clone + Mergeable + Serializable,
+{
+    /// Encode a proof in the format: ```sibling_num || omitted_bits || kept_proof```,
+    /// where ```kept_proof``` is the Merkle proof with the kept siblings,
+    /// and ```omitted_bits``` has one bit per sibling of the full proof, from the least significant bit of the first byte.
+    fn serialize(&self) -> Vec<u8> {
+        let mut bytes = u64_to_bytes(self.omitted.len() as u64, SIBLING_NUM_BYTE_NUM);
+        let mut bits = vec![0u8; self.omitted.len().div_ceil(8)];
+        for (i, _) in self.omitted.iter().enumerate().filter(|(_, x)| **x) {
+            bits[i / 8] |= 1 << (i % 8);
+        }
+        bytes.append(&mut bits);
+        bytes.append(&mut self.proof.serialize());
+        bytes
+    }
+
+    /// Decode input bytes (```sibling_num || omitted_bits || kept_proof```) as a padding-omitted proof.
+    ///
+    /// If the number of siblings that are not omitted doesn't match with the kept siblings,
+    /// return [DecodingError::ValueDecodingError](../error/enum.DecodingError.html#variant.ValueDecodingError).
+    fn deserialize_as_a_unit(
+        bytes: &[u8],
+        begin: &mut usize,
+    ) -> Result<PaddingOmittedProof<V>, DecodingError> {
+        let sibling_num = bytes_to_count(bytes, SIBLING_NUM_BYTE_NUM, begin)?;
+        let bits_len = sibling_num.div_ceil(8);
+        if bytes.len() - *begin < bits_len {
+            return Err(DecodingError::BytesNotEnough);
+        }
+        let bits = &bytes[*begin..*begin + bits_len];
+        *begin += bits_len;
+        let omitted: Vec<bool> = (0..sibling_num)
+            .map(|i| (bits[i / 8] >> (i % 8)) & 1 == 1)
+            .collect();
+        let proof = MerkleProof::<V>::deserialize_as_a_unit(bytes, begin)?;
+        if omitted.iter().filter(|x| !**x).count() != proof.siblings.len() {
+            return Err(DecodingError::ValueDecodingError {
+                msg: "Number of kept siblings mismatch".to_string(),
+            });
+        }
+        Ok(PaddingOmittedProof { proof, omitted })
+    }
+}
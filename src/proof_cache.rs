@@ -0,0 +1,172 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! This module provides a caching layer over an SMT, memoizing the generated Merkle proofs of single leaves,
+//! e.g., for hot leaves requested repeatedly between epochs.
+//!
+//! The tree is updated through the cache, which keeps the cached proofs valid.
+//! The path of any other leaf leaves the path of an updated leaf at some depth,
+//! where its sibling is the updated node at the next depth, and its other siblings don't change.
+//! So a single update patches that one sibling in each cached proof, leaving the proof of the updated leaf itself as it is,
+//! while an update of several leaves invalidates all cached proofs.
+
+use std::collections::HashMap;
+
+use crate::pad_secret::Secret;
+use crate::{
+    index::TreeIndex,
+    proof::MerkleProof,
+    traits::{InclusionProvable, Mergeable, Paddable, ProofExtractable, Serializable},
+    tree::{DuplicatePolicy, SparseMerkleTree},
+};
+
+/// The hit and miss counts of a [ProofCache].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProofCacheStats {
+    /// The number of proof requests served from the cache.
+    pub hits: u64,
+    /// The number of proof requests generating the proof from the tree.
+    pub misses: u64,
+    /// The number of cached proofs dropped by updates.
+    pub invalidations: u64,
+}
+
+impl ProofCacheStats {
+    /// Returns the ratio of the requests served from the cache, or 0 if there wasn't any request.
+    pub fn get_hit_rate(&self) -> f64 {
+        let requests = self.hits + self.misses;
+        if requests == 0 {
+            return 0.0;
+        }
+        self.hits as f64 / requests as f64
+    }
+}
+
+/// An SMT with a cache of the Merkle proofs of single leaves.
+pub struct ProofCache<P: Clone + Default + Mergeable + ProofExtractable>
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    tree: SparseMerkleTree<P>,
+    proofs: HashMap<TreeIndex, MerkleProof<P>>,
+    stats: ProofCacheStats,
+}
+
+impl<P: Clone + Default + Mergeable + Paddable + ProofExtractable> ProofCache<P>
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// The constructor, with an empty cache over the input tree.
+    pub fn new(tree: SparseMerkleTree<P>) -> ProofCache<P> {
+        ProofCache {
+            tree,
+            proofs: HashMap::new(),
+            stats: ProofCacheStats::default(),
+        }
+    }
+
+    /// Returns the tree.
+    pub fn get_tree(&self) -> &SparseMerkleTree<P> {
+        &self.tree
+    }
+
+    /// Returns the tree, dropping the cache.
+    pub fn into_tree(self) -> SparseMerkleTree<P> {
+        self.tree
+    }
+
+    /// Returns the number of cached proofs.
+    pub fn get_cached_num(&self) -> usize {
+        self.proofs.len()
+    }
+
+    /// Returns the hit and miss counts of the cache.
+    pub fn get_stats(&self) -> &ProofCacheStats {
+        &self.stats
+    }
+
+    /// Reset the hit and miss counts to zero, keeping the cached proofs.
+    pub fn reset_stats(&mut self) {
+        self.stats = ProofCacheStats::default();
+    }
+
+    /// Drop all cached proofs.
+    pub fn clear(&mut self) {
+        self.stats.invalidations += self.proofs.len() as u64;
+        self.proofs.clear();
+    }
+
+    /// Returns the Merkle proof of the leaf at the input index, from the cache if it is cached,
+    /// or None if the leaf doesn't exist.
+    ///
+    /// Panics if the height of the input index doesn't match with that of the tree.
+    pub fn get_proof(&mut self, idx: &TreeIndex) -> Option<&MerkleProof<P>> {
+        if self.proofs.contains_key(idx) {
+            self.stats.hits += 1;
+        } else {
+            self.stats.misses += 1;
+            let proof = MerkleProof::<P>::generate_inclusion_proof(&self.tree, &[*idx])?;
+            self.proofs.insert(*idx, proof);
+        }
+        self.proofs.get(idx)
+    }
+
+    /// Update the leaf at the input index, patching the sibling changed by the update in the cached proofs of the other leaves,
+    /// and return the previous value of the leaf as [update](../tree/struct.SparseMerkleTree.html#method.update) does.
+    ///
+    /// Panics if the height of the input index doesn't match with that of the tree.
+    pub fn update(&mut self, key: &TreeIndex, value: P, secret: &Secret) -> Option<P> {
+        let previous = self.tree.update(key, value, secret);
+        if self.proofs.is_empty() {
+            return previous;
+        }
+        // The proof nodes on the path of the updated leaf, from the child of the root to the leaf.
+        let mut path: Vec<P::ProofNode> = Vec::with_capacity(key.get_height());
+        let mut link = self.tree.root;
+        for i in 0..key.get_height() {
+            let node = self.tree.get_node_by_ref(link);
+            link = if key.get_bit(i) == 0 {
+                node.get_lch()
+            } else {
+                node.get_rch()
+            }
+            .unwrap();
+            path.push(self.tree.get_node_by_ref(link).get_value().get_proof_node());
+        }
+        // The proof of the updated leaf is only cached if the leaf existed before, so its siblings don't change.
+        // The sibling of another leaf at the depth its path leaves the updated path is the updated node there.
+        for (idx, proof) in self.proofs.iter_mut() {
+            if idx != key {
+                let depth = idx.common_prefix_len(key);
+                proof.set_sibling_at_idx(depth, path[depth].clone());
+            }
+        }
+        previous
+    }
+
+    /// Update the tree with the input list of index-value pairs in any order
+    /// as [update_batch](../tree/struct.SparseMerkleTree.html#method.update_batch) does,
+    /// invalidating the cached proofs.
+    ///
+    /// Panics if the height of some input index doesn't match with that of the tree,
+    /// or if there are duplicated indexes with the policy [DuplicatePolicy::Error](../tree/enum.DuplicatePolicy.html#variant.Error).
+    pub fn update_batch(
+        &mut self,
+        list: Vec<(TreeIndex, P)>,
+        secret: &Secret,
+        policy: &DuplicatePolicy<P>,
+    ) {
+        if list.is_empty() {
+            return;
+        }
+        if list.len() == 1 {
+            let (key, value) = list.into_iter().next().unwrap();
+            self.update(&key, value, secret);
+            return;
+        }
+        self.tree.update_batch(list, secret, policy);
+        self.clear();
+    }
+}
@@ -0,0 +1,171 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! This module provides prost messages of proofs, enabled by the ```proto``` feature,
+//! matching the schema in ```proto/smtree.proto```,
+//! and conversions between the messages and the proofs.
+//!
+//! Tree node values are carried as bytes in the encoding of their [Serializable] implementation.
+
+use std::convert::TryFrom;
+
+use crate::{
+    error::DecodingError,
+    index::TreeIndex,
+    proof::{MerkleProof, RandomSamplingProof},
+    traits::{Mergeable, Paddable, PaddingProvable, ProofExtractable, Serializable},
+};
+
+/// The protobuf schema of the messages.
+pub const PROTO_SCHEMA: &str = include_str!("../proto/smtree.proto");
+
+/// The index of a tree node.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TreeIndexProto {
+    #[prost(uint32, tag = "1")]
+    pub height: u32,
+    #[prost(bytes = "vec", tag = "2")]
+    pub path: Vec<u8>,
+}
+
+/// A (batched) Merkle proof.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct MerkleProofProto {
+    /// The height shared by all proved indexes.
+    #[prost(uint32, tag = "1")]
+    pub height: u32,
+    /// The paths of the proved indexes.
+    #[prost(bytes = "vec", repeated, tag = "2")]
+    pub paths: Vec<Vec<u8>>,
+    /// The encoded siblings.
+    #[prost(bytes = "vec", repeated, tag = "3")]
+    pub siblings: Vec<Vec<u8>>,
+}
+
+/// A random sampling proof.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RandomSamplingProofProto {
+    #[prost(message, optional, tag = "1")]
+    pub index: Option<TreeIndexProto>,
+    #[prost(bytes = "vec", repeated, tag = "2")]
+    pub padding_proofs: Vec<Vec<u8>>,
+    #[prost(message, optional, tag = "3")]
+    pub merkle_proof: Option<MerkleProofProto>,
+    #[prost(bytes = "vec", repeated, tag = "4")]
+    pub leaves: Vec<Vec<u8>>,
+}
+
+/// Decode a list of node values, each using up its bytes.
+fn values_from_bytes<T: Serializable>(list: &[Vec<u8>]) -> Result<Vec<T>, DecodingError> {
+    list.iter().map(|bytes| T::deserialize(bytes)).collect()
+}
+
+impl From<&TreeIndex> for TreeIndexProto {
+    fn from(index: &TreeIndex) -> TreeIndexProto {
+        TreeIndexProto {
+            height: index.get_height() as u32,
+            path: index.get_path_bytes(),
+        }
+    }
+}
+
+impl TryFrom<&TreeIndexProto> for TreeIndex {
+    type Error = DecodingError;
+
+    fn try_from(message: &TreeIndexProto) -> Result<TreeIndex, DecodingError> {
+        TreeIndex::from_path_bytes(message.height as usize, &message.path)
+    }
+}
+
+impl<V: Default + Clone + Mergeable + ProofExtractable> From<&MerkleProof<V>> for MerkleProofProto
+where
+    <V as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    fn from(proof: &MerkleProof<V>) -> MerkleProofProto {
+        let indexes = proof.get_indexes();
+        MerkleProofProto {
+            height: indexes.first().map_or(0, |x| x.get_height() as u32),
+            paths: indexes.iter().map(TreeIndex::get_path_bytes).collect(),
+            siblings: proof
+                .get_path_siblings()
+                .iter()
+                .map(|x| x.serialize())
+                .collect(),
+        }
+    }
+}
+
+/// If the height exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html),
+/// or some path or node value can't be decoded, return the decoding error.
+impl<V: Default + Clone + Mergeable + ProofExtractable> TryFrom<&MerkleProofProto>
+    for MerkleProof<V>
+where
+    <V as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    type Error = DecodingError;
+
+    fn try_from(message: &MerkleProofProto) -> Result<MerkleProof<V>, DecodingError> {
+        let indexes = message
+            .paths
+            .iter()
+            .map(|path| TreeIndex::from_path_bytes(message.height as usize, path))
+            .collect::<Result<Vec<TreeIndex>, DecodingError>>()?;
+        let mut proof = MerkleProof::new_batch(&indexes);
+        proof.set_siblings(values_from_bytes(&message.siblings)?);
+        Ok(proof)
+    }
+}
+
+impl<V: Clone + Default + Mergeable + Paddable + PaddingProvable + ProofExtractable>
+    From<&RandomSamplingProof<V>> for RandomSamplingProofProto
+where
+    V::ProofNode: Default + Eq + Clone + Mergeable + Serializable,
+    V::PaddingProof: Default + Eq + Clone + Serializable,
+{
+    fn from(proof: &RandomSamplingProof<V>) -> RandomSamplingProofProto {
+        RandomSamplingProofProto {
+            index: Some(proof.get_index().into()),
+            padding_proofs: proof
+                .get_padding_proofs()
+                .iter()
+                .map(|x| x.serialize())
+                .collect(),
+            merkle_proof: Some(proof.get_merkle_proof().into()),
+            leaves: proof.get_leaves().iter().map(|x| x.serialize()).collect(),
+        }
+    }
+}
+
+/// If the index or the Merkle proof is missing, return
+/// [DecodingError::BytesNotEnough](../error/enum.DecodingError.html#variant.BytesNotEnough).
+///
+/// If some index or node value can't be decoded, return the decoding error.
+impl<V: Clone + Default + Mergeable + Paddable + PaddingProvable + ProofExtractable>
+    TryFrom<&RandomSamplingProofProto> for RandomSamplingProof<V>
+where
+    V::ProofNode: Default + Eq + Clone + Mergeable + Serializable,
+    V::PaddingProof: Default + Eq + Clone + Serializable,
+{
+    type Error = DecodingError;
+
+    fn try_from(
+        message: &RandomSamplingProofProto,
+    ) -> Result<RandomSamplingProof<V>, DecodingError> {
+        let index = message
+            .index
+            .as_ref()
+            .ok_or(DecodingError::BytesNotEnough)?;
+        let merkle_proof = message
+            .merkle_proof
+            .as_ref()
+            .ok_or(DecodingError::BytesNotEnough)?;
+        Ok(RandomSamplingProof::new(
+            TreeIndex::try_from(index)?,
+            values_from_bytes(&message.padding_proofs)?,
+            MerkleProof::try_from(merkle_proof)?,
+            values_from_bytes(&message.leaves)?,
+        ))
+    }
+}
@@ -0,0 +1,257 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! A node template for any serde-serializable value, enabled by the ```serde``` feature.
+//!
+//! The value of a leaf is serialized canonically as deterministic CBOR:
+//! map keys are sorted by their encodings, lengths are definite, and integers take the shortest form,
+//! so that equal values always have the same bytes, e.g., maps with different insertion orders.
+//! The hash of a leaf is the hash of these canonical bytes,
+//! and the other nodes are the same as those of [HashNodeSmt](../node_template/struct.HashNodeSmt.html).
+
+use std::marker::PhantomData;
+
+use ciborium::value::Value;
+use digest::Digest;
+use serde::{de::DeserializeOwned, Serialize};
+use subtle::ConstantTimeEq;
+
+use crate::pad_secret::Secret;
+use crate::{
+    error::DecodingError,
+    index::TreeIndex,
+    node_template::HashNodeSmt,
+    traits::{Mergeable, Paddable, PaddingProvable, ProofExtractable, Serializable, TypeName},
+};
+
+/// The first byte of the encoding of a leaf, followed by the canonical bytes of the value.
+const LEAF_FLAG: u8 = 1;
+/// The first byte of the encoding of a node without value, followed by the hash.
+const HASH_FLAG: u8 = 0;
+
+/// Sorts the keys of all maps in the input CBOR value by their encodings.
+fn canonicalize(value: Value) -> Value {
+    match value {
+        Value::Array(items) => Value::Array(items.into_iter().map(canonicalize).collect()),
+        Value::Map(entries) => {
+            let mut entries: Vec<(Vec<u8>, Value, Value)> = entries
+                .into_iter()
+                .map(|(key, value)| {
+                    let key = canonicalize(key);
+                    (encode(&key), key, canonicalize(value))
+                })
+                .collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            Value::Map(entries.into_iter().map(|(_, k, v)| (k, v)).collect())
+        }
+        Value::Tag(tag, inner) => Value::Tag(tag, Box::new(canonicalize(*inner))),
+        other => other,
+    }
+}
+
+/// Encode a CBOR value as bytes.
+fn encode(value: &Value) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    // Writing into a vector never fails.
+    ciborium::ser::into_writer(value, &mut bytes).unwrap();
+    bytes
+}
+
+/// Returns the canonical bytes of the input value.
+///
+/// If the value can't be serialized,
+/// return [DecodingError::FormatError](../error/enum.DecodingError.html#variant.FormatError).
+pub fn to_canonical_bytes<T: Serialize>(value: &T) -> Result<Vec<u8>, DecodingError> {
+    let value =
+        Value::serialized(value).map_err(|e| DecodingError::FormatError { msg: e.to_string() })?;
+    Ok(encode(&canonicalize(value)))
+}
+
+/// Decode the canonical bytes of a value starting from the ```begin``` position,
+/// possibly with some bytes at the end left.
+///
+/// If the bytes are not the canonical encoding of a ```T``` value,
+/// return [DecodingError::FormatError](../error/enum.DecodingError.html#variant.FormatError).
+pub fn from_canonical_bytes<T: DeserializeOwned>(
+    bytes: &[u8],
+    begin: &mut usize,
+) -> Result<T, DecodingError> {
+    let mut reader = &bytes[*begin..];
+    let value: Value = ciborium::de::from_reader(&mut reader)
+        .map_err(|e| DecodingError::FormatError { msg: e.to_string() })?;
+    let end = bytes.len() - reader.len();
+    // Reject non-canonical encodings, so that every value has exactly one encoding.
+    let value = canonicalize(value);
+    if encode(&value) != bytes[*begin..end] {
+        return Err(DecodingError::FormatError {
+            msg: "non-canonical CBOR encoding".to_owned(),
+        });
+    }
+    let value = value
+        .deserialized()
+        .map_err(|e| DecodingError::FormatError { msg: e.to_string() })?;
+    *begin = end;
+    Ok(value)
+}
+
+// ======================================================================================
+
+/// An SMT node whose leaves store a value of any serde-serializable type,
+/// while the node visible in Merkle proofs is the [HashNodeSmt](../node_template/struct.HashNodeSmt.html)
+/// of the hash of the canonical bytes of the value.
+#[derive(Clone, Debug)]
+pub struct SerdeNodeSmt<T, D> {
+    // The value, which is only stored in leaves.
+    value: Option<T>,
+    hash: Vec<u8>,
+    phantom: PhantomData<D>,
+}
+
+impl<T: Serialize, D: Digest> SerdeNodeSmt<T, D> {
+    /// The constructor of a leaf storing the input value.
+    ///
+    /// Panics if the value can't be serialized.
+    pub fn new(value: T) -> SerdeNodeSmt<T, D> {
+        let bytes = to_canonical_bytes(&value).unwrap_or_else(|e| panic!("{}", e));
+        SerdeNodeSmt {
+            value: Some(value),
+            hash: D::digest(&bytes).to_vec(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T, D> SerdeNodeSmt<T, D> {
+    // The constructor of a node without value, i.e., an internal or padding node.
+    fn new_from_hash(hash: Vec<u8>) -> SerdeNodeSmt<T, D> {
+        SerdeNodeSmt {
+            value: None,
+            hash,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the value of a leaf, or None for other nodes.
+    pub fn get_value(&self) -> Option<&T> {
+        self.value.as_ref()
+    }
+
+    /// Returns the hash value.
+    pub fn get_hash(&self) -> &[u8] {
+        &self.hash
+    }
+}
+
+impl<T, D> Default for SerdeNodeSmt<T, D> {
+    fn default() -> SerdeNodeSmt<T, D> {
+        SerdeNodeSmt::new_from_hash(Vec::new())
+    }
+}
+
+// The hash of a leaf determines its value.
+impl<T, D> PartialEq for SerdeNodeSmt<T, D> {
+    fn eq(&self, other: &Self) -> bool {
+        bool::from(self.hash.ct_eq(&other.hash)) && self.value.is_some() == other.value.is_some()
+    }
+}
+
+impl<T, D> Eq for SerdeNodeSmt<T, D> {}
+
+impl<T, D: Digest> Mergeable for SerdeNodeSmt<T, D> {
+    fn merge(lch: &SerdeNodeSmt<T, D>, rch: &SerdeNodeSmt<T, D>) -> SerdeNodeSmt<T, D> {
+        let mut hasher = D::new();
+        hasher.update(&lch.hash);
+        hasher.update(&rch.hash);
+        SerdeNodeSmt::new_from_hash(hasher.finalize().to_vec())
+    }
+}
+
+impl<T, D: Digest> Paddable for SerdeNodeSmt<T, D> {
+    fn padding(idx: &TreeIndex, secret: &Secret) -> SerdeNodeSmt<T, D> {
+        SerdeNodeSmt::new_from_hash(HashNodeSmt::<D>::padding(idx, secret).get_hash().to_vec())
+    }
+}
+
+impl<T: Serialize + DeserializeOwned, D: Digest> Serializable for SerdeNodeSmt<T, D> {
+    /// Encode a leaf as ```1 || canonical_bytes``` and other nodes as ```0 || hash```.
+    fn serialize(&self) -> Vec<u8> {
+        match &self.value {
+            Some(value) => {
+                let mut bytes = vec![LEAF_FLAG];
+                // The value was serialized when the leaf was constructed.
+                bytes.append(&mut to_canonical_bytes(value).unwrap());
+                bytes
+            }
+            None => {
+                let mut bytes = vec![HASH_FLAG];
+                bytes.extend_from_slice(&self.hash);
+                bytes
+            }
+        }
+    }
+
+    /// Decode the input bytes (```1 || canonical_bytes``` or ```0 || hash```) as a node.
+    ///
+    /// If the first byte is neither 0 nor 1,
+    /// return [DecodingError::ValueDecodingError](../error/enum.DecodingError.html#variant.ValueDecodingError).
+    fn deserialize_as_a_unit(bytes: &[u8], begin: &mut usize) -> Result<Self, DecodingError> {
+        if bytes.len() - *begin < 1 {
+            return Err(DecodingError::BytesNotEnough);
+        }
+        match bytes[*begin] {
+            LEAF_FLAG => {
+                let mut end = *begin + 1;
+                let value: T = from_canonical_bytes(bytes, &mut end)?;
+                let node = SerdeNodeSmt {
+                    value: Some(value),
+                    hash: D::digest(&bytes[*begin + 1..end]).to_vec(),
+                    phantom: PhantomData,
+                };
+                *begin = end;
+                Ok(node)
+            }
+            HASH_FLAG => {
+                if bytes.len() - *begin - 1 < D::output_size() {
+                    return Err(DecodingError::BytesNotEnough);
+                }
+                let hash = bytes[*begin + 1..*begin + 1 + D::output_size()].to_vec();
+                *begin += 1 + D::output_size();
+                Ok(SerdeNodeSmt::new_from_hash(hash))
+            }
+            x => Err(DecodingError::ValueDecodingError {
+                msg: format!("Invalid node flag {}", x),
+            }),
+        }
+    }
+}
+
+impl<T, D: Clone> ProofExtractable for SerdeNodeSmt<T, D> {
+    type ProofNode = HashNodeSmt<D>;
+    fn get_proof_node(&self) -> Self::ProofNode {
+        HashNodeSmt::new(self.hash.clone())
+    }
+}
+
+impl<T, D: Clone + Digest> PaddingProvable for SerdeNodeSmt<T, D> {
+    type PaddingProof = HashNodeSmt<D>;
+
+    fn prove_padding_node(&self, idx: &TreeIndex, secret: &Secret) -> HashNodeSmt<D> {
+        self.get_proof_node().prove_padding_node(idx, secret)
+    }
+
+    fn verify_padding_node(
+        node: &<Self as ProofExtractable>::ProofNode,
+        proof: &Self::PaddingProof,
+        idx: &TreeIndex,
+    ) -> bool {
+        HashNodeSmt::<D>::verify_padding_node(node, proof, idx)
+    }
+}
+
+impl<T, D: TypeName> TypeName for SerdeNodeSmt<T, D> {
+    fn get_name() -> String {
+        format!("Serde ({})", D::get_name())
+    }
+}
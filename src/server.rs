@@ -0,0 +1,193 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! A minimal proof provider for serving the root and proofs of a tree, enabled by the ```server``` feature.
+//!
+//! A [ProofRequest] is encoded as ```tag || payload```, and the response to a request is the encoding of
+//! the root, the Merkle proof or the random sampling proof, so the wire contract doesn't depend on the transport.
+//! The transport is up to the application, see the ```proof_server``` example for an HTTP endpoint with axum.
+
+use crate::pad_secret::Secret;
+use crate::{
+    error::{DecodingError, ProviderError},
+    index::TreeIndex,
+    proof::{BorrowedMerkleProof, RandomSamplingProof},
+    traits::{
+        Mergeable, Paddable, PaddingProvable, ProofExtractable, RandomSampleable, Serializable,
+    },
+    tree::SparseMerkleTree,
+    utils::{bytes_to_count, u64_to_bytes},
+};
+
+/// The tag of a request of the root.
+const ROOT_TAG: u8 = 0;
+/// The tag of a request of an inclusion proof.
+const INCLUSION_TAG: u8 = 1;
+/// The tag of a request of a random sampling proof.
+const SAMPLING_TAG: u8 = 2;
+/// The number of bytes for encoding the number of indexes in a request of an inclusion proof.
+const BATCH_NUM_BYTE_NUM: usize = 8;
+
+/// A request to a proof provider.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProofRequest {
+    /// Request the root of the tree.
+    Root,
+    /// Request the (batched) Merkle proof of the leaves at the sorted indexes.
+    Inclusion(Vec<TreeIndex>),
+    /// Request the random sampling proof of the index.
+    RandomSampling(TreeIndex),
+}
+
+impl Serializable for ProofRequest {
+    /// Encode a request in the format: ```0``` for the root,
+    /// ```1 || batch_num || tree_indexes``` for an inclusion proof,
+    /// and ```2 || tree_index``` for a random sampling proof.
+    fn serialize(&self) -> Vec<u8> {
+        match self {
+            ProofRequest::Root => vec![ROOT_TAG],
+            ProofRequest::Inclusion(list) => {
+                let mut bytes = vec![INCLUSION_TAG];
+                bytes.append(&mut u64_to_bytes(list.len() as u64, BATCH_NUM_BYTE_NUM));
+                bytes.append(&mut TreeIndex::serialize(list));
+                bytes
+            }
+            ProofRequest::RandomSampling(idx) => {
+                let mut bytes = vec![SAMPLING_TAG];
+                bytes.append(&mut TreeIndex::serialize(&[*idx]));
+                bytes
+            }
+        }
+    }
+
+    /// Decode the input bytes as a request.
+    ///
+    /// If the tag is unknown,
+    /// return [DecodingError::ValueDecodingError](../error/enum.DecodingError.html#variant.ValueDecodingError).
+    fn deserialize_as_a_unit(bytes: &[u8], begin: &mut usize) -> Result<Self, DecodingError> {
+        if bytes.len() - *begin < 1 {
+            return Err(DecodingError::BytesNotEnough);
+        }
+        *begin += 1;
+        match bytes[*begin - 1] {
+            ROOT_TAG => Ok(ProofRequest::Root),
+            INCLUSION_TAG => {
+                let num = bytes_to_count(bytes, BATCH_NUM_BYTE_NUM, begin)?;
+                Ok(ProofRequest::Inclusion(TreeIndex::deserialize_as_a_unit(
+                    bytes, num, begin,
+                )?))
+            }
+            SAMPLING_TAG => Ok(ProofRequest::RandomSampling(
+                TreeIndex::deserialize_as_a_unit(bytes, 1, begin)?[0],
+            )),
+            x => Err(DecodingError::ValueDecodingError {
+                msg: format!("Unknown request tag {}", x),
+            }),
+        }
+    }
+}
+
+/// A provider of the root and the proofs of a tree, responding with encoded bytes.
+pub trait ProofProvider {
+    /// Returns the encoded root.
+    fn get_root(&self) -> Vec<u8>;
+
+    /// Returns the encoded Merkle proof of the leaves at the input sorted indexes.
+    fn get_inclusion_proof(&self, list: &[TreeIndex]) -> Result<Vec<u8>, ProviderError>;
+
+    /// Returns the encoded random sampling proof of the input index.
+    fn get_random_sampling_proof(&self, idx: &TreeIndex) -> Result<Vec<u8>, ProviderError>;
+
+    /// Returns the response to the input request.
+    fn handle(&self, request: &ProofRequest) -> Result<Vec<u8>, ProviderError> {
+        match request {
+            ProofRequest::Root => Ok(self.get_root()),
+            ProofRequest::Inclusion(list) => self.get_inclusion_proof(list),
+            ProofRequest::RandomSampling(idx) => self.get_random_sampling_proof(idx),
+        }
+    }
+
+    /// Returns the response to the input encoded request.
+    ///
+    /// If the request can't be decoded,
+    /// return [ProviderError::BadRequest](../error/enum.ProviderError.html#variant.BadRequest).
+    fn handle_bytes(&self, bytes: &[u8]) -> Result<Vec<u8>, ProviderError> {
+        self.handle(&ProofRequest::deserialize(bytes)?)
+    }
+}
+
+/// A proof provider of a sparse Merkle tree, holding the padding secret for random sampling proofs.
+pub struct TreeProofProvider<P> {
+    tree: SparseMerkleTree<P>,
+    secret: Secret,
+}
+
+impl<P> TreeProofProvider<P> {
+    /// The constructor, with the tree and the secret it was built with.
+    pub fn new(tree: SparseMerkleTree<P>, secret: &Secret) -> TreeProofProvider<P> {
+        TreeProofProvider {
+            tree,
+            // The secret has been checked at its construction.
+            secret: Secret::from_bytes(secret.as_bytes()).unwrap(),
+        }
+    }
+
+    /// Returns the tree.
+    pub fn get_tree(&self) -> &SparseMerkleTree<P> {
+        &self.tree
+    }
+
+    // Check that the input index fits to the tree.
+    fn check_index(&self, idx: &TreeIndex) -> Result<(), ProviderError> {
+        if idx.get_height() != self.tree.height {
+            return Err(ProviderError::BadRequest {
+                msg: "The height of the index doesn't match with the height of the tree."
+                    .to_owned(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<P: Clone + Default + Mergeable + Paddable + PaddingProvable + ProofExtractable> ProofProvider
+    for TreeProofProvider<P>
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+    <P as PaddingProvable>::PaddingProof: Clone + Default + Eq + Serializable,
+{
+    fn get_root(&self) -> Vec<u8> {
+        self.tree.get_root().serialize()
+    }
+
+    /// Returns the encoded Merkle proof of the leaves at the input sorted indexes.
+    ///
+    /// If the list is empty, not sorted, or of indexes of another height,
+    /// return [ProviderError::BadRequest](../error/enum.ProviderError.html#variant.BadRequest).
+    ///
+    /// If some index is not a leaf of the tree,
+    /// return [ProviderError::NotFound](../error/enum.ProviderError.html#variant.NotFound).
+    fn get_inclusion_proof(&self, list: &[TreeIndex]) -> Result<Vec<u8>, ProviderError> {
+        if list.is_empty() || list.windows(2).any(|x| x[0] >= x[1]) {
+            return Err(ProviderError::BadRequest {
+                msg: "The indexes are empty or not sorted.".to_owned(),
+            });
+        }
+        for idx in list {
+            self.check_index(idx)?;
+        }
+        BorrowedMerkleProof::generate(&self.tree, list)
+            .map(|proof| proof.serialize())
+            .ok_or(ProviderError::NotFound)
+    }
+
+    /// Returns the encoded random sampling proof of the input index.
+    ///
+    /// If the index is of another height,
+    /// return [ProviderError::BadRequest](../error/enum.ProviderError.html#variant.BadRequest).
+    fn get_random_sampling_proof(&self, idx: &TreeIndex) -> Result<Vec<u8>, ProviderError> {
+        self.check_index(idx)?;
+        Ok(RandomSamplingProof::<P>::random_sampling(&self.tree, idx, &self.secret).serialize())
+    }
+}
@@ -0,0 +1,255 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! SSZ encoding of tree indexes, proof nodes and Merkle proofs, enabled by the ```ssz``` feature.
+//!
+//! The encodings follow the Simple Serialize conventions of the Ethereum consensus specs
+//! for the following SSZ types:
+//! * ```TreeIndex```: ```Container { height: uint16, path: Vector[byte, 32] }```.
+//! * ```HashNodeSmt```, ```HashWiresNodeSmt``` and ```MTreeNodeSmt```: ```Vector[byte, n]```
+//!   where ```n``` is the output size of the digest, e.g., ```Bytes32``` for SHA-256.
+//! * ```SumNodeSmt```: ```uint64```.
+//! * ```MerkleProof```: ```Container { indexes: List[TreeIndex, N], siblings: List[ProofNode, N] }```.
+//!
+//! A tree root is encoded as a single proof node.
+//!
+//! With ```HashNodeSmt<Sha256>```, a parent is ```sha256(left | right)``` as in the SSZ Merkleization,
+//! so a single-leaf proof can be checked by ```is_valid_merkle_branch``` of the consensus specs
+//! using the branch and the generalized index returned by
+//! [get_ssz_branch](../proof/struct.MerkleProof.html#method.get_ssz_branch).
+
+use digest::Digest;
+
+use crate::{
+    error::DecodingError,
+    index::{TreeIndex, MAX_HEIGHT},
+    node_template::{HashNodeSmt, HashWiresNodeSmt, MTreeNodeSmt, SumNodeSmt},
+    proof::MerkleProof,
+    traits::{Mergeable, ProofExtractable, Serializable},
+};
+
+/// The number of bytes of an SSZ offset.
+const OFFSET_SIZE: usize = 4;
+
+/// Trait for the SSZ encoding of fixed-size objects.
+pub trait SszEncodable {
+    /// Returns the fixed length of the SSZ encoding.
+    fn get_ssz_len() -> usize
+    where
+        Self: std::marker::Sized;
+
+    /// Append the SSZ encoding of the object to the input buffer.
+    fn ssz_append(&self, buf: &mut Vec<u8>);
+
+    /// Decode the input bytes of length [get_ssz_len](#tymethod.get_ssz_len) as a ```Self``` object.
+    fn from_ssz_bytes(bytes: &[u8]) -> Result<Self, DecodingError>
+    where
+        Self: std::marker::Sized;
+
+    /// Encode the object as SSZ bytes.
+    fn to_ssz(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.ssz_append(&mut buf);
+        buf
+    }
+}
+
+/// Returns the generalized index of the input tree index, i.e., ```2^height + position```,
+/// or None if it doesn't fit in a u64.
+pub fn generalized_index(idx: &TreeIndex) -> Option<u64> {
+    let height = idx.get_height();
+    if height >= 64 {
+        return None;
+    }
+    let mut gindex = 1u64;
+    for i in 0..height {
+        gindex = (gindex << 1) | idx.get_bit(i) as u64;
+    }
+    Some(gindex)
+}
+
+/// Check that the input bytes are exactly of the expected length.
+fn check_len(bytes: &[u8], len: usize) -> Result<(), DecodingError> {
+    if bytes.len() < len {
+        return Err(DecodingError::BytesNotEnough);
+    }
+    if bytes.len() > len {
+        return Err(DecodingError::TooManyEncodedBytes);
+    }
+    Ok(())
+}
+
+fn read_offset(bytes: &[u8], begin: usize) -> Result<usize, DecodingError> {
+    if bytes.len() < begin + OFFSET_SIZE {
+        return Err(DecodingError::BytesNotEnough);
+    }
+    let mut offset = [0u8; OFFSET_SIZE];
+    offset.copy_from_slice(&bytes[begin..begin + OFFSET_SIZE]);
+    Ok(u32::from_le_bytes(offset) as usize)
+}
+
+/// Encode a list of fixed-size objects, i.e., the concatenation of their encodings.
+fn append_list<T: SszEncodable>(list: &[T], buf: &mut Vec<u8>) {
+    for item in list {
+        item.ssz_append(buf);
+    }
+}
+
+/// Decode the input bytes as a list of fixed-size objects.
+fn decode_list<T: SszEncodable>(bytes: &[u8]) -> Result<Vec<T>, DecodingError> {
+    let len = T::get_ssz_len();
+    if bytes.len() % len != 0 {
+        return Err(DecodingError::BytesNotEnough);
+    }
+    bytes.chunks(len).map(T::from_ssz_bytes).collect()
+}
+
+impl SszEncodable for TreeIndex {
+    fn get_ssz_len() -> usize {
+        2 + MAX_HEIGHT / 8
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&(self.get_height() as u16).to_le_bytes());
+        let mut path = self.get_path_bytes();
+        path.resize(MAX_HEIGHT / 8, 0);
+        buf.extend_from_slice(&path);
+    }
+
+    /// The path bytes not used by the height must be zero,
+    /// otherwise return [DecodingError::FormatError](../error/enum.DecodingError.html#variant.FormatError).
+    fn from_ssz_bytes(bytes: &[u8]) -> Result<TreeIndex, DecodingError> {
+        check_len(bytes, Self::get_ssz_len())?;
+        let height = u16::from_le_bytes([bytes[0], bytes[1]]) as usize;
+        if height > MAX_HEIGHT {
+            return Err(DecodingError::ExceedMaxHeight);
+        }
+        let byte_num = height.div_ceil(8);
+        if bytes[2 + byte_num..].iter().any(|x| *x != 0) {
+            return Err(DecodingError::FormatError {
+                msg: "non-zero unused path bytes".to_owned(),
+            });
+        }
+        TreeIndex::from_path_bytes(height, &bytes[2..2 + byte_num])
+    }
+}
+
+impl<D: Digest> SszEncodable for HashNodeSmt<D> {
+    fn get_ssz_len() -> usize {
+        D::output_size()
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.get_hash());
+    }
+
+    fn from_ssz_bytes(bytes: &[u8]) -> Result<HashNodeSmt<D>, DecodingError> {
+        check_len(bytes, Self::get_ssz_len())?;
+        Ok(HashNodeSmt::new(bytes.to_vec()))
+    }
+}
+
+impl<D: Digest, const VERSION: u8> SszEncodable for HashWiresNodeSmt<D, VERSION> {
+    fn get_ssz_len() -> usize {
+        D::output_size()
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.get_hash());
+    }
+
+    fn from_ssz_bytes(bytes: &[u8]) -> Result<HashWiresNodeSmt<D, VERSION>, DecodingError> {
+        check_len(bytes, Self::get_ssz_len())?;
+        Ok(HashWiresNodeSmt::new(bytes.to_vec()))
+    }
+}
+
+impl<D: Digest> SszEncodable for MTreeNodeSmt<D> {
+    fn get_ssz_len() -> usize {
+        D::output_size()
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.get_hash());
+    }
+
+    fn from_ssz_bytes(bytes: &[u8]) -> Result<MTreeNodeSmt<D>, DecodingError> {
+        check_len(bytes, Self::get_ssz_len())?;
+        Ok(MTreeNodeSmt::new(bytes.to_vec()))
+    }
+}
+
+impl SszEncodable for SumNodeSmt {
+    fn get_ssz_len() -> usize {
+        8
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.get_value().to_le_bytes());
+    }
+
+    fn from_ssz_bytes(bytes: &[u8]) -> Result<SumNodeSmt, DecodingError> {
+        check_len(bytes, Self::get_ssz_len())?;
+        let mut value = [0u8; 8];
+        value.copy_from_slice(bytes);
+        Ok(SumNodeSmt::new(u64::from_le_bytes(value)))
+    }
+}
+
+impl<V: Default + Clone + Mergeable + ProofExtractable> MerkleProof<V>
+where
+    <V as ProofExtractable>::ProofNode:
+        Clone + Default + Eq + Mergeable + Serializable + SszEncodable,
+{
+    /// Encode the proof as an SSZ container of the indexes and the siblings.
+    pub fn to_ssz(&self) -> Vec<u8> {
+        let indexes_len = self.get_indexes().len() * TreeIndex::get_ssz_len();
+        let mut buf = Vec::new();
+        // Both fields are variable-size, so the fixed part consists of their offsets.
+        buf.extend_from_slice(&((2 * OFFSET_SIZE) as u32).to_le_bytes());
+        buf.extend_from_slice(&((2 * OFFSET_SIZE + indexes_len) as u32).to_le_bytes());
+        append_list(self.get_indexes(), &mut buf);
+        append_list(self.get_path_siblings(), &mut buf);
+        buf
+    }
+
+    /// Decode the input SSZ bytes as a proof.
+    ///
+    /// If the offsets are not consistent with the input length,
+    /// return [DecodingError::FormatError](../error/enum.DecodingError.html#variant.FormatError).
+    pub fn from_ssz(bytes: &[u8]) -> Result<MerkleProof<V>, DecodingError> {
+        let indexes_offset = read_offset(bytes, 0)?;
+        let siblings_offset = read_offset(bytes, OFFSET_SIZE)?;
+        if indexes_offset != 2 * OFFSET_SIZE
+            || siblings_offset < indexes_offset
+            || siblings_offset > bytes.len()
+        {
+            return Err(DecodingError::FormatError {
+                msg: "invalid SSZ offsets".to_owned(),
+            });
+        }
+        let indexes: Vec<TreeIndex> = decode_list(&bytes[indexes_offset..siblings_offset])?;
+        let mut proof = MerkleProof::new_batch(&indexes);
+        proof.set_siblings(decode_list(&bytes[siblings_offset..])?);
+        Ok(proof)
+    }
+
+    /// Returns the generalized index of the proved leaf and its Merkle branch ordered from the leaf level up,
+    /// as taken by ```is_valid_merkle_branch``` in the Ethereum consensus specs.
+    ///
+    /// Returns None if the proof is not a single-leaf proof of height less than 64.
+    pub fn get_ssz_branch(&self) -> Option<(u64, Vec<V::ProofNode>)> {
+        if self.get_indexes().len() != 1
+            || self.get_siblings_num() != self.get_indexes()[0].get_height()
+        {
+            return None;
+        }
+        let gindex = generalized_index(&self.get_indexes()[0])?;
+        Some((
+            gindex,
+            self.get_path_siblings().iter().rev().cloned().collect(),
+        ))
+    }
+}
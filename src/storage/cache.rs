@@ -0,0 +1,256 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! An LRU node cache in front of a [NodeStore](../trait.NodeStore.html).
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::marker::PhantomData;
+use std::sync::Mutex;
+
+use super::{NodeBatch, NodeStore, TreeMetadata};
+use crate::{
+    error::StorageError,
+    traits::{Mergeable, Paddable},
+    tree::TreeNode,
+};
+
+/// Hit/miss statistics of a [CachedNodeStore].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// The number of node reads served by the cache, including the pinned nodes.
+    pub hits: u64,
+    /// The number of node reads forwarded to the underlying store.
+    pub misses: u64,
+    /// The number of nodes evicted from the LRU cache.
+    pub evictions: u64,
+    /// The number of cached nodes invalidated by writes.
+    pub invalidations: u64,
+}
+
+// The mutable state of the cache.
+struct CacheState<P> {
+    // The pinned nodes, never evicted.
+    pinned: HashMap<usize, TreeNode<P>>,
+    // The LRU nodes with the tick of their last access.
+    lru: HashMap<usize, (TreeNode<P>, u64)>,
+    // The references of LRU nodes ordered by the tick of their last access.
+    order: BTreeMap<u64, usize>,
+    tick: u64,
+    metadata: Option<TreeMetadata>,
+    stats: CacheStats,
+}
+
+impl<P: Clone> CacheState<P> {
+    fn touch(&mut self, link: usize) -> Option<TreeNode<P>> {
+        self.tick += 1;
+        let tick = self.tick;
+        let (node, last) = self.lru.get_mut(&link)?;
+        self.order.remove(last);
+        *last = tick;
+        self.order.insert(tick, link);
+        Some(node.clone())
+    }
+
+    fn insert(&mut self, link: usize, node: TreeNode<P>, capacity: usize) {
+        if capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        if let Some((_, last)) = self.lru.insert(link, (node, self.tick)) {
+            self.order.remove(&last);
+        }
+        self.order.insert(self.tick, link);
+        // Evict the least recently used nodes beyond the capacity.
+        while self.lru.len() > capacity {
+            let (&oldest, &victim) = self.order.iter().next().unwrap();
+            self.order.remove(&oldest);
+            self.lru.remove(&victim);
+            self.stats.evictions += 1;
+        }
+    }
+
+    fn invalidate(&mut self, link: usize) {
+        if let Some((_, last)) = self.lru.remove(&link) {
+            self.order.remove(&last);
+            self.stats.invalidations += 1;
+        }
+    }
+}
+
+/// A node store caching the most recently read nodes of an underlying store in memory,
+/// e.g., the hot top-of-tree nodes read by every proof generation on a disk-backed tree.
+///
+/// Nodes in the top levels of the tree can be pinned so that they are never evicted.
+/// Writes go through to the underlying store and invalidate the stale cached nodes.
+pub struct CachedNodeStore<P, S: NodeStore<P>> {
+    store: S,
+    capacity: usize,
+    state: Mutex<CacheState<P>>,
+    phantom: PhantomData<P>,
+}
+
+impl<P: Clone + Default + Mergeable + Paddable, S: NodeStore<P>> CachedNodeStore<P, S> {
+    /// The constructor, caching at most ```capacity``` unpinned nodes.
+    pub fn new(store: S, capacity: usize) -> CachedNodeStore<P, S> {
+        CachedNodeStore {
+            store,
+            capacity,
+            state: Mutex::new(CacheState {
+                pinned: HashMap::new(),
+                lru: HashMap::new(),
+                order: BTreeMap::new(),
+                tick: 0,
+                metadata: None,
+                stats: CacheStats::default(),
+            }),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the underlying store.
+    pub fn get_store(&self) -> &S {
+        &self.store
+    }
+
+    /// Returns the underlying store, dropping the cache.
+    pub fn into_store(self) -> S {
+        self.store
+    }
+
+    /// Returns the capacity of the LRU cache, excluding the pinned nodes.
+    pub fn get_capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the hit/miss statistics since the creation or the last reset.
+    pub fn get_stats(&self) -> CacheStats {
+        self.state.lock().unwrap().stats
+    }
+
+    /// Reset the hit/miss statistics.
+    pub fn reset_stats(&self) {
+        self.state.lock().unwrap().stats = CacheStats::default();
+    }
+
+    /// Returns the number of pinned nodes.
+    pub fn get_pinned_num(&self) -> usize {
+        self.state.lock().unwrap().pinned.len()
+    }
+
+    /// Returns the number of nodes in the LRU cache.
+    pub fn get_cached_num(&self) -> usize {
+        self.state.lock().unwrap().lru.len()
+    }
+
+    /// Pin all nodes in the top ```levels``` levels of the stored tree (the root being at level 0),
+    /// replacing the previously pinned nodes.
+    ///
+    /// Nodes created by later writes are not pinned, call this method again after rebuilding the tree.
+    pub fn pin_top_levels(&self, levels: usize) -> Result<(), StorageError> {
+        let mut pinned = HashMap::new();
+        if levels > 0 {
+            if let Some(metadata) = self.store.get_metadata()? {
+                let mut queue = VecDeque::new();
+                queue.push_back((metadata.root, 0usize));
+                while let Some((link, level)) = queue.pop_front() {
+                    let node = self
+                        .store
+                        .get_node(link)?
+                        .ok_or(StorageError::NodeMissing(link))?;
+                    if level + 1 < levels {
+                        if let Some(x) = node.get_lch() {
+                            queue.push_back((x, level + 1));
+                        }
+                        if let Some(x) = node.get_rch() {
+                            queue.push_back((x, level + 1));
+                        }
+                    }
+                    pinned.insert(link, node);
+                }
+            }
+        }
+
+        let mut state = self.state.lock().unwrap();
+        for link in pinned.keys() {
+            state.invalidate(*link);
+        }
+        state.pinned = pinned;
+        Ok(())
+    }
+
+    /// Drop all cached and pinned nodes.
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.pinned.clear();
+        state.lru.clear();
+        state.order.clear();
+        state.metadata = None;
+    }
+}
+
+impl<P: Clone + Default + Mergeable + Paddable, S: NodeStore<P>> NodeStore<P>
+    for CachedNodeStore<P, S>
+{
+    fn get_node(&self, link: usize) -> Result<Option<TreeNode<P>>, StorageError> {
+        {
+            let mut state = self.state.lock().unwrap();
+            if let Some(node) = state.pinned.get(&link).cloned() {
+                state.stats.hits += 1;
+                return Ok(Some(node));
+            }
+            if let Some(node) = state.touch(link) {
+                state.stats.hits += 1;
+                return Ok(Some(node));
+            }
+            state.stats.misses += 1;
+        }
+
+        // Read from the underlying store without holding the lock.
+        let node = self.store.get_node(link)?;
+        if let Some(node) = &node {
+            self.state
+                .lock()
+                .unwrap()
+                .insert(link, node.clone(), self.capacity);
+        }
+        Ok(node)
+    }
+
+    fn get_metadata(&self) -> Result<Option<TreeMetadata>, StorageError> {
+        if let Some(metadata) = self.state.lock().unwrap().metadata {
+            return Ok(Some(metadata));
+        }
+        let metadata = self.store.get_metadata()?;
+        self.state.lock().unwrap().metadata = metadata;
+        Ok(metadata)
+    }
+
+    /// Write the batch through to the underlying store,
+    /// then refresh the pinned nodes and invalidate the cached nodes that are overwritten.
+    fn write_batch(&mut self, batch: NodeBatch<P>) -> Result<(), StorageError> {
+        let updates: Vec<(usize, TreeNode<P>)> = batch.get_nodes().to_vec();
+        let metadata = *batch.get_metadata();
+        let res = self.store.write_batch(batch);
+
+        let mut state = self.state.lock().unwrap();
+        if res.is_err() {
+            // The state of the underlying store is unknown, drop everything.
+            state.pinned.clear();
+            state.lru.clear();
+            state.order.clear();
+            state.metadata = None;
+            return res;
+        }
+        for (link, node) in updates {
+            if let Some(pinned) = state.pinned.get_mut(&link) {
+                *pinned = node;
+            } else {
+                state.invalidate(link);
+            }
+        }
+        state.metadata = Some(metadata);
+        Ok(())
+    }
+}
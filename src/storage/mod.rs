@@ -0,0 +1,807 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! This module provides a storage abstraction for persisting the nodes of an SMT,
+//! together with an in-memory reference backend and optional disk-backed backends.
+//!
+//! Nodes are stored by their reference in the node arena of
+//! [SparseMerkleTree](../tree/struct.SparseMerkleTree.html),
+//! and the tree metadata (height, root reference and number of nodes) is stored alongside them.
+
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::sync::OnceLock;
+
+use crate::pad_secret::Secret;
+use crate::{
+    arena::NodeArena,
+    error::{DecodingError, SmtError, StorageError, TreeError},
+    index::TreeIndex,
+    proof::MerkleProof,
+    traits::{Mergeable, Paddable, PaddingDomain, ProofExtractable, Serializable},
+    tree::{NodeType, SmtSnapshot, SparseMerkleTree, TreeNode},
+    utils::{bytes_to_count, bytes_to_u64, read_unit, u64_to_bytes, write_unit},
+};
+
+mod cache;
+pub use cache::{CacheStats, CachedNodeStore};
+
+#[cfg(feature = "rocksdb")]
+mod rocks_store;
+#[cfg(feature = "rocksdb")]
+pub use rocks_store::RocksNodeStore;
+#[cfg(feature = "sled")]
+mod sled_store;
+#[cfg(feature = "sled")]
+pub use sled_store::SledNodeStore;
+
+/// The number of bytes for encoding a reference to a tree node.
+const LINK_BYTE_NUM: usize = 8;
+/// The number of bytes for encoding the height of the tree in the metadata.
+const HEIGHT_BYTE_NUM: usize = 2;
+/// The key of the tree metadata in key-value backends.
+#[cfg(any(feature = "rocksdb", feature = "sled"))]
+const METADATA_KEY: &[u8] = b"metadata";
+
+/// The metadata of a stored SMT.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TreeMetadata {
+    /// The height of the SMT.
+    pub height: usize,
+    /// The reference to the root node.
+    pub root: usize,
+    /// The number of nodes in the node arena, nodes with greater references are ignored.
+    pub nodes_num: usize,
+}
+
+impl Serializable for TreeMetadata {
+    /// Encode the metadata in the format: ```height || root || nodes_num```.
+    fn serialize(&self) -> Vec<u8> {
+        let mut bytes = u64_to_bytes(self.height as u64, HEIGHT_BYTE_NUM);
+        bytes.append(&mut u64_to_bytes(self.root as u64, LINK_BYTE_NUM));
+        bytes.append(&mut u64_to_bytes(self.nodes_num as u64, LINK_BYTE_NUM));
+        bytes
+    }
+
+    fn deserialize_as_a_unit(bytes: &[u8], begin: &mut usize) -> Result<Self, DecodingError> {
+        let height = bytes_to_count(bytes, HEIGHT_BYTE_NUM, begin)?;
+        let root = bytes_to_count(bytes, LINK_BYTE_NUM, begin)?;
+        let nodes_num = bytes_to_count(bytes, LINK_BYTE_NUM, begin)?;
+        Ok(TreeMetadata {
+            height,
+            root,
+            nodes_num,
+        })
+    }
+}
+
+/// Encode an optional reference, with ```0``` for ```None``` and ```link + 1``` otherwise.
+fn link_to_bytes(link: Option<usize>) -> Vec<u8> {
+    u64_to_bytes(link.map_or(0, |x| x + 1) as u64, LINK_BYTE_NUM)
+}
+
+/// Decode an optional reference encoded by ```link_to_bytes```.
+fn bytes_to_link(bytes: &[u8], begin: &mut usize) -> Result<Option<usize>, DecodingError> {
+    let link = bytes_to_count(bytes, LINK_BYTE_NUM, begin)?;
+    Ok(link.checked_sub(1))
+}
+
+/// Encode a node type as one byte.
+fn node_type_to_byte(node_type: &NodeType) -> u8 {
+    match node_type {
+        NodeType::Internal => 0u8,
+        NodeType::Padding => 1u8,
+        NodeType::Leaf => 2u8,
+    }
+}
+
+/// Decode a node type encoded by ```node_type_to_byte```.
+fn byte_to_node_type(byte: u8) -> Result<NodeType, DecodingError> {
+    match byte {
+        0 => Ok(NodeType::Internal),
+        1 => Ok(NodeType::Padding),
+        2 => Ok(NodeType::Leaf),
+        x => Err(DecodingError::ValueDecodingError {
+            msg: format!("Unknown node type {}", x),
+        }),
+    }
+}
+
+impl<P: Clone + Default + Mergeable + Paddable + Serializable> Serializable for TreeNode<P> {
+    /// Encode a tree node in the format: ```node_type || parent || lch || rch || value```.
+    fn serialize(&self) -> Vec<u8> {
+        let mut bytes = vec![node_type_to_byte(self.get_node_type())];
+        bytes.append(&mut link_to_bytes(self.get_parent()));
+        bytes.append(&mut link_to_bytes(self.get_lch()));
+        bytes.append(&mut link_to_bytes(self.get_rch()));
+        bytes.append(&mut self.get_value().serialize());
+        bytes
+    }
+
+    fn deserialize_as_a_unit(bytes: &[u8], begin: &mut usize) -> Result<Self, DecodingError> {
+        if bytes.len() - *begin < 1 {
+            return Err(DecodingError::BytesNotEnough);
+        }
+        let node_type = byte_to_node_type(bytes[*begin])?;
+        *begin += 1;
+
+        let mut node = TreeNode::new(node_type);
+        if let Some(x) = bytes_to_link(bytes, begin)? {
+            node.set_parent(x);
+        }
+        if let Some(x) = bytes_to_link(bytes, begin)? {
+            node.set_lch(x);
+        }
+        if let Some(x) = bytes_to_link(bytes, begin)? {
+            node.set_rch(x);
+        }
+        node.set_value(P::deserialize_as_a_unit(bytes, begin)?);
+        Ok(node)
+    }
+}
+
+/// Check that the links between the input nodes form a tree with the input root:
+/// every link is a node, the children of a node are distinct and link back to it as their parent,
+/// the parent of a node links to it as a child, and only the root has no parent.
+fn check_links<P: Clone + Default + Mergeable + Paddable>(
+    root: usize,
+    nodes: &[TreeNode<P>],
+) -> Result<(), DecodingError> {
+    let invalid = |link: usize| DecodingError::ValueDecodingError {
+        msg: format!("The links of the node {} are inconsistent", link),
+    };
+    for (link, node) in nodes.iter().enumerate() {
+        if node.get_lch().is_some() && node.get_lch() == node.get_rch() {
+            return Err(invalid(link));
+        }
+        for child in node.get_lch().iter().chain(node.get_rch().iter()) {
+            match nodes.get(*child) {
+                Some(x) if x.get_parent() == Some(link) => (),
+                _ => return Err(invalid(link)),
+            }
+        }
+        match node.get_parent() {
+            None if link == root => (),
+            Some(parent) if link != root => match nodes.get(parent) {
+                Some(x) if x.get_lch() == Some(link) || x.get_rch() == Some(link) => (),
+                _ => return Err(invalid(link)),
+            },
+            _ => return Err(invalid(link)),
+        }
+    }
+    Ok(())
+}
+
+/// Returns the key of a node in key-value backends, the big-endian reference,
+/// so that the key order is the same as the reference order.
+#[cfg(any(feature = "rocksdb", feature = "sled"))]
+fn node_key(link: usize) -> [u8; 8] {
+    (link as u64).to_be_bytes()
+}
+
+/// Decode the reference from a node key in key-value backends.
+#[cfg(any(feature = "rocksdb", feature = "sled"))]
+fn link_from_node_key(bytes: &[u8]) -> Result<usize, StorageError> {
+    let mut key = [0u8; 8];
+    if bytes.len() != key.len() {
+        return Err(StorageError::BackendError {
+            msg: "Invalid node key length".to_owned(),
+        });
+    }
+    key.copy_from_slice(bytes);
+    Ok(u64::from_be_bytes(key) as usize)
+}
+
+/// A set of node writes applied to a [NodeStore] atomically, together with the new tree metadata.
+#[derive(Debug, Clone, Default)]
+pub struct NodeBatch<P> {
+    nodes: Vec<(usize, TreeNode<P>)>,
+    metadata: TreeMetadata,
+}
+
+impl<P> NodeBatch<P> {
+    /// The constructor.
+    pub fn new(metadata: TreeMetadata) -> NodeBatch<P> {
+        NodeBatch {
+            nodes: Vec::new(),
+            metadata,
+        }
+    }
+
+    /// Add a node to be written under the input reference.
+    pub fn put(&mut self, link: usize, node: TreeNode<P>) {
+        self.nodes.push((link, node));
+    }
+
+    /// Returns the reference-node pairs to be written.
+    pub fn get_nodes(&self) -> &[(usize, TreeNode<P>)] {
+        &self.nodes
+    }
+
+    /// Returns the tree metadata to be written.
+    pub fn get_metadata(&self) -> &TreeMetadata {
+        &self.metadata
+    }
+
+    /// Returns the number of nodes in the batch.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns whether the batch contains no node.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+/// Trait for a storage backend of SMT nodes.
+pub trait NodeStore<P> {
+    /// Returns the node stored under the input reference.
+    ///
+    /// If the node doesn't exist, return ```None```.
+    fn get_node(&self, link: usize) -> Result<Option<TreeNode<P>>, StorageError>;
+
+    /// Returns the metadata of the stored tree.
+    ///
+    /// If no tree has been stored yet, return ```None```.
+    fn get_metadata(&self) -> Result<Option<TreeMetadata>, StorageError>;
+
+    /// Write all nodes in the batch and the new metadata.
+    ///
+    /// Either all writes in the batch are applied or none of them is.
+    fn write_batch(&mut self, batch: NodeBatch<P>) -> Result<(), StorageError>;
+}
+
+/// A node store keeping all nodes in memory, mainly for testing and as a reference backend.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryNodeStore<P> {
+    nodes: HashMap<usize, TreeNode<P>>,
+    metadata: Option<TreeMetadata>,
+}
+
+impl<P> MemoryNodeStore<P> {
+    /// The constructor.
+    pub fn new() -> MemoryNodeStore<P> {
+        MemoryNodeStore {
+            nodes: HashMap::new(),
+            metadata: None,
+        }
+    }
+
+    /// Returns the number of stored nodes, including the ones no longer referenced by the tree.
+    pub fn get_stored_num(&self) -> usize {
+        self.nodes.len()
+    }
+}
+
+impl<P: Clone> NodeStore<P> for MemoryNodeStore<P> {
+    fn get_node(&self, link: usize) -> Result<Option<TreeNode<P>>, StorageError> {
+        Ok(self.nodes.get(&link).cloned())
+    }
+
+    fn get_metadata(&self) -> Result<Option<TreeMetadata>, StorageError> {
+        Ok(self.metadata)
+    }
+
+    fn write_batch(&mut self, batch: NodeBatch<P>) -> Result<(), StorageError> {
+        for (link, node) in batch.nodes {
+            self.nodes.insert(link, node);
+        }
+        self.metadata = Some(batch.metadata);
+        Ok(())
+    }
+}
+
+/// Returns the metadata of the stored tree,
+/// or [StorageError::MetadataMissing](../error/enum.StorageError.html#variant.MetadataMissing)
+/// if there is no tree in the storage.
+fn get_metadata_or_err<P, S: NodeStore<P> + ?Sized>(
+    store: &S,
+) -> Result<TreeMetadata, StorageError> {
+    store.get_metadata()?.ok_or(StorageError::MetadataMissing)
+}
+
+/// Returns the node of the input reference,
+/// or [StorageError::NodeMissing](../error/enum.StorageError.html#variant.NodeMissing)
+/// if it doesn't exist in the storage.
+fn get_node_or_err<P, S: NodeStore<P> + ?Sized>(
+    store: &S,
+    link: usize,
+) -> Result<TreeNode<P>, StorageError> {
+    store.get_node(link)?.ok_or(StorageError::NodeMissing(link))
+}
+
+/// An iterator going through the nodes of a stored tree in the BFS order,
+/// yielding the tree index of each node together with the node.
+pub struct StoreBfsIter<'a, P, S: NodeStore<P> + ?Sized> {
+    store: &'a S,
+    // The index-reference pairs of nodes to be visited.
+    queue: std::collections::VecDeque<(TreeIndex, usize)>,
+    phantom: std::marker::PhantomData<P>,
+}
+
+impl<'a, P, S: NodeStore<P> + ?Sized> StoreBfsIter<'a, P, S> {
+    /// Returns an iterator starting from the root of the tree in the input store.
+    pub fn new(store: &'a S) -> Result<StoreBfsIter<'a, P, S>, StorageError> {
+        let metadata = get_metadata_or_err(store)?;
+        let mut queue = std::collections::VecDeque::new();
+        queue.push_back((TreeIndex::zero(0), metadata.root));
+        Ok(StoreBfsIter {
+            store,
+            queue,
+            phantom: std::marker::PhantomData,
+        })
+    }
+}
+
+impl<'a, P: Clone + Default + Mergeable + Paddable, S: NodeStore<P> + ?Sized> Iterator
+    for StoreBfsIter<'a, P, S>
+{
+    type Item = Result<(TreeIndex, TreeNode<P>), StorageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (idx, link) = self.queue.pop_front()?;
+        let node = match get_node_or_err(self.store, link) {
+            Ok(node) => node,
+            Err(e) => {
+                // Stop the traversal at the first error.
+                self.queue.clear();
+                return Some(Err(e));
+            }
+        };
+        if let Some(x) = node.get_lch() {
+            self.queue.push_back((idx.get_lch_index(), x));
+        }
+        if let Some(x) = node.get_rch() {
+            self.queue.push_back((idx.get_rch_index(), x));
+        }
+        Some(Ok((idx, node)))
+    }
+}
+
+/// Generate the Merkle proof of a single leaf by reading the nodes along the path from the storage,
+/// without loading the whole tree into memory.
+///
+/// If the leaf doesn't exist in the stored tree, return ```Ok(None)```.
+///
+/// If the height of the input index doesn't match with that of the stored tree,
+/// return [StorageError::BackendError](../error/enum.StorageError.html#variant.BackendError).
+pub fn get_merkle_proof_from_store<P, S>(
+    store: &S,
+    idx: &TreeIndex,
+) -> Result<Option<MerkleProof<P>>, StorageError>
+where
+    P: Clone + Default + Mergeable + Paddable + ProofExtractable,
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+    S: NodeStore<P> + ?Sized,
+{
+    let metadata = get_metadata_or_err(store)?;
+    if idx.get_height() != metadata.height {
+        return Err(StorageError::BackendError {
+            msg: TreeError::HeightNotMatch.to_string(),
+        });
+    }
+
+    let mut proof = MerkleProof::<P>::new(*idx);
+    let mut node = get_node_or_err(store, metadata.root)?;
+    for i in 0..metadata.height {
+        let (next, sibling) = if idx.get_bit(i) == 0 {
+            (node.get_lch(), node.get_rch())
+        } else {
+            (node.get_rch(), node.get_lch())
+        };
+        match (next, sibling) {
+            (Some(next), Some(sibling)) => {
+                proof.add_sibling(
+                    get_node_or_err(store, sibling)?
+                        .get_value()
+                        .get_proof_node(),
+                );
+                node = get_node_or_err(store, next)?;
+            }
+            _ => return Ok(None),
+        }
+    }
+    if *node.get_node_type() != NodeType::Leaf {
+        return Ok(None);
+    }
+    Ok(Some(proof))
+}
+
+impl<P: Clone + Default + Mergeable + Paddable + ProofExtractable> SparseMerkleTree<P>
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// Returns the metadata describing the current state of the tree.
+    pub fn get_metadata(&self) -> TreeMetadata {
+        TreeMetadata {
+            height: self.height,
+            root: self.root,
+            nodes_num: self.nodes.len(),
+        }
+    }
+
+    /// Write all nodes of the tree into the input store in one batch.
+    pub fn persist<S: NodeStore<P> + ?Sized>(&self, store: &mut S) -> Result<(), StorageError> {
+        let mut batch = NodeBatch::new(self.get_metadata());
+        for (link, node) in self.nodes.iter().enumerate() {
+            batch.put(link, node.clone());
+        }
+        store.write_batch(batch)
+    }
+
+    /// Load the tree stored in the input store.
+    ///
+    /// If there is no tree in the storage,
+    /// return [StorageError::MetadataMissing](../error/enum.StorageError.html#variant.MetadataMissing).
+    ///
+    /// If some node of the tree doesn't exist in the storage,
+    /// return [StorageError::NodeMissing](../error/enum.StorageError.html#variant.NodeMissing).
+    ///
+    /// If the links between the stored nodes don't form a tree,
+    /// return [StorageError::DecodingError](../error/enum.StorageError.html#variant.DecodingError)
+    /// wrapping [DecodingError::ValueDecodingError](../error/enum.DecodingError.html#variant.ValueDecodingError).
+    pub fn load<S: NodeStore<P> + ?Sized>(store: &S) -> Result<SparseMerkleTree<P>, StorageError> {
+        let metadata = get_metadata_or_err(store)?;
+        if metadata.height > crate::index::MAX_HEIGHT {
+            return Err(StorageError::DecodingError(DecodingError::ExceedMaxHeight));
+        }
+        if metadata.root >= metadata.nodes_num {
+            return Err(StorageError::NodeMissing(metadata.root));
+        }
+        // Read the nodes one by one, not trusting the stored number of nodes for allocation.
+        let mut nodes = Vec::new();
+        for link in 0..metadata.nodes_num {
+            nodes.push(get_node_or_err(store, link)?);
+        }
+        check_links(metadata.root, &nodes)?;
+        Ok(SparseMerkleTree {
+            height: metadata.height,
+            root: metadata.root,
+            nodes: NodeArena::from(nodes),
+            metrics: Default::default(),
+            dirty: Vec::new(),
+            top: OnceLock::new(),
+            leaf_filter: None,
+            padding_domain: PaddingDomain::default(),
+        })
+    }
+
+    /// Build SMT from the input list of sorted index-value pairs as [build](#method.build) does,
+    /// and write all nodes into the input store in one batch.
+    ///
+    /// Panics if the input list is not valid.
+    pub fn build_and_persist<S: NodeStore<P> + ?Sized>(
+        &mut self,
+        list: &[(TreeIndex, P)],
+        secret: &Secret,
+        store: &mut S,
+    ) -> Result<(), StorageError> {
+        self.build(list, secret);
+        self.persist(store)
+    }
+
+    /// Update the tree as [update](#method.update) does,
+    /// and write the nodes changed along the path in one batch into the input store.
+    ///
+    /// Panics if the height of the input index doesn't match with that of the tree.
+    pub fn update_and_persist<S: NodeStore<P> + ?Sized>(
+        &mut self,
+        key: &TreeIndex,
+        value: P,
+        secret: &Secret,
+        store: &mut S,
+    ) -> Result<(), StorageError> {
+        self.update(key, value, secret);
+
+        // The changed nodes are the nodes on the path from the leaf to the root and their children,
+        // which also include all newly added nodes.
+        let mut batch = NodeBatch::new(self.get_metadata());
+        let (leaf, _) = self.get_closest_ancestor_ref_index(key);
+        batch.put(leaf, self.nodes[leaf].clone());
+        let mut node = leaf;
+        while let Some(parent) = self.nodes[node].get_parent() {
+            let sibling = if self.nodes[parent].get_lch() == Some(node) {
+                self.nodes[parent].get_rch()
+            } else {
+                self.nodes[parent].get_lch()
+            };
+            if let Some(sibling) = sibling {
+                batch.put(sibling, self.nodes[sibling].clone());
+            }
+            batch.put(parent, self.nodes[parent].clone());
+            node = parent;
+        }
+        store.write_batch(batch)
+    }
+}
+
+impl<P: Clone + Default + Mergeable + Paddable + ProofExtractable + Serializable>
+    SparseMerkleTree<P>
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// Write a snapshot of the tree into the input writer in the format:
+    /// ```metadata || node_0 || ... || node_{nodes_num - 1}```,
+    /// with the nodes in the order of their references, encoded as in the storage backends,
+    /// and the metadata and each node prefixed with its length as by [write_unit](../utils/fn.write_unit.html).
+    ///
+    /// The nodes are written one by one without building the whole encoding in memory.
+    /// If the writer fails, return the I/O error, with the snapshot possibly partially written.
+    pub fn serialize_into<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        write_unit(w, &self.get_metadata().serialize())?;
+        for node in self.nodes.iter() {
+            write_unit(w, &node.serialize())?;
+        }
+        Ok(())
+    }
+
+    /// Read a snapshot of a tree written by [serialize_into](#method.serialize_into) from the reader,
+    /// reading exactly the encoded bytes.
+    ///
+    /// If the height exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html),
+    /// return [DecodingError::ExceedMaxHeight](../error/enum.DecodingError.html#variant.ExceedMaxHeight).
+    /// If the root isn't one of the nodes, or if the links between the nodes don't form a tree,
+    /// i.e., a link isn't a node, a child doesn't link back to its parent,
+    /// a parent doesn't link to its child, or the root has a parent,
+    /// return [DecodingError::ValueDecodingError](../error/enum.DecodingError.html#variant.ValueDecodingError).
+    /// If the reader ends in the middle of the snapshot,
+    /// return [DecodingError::BytesNotEnough](../error/enum.DecodingError.html#variant.BytesNotEnough).
+    pub fn deserialize_from<R: Read + ?Sized>(
+        r: &mut R,
+    ) -> Result<SparseMerkleTree<P>, DecodingError> {
+        let metadata: TreeMetadata = read_unit(r, TreeMetadata::deserialize_as_a_unit)?;
+        if metadata.height > crate::index::MAX_HEIGHT {
+            return Err(DecodingError::ExceedMaxHeight);
+        }
+        if metadata.root >= metadata.nodes_num {
+            return Err(DecodingError::ValueDecodingError {
+                msg: format!("The root {} is not a node", metadata.root),
+            });
+        }
+        // Read the nodes one by one, not trusting the number of nodes for allocation.
+        let mut nodes = Vec::new();
+        for _i in 0..metadata.nodes_num {
+            nodes.push(read_unit(r, TreeNode::<P>::deserialize_as_a_unit)?);
+        }
+        check_links(metadata.root, &nodes)?;
+        Ok(SparseMerkleTree {
+            height: metadata.height,
+            root: metadata.root,
+            nodes: NodeArena::from(nodes),
+            metrics: Default::default(),
+            dirty: Vec::new(),
+            top: OnceLock::new(),
+            leaf_filter: None,
+            padding_domain: PaddingDomain::default(),
+        })
+    }
+
+    /// Encode the nodes changed since the input snapshot of an earlier version of the tree,
+    /// so that a replica holding that version catches up with [apply_delta](#method.apply_delta)
+    /// without transferring the whole tree.
+    ///
+    /// Walking both versions from the root, a node is changed if it is new,
+    /// or if its type or proof node differs from the node at the same tree index in the snapshot.
+    /// The subtree of an unchanged node is skipped, as its proof node commits to the subtree,
+    /// so the cost is linear in the number of changed nodes.
+    ///
+    /// The delta is encoded in the format: ```base_root || target_root || entries_num || entry_0 || ...```,
+    /// where ```base_root``` is the root of the snapshot, ```target_root``` is the root of the tree,
+    /// and the entries of the changed nodes, ```tree_index || node_type || value```, are in the pre-order of the tree.
+    ///
+    /// Panics if the height of the snapshot doesn't match with that of the tree.
+    pub fn export_delta(&self, since: &SmtSnapshot<P>) -> Vec<u8> {
+        let base = since.get_tree();
+        if base.height != self.height {
+            panic!("{}", TreeError::HeightNotMatch);
+        }
+        let mut entries: Vec<u8> = Vec::new();
+        let mut entries_num: u64 = 0;
+        // The reference to a node of the tree, the reference to the node at the same index in the snapshot,
+        // and the tree index.
+        let mut stack: Vec<(usize, Option<usize>, TreeIndex)> =
+            vec![(self.root, Some(base.root), TreeIndex::zero(0))];
+        while let Some((link, base_link, idx)) = stack.pop() {
+            let node = &self.nodes[link];
+            let base_node = base_link.map(|x| &base.nodes[x]);
+            if let Some(x) = base_node {
+                if x.get_node_type() == node.get_node_type()
+                    && x.get_value().get_proof_node() == node.get_value().get_proof_node()
+                {
+                    continue;
+                }
+            }
+            entries.append(&mut TreeIndex::serialize(&[idx]));
+            entries.push(node_type_to_byte(node.get_node_type()));
+            entries.append(&mut node.get_value().serialize());
+            entries_num += 1;
+            if let (Some(lch), Some(rch)) = (node.get_lch(), node.get_rch()) {
+                let base_children = match base_node {
+                    Some(x) if *x.get_node_type() == NodeType::Internal => {
+                        (x.get_lch(), x.get_rch())
+                    }
+                    _ => (None, None),
+                };
+                // The left child is visited first.
+                stack.push((rch, base_children.1, idx.get_rch_index()));
+                stack.push((lch, base_children.0, idx.get_lch_index()));
+            }
+        }
+        let mut bytes = base.get_root().serialize();
+        bytes.append(&mut self.get_root().serialize());
+        bytes.append(&mut u64_to_bytes(entries_num, LINK_BYTE_NUM));
+        bytes.append(&mut entries);
+        bytes
+    }
+
+    /// Apply a delta exported by [export_delta](#method.export_delta),
+    /// updating the tree from the version of the snapshot the delta was exported against
+    /// to the version it was exported from.
+    ///
+    /// The whole delta is decoded and checked before the tree is changed,
+    /// so the tree is left unchanged if an error is returned.
+    /// The changed internal nodes are re-merged from their children,
+    /// and the root of the updated tree is checked against the target root of the delta.
+    ///
+    /// If the root of the tree isn't the root of the base version of the delta,
+    /// return [TreeError::BaseNotMatch](../error/enum.TreeError.html#variant.BaseNotMatch).
+    /// If a changed internal node isn't the merge of its children, or the root isn't the target root,
+    /// return [TreeError::TargetNotMatch](../error/enum.TreeError.html#variant.TargetNotMatch).
+    /// If the delta can't be decoded, or its entries don't form a valid update of the tree,
+    /// return [DecodingError](../error/enum.DecodingError.html).
+    pub fn apply_delta(&mut self, bytes: &[u8]) -> Result<(), SmtError> {
+        let mut begin = 0;
+        let base_root = P::ProofNode::deserialize_as_a_unit(bytes, &mut begin)?;
+        if base_root != self.get_root() {
+            return Err(TreeError::BaseNotMatch.into());
+        }
+        let target_root = P::ProofNode::deserialize_as_a_unit(bytes, &mut begin)?;
+        let entries_num = bytes_to_u64(bytes, LINK_BYTE_NUM, &mut begin)?;
+
+        // Decode the entries, locating the node of each entry at the parent of the entry,
+        // with the reference to the existing node at the same tree index if any.
+        let invalid = |msg: &str| -> SmtError {
+            DecodingError::ValueDecodingError {
+                msg: msg.to_owned(),
+            }
+            .into()
+        };
+        let mut entries: Vec<(TreeIndex, NodeType, P, Option<usize>)> = Vec::new();
+        let mut positions: HashMap<TreeIndex, usize> = HashMap::new();
+        for _i in 0..entries_num {
+            let idx = TreeIndex::deserialize_as_a_unit(bytes, 1, &mut begin)?[0];
+            if begin >= bytes.len() {
+                return Err(DecodingError::BytesNotEnough.into());
+            }
+            let node_type = byte_to_node_type(bytes[begin])?;
+            begin += 1;
+            let value = P::deserialize_as_a_unit(bytes, &mut begin)?;
+            let height = idx.get_height();
+            if height > self.height
+                || (node_type == NodeType::Leaf && height != self.height)
+                || (node_type == NodeType::Internal && height == self.height)
+            {
+                return Err(invalid("The node type doesn't match with the tree index."));
+            }
+            let link = if height == 0 {
+                if !entries.is_empty() {
+                    return Err(invalid("The root is not the first entry."));
+                }
+                Some(self.root)
+            } else {
+                // The parent of a changed node is changed as well, thus comes earlier in the pre-order.
+                let parent = match positions.get(&idx.get_parent_index()) {
+                    Some(&x) if entries[x].1 == NodeType::Internal => &entries[x],
+                    _ => return Err(invalid("The parent of an entry is not an internal entry.")),
+                };
+                match parent.3 {
+                    Some(x) if *self.nodes[x].get_node_type() == NodeType::Internal => {
+                        if idx.get_last_bit() == 0 {
+                            self.nodes[x].get_lch()
+                        } else {
+                            self.nodes[x].get_rch()
+                        }
+                    }
+                    _ => None,
+                }
+            };
+            if positions.insert(idx, entries.len()).is_some() {
+                return Err(invalid("The tree index of an entry is duplicated."));
+            }
+            entries.push((idx, node_type, value, link));
+        }
+        if begin != bytes.len() {
+            return Err(invalid("There are redundant bytes after the delta."));
+        }
+        // A node that newly becomes internal needs both children in the delta.
+        for (idx, node_type, _, link) in entries.iter() {
+            let was_internal =
+                link.is_some_and(|x| *self.nodes[x].get_node_type() == NodeType::Internal);
+            if *node_type == NodeType::Internal
+                && !was_internal
+                && !(positions.contains_key(&idx.get_lch_index())
+                    && positions.contains_key(&idx.get_rch_index()))
+            {
+                return Err(invalid("A new internal node misses a child."));
+            }
+        }
+        // Re-merge the changed internal nodes bottom-up, i.e., in the reverse pre-order,
+        // with the children in the delta or the existing children otherwise.
+        for (idx, node_type, value, link) in entries.iter().rev() {
+            if *node_type != NodeType::Internal {
+                continue;
+            }
+            let child = |child_idx: TreeIndex, existing: Option<usize>| -> &P {
+                match positions.get(&child_idx) {
+                    Some(&x) => &entries[x].2,
+                    // A node that was internal has both children, as checked above.
+                    None => self.nodes[existing.unwrap()].get_value(),
+                }
+            };
+            let existing = link.map(|x| &self.nodes[x]);
+            let lch = child(idx.get_lch_index(), existing.and_then(|x| x.get_lch()));
+            let rch = child(idx.get_rch_index(), existing.and_then(|x| x.get_rch()));
+            if Mergeable::merge_with_index(lch, rch, idx).get_proof_node() != value.get_proof_node()
+            {
+                return Err(TreeError::TargetNotMatch.into());
+            }
+        }
+        let root = match entries.first() {
+            Some((_, _, value, _)) => value.get_proof_node(),
+            None => self.get_root(),
+        };
+        if root != target_root {
+            return Err(TreeError::TargetNotMatch.into());
+        }
+
+        // Apply the entries top-down, adding the new nodes.
+        let mut links: Vec<usize> = Vec::with_capacity(entries.len());
+        for (idx, node_type, value, link) in entries {
+            let parent = if idx.get_height() == 0 {
+                None
+            } else {
+                Some(links[positions[&idx.get_parent_index()]])
+            };
+            let nodes = self.links_mut();
+            let existing = link.is_some();
+            let link = match link {
+                Some(x) => x,
+                None => {
+                    nodes.push(TreeNode::new(node_type.clone()));
+                    let x = nodes.len() - 1;
+                    // A new node is never the root, as the root always exists.
+                    let parent = parent.unwrap();
+                    if idx.get_last_bit() == 0 {
+                        nodes[parent].set_lch(x);
+                    } else {
+                        nodes[parent].set_rch(x);
+                    }
+                    x
+                }
+            };
+            if existing
+                && node_type == NodeType::Internal
+                && *nodes[link].get_node_type() == NodeType::Internal
+            {
+                nodes[link].set_value(value);
+            } else {
+                // Replace the node, dropping the children of a node that is no longer internal.
+                let is_leaf = node_type == NodeType::Leaf;
+                let mut node = TreeNode::new(node_type);
+                node.set_value(value);
+                if let Some(x) = parent {
+                    node.set_parent(x);
+                }
+                nodes[link] = node;
+                if is_leaf {
+                    self.insert_into_leaf_filter(&idx);
+                }
+            }
+            links.push(link);
+        }
+        Ok(())
+    }
+}
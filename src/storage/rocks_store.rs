@@ -0,0 +1,131 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! A RocksDB-backed node store, enabled by the ```rocksdb``` feature.
+
+use std::marker::PhantomData;
+use std::path::Path;
+
+use rocksdb::{ColumnFamily, ColumnFamilyDescriptor, IteratorMode, Options, WriteBatch, DB};
+
+use super::{link_from_node_key, node_key, NodeBatch, NodeStore, TreeMetadata, METADATA_KEY};
+use crate::{
+    error::StorageError,
+    traits::{Mergeable, Paddable, Serializable},
+    tree::TreeNode,
+};
+
+/// The column family storing tree nodes, keyed by the big-endian node reference.
+const NODES_CF: &str = "nodes";
+/// The column family storing the tree metadata.
+const META_CF: &str = "meta";
+
+fn backend_error(e: rocksdb::Error) -> StorageError {
+    StorageError::BackendError {
+        msg: e.into_string(),
+    }
+}
+
+/// A node store persisting SMT nodes in RocksDB,
+/// with one column family for nodes and one for the tree metadata.
+pub struct RocksNodeStore<P> {
+    db: DB,
+    phantom: PhantomData<P>,
+}
+
+impl<P> RocksNodeStore<P> {
+    /// Open the database at the input path, creating it and the column families if missing.
+    pub fn open<T: AsRef<Path>>(path: T) -> Result<RocksNodeStore<P>, StorageError> {
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+        RocksNodeStore::open_with_options(path, opts)
+    }
+
+    /// Open the database at the input path with customized RocksDB options.
+    pub fn open_with_options<T: AsRef<Path>>(
+        path: T,
+        opts: Options,
+    ) -> Result<RocksNodeStore<P>, StorageError> {
+        let cfs = vec![
+            ColumnFamilyDescriptor::new(NODES_CF, Options::default()),
+            ColumnFamilyDescriptor::new(META_CF, Options::default()),
+        ];
+        let db = DB::open_cf_descriptors(&opts, path, cfs).map_err(backend_error)?;
+        Ok(RocksNodeStore {
+            db,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Returns the underlying database.
+    pub fn get_db(&self) -> &DB {
+        &self.db
+    }
+
+    fn cf(&self, name: &str) -> Result<&ColumnFamily, StorageError> {
+        self.db
+            .cf_handle(name)
+            .ok_or_else(|| StorageError::BackendError {
+                msg: format!("Column family {} doesn't exist", name),
+            })
+    }
+}
+
+impl<P: Clone + Default + Mergeable + Paddable + Serializable> RocksNodeStore<P> {
+    /// Returns an iterator over all stored reference-node pairs in the order of references,
+    /// including nodes no longer referenced by the tree.
+    ///
+    /// For a traversal of the tree in the BFS order, see [StoreBfsIter](../struct.StoreBfsIter.html).
+    pub fn iter_nodes(
+        &self,
+    ) -> Result<impl Iterator<Item = Result<(usize, TreeNode<P>), StorageError>> + '_, StorageError>
+    {
+        let cf = self.cf(NODES_CF)?;
+        Ok(self.db.iterator_cf(cf, IteratorMode::Start).map(|item| {
+            let (key, value) = item.map_err(backend_error)?;
+            Ok((
+                link_from_node_key(&key)?,
+                TreeNode::<P>::deserialize(&value)?,
+            ))
+        }))
+    }
+}
+
+impl<P: Clone + Default + Mergeable + Paddable + Serializable> NodeStore<P> for RocksNodeStore<P> {
+    fn get_node(&self, link: usize) -> Result<Option<TreeNode<P>>, StorageError> {
+        let value = self
+            .db
+            .get_cf(self.cf(NODES_CF)?, node_key(link))
+            .map_err(backend_error)?;
+        match value {
+            None => Ok(None),
+            Some(bytes) => Ok(Some(TreeNode::<P>::deserialize(&bytes)?)),
+        }
+    }
+
+    fn get_metadata(&self) -> Result<Option<TreeMetadata>, StorageError> {
+        let value = self
+            .db
+            .get_cf(self.cf(META_CF)?, METADATA_KEY)
+            .map_err(backend_error)?;
+        match value {
+            None => Ok(None),
+            Some(bytes) => Ok(Some(TreeMetadata::deserialize(&bytes)?)),
+        }
+    }
+
+    /// Write the nodes and the metadata in a single RocksDB write batch, which is applied atomically.
+    fn write_batch(&mut self, batch: NodeBatch<P>) -> Result<(), StorageError> {
+        let nodes_cf = self.cf(NODES_CF)?;
+        let meta_cf = self.cf(META_CF)?;
+        let mut write_batch = WriteBatch::default();
+        for (link, node) in batch.get_nodes() {
+            write_batch.put_cf(nodes_cf, node_key(*link), node.serialize());
+        }
+        write_batch.put_cf(meta_cf, METADATA_KEY, batch.get_metadata().serialize());
+        self.db.write(write_batch).map_err(backend_error)
+    }
+}
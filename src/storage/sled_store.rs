@@ -0,0 +1,127 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! A sled-backed node store, enabled by the ```sled``` feature.
+
+use std::marker::PhantomData;
+use std::path::Path;
+
+use sled::transaction::{TransactionError, Transactional};
+
+use super::{link_from_node_key, node_key, NodeBatch, NodeStore, TreeMetadata, METADATA_KEY};
+use crate::{
+    error::StorageError,
+    traits::{Mergeable, Paddable, Serializable},
+    tree::TreeNode,
+};
+
+/// The sled tree storing tree nodes, keyed by the big-endian node reference.
+const NODES_TREE: &str = "nodes";
+/// The sled tree storing the tree metadata, i.e., the root pointer.
+const META_TREE: &str = "meta";
+
+fn backend_error(e: sled::Error) -> StorageError {
+    StorageError::BackendError { msg: e.to_string() }
+}
+
+/// A node store persisting SMT nodes in an embedded sled database.
+///
+/// Every batch of node writes is committed in one transaction together with the metadata,
+/// which holds the reference to the root.
+/// Thus after a crash, the visible root is always the one of the last fully applied batch,
+/// never a partially applied one.
+pub struct SledNodeStore<P> {
+    db: sled::Db,
+    nodes: sled::Tree,
+    meta: sled::Tree,
+    phantom: PhantomData<P>,
+}
+
+impl<P> SledNodeStore<P> {
+    /// Open the database at the input path, creating it if missing.
+    pub fn open<T: AsRef<Path>>(path: T) -> Result<SledNodeStore<P>, StorageError> {
+        SledNodeStore::from_db(sled::open(path).map_err(backend_error)?)
+    }
+
+    /// Use an already opened sled database, e.g., one opened with a customized ```sled::Config```.
+    pub fn from_db(db: sled::Db) -> Result<SledNodeStore<P>, StorageError> {
+        let nodes = db.open_tree(NODES_TREE).map_err(backend_error)?;
+        let meta = db.open_tree(META_TREE).map_err(backend_error)?;
+        Ok(SledNodeStore {
+            db,
+            nodes,
+            meta,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Returns the underlying database.
+    pub fn get_db(&self) -> &sled::Db {
+        &self.db
+    }
+
+    /// Flush all committed batches to disk, returning the number of bytes flushed.
+    ///
+    /// Committed batches are atomic but only become durable after being flushed,
+    /// which sled also does periodically in the background.
+    pub fn flush(&self) -> Result<usize, StorageError> {
+        self.db.flush().map_err(backend_error)
+    }
+}
+
+impl<P: Clone + Default + Mergeable + Paddable + Serializable> SledNodeStore<P> {
+    /// Returns an iterator over all stored reference-node pairs in the order of references,
+    /// including nodes no longer referenced by the tree.
+    ///
+    /// For a traversal of the tree in the BFS order, see [StoreBfsIter](../struct.StoreBfsIter.html).
+    pub fn iter_nodes(&self) -> impl Iterator<Item = Result<(usize, TreeNode<P>), StorageError>> {
+        self.nodes.iter().map(|item| {
+            let (key, value) = item.map_err(backend_error)?;
+            Ok((
+                link_from_node_key(&key)?,
+                TreeNode::<P>::deserialize(&value)?,
+            ))
+        })
+    }
+}
+
+impl<P: Clone + Default + Mergeable + Paddable + Serializable> NodeStore<P> for SledNodeStore<P> {
+    fn get_node(&self, link: usize) -> Result<Option<TreeNode<P>>, StorageError> {
+        match self.nodes.get(node_key(link)).map_err(backend_error)? {
+            None => Ok(None),
+            Some(bytes) => Ok(Some(TreeNode::<P>::deserialize(&bytes)?)),
+        }
+    }
+
+    fn get_metadata(&self) -> Result<Option<TreeMetadata>, StorageError> {
+        match self.meta.get(METADATA_KEY).map_err(backend_error)? {
+            None => Ok(None),
+            Some(bytes) => Ok(Some(TreeMetadata::deserialize(&bytes)?)),
+        }
+    }
+
+    /// Write the nodes and the metadata in a single transaction across the two sled trees.
+    fn write_batch(&mut self, batch: NodeBatch<P>) -> Result<(), StorageError> {
+        let mut nodes_batch = sled::Batch::default();
+        for (link, node) in batch.get_nodes() {
+            nodes_batch.insert(&node_key(*link), node.serialize());
+        }
+        let metadata = batch.get_metadata().serialize();
+
+        (&self.nodes, &self.meta)
+            .transaction(|(nodes, meta)| {
+                nodes.apply_batch(&nodes_batch)?;
+                // The root pointer is updated in the same transaction as the nodes.
+                meta.insert(METADATA_KEY, metadata.as_slice())?;
+                Ok(())
+            })
+            .map_err(|e: TransactionError<()>| match e {
+                TransactionError::Abort(()) => StorageError::BackendError {
+                    msg: "Transaction aborted".to_owned(),
+                },
+                TransactionError::Storage(e) => backend_error(e),
+            })
+    }
+}
@@ -0,0 +1,248 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Known-answer test vectors for validating other implementations byte for byte,
+//! enabled by the ```test-vectors``` feature.
+//!
+//! Each [TestVector] fixes a set of leaves and the padding secret, and gives the expected root
+//! and the expected encoding of the Merkle proof of one leaf, all hex encoded.
+//! The value of a leaf is the digest of its data, e.g., ```H("leaf 0")```.
+//!
+//! The vectors cover [HashNodeSmt](../node_template/struct.HashNodeSmt.html) sparse trees
+//! and [MTreeNodeSmt](../node_template/struct.MTreeNodeSmt.html) plain Merkle trees,
+//! for each supported digest: Blake3, Blake2b, SHA-256 and SHA3-256.
+
+use digest::Digest;
+
+use crate::pad_secret::Secret;
+use crate::{
+    error::DecodingError,
+    index::TreeIndex,
+    node_template::{HashNodeSmt, MTreeNodeSmt},
+    proof::MerkleProof,
+    traits::{InclusionProvable, Mergeable, Paddable, ProofExtractable, Serializable, TypeName},
+    tree::SparseMerkleTree,
+    utils::{bytes_to_hex, hex_to_bytes, tree_index_from_u64},
+};
+
+/// The kind of tree of a test vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeMode {
+    /// A sparse Merkle tree of [HashNodeSmt](../node_template/struct.HashNodeSmt.html) nodes,
+    /// built with [build](../tree/struct.SparseMerkleTree.html#method.build).
+    Sparse,
+    /// A plain Merkle tree of [MTreeNodeSmt](../node_template/struct.MTreeNodeSmt.html) nodes,
+    /// built with [new_merkle_tree](../tree/struct.SparseMerkleTree.html#method.new_merkle_tree)
+    /// from the leaves at consecutive positions.
+    PlainMerkle,
+}
+
+/// A known-answer test vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TestVector {
+    /// The name of the digest, as returned by [TypeName::get_name].
+    pub digest: &'static str,
+    /// The kind of tree.
+    pub mode: TreeMode,
+    /// The height of the tree.
+    pub height: usize,
+    /// The hex encoded padding secret.
+    pub secret: &'static str,
+    /// The positions of the leaves from left to right, and the data whose digests are the leaves.
+    pub leaves: &'static [(u64, &'static str)],
+    /// The hex encoded expected root.
+    pub root: &'static str,
+    /// The position of the proved leaf.
+    pub proof_position: u64,
+    /// The hex encoded expected Merkle proof of the proved leaf.
+    pub proof: &'static str,
+}
+
+/// The secret of the sparse trees in the test vectors.
+const SECRET: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+/// The all-zeros secret of the plain Merkle trees.
+const ZERO_SECRET: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+/// The leaves of the sparse trees.
+const SPARSE_LEAVES: &[(u64, &str)] = &[
+    (0, "leaf 0"),
+    (3, "leaf 3"),
+    (17, "leaf 17"),
+    (128, "leaf 128"),
+    (255, "leaf 255"),
+];
+/// The leaves of the plain Merkle trees.
+const PLAIN_LEAVES: &[(u64, &str)] = &[
+    (0, "leaf 0"),
+    (1, "leaf 1"),
+    (2, "leaf 2"),
+    (3, "leaf 3"),
+    (4, "leaf 4"),
+];
+
+/// The known-answer test vectors.
+pub const TEST_VECTORS: &[TestVector] = &[
+    TestVector {
+        digest: "Blake3",
+        mode: TreeMode::Sparse,
+        height: 8,
+        secret: SECRET,
+        leaves: SPARSE_LEAVES,
+        root: "5ecf28b1d5c6e2a5c1c96f27bf814f2e8c85486b7ea6fce5eff78969e18759d1",
+        proof_position: 17,
+        proof: "010000000000000008008808000000000000008bc1999c55e941ce6a348e301d558008c5432cbfd40e429815e5c5564b2193cfc6cb4c91cc14f5abffd19961850f2520ed731cff806bdd7133bc518a37243f5762d668dbcedf3bebe9fb4305cd521a13cbc5d04557960e70a02a5cde24e60627d8b8e18c868115ff56b88dc7ab2d542a8d522a45c698183dde900757bc085e789af5ca3aa4946a5ce15537298e683249aeca9b77a7041e7adb866b2cb32284136ae8ffce10c461a0dfdcd57f0a48ba8ff88c94dcb1e1e495e9fd8b773d60aa661b996a2f416e998ed7a3d650e5f78ebdd64dac1e4618cc1229f284aa67c6e66ddbb78c4ac7fa97c75d45e75c1eefebe85612f8be9eeb8ad86192a162e3f5fe30",
+    },
+    TestVector {
+        digest: "Blake3",
+        mode: TreeMode::PlainMerkle,
+        height: 3,
+        secret: ZERO_SECRET,
+        leaves: PLAIN_LEAVES,
+        root: "10f862d362e12f935b9c07c3335b0a3b5075c920680137506a2c318b5f4c2e50",
+        proof_position: 2,
+        proof: "01000000000000000300020300000000000000f7faa6bbdcfd920c2f2f18ea4a5fc4612f5c2960b5fa2c1100460ebe0e5fa12faf311d272dc334e92c7d626141fa11430dc3b8f55a4911ae1b2542124bdbbef2ceb50f111fece8844fe4432ed3d19cbce3f54c2ba3994dcd37fe2ceca29791a4",
+    },
+    TestVector {
+        digest: "Blake2b",
+        mode: TreeMode::Sparse,
+        height: 8,
+        secret: SECRET,
+        leaves: SPARSE_LEAVES,
+        root: "c617d17d1f38102cdf427f697e3de502ea8dda68272563efa0762264ad684391085e1c5e24d96dfa54f2498ed7a9aaa249272624088512be1ac4b54af3b0e0fd",
+        proof_position: 17,
+        proof: "0100000000000000080088080000000000000047db5c314989a87fdabe49295b1422b8cc8ef27f0686eb261488f3eb22775e7a076afaa302f13944a3c09b80e327ce9686db970ccc67f57b468f6c81e67a90b64e0c512d0ced1b12e8c0308c011063e6a21c9d5adfbe60531fb47f928c76880be6f4080bf15ae6b12be489974231f71ecb2284ca3fc35d232efd1dd277ef4d082d3c30605e819a99cc1dee56d49306d633829e923781b19f0dcc24f2a877b7eeaf7f92fc9acd3881897303f7db8af0446d2496a5849612ed0862d0ebef4c0addf87b8bacf1d3d1f34ab406ac20a1394e46f0edb51552d29add2f3657459b6fa55fadd324c34cb1d3a6cf78d02d86c88432ba62f0e48431a9050726bbca1d0855ead3466119de9841791d39f5e25f4b071e01bf61e757556f852c0983722bd22b0a77bce8cfbf44eedbc473ec5bafd07631af89b4f622ae59bd21fb899de2452eed381e9bc153b4c7e673ea293b56da4b9cfa3390d8b6ca605ab36091be1befe0a1458905cecb649348d294ec27e435a4c786deee1214115ae6b53fbc560d57c199d7daa5a77fcbae85559a7df92ca4eac2661a28530232eaec3ffc8bf01097a4bd546fbf30fe2cf29515915e5589e4bf46ea38c7885d1ae3165805b2222482e155281d1883c29c64dd7b6ddb0e26ed50fb89aee9cb0b4f3af769d4d607f494c8334f98ecac1a9101d542f3dd13f74c8f0b4f285635a6213c390ed8c21fc31ad2",
+    },
+    TestVector {
+        digest: "Blake2b",
+        mode: TreeMode::PlainMerkle,
+        height: 3,
+        secret: ZERO_SECRET,
+        leaves: PLAIN_LEAVES,
+        root: "7d1ede73a3729963111069c3192ad9dd8d57135c2225d67e7334adae223c3bc4dbefda2e58c2270d407eb332610e94d5aeb378803980fc1539f17c98f8feccaa",
+        proof_position: 2,
+        proof: "01000000000000000300020300000000000000e57226eb6e2e8bda0afcff90b7aa85387680624af74ddac3dbffccd1fd345b3382d4613988b71ff135e082ef339ddf7dc4d1328f36b8ec5fdbb394641378cda5ada06380b7604b07a13c2ffc3f0473224544adf56e0fe046059296953c9593cd8566ebe16a4ab8b2d565c25543f6991123f076ab4a85f5f7514448ee646084cbd7e17b1f18c70f17047b24fef3b239645f11aa719c465bcf918add6da071616d8756de0c3858440240223a7ef277fbee40d538f4dce99960848f6f956c52e277",
+    },
+    TestVector {
+        digest: "Sha2",
+        mode: TreeMode::Sparse,
+        height: 8,
+        secret: SECRET,
+        leaves: SPARSE_LEAVES,
+        root: "cd26bb697622264a77bf6116a005bb2883697cf50d33dfa7ab648bbc03c1f6a5",
+        proof_position: 17,
+        proof: "010000000000000008008808000000000000000463c424e2700fa48b171dc957b5258d336d8b6866d416deb928808e8a58db5e52ca6cbc07286d35f294f40f8e537398d3948d734f65f40b7cdc48f9e0c2aa6d04e0c3bfd58bd923772a72b8d730c1bac262e87245202b66bef28ca2ddacbfbd69c70b74b515cb66bc22ede4f1a8e9663f8525487050173b025a38cc8e6fee21f723b37fe28b50a7f7f1e5b0dc1f35bf2ca065fc746035f13422905ae461d6ded6965610bed6d6fa6dfde9f09a0dd702b4724fdc8694c51a904f73c65c320bfc814f824bf634f0e8caefb0aa7f1ffea72fc2943f2e8cd6f026b3884a8094d1cdad95abd2fa06dee2367098e1b7089069a14d767caab02beb6dff9c543d8947b3",
+    },
+    TestVector {
+        digest: "Sha2",
+        mode: TreeMode::PlainMerkle,
+        height: 3,
+        secret: ZERO_SECRET,
+        leaves: PLAIN_LEAVES,
+        root: "5753b13904101feca72cf1167c2429223295649beca05d8bd2c5f1dc900c59ee",
+        proof_position: 2,
+        proof: "01000000000000000300020300000000000000b2603bb694e0aeea2a9027a63e90ecc5744bd344cce5f4e09c2023299f3d851b06f4672c8871ec3b0085b38a1682a938005d5fa05ef1366bf23b5f9eb46ff543ca6e6588d55d58a70e0b4de60c2dab1e4574bb97d68fa88679852a5daaa9db02",
+    },
+    TestVector {
+        digest: "Sha3",
+        mode: TreeMode::Sparse,
+        height: 8,
+        secret: SECRET,
+        leaves: SPARSE_LEAVES,
+        root: "c7e5555463159c18b1fea76e535735698f553b4a251074274fce3364ebadc9e9",
+        proof_position: 17,
+        proof: "01000000000000000800880800000000000000db57344f0e245d6ac559ad2167750fbf9fd649504c7ed4e5f3e92205d767a0f7d318893e4848c4d98be127dabcf2ac8e7e6e035e44300219483537ae93472f25f0dd00953eab51d84e37c437b99847149edea3697c3e9b80dd63fbfb885dc1de5efa686b4ff25ec82f2f7c8719138f88d031d1f1d3b1edc3dec8d29f63e9b37f9aa626541f62b39f4a223c3f7d43ace31bd6ed6a5d149ed513654440b2095942e2adfa60136b1848ab6045b8d54ac40908655cf6a62f5b2750659145fd89011d76264f1206b800d8b1c216c70c0790451e48d6b3267247d2b46f6d0118cd35869ec6d0615709f7309bb3edde4447972fb56458ed71166b508148b11fc0b9ac3a",
+    },
+    TestVector {
+        digest: "Sha3",
+        mode: TreeMode::PlainMerkle,
+        height: 3,
+        secret: ZERO_SECRET,
+        leaves: PLAIN_LEAVES,
+        root: "8ddd86f20e2bb67ee154294637fbc42067e88f0848795795233e8087f767ef01",
+        proof_position: 2,
+        proof: "010000000000000003000203000000000000006b8d44915b9ac0b43285541df9dc3f50c4cd9c07b51f47d8adb9249f4a62f428eb029c57ea9294dd2c39808cd397c380aaf919b004aa6affa4b62b5c6ff12c655416f62d9b723cce07c1dc02ab44a0db33338b3eac9ea953972aeaf878d1a3e5",
+    },
+];
+
+/// The result of a test vector computed by this implementation,
+/// i.e., the hex encoded root and Merkle proof of the proved leaf.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestVectorResult {
+    /// The hex encoded root.
+    pub root: String,
+    /// The hex encoded Merkle proof of the proved leaf.
+    pub proof: String,
+}
+
+/// Compute the root and the Merkle proof of the input test vector with the digest ```D```.
+///
+/// If the secret is not a valid hex encoded secret,
+/// return [DecodingError::TextDecodingError](../error/enum.DecodingError.html#variant.TextDecodingError)
+/// or [DecodingError::ValueDecodingError](../error/enum.DecodingError.html#variant.ValueDecodingError).
+///
+/// Panics if the positions of the leaves are not valid for the tree.
+pub fn compute_test_vector<D: Clone + Default + Digest + TypeName>(
+    vector: &TestVector,
+) -> Result<TestVectorResult, DecodingError> {
+    let secret = Secret::from_bytes(&hex_to_bytes(vector.secret)?)
+        .map_err(|e| DecodingError::ValueDecodingError { msg: e.to_string() })?;
+    match vector.mode {
+        TreeMode::Sparse => {
+            let list: Vec<(TreeIndex, HashNodeSmt<D>)> = vector
+                .leaves
+                .iter()
+                .map(|(position, data)| {
+                    (
+                        tree_index_from_u64(vector.height, *position),
+                        HashNodeSmt::new(D::digest(data.as_bytes()).to_vec()),
+                    )
+                })
+                .collect();
+            let mut tree = SparseMerkleTree::new_with_secret(vector.height, &secret);
+            tree.build(&list, &secret);
+            Ok(get_result(&tree, vector))
+        }
+        TreeMode::PlainMerkle => {
+            let list: Vec<MTreeNodeSmt<D>> = vector
+                .leaves
+                .iter()
+                .map(|(_, data)| MTreeNodeSmt::new(D::digest(data.as_bytes()).to_vec()))
+                .collect();
+            let tree = SparseMerkleTree::new_merkle_tree(&list);
+            Ok(get_result(&tree, vector))
+        }
+    }
+}
+
+/// Check the input test vector with the digest ```D```,
+/// i.e., that the computed root and Merkle proof are the expected ones.
+///
+/// Return false if the digest of the vector is not ```D```.
+pub fn check_test_vector<D: Clone + Default + Digest + TypeName>(vector: &TestVector) -> bool {
+    if vector.digest != D::get_name() {
+        return false;
+    }
+    match compute_test_vector::<D>(vector) {
+        Ok(result) => result.root == vector.root && result.proof == vector.proof,
+        Err(_) => false,
+    }
+}
+
+// Returns the hex encoded root and Merkle proof of the tree.
+fn get_result<P: Clone + Default + Mergeable + Paddable + ProofExtractable>(
+    tree: &SparseMerkleTree<P>,
+    vector: &TestVector,
+) -> TestVectorResult
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    let idx = tree_index_from_u64(tree.get_height(), vector.proof_position);
+    let proof = MerkleProof::<P>::generate_inclusion_proof(tree, &[idx])
+        .map(|x| x.serialize())
+        .unwrap_or_default();
+    TestVectorResult {
+        root: bytes_to_hex(&tree.get_root().serialize()),
+        proof: bytes_to_hex(&proof),
+    }
+}
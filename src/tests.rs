@@ -5,22 +5,42 @@
 
 use std::fmt::Debug;
 use std::marker::PhantomData;
+use std::sync::Arc;
 
-use crate::node_template::{HashNodeSmt, MTreeNodeSmt, SumNodeSmt};
-use crate::pad_secret::ALL_ZEROS_SECRET;
+use crate::node_template::{
+    FixedHashNodeSmt, HashNodeSmt, MTreeNodeSmt, MaxTimestampNodeSmt, MinTimestampNodeSmt,
+    NamespaceNodeSmt, NamespaceProof, NumSumNode, SumNodeSmt, SumNodeSmt128,
+};
+use crate::pad_secret::{Secret, ALL_ZEROS_SECRET};
 use crate::{
-    index::{TreeIndex, MAX_HEIGHT},
+    dynamic::{new_dyn_smt, DynSmt},
+    epoch_log::EpochLog,
+    error::{DecodingError, IntegrityError, SmtError, StorageError, TreeError},
+    index::{TreeIndex, TreeIndex16, TreeIndex32, TreeIndex64, MAX_HEIGHT},
+    metrics::{CountingRecorder, Operation},
     node_template,
-    proof::{MerkleProof, RandomSamplingProof},
+    partial::PartialSmt,
+    proof::{
+        AnchoredProof, BorrowedMerkleProof, CircuitWitness, MerkleProof, MerkleProofRef,
+        NestedProof, PaddingOmittedProof, RandomSamplingProof, SelfContainedProof,
+    },
+    proof_cache::ProofCache,
+    storage::{get_merkle_proof_from_store, CachedNodeStore, MemoryNodeStore, StoreBfsIter},
     traits::{
-        InclusionProvable, Mergeable, Paddable, PaddingProvable, ProofExtractable, Rand,
-        RandomSampleable, Serializable, TypeName,
+        InclusionProvable, Mergeable, MergeableEncoded, Paddable, PaddingContext, PaddingDomain,
+        PaddingProvable, ProofExtractable, Rand, RandomSampleable, Serializable, TypeName,
+    },
+    tree::{
+        BufferedWriter, DuplicatePolicy, NodeType, PaddingPolicy, SmtSnapshot, SparseMerkleTree,
+        TreeBuilder, TreeNode, TreeStats,
+    },
+    utils::{
+        bytes_to_count, bytes_to_u64, generate_sorted_index_value_pairs, print_output, read_unit,
+        u64_to_bytes, usize_to_bytes, write_unit, UNIT_LEN_BYTE_NUM,
     },
-    tree::SparseMerkleTree,
-    utils::{generate_sorted_index_value_pairs, print_output},
 };
 
-type SMT<P> = SparseMerkleTree<P>;
+type Smt<P> = SparseMerkleTree<P>;
 
 pub struct Tester<P> {
     _phantom: PhantomData<P>,
@@ -38,7 +58,7 @@ fn test_index_exceed_max_height() {
 #[test]
 #[should_panic]
 fn test_tree_exceed_max_height() {
-    let _tree: SMT<SumNodeSmt> = SMT::new(MAX_HEIGHT + 1);
+    let _tree: Smt<SumNodeSmt> = Smt::new(MAX_HEIGHT + 1);
 }
 
 #[test]
@@ -58,7 +78,7 @@ fn test_padding_provable() {
         assert!(
             node_template::HashNodeSmt::<blake3::Hasher>::verify_padding_node(
                 &node.get_proof_node(),
-                &node.prove_padding_node(&idx, &secret),
+                &node.prove_padding_node(&idx, secret),
                 &idx,
             )
         );
@@ -80,14 +100,14 @@ where
         Debug + Clone + Default + Eq + Debug + Mergeable + Serializable,
     <P as PaddingProvable>::PaddingProof: Clone + Default + Eq + Serializable,
 {
-    fn test_building_smt(list: &[(TreeIndex, P)]) -> SMT<P> {
+    fn test_building_smt(list: &[(TreeIndex, P)]) -> Smt<P> {
         let secret = &ALL_ZEROS_SECRET;
         // Build the SMT from a list.
-        let mut build_tree = SMT::new(TREE_HEIGHT);
-        build_tree.build(&list, secret);
+        let mut build_tree = Smt::new(TREE_HEIGHT);
+        build_tree.build(list, secret);
 
         // Build the SMT by updating elements in the list one by one.
-        let mut update_tree = SMT::new(TREE_HEIGHT);
+        let mut update_tree = Smt::new(TREE_HEIGHT);
         for item in list.iter() {
             update_tree.update(&item.0, item.1.clone(), secret);
         }
@@ -112,8 +132,8 @@ where
         build_tree
     }
 
-    fn merkle_proof_existing(tree: &SMT<P>, leaves: &[P::ProofNode], list: &[TreeIndex]) -> bool {
-        let proof = MerkleProof::<P>::generate_inclusion_proof(&tree, list);
+    fn merkle_proof_existing(tree: &Smt<P>, leaves: &[P::ProofNode], list: &[TreeIndex]) -> bool {
+        let proof = MerkleProof::<P>::generate_inclusion_proof(tree, list);
         match proof {
             None => unreachable!(),
             Some(proof) => {
@@ -125,7 +145,7 @@ where
         }
     }
 
-    fn test_merkle_proof(list: &[(TreeIndex, P)], tree: &SMT<P>) {
+    fn test_merkle_proof(list: &[(TreeIndex, P)], tree: &Smt<P>) {
         // Test single node Merkle proof generation and verification.
         for item in list.iter() {
             assert!(Tester::<P>::merkle_proof_existing(
@@ -136,7 +156,7 @@ where
         }
     }
 
-    fn test_merkle_proof_batch(list: &[(TreeIndex, P)], tree: &SMT<P>) {
+    fn test_merkle_proof_batch(list: &[(TreeIndex, P)], tree: &Smt<P>) {
         // Test batched Merkle proof generation and verification.
 
         // Test batched proof of an empty list of tree indexes.
@@ -160,7 +180,7 @@ where
         }
     }
 
-    fn random_sampling(tree: &SMT<P>, idx: &TreeIndex) -> bool {
+    fn random_sampling(tree: &Smt<P>, idx: &TreeIndex) -> bool {
         let secret = &ALL_ZEROS_SECRET;
 
         let proof = RandomSamplingProof::<P>::random_sampling(tree, idx, secret);
@@ -169,7 +189,7 @@ where
         deserialized.verify_random_sampling_proof(&tree.get_root())
     }
 
-    fn test_random_sampling(list: &[(TreeIndex, P)], tree: &SMT<P>) {
+    fn test_random_sampling(list: &[(TreeIndex, P)], tree: &Smt<P>) {
         let secret = &ALL_ZEROS_SECRET;
         // Test random sampling.
 
@@ -179,7 +199,7 @@ where
         }
 
         // When no node exists.
-        let empty_tree: SMT<P> = SMT::new(TREE_HEIGHT);
+        let empty_tree: Smt<P> = Smt::new(TREE_HEIGHT);
         assert!(Tester::<P>::random_sampling(&empty_tree, &list[0].0));
 
         // When the index looked up doesn't exist and left neighbour doesn't exist.
@@ -253,13 +273,15 @@ fn test_smt() {
     Tester::<node_template::HashNodeSmt<blake2::Blake2b>>::test();
     Tester::<node_template::HashNodeSmt<sha2::Sha256>>::test();
     Tester::<node_template::HashNodeSmt<sha3::Sha3_256>>::test();
+    Tester::<node_template::HashWiresNodeSmt<blake3::Hasher, 2>>::test();
+    Tester::<node_template::HashWiresNodeSmt<blake3::Hasher, 3>>::test();
 }
 
 #[test]
 fn test_merkle_tree() {
     let example_leaf = MTreeNodeSmt::new(vec![0; 32]);
     let list: Vec<MTreeNodeSmt<blake3::Hasher>> = vec![example_leaf.clone(); 5];
-    let tree = SMT::<MTreeNodeSmt<blake3::Hasher>>::new_merkle_tree(&list);
+    let tree = Smt::<MTreeNodeSmt<blake3::Hasher>>::new_merkle_tree(&list);
     assert_eq!(tree.get_height(), 3); // starting from zero
     assert_eq!(tree.get_paddings().len(), 2);
 
@@ -269,16 +291,4635 @@ fn test_merkle_tree() {
     let proof =
         MerkleProof::<MTreeNodeSmt<blake3::Hasher>>::generate_inclusion_proof(&tree, &index_list)
             .unwrap();
-    assert_eq!(proof.verify(&example_leaf, &tree.get_root()), true);
+    assert!(proof.verify(&example_leaf, &tree.get_root()));
 
     let serialized_proof = proof.serialize();
     let deserialized_proof =
         MerkleProof::<MTreeNodeSmt<blake3::Hasher>>::deserialize(&serialized_proof).unwrap();
     assert_eq!(serialized_proof, deserialized_proof.serialize());
+    assert!(deserialized_proof.verify(&example_leaf, &tree.get_root()));
+
+    print_output(&tree);
+}
+
+#[test]
+fn test_storage() {
+    type P = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, P)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+
+    // Persist a built tree and load it back.
+    let mut store = MemoryNodeStore::<P>::new();
+    let mut tree = Smt::<P>::new(TREE_HEIGHT);
+    tree.build_and_persist(&list[1..], secret, &mut store)
+        .unwrap();
+    let loaded = Smt::<P>::load(&store).unwrap();
+    assert_eq!(loaded.get_root(), tree.get_root());
+    assert_eq!(loaded.get_nodes_num(), tree.get_nodes_num());
+
+    // Updates only write the changed path.
+    tree.update_and_persist(&list[0].0, list[0].1.clone(), secret, &mut store)
+        .unwrap();
+    let loaded = Smt::<P>::load(&store).unwrap();
+    assert_eq!(loaded.get_root(), tree.get_root());
     assert_eq!(
-        deserialized_proof.verify(&example_leaf, &tree.get_root()),
-        true
+        StoreBfsIter::new(&store).unwrap().count(),
+        tree.get_index_ref_pairs().len()
     );
 
-    print_output(&tree);
+    // Proofs generated from the store verify against the tree root.
+    for (idx, value) in list.iter() {
+        let proof = get_merkle_proof_from_store(&store, idx).unwrap().unwrap();
+        assert!(proof.verify(&value.get_proof_node(), &tree.get_root()));
+    }
+
+    // Node encoding round trip.
+    let (_, node) = tree.get_leaves()[0];
+    let decoded = TreeNode::<P>::deserialize(&node.serialize()).unwrap();
+    assert_eq!(decoded.get_value(), node.get_value());
+
+    // A corrupted store isn't loaded.
+    use crate::storage::{NodeBatch, NodeStore, TreeMetadata};
+    let metadata = tree.get_metadata();
+    let mut corrupted = MemoryNodeStore::<P>::new();
+    tree.persist(&mut corrupted).unwrap();
+    let mut root = tree.get_node_by_ref(metadata.root).clone();
+    root.set_lch(99999);
+    let mut batch = NodeBatch::new(metadata);
+    batch.put(metadata.root, root);
+    corrupted.write_batch(batch).unwrap();
+    assert!(matches!(
+        Smt::<P>::load(&corrupted),
+        Err(StorageError::DecodingError(
+            DecodingError::ValueDecodingError { .. }
+        ))
+    ));
+    let mut corrupted = MemoryNodeStore::<P>::new();
+    tree.persist(&mut corrupted).unwrap();
+    let mut batch = NodeBatch::new(TreeMetadata {
+        nodes_num: usize::MAX / 2,
+        ..metadata
+    });
+    batch.put(0, tree.get_node_by_ref(0).clone());
+    corrupted.write_batch(batch).unwrap();
+    assert_eq!(
+        Smt::<P>::load(&corrupted).err(),
+        Some(StorageError::NodeMissing(tree.get_nodes_num()))
+    );
+}
+
+#[test]
+fn test_storage_cache() {
+    type P = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, P)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+
+    let mut tree = Smt::<P>::new(TREE_HEIGHT);
+    let mut store = CachedNodeStore::new(MemoryNodeStore::<P>::new(), 64);
+    tree.build_and_persist(&list, secret, &mut store).unwrap();
+    store.pin_top_levels(3).unwrap();
+    assert_eq!(store.get_pinned_num(), 7);
+
+    for (idx, value) in list.iter() {
+        let proof = get_merkle_proof_from_store(&store, idx).unwrap().unwrap();
+        assert!(proof.verify(&value.get_proof_node(), &tree.get_root()));
+    }
+    let stats = store.get_stats();
+    assert!(stats.hits > 0 && stats.misses > 0 && stats.evictions > 0);
+    assert!(store.get_cached_num() <= 64);
+
+    // Updates invalidate the cached nodes, so proofs keep verifying against the new root.
+    let mut value = list[0].1.clone();
+    value.randomize();
+    tree.update_and_persist(&list[0].0, value.clone(), secret, &mut store)
+        .unwrap();
+    let proof = get_merkle_proof_from_store(&store, &list[0].0)
+        .unwrap()
+        .unwrap();
+    assert!(proof.verify(&value.get_proof_node(), &tree.get_root()));
+}
+
+#[cfg(feature = "sled")]
+#[test]
+fn test_sled_storage() {
+    use crate::storage::{NodeStore, SledNodeStore};
+
+    type P = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, P)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+
+    let db = sled::Config::new().temporary(true).open().unwrap();
+    let mut store = SledNodeStore::<P>::from_db(db).unwrap();
+    assert!(store.get_metadata().unwrap().is_none());
+
+    let mut tree = Smt::<P>::new(TREE_HEIGHT);
+    tree.build_and_persist(&list[1..], secret, &mut store)
+        .unwrap();
+    tree.update_and_persist(&list[0].0, list[0].1.clone(), secret, &mut store)
+        .unwrap();
+    store.flush().unwrap();
+
+    let loaded = Smt::<P>::load(&store).unwrap();
+    assert_eq!(loaded.get_root(), tree.get_root());
+    assert_eq!(store.iter_nodes().count(), tree.get_nodes_num());
+}
+
+#[cfg(feature = "rocksdb")]
+#[test]
+fn test_rocks_storage() {
+    use crate::storage::{NodeStore, RocksNodeStore};
+
+    type P = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, P)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+
+    let path = std::env::temp_dir().join(format!("smtree-rocks-{}", std::process::id()));
+    {
+        let mut store = RocksNodeStore::<P>::open(&path).unwrap();
+        assert!(store.get_metadata().unwrap().is_none());
+
+        let mut tree = Smt::<P>::new(TREE_HEIGHT);
+        tree.build_and_persist(&list[1..], secret, &mut store)
+            .unwrap();
+        tree.update_and_persist(&list[0].0, list[0].1.clone(), secret, &mut store)
+            .unwrap();
+
+        let loaded = Smt::<P>::load(&store).unwrap();
+        assert_eq!(loaded.get_root(), tree.get_root());
+        assert_eq!(store.iter_nodes().unwrap().count(), tree.get_nodes_num());
+    }
+    rocksdb::DB::destroy(&rocksdb::Options::default(), &path).unwrap();
+}
+
+#[test]
+fn test_build_unsorted() {
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, SumNodeSmt)> =
+        generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<SumNodeSmt>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+
+    let mut reversed = list.clone();
+    reversed.reverse();
+    let mut unsorted_tree = Smt::<SumNodeSmt>::new(TREE_HEIGHT);
+    unsorted_tree.build_unsorted(reversed, secret);
+    assert_eq!(unsorted_tree.get_root(), tree.get_root());
+}
+
+#[test]
+fn test_duplicate_policy() {
+    let secret = &ALL_ZEROS_SECRET;
+    let idx = TreeIndex::from_u64(TREE_HEIGHT, 3);
+    let list = vec![
+        (idx, SumNodeSmt::new(1)),
+        (TreeIndex::from_u64(TREE_HEIGHT, 1), SumNodeSmt::new(10)),
+        (idx, SumNodeSmt::new(2)),
+    ];
+    assert_eq!(
+        Smt::<SumNodeSmt>::resolve_duplicates(list.clone(), &DuplicatePolicy::Error),
+        Err(TreeError::IndexDuplicatedAt {
+            position: 2,
+            index: idx
+        })
+    );
+
+    let cases: Vec<(DuplicatePolicy<SumNodeSmt>, u64)> = vec![
+        (DuplicatePolicy::KeepFirst, 11),
+        (DuplicatePolicy::KeepLast, 12),
+        (
+            DuplicatePolicy::Combine(&|a, b| SumNodeSmt::merge(a, b)),
+            13,
+        ),
+    ];
+    for (policy, sum) in cases {
+        let mut tree = Smt::<SumNodeSmt>::new(TREE_HEIGHT);
+        tree.build_with_policy(list.clone(), secret, &policy);
+        assert_eq!(tree.get_root(), SumNodeSmt::new(sum));
+
+        let mut updated = Smt::<SumNodeSmt>::new(TREE_HEIGHT);
+        updated.update_batch(list.clone(), secret, &policy);
+        assert_eq!(updated.get_root(), SumNodeSmt::new(sum));
+    }
+}
+
+#[test]
+fn test_index_list_error_context() {
+    let tree = Smt::<SumNodeSmt>::new(TREE_HEIGHT);
+    let mut list: Vec<(TreeIndex, SumNodeSmt)> = (0..10)
+        .map(|i| (TreeIndex::from_u64(TREE_HEIGHT, i), SumNodeSmt::new(i)))
+        .collect();
+    assert_eq!(tree.check_index_list_validity_at(&list), None);
+
+    list.swap(4, 5);
+    let err = tree.check_index_list_validity_at(&list).unwrap();
+    assert_eq!(err.get_kind(), TreeError::IndexNotSorted);
+    assert_eq!(err.get_entry(), Some((5, list[5].0)));
+    // The errors without context are kept for the existing callers.
+    assert_eq!(
+        tree.check_index_list_validity(&list),
+        Some(TreeError::IndexNotSorted)
+    );
+    assert_eq!(
+        Smt::<SumNodeSmt>::new(TREE_HEIGHT).construct_smt_nodes(&list, &ALL_ZEROS_SECRET),
+        Some(TreeError::IndexNotSorted)
+    );
+
+    list[5] = list[4].clone();
+    let err = tree.check_index_list_validity_at(&list).unwrap();
+    assert_eq!(err.get_kind(), TreeError::IndexDuplicated);
+    assert_eq!(err.get_entry(), Some((5, list[4].0)));
+    assert_eq!(
+        tree.check_index_list_validity(&list),
+        Some(TreeError::IndexDuplicated)
+    );
+
+    list[7].0 = TreeIndex::zero(TREE_HEIGHT - 1);
+    list[4].0 = TreeIndex::from_u64(TREE_HEIGHT, 4);
+    list[5].0 = TreeIndex::from_u64(TREE_HEIGHT, 5);
+    assert_eq!(
+        tree.check_index_list_validity(&list),
+        Some(TreeError::HeightNotMatch)
+    );
+    let err = tree.check_index_list_validity_at(&list).unwrap();
+    assert_eq!(
+        err,
+        TreeError::HeightNotMatchAt {
+            position: 7,
+            index: TreeIndex::zero(TREE_HEIGHT - 1)
+        }
+    );
+}
+
+#[test]
+fn test_index_list_validity_all() {
+    let tree = Smt::<SumNodeSmt>::new(TREE_HEIGHT);
+    let mut list: Vec<(TreeIndex, SumNodeSmt)> = (0..10)
+        .map(|i| (TreeIndex::from_u64(TREE_HEIGHT, i), SumNodeSmt::new(i)))
+        .collect();
+    assert!(tree.check_index_list_validity_all(&list).is_empty());
+
+    list.swap(1, 2);
+    list[5].0 = list[4].0;
+    list[8].0 = TreeIndex::zero(1);
+    let errors = tree.check_index_list_validity_all(&list);
+    let kinds: Vec<(TreeError, usize)> = errors
+        .iter()
+        .map(|e| (e.get_kind(), e.get_entry().unwrap().0))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            (TreeError::IndexNotSorted, 2),
+            (TreeError::IndexDuplicated, 5),
+            (TreeError::HeightNotMatch, 8),
+        ]
+    );
+    assert_eq!(
+        tree.check_index_list_validity_at(&list),
+        Some(errors[0].clone())
+    );
+}
+
+#[test]
+fn test_merkle_path_refs() {
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, SumNodeSmt)> =
+        generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<SumNodeSmt>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+
+    let idx = list[0].0;
+    let refs = tree.get_merkle_path_ref(&idx).unwrap();
+    assert_eq!(refs.leaves().len(), 1);
+    assert_eq!(refs.siblings().len(), TREE_HEIGHT);
+    for level in 1..=TREE_HEIGHT {
+        assert_eq!(
+            refs.sibling_for(&idx, level),
+            Some(refs.siblings()[level - 1])
+        );
+    }
+    assert_eq!(refs.sibling_for(&idx, 0), None);
+
+    // In a batched proof, siblings on the path of another proved leaf are not part of the proof.
+    let batch = [list[0].0, list[1].0];
+    let refs = tree.get_merkle_path_ref_batch(&batch).unwrap();
+    assert_eq!(refs.leaves().len(), 2);
+    assert_eq!(refs.sibling_indexes().len(), refs.siblings().len());
+    let common = (0..TREE_HEIGHT)
+        .take_while(|i| batch[0].get_bit(*i) == batch[1].get_bit(*i))
+        .count();
+    assert_eq!(refs.sibling_for(&batch[0], common + 1), None);
+    assert_eq!(refs.siblings().len(), 2 * TREE_HEIGHT - common - 2);
+}
+
+#[test]
+fn test_iter_siblings_with_index() {
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, SumNodeSmt)> =
+        generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<SumNodeSmt>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+    let nodes: std::collections::HashMap<TreeIndex, SumNodeSmt> = tree
+        .get_index_node_pairs()
+        .into_iter()
+        .map(|(index, node)| (index, node.get_value().clone()))
+        .collect();
+
+    let batch: Vec<TreeIndex> = list.iter().step_by(7).map(|(index, _)| *index).collect();
+    for indexes in [&batch[0..1], &batch[..]].iter() {
+        let proof = MerkleProof::<SumNodeSmt>::generate_inclusion_proof(&tree, indexes).unwrap();
+        let pairs: Vec<_> = proof.iter_siblings_with_index().collect();
+        assert_eq!(pairs.len(), proof.get_siblings_num());
+        for (index, sibling) in pairs {
+            assert_eq!(nodes[&index].get_proof_node(), *sibling);
+        }
+    }
+
+    // Invalid indexes don't have sibling positions.
+    let mut proof = MerkleProof::<SumNodeSmt>::new_batch(&[batch[1], batch[0]]);
+    proof.set_siblings(vec![SumNodeSmt::default().get_proof_node(); 3]);
+    assert_eq!(proof.iter_siblings_with_index().count(), 0);
+}
+
+#[test]
+fn test_metrics_recorder() {
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, SumNodeSmt)> =
+        generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let recorder = Arc::new(CountingRecorder::new());
+    let mut tree = Smt::<SumNodeSmt>::new(TREE_HEIGHT);
+    tree.set_metrics_recorder(recorder.clone());
+    tree.build(&list, secret);
+
+    let totals = recorder.get_totals(Operation::Build);
+    assert_eq!(recorder.get_operations_num(Operation::Build), 1);
+    assert_eq!(totals.nodes_allocated, tree.get_nodes_num() as u64);
+    assert_eq!(totals.merges, tree.get_internals().len() as u64);
+    assert_eq!(totals.paddings, tree.get_paddings().len() as u64);
+
+    // Updating an existing leaf merges along the path without allocating nodes.
+    tree.update(&list[0].0, SumNodeSmt::default(), secret);
+    let totals = recorder.get_totals(Operation::Update);
+    assert_eq!(totals.merges, TREE_HEIGHT as u64);
+    assert_eq!(totals.nodes_allocated, 0);
+
+    // Generating a proof copies the proof nodes from the tree without merging.
+    let proof = RandomSamplingProof::<SumNodeSmt>::random_sampling(&tree, &list[0].0, secret);
+    assert!(proof.verify_random_sampling_proof(&tree.get_root()));
+    assert_eq!(recorder.get_operations_num(Operation::ProofGeneration), 1);
+    let totals = recorder.get_totals(Operation::ProofGeneration);
+    assert_eq!(totals.nodes_allocated, TREE_HEIGHT as u64 + 1);
+    assert_eq!(totals.merges, 0);
+    MerkleProof::<SumNodeSmt>::generate_inclusion_proof(&tree, &[list[1].0]).unwrap();
+    let totals = recorder.get_totals(Operation::ProofGeneration);
+    assert_eq!(totals.nodes_allocated, 2 * TREE_HEIGHT as u64 + 1);
+
+    // Building on several threads does the same work.
+    recorder.reset();
+    tree.construct_smt_nodes_parallel(&list, secret, 4);
+    let totals = recorder.get_totals(Operation::Build);
+    assert_eq!(totals.nodes_allocated, tree.get_nodes_num() as u64);
+    assert_eq!(totals.merges, tree.get_internals().len() as u64);
+    assert_eq!(totals.paddings, tree.get_paddings().len() as u64);
+
+    recorder.reset();
+    tree.clear_metrics_recorder();
+    tree.update(&list[1].0, SumNodeSmt::default(), secret);
+    assert_eq!(recorder.get_operations_num(Operation::Update), 0);
+}
+
+#[test]
+fn test_compact_tree_index() {
+    assert_eq!(std::mem::size_of::<TreeIndex64>(), 10);
+    assert_eq!(TreeIndex16::MAX_HEIGHT, 16);
+    assert_eq!(TreeIndex::MAX_HEIGHT, MAX_HEIGHT);
+
+    let list: Vec<(TreeIndex, SumNodeSmt)> = generate_sorted_index_value_pairs(64, LEAF_NUM);
+    let compact: Vec<TreeIndex64> = list
+        .iter()
+        .map(|(index, _)| index.convert::<8>().unwrap())
+        .collect();
+    for (i, index) in compact.iter().enumerate() {
+        let full = list[i].0;
+        assert_eq!(index.convert::<32>().unwrap(), full);
+        assert_eq!(
+            index.get_sibling_index().convert::<32>(),
+            Some(full.get_sibling_index())
+        );
+        assert_eq!(
+            index.get_prefix(17).convert::<32>(),
+            Some(full.get_prefix(17))
+        );
+        if i > 0 {
+            assert!(compact[i - 1] < *index);
+        }
+    }
+    let bytes = TreeIndex64::serialize(&compact);
+    assert_eq!(
+        TreeIndex64::deserialize_as_a_unit(&bytes, compact.len(), &mut 0).unwrap(),
+        compact
+    );
+    assert_eq!(compact[0].convert::<4>(), None);
+    assert_eq!(
+        TreeIndex32::from_u64(32, 5).convert::<32>(),
+        Some(TreeIndex::from_u64(32, 5))
+    );
+}
+
+#[test]
+fn test_lch_index() {
+    // The left child clears the new bit, even if the path has it set beyond the height.
+    let mut idx = TreeIndex::from_u64(8, 0xff);
+    idx.set_height(4);
+    let lch = idx.get_lch_index();
+    assert_eq!(lch.get_height(), 5);
+    assert_eq!(lch.get_bit(4), 0);
+    assert_eq!(lch.get_prefix(5), TreeIndex::from_u64(5, 0b11110));
+    assert_eq!(
+        idx.get_rch_index().get_prefix(5),
+        TreeIndex::from_u64(5, 0b11111)
+    );
+}
+
+#[test]
+fn test_tree_index_fast_path() {
+    // Compare the u64 fast path against the bit-by-bit definitions.
+    for height in [1usize, 7, 8, 31, 63, 64, 65] {
+        let mut indexes: Vec<TreeIndex> = (0..50)
+            .map(|_| {
+                let mut index = TreeIndex::zero(height);
+                index.randomize();
+                index
+            })
+            .collect();
+        for index in &indexes {
+            for prefix in 0..=height {
+                let mut index_prefix = index.get_prefix(prefix);
+                assert!((0..prefix).all(|i| index_prefix.get_bit(i) == index.get_bit(i)));
+                // The bits beyond the prefix are cleared.
+                index_prefix.set_height(height);
+                assert!((prefix..height).all(|i| index_prefix.get_bit(i) == 0));
+            }
+        }
+        let mut expected = indexes.clone();
+        expected.sort_by(|a, b| {
+            let bits_a: Vec<u8> = (0..height).map(|i| a.get_bit(i)).collect();
+            let bits_b: Vec<u8> = (0..height).map(|i| b.get_bit(i)).collect();
+            bits_a.cmp(&bits_b)
+        });
+        indexes.sort();
+        assert_eq!(indexes, expected);
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_parallel_verification() {
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, SumNodeSmt)> =
+        generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<SumNodeSmt>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+    let root = tree.get_root();
+
+    let indexes: Vec<TreeIndex> = list.iter().step_by(3).map(|(index, _)| *index).collect();
+    let leaves: Vec<_> = list
+        .iter()
+        .step_by(3)
+        .map(|(_, value)| value.get_proof_node())
+        .collect();
+    let proof = MerkleProof::<SumNodeSmt>::generate_inclusion_proof(&tree, &indexes).unwrap();
+    assert!(proof.verify_batch_parallel(&leaves, &root));
+    assert!(!proof.verify_batch_parallel(&leaves[1..], &root));
+    let mut wrong_leaves = leaves.clone();
+    wrong_leaves[0] = Mergeable::merge(&leaves[0], &leaves[1]);
+    assert!(!proof.verify_batch_parallel(&wrong_leaves, &root));
+
+    let proofs: Vec<_> = list
+        .iter()
+        .map(|(index, value)| {
+            let proof = MerkleProof::<SumNodeSmt>::generate_inclusion_proof(&tree, &[*index]);
+            (proof.unwrap(), vec![value.get_proof_node()])
+        })
+        .chain(std::iter::once((proof, wrong_leaves)))
+        .collect();
+    let results = MerkleProof::verify_many_parallel(&proofs, &root);
+    assert!(results[..list.len()].iter().all(|x| *x));
+    assert!(!results[list.len()]);
+}
+
+// The number of calls to merge_many of BatchCountingNode.
+static MERGE_MANY_CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+// A sum node counting the batches of merges.
+#[derive(Clone, Default, Debug)]
+struct BatchCountingNode(SumNodeSmt);
+
+impl Mergeable for BatchCountingNode {
+    fn merge(lch: &BatchCountingNode, rch: &BatchCountingNode) -> BatchCountingNode {
+        BatchCountingNode(Mergeable::merge(&lch.0, &rch.0))
+    }
+
+    fn merge_many(pairs: &[(&Self, &Self)]) -> Vec<Self> {
+        MERGE_MANY_CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        pairs
+            .iter()
+            .map(|(lch, rch)| Mergeable::merge(*lch, *rch))
+            .collect()
+    }
+}
+
+impl Paddable for BatchCountingNode {
+    fn padding(idx: &TreeIndex, secret: &Secret) -> BatchCountingNode {
+        BatchCountingNode(SumNodeSmt::padding(idx, secret))
+    }
+}
+
+impl ProofExtractable for BatchCountingNode {
+    type ProofNode = SumNodeSmt;
+    fn get_proof_node(&self) -> SumNodeSmt {
+        self.0.get_proof_node()
+    }
+}
+
+#[test]
+fn test_merge_many() {
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, SumNodeSmt)> =
+        generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<SumNodeSmt>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+
+    // The default implementation merges pair by pair.
+    let pairs: Vec<(&SumNodeSmt, &SumNodeSmt)> =
+        list.windows(2).map(|x| (&x[0].1, &x[1].1)).collect();
+    let merged = SumNodeSmt::merge_many(&pairs);
+    for (i, value) in merged.iter().enumerate() {
+        assert_eq!(*value, Mergeable::merge(pairs[i].0, pairs[i].1));
+    }
+
+    // Building merges one layer in one batch.
+    let counting_list: Vec<(TreeIndex, BatchCountingNode)> = list
+        .iter()
+        .map(|(index, value)| (*index, BatchCountingNode(value.clone())))
+        .collect();
+    let mut counting_tree = Smt::<BatchCountingNode>::new(TREE_HEIGHT);
+    counting_tree.build(&counting_list, secret);
+    assert_eq!(
+        MERGE_MANY_CALLS.load(std::sync::atomic::Ordering::SeqCst),
+        TREE_HEIGHT
+    );
+    assert_eq!(counting_tree.get_root(), tree.get_root());
+}
+
+#[test]
+fn test_hash_merge_many() {
+    fn check<P: Clone + Default + Debug + Eq + Mergeable + Rand>() {
+        let values: Vec<P> = (0..10)
+            .map(|_| {
+                let mut value = P::default();
+                value.randomize();
+                value
+            })
+            .collect();
+        let pairs: Vec<(&P, &P)> = values.windows(2).map(|x| (&x[0], &x[1])).collect();
+        let merged = P::merge_many(&pairs);
+        assert_eq!(merged.len(), pairs.len());
+        for (i, value) in merged.iter().enumerate() {
+            assert_eq!(*value, Mergeable::merge(pairs[i].0, pairs[i].1));
+        }
+    }
+    check::<HashNodeSmt<blake3::Hasher>>();
+    check::<HashNodeSmt<sha2::Sha256>>();
+    check::<MTreeNodeSmt<blake2::Blake2b>>();
+    check::<node_template::HashWiresNodeSmt<sha3::Sha3_256>>();
+}
+
+#[test]
+fn test_verify_serialized() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+    let root = tree.get_root();
+
+    for step in [LEAF_NUM, 7, 1].iter() {
+        let indexes: Vec<TreeIndex> = list
+            .iter()
+            .step_by(*step)
+            .map(|(index, _)| *index)
+            .collect();
+        let leaves: Vec<Node> = list
+            .iter()
+            .step_by(*step)
+            .map(|(_, value)| value.get_proof_node())
+            .collect();
+        let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &indexes).unwrap();
+        let bytes = proof.serialize();
+        assert_eq!(
+            MerkleProof::<Node>::verify_serialized(&bytes, &leaves, &root),
+            Ok(true)
+        );
+        // A wrong leaf fails the verification.
+        let mut wrong_leaves = leaves.clone();
+        wrong_leaves[0] = leaves[leaves.len() - 1].clone();
+        if leaves.len() > 1 {
+            assert_eq!(
+                MerkleProof::<Node>::verify_serialized(&bytes, &wrong_leaves, &root),
+                Ok(false)
+            );
+        }
+        // Missing or extra siblings fail the verification.
+        let mut siblings = proof.get_path_siblings().to_vec();
+        let mut shorter = proof.clone();
+        shorter.set_siblings(siblings[1..].to_vec());
+        assert_eq!(
+            MerkleProof::<Node>::verify_serialized(&shorter.serialize(), &leaves, &root),
+            Ok(false)
+        );
+        siblings.push(siblings[0].clone());
+        let mut padded = proof.clone();
+        padded.set_siblings(siblings);
+        assert_eq!(
+            MerkleProof::<Node>::verify_serialized(&padded.serialize(), &leaves, &root),
+            Ok(false)
+        );
+        // Malformed encodings are decoding errors.
+        assert_eq!(
+            MerkleProof::<Node>::verify_serialized(&bytes[..bytes.len() - 1], &leaves, &root),
+            Err(DecodingError::BytesNotEnough)
+        );
+        let mut longer = bytes.clone();
+        longer.push(0);
+        assert_eq!(
+            MerkleProof::<Node>::verify_serialized(&longer, &leaves, &root),
+            Err(DecodingError::TooManyEncodedBytes)
+        );
+    }
+    assert_eq!(
+        MerkleProof::<Node>::verify_serialized(&[], &[], &root),
+        Ok(true)
+    );
+}
+
+#[cfg(all(feature = "hex", feature = "base64"))]
+#[test]
+fn test_text_encodings() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+
+    let indexes = [list[0].0, list[1].0];
+    let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &indexes).unwrap();
+    let hex = proof.to_hex();
+    assert_eq!(
+        MerkleProof::<Node>::from_hex(&hex).unwrap().serialize(),
+        proof.serialize()
+    );
+    let base64 = proof.to_base64();
+    assert_eq!(
+        MerkleProof::<Node>::from_base64(&base64)
+            .unwrap()
+            .serialize(),
+        proof.serialize()
+    );
+
+    let mut index = TreeIndex::zero(TREE_HEIGHT);
+    index.randomize();
+    let sampling = RandomSamplingProof::<Node>::random_sampling(&tree, &index, secret);
+    let decoded = RandomSamplingProof::<Node>::from_hex(&sampling.to_hex()).unwrap();
+    assert_eq!(decoded.serialize(), sampling.serialize());
+    let decoded = RandomSamplingProof::<Node>::from_base64(&sampling.to_base64()).unwrap();
+    assert_eq!(decoded.serialize(), sampling.serialize());
+
+    assert!(matches!(
+        MerkleProof::<Node>::from_hex("not hex"),
+        Err(DecodingError::TextDecodingError { .. })
+    ));
+    assert!(matches!(
+        MerkleProof::<Node>::from_base64("not base64!"),
+        Err(DecodingError::TextDecodingError { .. })
+    ));
+}
+
+#[cfg(feature = "proto")]
+#[test]
+fn test_proto() {
+    use crate::proto::{MerkleProofProto, RandomSamplingProofProto};
+    use prost::Message;
+    use std::convert::TryFrom;
+
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+
+    let indexes = [list[0].0, list[5].0, list[9].0];
+    let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &indexes).unwrap();
+    let bytes = MerkleProofProto::from(&proof).encode_to_vec();
+    let decoded = MerkleProof::<Node>::try_from(&MerkleProofProto::decode(&bytes[..]).unwrap());
+    assert_eq!(decoded.unwrap().serialize(), proof.serialize());
+
+    let mut index = TreeIndex::zero(TREE_HEIGHT);
+    index.randomize();
+    let sampling = RandomSamplingProof::<Node>::random_sampling(&tree, &index, secret);
+    let bytes = RandomSamplingProofProto::from(&sampling).encode_to_vec();
+    let message = RandomSamplingProofProto::decode(&bytes[..]).unwrap();
+    let decoded = RandomSamplingProof::<Node>::try_from(&message).unwrap();
+    assert_eq!(decoded.serialize(), sampling.serialize());
+    assert!(decoded.verify_random_sampling_proof(&tree.get_root()));
+
+    // A path longer than the height is rejected.
+    let mut message = MerkleProofProto::from(&proof);
+    message.paths[0].push(0);
+    assert_eq!(
+        MerkleProof::<Node>::try_from(&message).err(),
+        Some(DecodingError::TooManyEncodedBytes)
+    );
+}
+
+#[cfg(feature = "cbor")]
+#[test]
+fn test_cbor() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+
+    let index = TreeIndex::from_u64(10, 5);
+    // {1: 10, 2: h'8002'}
+    assert_eq!(
+        index.to_cbor(),
+        vec![0xa2, 0x01, 0x0a, 0x02, 0x42, 0x80, 0x02]
+    );
+    assert_eq!(TreeIndex::from_cbor(&index.to_cbor()), Ok(index));
+
+    let indexes = [list[0].0, list[5].0, list[9].0];
+    let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &indexes).unwrap();
+    let bytes = proof.to_cbor();
+    assert_eq!(
+        MerkleProof::<Node>::from_cbor(&bytes).unwrap().serialize(),
+        proof.serialize()
+    );
+
+    let mut index = TreeIndex::zero(TREE_HEIGHT);
+    index.randomize();
+    let sampling = RandomSamplingProof::<Node>::random_sampling(&tree, &index, secret);
+    let decoded = RandomSamplingProof::<Node>::from_cbor(&sampling.to_cbor()).unwrap();
+    assert_eq!(decoded.serialize(), sampling.serialize());
+
+    // Trailing bytes and non-deterministic encodings are rejected.
+    let mut longer = bytes.clone();
+    longer.push(0);
+    assert_eq!(
+        MerkleProof::<Node>::from_cbor(&longer).err(),
+        Some(DecodingError::TooManyEncodedBytes)
+    );
+    // {1: 10, 2: h'8002'} with the height encoded in two bytes.
+    let non_shortest = [0xa2, 0x01, 0x18, 0x0a, 0x02, 0x42, 0x80, 0x02];
+    assert!(matches!(
+        TreeIndex::from_cbor(&non_shortest),
+        Err(DecodingError::FormatError { .. })
+    ));
+}
+
+#[cfg(feature = "borsh")]
+#[test]
+fn test_borsh() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+
+    let index = TreeIndex::from_u64(10, 5);
+    // height: u16 = 10, path: Vec<u8> = [0x80, 0x02]
+    assert_eq!(
+        borsh::to_vec(&index).unwrap(),
+        vec![10, 0, 2, 0, 0, 0, 0x80, 0x02]
+    );
+    assert_eq!(
+        borsh::from_slice::<TreeIndex>(&borsh::to_vec(&index).unwrap()).unwrap(),
+        index
+    );
+    assert!(borsh::from_slice::<TreeIndex>(&[10, 0, 1, 0, 0, 0, 0x80]).is_err());
+
+    let indexes = [list[0].0, list[5].0, list[9].0];
+    let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &indexes).unwrap();
+    let decoded: MerkleProof<Node> = borsh::from_slice(&borsh::to_vec(&proof).unwrap()).unwrap();
+    assert_eq!(decoded.serialize(), proof.serialize());
+
+    let mut index = TreeIndex::zero(TREE_HEIGHT);
+    index.randomize();
+    let sampling = RandomSamplingProof::<Node>::random_sampling(&tree, &index, secret);
+    let decoded: RandomSamplingProof<Node> =
+        borsh::from_slice(&borsh::to_vec(&sampling).unwrap()).unwrap();
+    assert_eq!(decoded.serialize(), sampling.serialize());
+
+    // A hash of the wrong size is rejected.
+    assert!(borsh::from_slice::<Node>(&borsh::to_vec(&vec![0u8; 5]).unwrap()).is_err());
+    let sum = SumNodeSmt::new(7);
+    assert_eq!(
+        borsh::from_slice::<SumNodeSmt>(&borsh::to_vec(&sum).unwrap()).unwrap(),
+        sum
+    );
+}
+
+#[cfg(feature = "ssz")]
+#[test]
+fn test_ssz() {
+    use crate::ssz::{generalized_index, SszEncodable};
+    use digest::Digest;
+
+    type Node = HashNodeSmt<sha2::Sha256>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+
+    let index = TreeIndex::from_u64(10, 5);
+    let bytes = index.to_ssz();
+    assert_eq!(bytes.len(), 34);
+    assert_eq!(&bytes[..4], &[10, 0, 0x80, 0x02]);
+    assert_eq!(TreeIndex::from_ssz_bytes(&bytes).unwrap(), index);
+    let mut bad = bytes.clone();
+    bad[33] = 1;
+    assert!(TreeIndex::from_ssz_bytes(&bad).is_err());
+    assert_eq!(generalized_index(&index), Some((1 << 10) + 5));
+
+    let root = tree.get_root();
+    assert_eq!(Node::from_ssz_bytes(&root.to_ssz()).unwrap(), root);
+
+    let indexes = [list[0].0, list[5].0, list[9].0];
+    let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &indexes).unwrap();
+    let decoded = MerkleProof::<Node>::from_ssz(&proof.to_ssz()).unwrap();
+    assert_eq!(decoded.serialize(), proof.serialize());
+    assert!(MerkleProof::<Node>::from_ssz(&proof.to_ssz()[..7]).is_err());
+    assert!(proof.get_ssz_branch().is_none());
+
+    // is_valid_merkle_branch of the Ethereum consensus specs.
+    let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[list[3].0]).unwrap();
+    let (gindex, branch) = proof.get_ssz_branch().unwrap();
+    let mut value = list[3].1.to_ssz();
+    for (i, sibling) in branch.iter().enumerate() {
+        let mut hasher = sha2::Sha256::new();
+        if (gindex >> i) & 1 == 1 {
+            hasher.update(sibling.to_ssz());
+            hasher.update(&value);
+        } else {
+            hasher.update(&value);
+            hasher.update(sibling.to_ssz());
+        }
+        value = hasher.finalize().to_vec();
+    }
+    assert_eq!(value, root.to_ssz());
+}
+
+#[test]
+fn test_sorted_pair_node() {
+    use digest::Digest;
+    use node_template::SortedPairNodeSmt;
+
+    type Node = SortedPairNodeSmt<sha3::Keccak256>;
+    let a = Node::new(vec![1u8; 32]);
+    let b = Node::new(vec![2u8; 32]);
+    let mut hasher = sha3::Keccak256::new();
+    hasher.update([1u8; 32]);
+    hasher.update([2u8; 32]);
+    let expected = Node::new(hasher.finalize().to_vec());
+    assert_eq!(Node::merge(&a, &b), expected);
+    assert_eq!(Node::merge(&b, &a), expected);
+    assert_eq!(Node::merge_many(&[(&b, &a)]), vec![expected]);
+
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+    let root = tree.get_root();
+
+    let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[list[3].0]).unwrap();
+    assert!(proof.verify(&list[3].1, &root));
+    let branch = proof.get_sorted_pair_proof().unwrap();
+    assert_eq!(branch.len(), TREE_HEIGHT);
+    assert!(MerkleProof::<Node>::verify_sorted_pair_proof(
+        &branch, &list[3].1, &root
+    ));
+    assert!(!MerkleProof::<Node>::verify_sorted_pair_proof(
+        &branch, &list[4].1, &root
+    ));
+    // The proof doesn't bind the leaf to its index.
+    let mut moved = MerkleProof::<Node>::new(list[4].0);
+    moved.set_siblings(proof.get_path_siblings().to_vec());
+    assert!(moved.verify(&list[3].1, &root));
+
+    let batch =
+        MerkleProof::<Node>::generate_inclusion_proof(&tree, &[list[3].0, list[4].0]).unwrap();
+    assert!(batch.get_sorted_pair_proof().is_none());
+
+    let mut index = TreeIndex::zero(TREE_HEIGHT);
+    index.randomize();
+    let sampling = RandomSamplingProof::<Node>::random_sampling(&tree, &index, secret);
+    assert!(sampling.verify_random_sampling_proof(&root));
+}
+
+#[test]
+fn test_incremental_tree() {
+    use crate::incremental::IncrementalMerkleTree;
+
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let height = 4;
+    let zero = Node::new(vec![0u8; 32]);
+    let mut tree = IncrementalMerkleTree::<Node>::new_with_zero(height, zero.clone());
+    assert!(tree.prove_last().is_none());
+
+    let mut leaves: Vec<Node> = Vec::new();
+    for i in 0..(1u64 << height) {
+        let mut leaf = Node::default();
+        leaf.randomize();
+        assert_eq!(
+            tree.append(leaf.clone()).unwrap(),
+            TreeIndex::from_u64(height, i)
+        );
+        leaves.push(leaf.clone());
+
+        // Compute the root from all leaves with the empty positions filled by the zero leaf.
+        let mut layer = leaves.clone();
+        layer.resize(1 << height, zero.clone());
+        while layer.len() > 1 {
+            layer = layer
+                .chunks(2)
+                .map(|pair| Node::merge(&pair[0], &pair[1]))
+                .collect();
+        }
+        assert_eq!(tree.get_root(), layer[0]);
+        assert!(tree.prove_last().unwrap().verify(&leaf, &layer[0]));
+    }
+    assert!(tree.is_full());
+    assert_eq!(tree.append(zero), Err(TreeError::TreeFull));
+    assert_eq!(tree.get_leaves_num(), 1 << height);
+}
+
+#[test]
+fn test_nary_tree() {
+    use crate::nary::{NaryMerkleProof, NaryMerkleTree};
+
+    // The default merge_n agrees with merge on two children.
+    let (a, b) = (SumNodeSmt::new(3), SumNodeSmt::new(4));
+    assert_eq!(
+        Mergeable::merge_n(&[a.clone(), b.clone()]),
+        SumNodeSmt::merge(&a, &b)
+    );
+    // The default merge_n of BatchCountingNode merges as a balanced binary tree.
+    let children: Vec<BatchCountingNode> = (1..=3)
+        .map(|x| BatchCountingNode(SumNodeSmt::new(x)))
+        .collect();
+    assert_eq!(Mergeable::merge_n(&children).0, SumNodeSmt::new(6));
+
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    // A 16-ary tree of 2 levels, i.e., with leaf indexes of 8 bits.
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, 20);
+    let mut tree = NaryMerkleTree::<Node, 16>::new(2);
+    assert_eq!(tree.get_index_height(), TREE_HEIGHT);
+    tree.build(&list, secret);
+
+    for (idx, value) in &list {
+        let proof = tree.generate_proof(idx).unwrap();
+        assert_eq!(proof.get_siblings().len(), 2 * 15);
+        assert!(proof.verify(value, &tree.get_root()));
+        let decoded = NaryMerkleProof::<Node, 16>::deserialize(&proof.serialize()).unwrap();
+        assert_eq!(decoded, proof);
+    }
+    let proof = tree.generate_proof(&list[0].0).unwrap();
+    assert!(!proof.verify(&list[1].1, &tree.get_root()));
+
+    // Updating a leaf is the same as building with the updated list.
+    let mut value = Node::default();
+    value.randomize();
+    let mut updated = list.clone();
+    updated[3].1 = value.clone();
+    tree.update(&list[3].0, value, secret);
+    let mut rebuilt = NaryMerkleTree::<Node, 16>::new(2);
+    rebuilt.build(&updated, secret);
+    assert_eq!(tree.get_root(), rebuilt.get_root());
+    assert_eq!(tree.get_nodes_num(), rebuilt.get_nodes_num());
+
+    // A full 4-ary tree of sums has the same root as the binary SMT.
+    let list: Vec<(TreeIndex, SumNodeSmt)> = generate_sorted_index_value_pairs(4, 16);
+    let mut nary = NaryMerkleTree::<SumNodeSmt, 4>::new(2);
+    nary.build(&list, secret);
+    let mut binary = Smt::<SumNodeSmt>::new(4);
+    binary.build(&list, secret);
+    assert_eq!(nary.get_root(), binary.get_root());
+}
+
+#[test]
+fn test_compressed_tree() {
+    use crate::compressed::CompressedSmt;
+
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let height = 256;
+    let secret = Secret::from_bytes(&[7u8; 32]).unwrap();
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(height, LEAF_NUM);
+
+    let mut tree = Smt::<Node>::new(height);
+    assert_eq!(
+        CompressedSmt::<Node>::new(height).get_root(),
+        tree.get_root()
+    );
+    tree.build(&list, &secret);
+    let mut compressed = CompressedSmt::<Node>::new(height);
+    compressed.build(&list, &secret);
+    assert_eq!(compressed.get_root(), tree.get_root());
+    // The leaves, at most LEAF_NUM - 1 branching nodes and at most 2 * (LEAF_NUM - 1) + 1 extension tops.
+    assert!(compressed.get_nodes_num() < 4 * LEAF_NUM);
+    assert!(tree.get_nodes_num() > 100 * LEAF_NUM);
+
+    for (idx, _) in list.iter().step_by(7) {
+        let proof = compressed.generate_proof(idx, &secret).unwrap();
+        let expected = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[*idx]).unwrap();
+        assert_eq!(proof.serialize(), expected.serialize());
+    }
+    let mut missing = TreeIndex::zero(height);
+    missing.randomize();
+    assert!(compressed.generate_proof(&missing, &secret).is_none());
+
+    // Update an existing leaf and insert a new one.
+    let mut value = Node::default();
+    for key in [list[10].0, missing] {
+        value.randomize();
+        tree.update(&key, value.clone(), &secret);
+        compressed.update(&key, value.clone(), &secret);
+        assert_eq!(compressed.get_root(), tree.get_root());
+        let proof = compressed.generate_proof(&key, &secret).unwrap();
+        assert!(proof.verify(&value, &tree.get_root()));
+    }
+    assert_eq!(compressed.get_leaves_num(), LEAF_NUM + 1);
+}
+
+#[test]
+fn test_forest() {
+    use crate::forest::{get_tree_index, Forest};
+
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = Secret::from_bytes(&[3u8; 32]).unwrap();
+    let mut forest = Forest::<Node>::new(TREE_HEIGHT, 16, &secret);
+    let names = ["usd", "eur", "gbp"];
+    let lists: Vec<Vec<(TreeIndex, Node)>> = names
+        .iter()
+        .map(|_| generate_sorted_index_value_pairs(TREE_HEIGHT, 20))
+        .collect();
+    for (name, list) in names.iter().zip(&lists) {
+        forest.add_tree(name, list).unwrap();
+    }
+    assert_eq!(forest.get_names(), vec!["eur", "gbp", "usd"]);
+
+    // The forest root commits to the root of each tree.
+    let mut top: Vec<(TreeIndex, Node)> = names
+        .iter()
+        .map(|name| {
+            (
+                get_tree_index(name, 16),
+                forest.get_tree(name).unwrap().get_root(),
+            )
+        })
+        .collect();
+    top.sort_by_key(|x| x.0);
+    let mut expected = Smt::<Node>::new(16);
+    expected.build(&top, &secret);
+    assert_eq!(forest.get_root(), expected.get_root());
+
+    let (idx, value) = &lists[1][4];
+    let proof = forest.generate_proof("eur", idx).unwrap();
+    assert!(proof.verify("eur", value, &forest.get_root()));
+    assert!(!proof.verify("usd", value, &forest.get_root()));
+    assert!(!proof.verify("eur", &lists[1][5].1, &forest.get_root()));
+    assert!(forest.generate_proof("jpy", idx).is_none());
+
+    let mut new_value = Node::default();
+    new_value.randomize();
+    let old_root = forest.get_root();
+    forest.update("eur", idx, new_value.clone()).unwrap();
+    assert!(forest.get_root() != old_root);
+    let proof = forest.generate_proof("eur", idx).unwrap();
+    assert!(proof.verify("eur", &new_value, &forest.get_root()));
+    assert_eq!(
+        forest.update("jpy", idx, new_value),
+        Err(TreeError::TreeNotFound)
+    );
+    assert!(forest
+        .add_tree("usd", &[lists[0][1].clone(), lists[0][0].clone()])
+        .is_err());
+}
+
+#[test]
+fn test_subtree_completeness_proof() {
+    use crate::proof::SubtreeCompletenessProof;
+
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = Secret::from_bytes(&[5u8; 32]).unwrap();
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, 20);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, &secret);
+    let root = tree.get_root();
+
+    // A subtree root at height 2 above the first leaf.
+    let idx = list[0].0.get_prefix(2);
+    let proof = SubtreeCompletenessProof::generate(&tree, &idx, &secret).unwrap();
+    let leaves: Vec<(TreeIndex, Node)> = list
+        .iter()
+        .filter(|(index, _)| index.get_prefix(2) == idx)
+        .cloned()
+        .collect();
+    assert!(proof.verify(&leaves, &root));
+    // Omitting a leaf or adding a foreign one fails.
+    assert!(!proof.verify(&leaves[1..], &root));
+    let mut more = leaves.clone();
+    let foreign = list
+        .iter()
+        .find(|(index, _)| index.get_prefix(2) != idx)
+        .unwrap();
+    more.push(foreign.clone());
+    assert!(!proof.verify(&more, &root));
+
+    // The whole tree and a single leaf.
+    let proof = SubtreeCompletenessProof::generate(&tree, &TreeIndex::zero(0), &secret).unwrap();
+    assert!(proof.verify(&list, &root));
+    assert!(!proof.verify(&list[1..], &root));
+    let proof = SubtreeCompletenessProof::generate(&tree, &list[3].0, &secret).unwrap();
+    assert!(proof.verify(&list[3..4], &root));
+    assert!(!proof.verify(&list[4..5], &root));
+
+    // An empty subtree is a padding node.
+    let empty = Smt::<Node>::new(TREE_HEIGHT);
+    let proof =
+        SubtreeCompletenessProof::generate(&empty, &TreeIndex::zero(0), &ALL_ZEROS_SECRET).unwrap();
+    assert!(proof.verify(&[], &empty.get_root()));
+    assert!(!proof.verify(&list[..1], &empty.get_root()));
+}
+
+#[test]
+fn test_count_node() {
+    use node_template::CountNodeSmt;
+
+    type Node = CountNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, 30);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+    let root = tree.get_root();
+    assert_eq!(root.get_count(), 30);
+
+    for (rank, (idx, value)) in list.iter().enumerate() {
+        let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[*idx]).unwrap();
+        assert_eq!(proof.get_rank(value, &root), Some(rank as u64));
+        assert!(proof.verify_rank(value, &root, rank as u64));
+        assert!(!proof.verify_rank(value, &root, rank as u64 + 1));
+    }
+    let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[list[0].0]).unwrap();
+    assert_eq!(proof.get_rank(&list[1].1, &root), None);
+    assert_eq!(Node::deserialize(&root.serialize()).unwrap(), root);
+
+    let mut index = TreeIndex::zero(TREE_HEIGHT);
+    index.randomize();
+    let sampling = RandomSamplingProof::<Node>::random_sampling(&tree, &index, secret);
+    assert!(sampling.verify_random_sampling_proof(&root));
+}
+
+#[test]
+fn test_select_by_cumulative_sum() {
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, SumNodeSmt)> = (0..10)
+        .map(|i| {
+            (
+                TreeIndex::from_u64(TREE_HEIGHT, i * 7),
+                SumNodeSmt::new(i + 1),
+            )
+        })
+        .collect();
+    let mut tree = Smt::<SumNodeSmt>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+    let root = tree.get_root();
+    assert_eq!(root.get_value(), 55);
+
+    let mut offset = 0;
+    for (idx, value) in &list {
+        for target in offset..offset + value.get_value() {
+            let (selected, leaf) = tree.select_by_cumulative_sum(target).unwrap();
+            assert_eq!((selected, leaf), (*idx, value));
+            let proof =
+                MerkleProof::<SumNodeSmt>::generate_inclusion_proof(&tree, &[selected]).unwrap();
+            assert!(proof.verify_cumulative_sum(leaf, &root, target));
+            assert!(!proof.verify_cumulative_sum(leaf, &root, target + value.get_value()));
+        }
+        offset += value.get_value();
+    }
+    assert!(tree.select_by_cumulative_sum(55).is_none());
+}
+
+#[test]
+fn test_common_prefix() {
+    fn naive_len<const N: usize>(
+        a: &crate::index::TreeIndexN<N>,
+        b: &crate::index::TreeIndexN<N>,
+    ) -> usize {
+        let height = a.get_height().min(b.get_height());
+        (0..height)
+            .find(|i| a.get_bit(*i) != b.get_bit(*i))
+            .unwrap_or(height)
+    }
+
+    for height in [0, 1, 7, 63, 64, 65, 130, MAX_HEIGHT] {
+        for _ in 0..20 {
+            let mut a = TreeIndex::zero(height);
+            a.randomize();
+            // Share a random prefix so that long common prefixes are covered.
+            let shared = rand::random::<usize>() % (height + 1);
+            let mut b = TreeIndex::zero(height - shared);
+            b.randomize();
+            let mut c = a.get_prefix(shared);
+            for i in 0..b.get_height() {
+                c = if b.get_bit(i) == 0 {
+                    c.get_lch_index()
+                } else {
+                    c.get_rch_index()
+                };
+            }
+            assert_eq!(a.common_prefix_len(&c), naive_len(&a, &c));
+            assert!(a.common_prefix_len(&c) >= shared);
+            assert_eq!(
+                a.lowest_common_ancestor(&c),
+                a.get_prefix(naive_len(&a, &c))
+            );
+            let short = c.get_prefix(height / 2);
+            assert_eq!(a.common_prefix_len(&short), naive_len(&a, &short));
+        }
+    }
+    let a = TreeIndex16::from_u64(12, 0b1010_1100_0011);
+    let b = TreeIndex16::from_u64(12, 0b1010_1110_0011);
+    assert_eq!(a.common_prefix_len(&b), 6);
+    assert_eq!(a.common_prefix_len(&a), 12);
+    assert_eq!(a.lowest_common_ancestor(&b), a.get_prefix(6));
+}
+
+#[test]
+fn test_ancestor_queries() {
+    let leaf = TreeIndex::from_u64(TREE_HEIGHT, 0b1011_0110);
+    let ancestor = leaf.ancestor_at(3).unwrap();
+    assert_eq!(ancestor, TreeIndex::from_u64(3, 0b101));
+    assert_eq!(leaf.ancestor_at(0), Some(TreeIndex::zero(0)));
+    assert_eq!(leaf.ancestor_at(TREE_HEIGHT), Some(leaf));
+    assert_eq!(leaf.ancestor_at(TREE_HEIGHT + 1), None);
+
+    assert!(ancestor.is_ancestor_of(&leaf));
+    assert!(leaf.is_descendant_of(&ancestor));
+    assert!(leaf.is_ancestor_of(&leaf));
+    assert!(TreeIndex::zero(0).is_ancestor_of(&leaf));
+    assert!(!leaf.is_ancestor_of(&ancestor));
+    assert!(!ancestor.get_sibling_index().is_ancestor_of(&leaf));
+    assert!(!leaf.is_descendant_of(&ancestor.get_sibling_index()));
+}
+
+#[test]
+fn test_tree_index_range() {
+    use crate::index::TreeIndexRange;
+
+    let all: Vec<TreeIndex> = TreeIndexRange::all(4).collect();
+    let expected: Vec<TreeIndex> = (0..16).map(|i| TreeIndex::from_u64(4, i)).collect();
+    assert_eq!(all, expected);
+    let reversed: Vec<TreeIndex> = TreeIndexRange::all(4).rev().collect();
+    assert_eq!(reversed, expected.iter().rev().cloned().collect::<Vec<_>>());
+
+    let range = TreeIndexRange::new(TreeIndex::from_u64(4, 2), TreeIndex::from_u64(4, 9));
+    let stepped: Vec<TreeIndex> = range.clone().step_by(3).collect();
+    assert_eq!(stepped, vec![expected[2], expected[5], expected[8]]);
+    let mut range = range;
+    assert_eq!(range.next(), Some(expected[2]));
+    assert_eq!(range.next_back(), Some(expected[9]));
+    assert_eq!(range.count(), 6);
+
+    assert_eq!(
+        TreeIndexRange::new(expected[3], expected[3]).collect::<Vec<_>>(),
+        vec![expected[3]]
+    );
+    assert_eq!(TreeIndexRange::new(expected[3], expected[2]).count(), 0);
+    assert_eq!(TreeIndexRange::<2>::all(0).count(), 1);
+    assert_eq!(TreeIndexRange::<2>::all(8).count(), 256);
+}
+
+#[cfg(feature = "primitive-types")]
+#[test]
+fn test_u256_conversions() {
+    use primitive_types::U256;
+
+    let value = U256::from_dec_str(
+        "57896044618658097711785492504343953926634992332820282019728792003956564819969",
+    )
+    .unwrap(); // 2^255 + 1
+    let index = TreeIndex::from(value);
+    assert_eq!(index.get_height(), MAX_HEIGHT);
+    assert_eq!(index.get_bit(0), 1);
+    assert_eq!(index.get_bit(MAX_HEIGHT - 1), 1);
+    assert_eq!((1..MAX_HEIGHT - 1).map(|i| index.get_bit(i)).max(), Some(0));
+    assert_eq!(U256::from(index), value);
+
+    // The order of indexes follows the order of numbers.
+    let (a, b) = (U256::from(5u64) << 100, (U256::from(5u64) << 100) + 1);
+    assert!(TreeIndex::from(a) < TreeIndex::from(b));
+
+    // An index of a smaller height maps to its position.
+    assert_eq!(U256::from(TreeIndex::from_u64(10, 700)), U256::from(700u64));
+    assert_eq!(U256::from(TreeIndex::zero(0)), U256::zero());
+}
+
+#[test]
+fn test_seeded_randomness() {
+    use crate::utils::generate_sorted_index_value_pairs_from_seed;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let a: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs_from_seed(64, 50, 7);
+    let b: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs_from_seed(64, 50, 7);
+    let c: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs_from_seed(64, 50, 8);
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+
+    let (mut x, mut y) = (TreeIndex::zero(100), TreeIndex::zero(100));
+    x.randomize_with(&mut StdRng::seed_from_u64(1));
+    y.randomize_with(&mut StdRng::seed_from_u64(1));
+    assert_eq!(x, y);
+    assert_eq!(x, x.get_prefix(100));
+
+    let (mut u, mut v) = (SumNodeSmt::default(), SumNodeSmt::default());
+    u.randomize_with(&mut StdRng::seed_from_u64(3));
+    v.randomize_with(&mut StdRng::seed_from_u64(3));
+    assert_eq!(u, v);
+}
+
+#[test]
+fn test_merkle_proof_ref() {
+    fn check<P: Clone + Default + Debug + Mergeable + Paddable + ProofExtractable + Rand>()
+    where
+        <P as ProofExtractable>::ProofNode:
+            Debug + Clone + Default + Eq + Mergeable + MergeableEncoded + Serializable,
+    {
+        let list: Vec<(TreeIndex, P)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+        let mut tree = Smt::<P>::new(TREE_HEIGHT);
+        tree.build(&list, &ALL_ZEROS_SECRET);
+        let root = tree.get_root();
+
+        for step in [LEAF_NUM, 7, 1].iter() {
+            let indexes: Vec<TreeIndex> = list.iter().step_by(*step).map(|x| x.0).collect();
+            let leaves: Vec<P::ProofNode> = list
+                .iter()
+                .step_by(*step)
+                .map(|x| x.1.get_proof_node())
+                .collect();
+            let proof = MerkleProof::<P>::generate_inclusion_proof(&tree, &indexes).unwrap();
+            let bytes = proof.serialize();
+            let view = MerkleProofRef::<P>::parse(&bytes).unwrap();
+            assert_eq!(view.get_batch_num(), indexes.len());
+            assert_eq!(view.get_index(0), Some(indexes[0]));
+            assert_eq!(view.get_index(indexes.len()), None);
+            assert_eq!(view.to_proof().unwrap().serialize(), bytes);
+            assert!(view.verify_batch(&leaves, &root));
+            assert_eq!(view.verify(&leaves[0], &root), leaves.len() == 1);
+            let mut wrong_leaves = leaves.clone();
+            wrong_leaves[0] = P::default().get_proof_node();
+            assert!(!view.verify_batch(&wrong_leaves, &root));
+
+            // Malformed encodings are rejected when parsing.
+            assert_eq!(
+                MerkleProofRef::<P>::parse(&bytes[..bytes.len() - 1]).err(),
+                Some(DecodingError::BytesNotEnough)
+            );
+            let mut longer = bytes.clone();
+            longer.push(0);
+            assert_eq!(
+                MerkleProofRef::<P>::parse(&longer).err(),
+                Some(DecodingError::TooManyEncodedBytes)
+            );
+        }
+        let view = MerkleProofRef::<P>::parse(&[]).unwrap();
+        assert!(view.verify_batch(&[], &root));
+        assert!(!view.verify(&root, &root));
+    }
+    check::<HashNodeSmt<blake3::Hasher>>();
+    check::<SumNodeSmt>();
+    check::<MTreeNodeSmt<sha2::Sha256>>();
+}
+
+#[test]
+fn test_self_contained_proof() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, &ALL_ZEROS_SECRET);
+    let root = tree.get_root();
+
+    for step in [LEAF_NUM, 7].iter() {
+        let indexes: Vec<TreeIndex> = list.iter().step_by(*step).map(|x| x.0).collect();
+        let proof = SelfContainedProof::<Node>::generate(&tree, &indexes).unwrap();
+        assert_eq!(proof.get_root(), Some(&root));
+        assert!(proof.verify_self());
+        assert!(proof.verify_against(&root));
+        assert!(!proof.verify_against(&list[0].1));
+
+        let decoded = SelfContainedProof::<Node>::deserialize(&proof.serialize()).unwrap();
+        assert_eq!(decoded.get_leaves(), proof.get_leaves());
+        assert!(decoded.verify_self());
+
+        // Without an embedded root, the proof is only verified against an input root.
+        let unrooted = SelfContainedProof::new(
+            proof.get_merkle_proof().clone(),
+            proof.get_leaves().to_vec(),
+        );
+        assert!(!unrooted.verify_self());
+        assert!(unrooted.verify_against(&root));
+        let decoded = SelfContainedProof::<Node>::deserialize(&unrooted.serialize()).unwrap();
+        assert_eq!(decoded.get_root(), None);
+        assert!(decoded.verify_against(&root));
+    }
+    // An index that isn't a leaf has no proof.
+    let mut missing = TreeIndex::zero(TREE_HEIGHT);
+    while tree.get_leaf_by_index(&missing).is_some() {
+        missing.randomize();
+    }
+    assert!(SelfContainedProof::<Node>::generate(&tree, &[missing]).is_none());
+}
+
+#[test]
+fn test_bound_proof() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, &ALL_ZEROS_SECRET);
+    let root = tree.get_root();
+    let domain = b"accounts";
+
+    let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[list[0].0]).unwrap();
+    let bytes = proof.serialize_bound(TREE_HEIGHT, domain);
+    let leaves = [list[0].1.clone()];
+    assert_eq!(
+        MerkleProof::<Node>::verify_bound(&bytes, TREE_HEIGHT, domain, &leaves, &root),
+        Ok(true)
+    );
+    assert_eq!(
+        MerkleProof::<Node>::verify_bound(&bytes, TREE_HEIGHT, domain, &[list[1].1.clone()], &root),
+        Ok(false)
+    );
+    // The proof can't be replayed for another height or domain.
+    assert_eq!(
+        MerkleProof::<Node>::deserialize_bound(&bytes, 256, domain).err(),
+        Some(DecodingError::HeightNotMatch)
+    );
+    assert_eq!(
+        MerkleProof::<Node>::deserialize_bound(&bytes, TREE_HEIGHT, b"balances").err(),
+        Some(DecodingError::DomainNotMatch)
+    );
+    // The proved index must also be of the bound height.
+    let forged = proof.serialize_bound(256, domain);
+    assert_eq!(
+        MerkleProof::<Node>::deserialize_bound(&forged, 256, domain).err(),
+        Some(DecodingError::HeightNotMatch)
+    );
+    // The domain tag is a label not authenticated by the root.
+    assert_eq!(
+        MerkleProof::<Node>::verify_bound(
+            &proof.serialize_bound(TREE_HEIGHT, b"balances"),
+            TREE_HEIGHT,
+            b"balances",
+            &leaves,
+            &root
+        ),
+        Ok(true)
+    );
+
+    // The batched proof with an empty domain tag.
+    let indexes: Vec<TreeIndex> = list.iter().map(|x| x.0).collect();
+    let leaves: Vec<Node> = list.iter().map(|x| x.1.clone()).collect();
+    let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &indexes).unwrap();
+    let bytes = proof.serialize_bound(TREE_HEIGHT, &[]);
+    assert_eq!(
+        MerkleProof::<Node>::verify_bound(&bytes, TREE_HEIGHT, &[], &leaves, &root),
+        Ok(true)
+    );
+}
+
+#[test]
+fn test_constant_time_eq() {
+    use subtle::ConstantTimeEq;
+
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let a = Node::new(vec![1u8; 32]);
+    let mut bytes = vec![1u8; 32];
+    bytes[31] = 2;
+    let b = Node::new(bytes);
+    assert!(bool::from(a.ct_eq(&a.clone())));
+    assert!(!bool::from(a.ct_eq(&b)));
+    assert_ne!(a, b);
+    // Hashes of different lengths are different.
+    assert_ne!(a, Node::new(vec![1u8; 31]));
+
+    let count = node_template::CountNodeSmt::<blake3::Hasher>::new_with_count(2, vec![1u8; 32]);
+    let other = node_template::CountNodeSmt::<blake3::Hasher>::new_with_count(3, vec![1u8; 32]);
+    assert_eq!(count, count.clone());
+    assert_ne!(count, other);
+}
+
+#[test]
+fn test_hashwires_padding_versions() {
+    use node_template::HashWiresNodeSmt;
+
+    let idx = TreeIndex::from_u64(8, 5);
+    let secret = Secret::from_bytes(&[7u8; 32]).unwrap();
+    let v1 = HashWiresNodeSmt::<blake3::Hasher>::padding(&idx, &secret);
+    let v2 = HashWiresNodeSmt::<blake3::Hasher, 2>::padding(&idx, &secret);
+    let v3 = HashWiresNodeSmt::<blake3::Hasher, 3>::padding(&idx, &secret);
+    assert_ne!(v1.get_hash(), v2.get_hash());
+    assert_ne!(v2.get_hash(), v3.get_hash());
+    // The default padding node is the pre-image of the provable ones.
+    let proof_v2 = v2.prove_padding_node(&idx, &secret);
+    assert_eq!(proof_v2.get_hash(), v1.get_hash());
+    // The tagged padding node follows the same construction as that of HashNodeSmt.
+    assert_eq!(
+        v3.get_hash(),
+        HashNodeSmt::<blake3::Hasher>::padding(&idx, &secret).get_hash()
+    );
+
+    // The padding proofs are only valid for their own version.
+    assert!(HashWiresNodeSmt::<blake3::Hasher, 2>::verify_padding_node(
+        &v2, &proof_v2, &idx
+    ));
+    let proof_v3 = v3.prove_padding_node(&idx, &secret);
+    assert!(HashWiresNodeSmt::<blake3::Hasher, 3>::verify_padding_node(
+        &v3, &proof_v3, &idx
+    ));
+    assert!(!HashWiresNodeSmt::<blake3::Hasher, 2>::verify_padding_node(
+        &HashWiresNodeSmt::new(v3.get_hash().to_vec()),
+        &proof_v2,
+        &idx
+    ));
+}
+
+#[test]
+fn test_hashwires_released_root() {
+    use node_template::HashWiresNodeSmt;
+
+    // The root of this tree computed by smtree 0.1.2, which the default version reproduces.
+    let secret = Secret::from_bytes(&[7u8; 32]).unwrap();
+    let list: Vec<(TreeIndex, HashWiresNodeSmt<sha2::Sha256>)> = [3u64, 77, 200]
+        .iter()
+        .map(|&i| {
+            (
+                TreeIndex::from_u64(8, i),
+                HashWiresNodeSmt::new(vec![i as u8; 32]),
+            )
+        })
+        .collect();
+    let mut tree = Smt::<HashWiresNodeSmt<sha2::Sha256>>::new(8);
+    tree.build(&list, &secret);
+    let root: String = tree
+        .get_root()
+        .serialize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    assert_eq!(
+        root,
+        "e4c124ca789637a59b81fec3b503f199652b8e4b08f0b5be8115ac1ce8a1e990"
+    );
+}
+
+#[test]
+fn test_payload_node() {
+    use digest::Digest;
+    use node_template::PayloadNodeSmt;
+
+    type Node = PayloadNodeSmt<blake3::Hasher>;
+    Tester::<Node>::test();
+
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, 20);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+
+    // The tree has the same root as the tree of the payload hashes.
+    let hashes: Vec<(TreeIndex, HashNodeSmt<blake3::Hasher>)> = list
+        .iter()
+        .map(|(idx, value)| (*idx, HashNodeSmt::new(value.get_hash().to_vec())))
+        .collect();
+    let mut hash_tree = Smt::<HashNodeSmt<blake3::Hasher>>::new(TREE_HEIGHT);
+    hash_tree.build(&hashes, secret);
+    assert_eq!(tree.get_root(), hash_tree.get_root());
+    assert_eq!(tree.get_root_raw().get_payload(), None);
+
+    // The payload is stored in the leaf, and proved by its hash.
+    let (idx, value) = &list[0];
+    let leaf = tree.get_leaf_by_index(idx).unwrap().get_value();
+    assert_eq!(leaf.get_payload(), value.get_payload());
+    let digest = blake3::Hasher::digest(leaf.get_payload().unwrap()).to_vec();
+    let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[*idx]).unwrap();
+    assert!(proof.verify(&HashNodeSmt::new(digest), &tree.get_root()));
+}
+
+#[cfg(feature = "pedersen")]
+#[test]
+fn test_pedersen_node() {
+    use crate::pedersen::{PedersenNodeSmt, PedersenPaddingProof, PedersenProofNode};
+
+    type Node = PedersenNodeSmt<blake3::Hasher>;
+    let secret = ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, 20);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, &secret);
+
+    // The root commits to the total balance, and the owner can open it.
+    let total: u64 = list.iter().map(|(_, x)| x.get_value()).sum();
+    let root = tree.get_root_raw();
+    assert_eq!(root.get_value(), total);
+    assert!(tree.get_root().verify_opening(total, root.get_blinding()));
+    assert!(!tree
+        .get_root()
+        .verify_opening(total + 1, root.get_blinding()));
+
+    // The proof nodes and padding proofs are encoded.
+    let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[list[0].0]).unwrap();
+    let decoded = MerkleProof::<Node>::deserialize(&proof.serialize()).unwrap();
+    assert!(decoded.verify(&list[0].1.get_proof_node(), &tree.get_root()));
+    let padding = PedersenPaddingProof::new(*root.get_blinding());
+    assert_eq!(
+        PedersenPaddingProof::deserialize(&padding.serialize()),
+        Ok(padding)
+    );
+    assert!(PedersenProofNode::<blake3::Hasher>::deserialize(&[0xffu8; 64]).is_err());
+
+    // Random sampling proves the padding nodes to commit to zero balances.
+    let mut missing = list[0].0;
+    while tree.get_leaf_by_index(&missing).is_some() {
+        missing.randomize();
+    }
+    assert!(Tester::<Node>::random_sampling(&tree, &missing));
+
+    // Merging rejects an overflowing sum of balances.
+    let max = Node::new(u64::MAX, *root.get_blinding());
+    let one = Node::new(1, *root.get_blinding());
+    assert!(Node::checked_merge(&max, &one).is_none());
+    assert!(std::panic::catch_unwind(|| Node::merge(&max, &one)).is_err());
+    assert_eq!(
+        Node::checked_merge(&max, &Node::new(0, *root.get_blinding())).map(|x| x.get_value()),
+        Some(u64::MAX)
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_node() {
+    use std::collections::HashMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::serde_node::{from_canonical_bytes, to_canonical_bytes, SerdeNodeSmt};
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Account {
+        name: String,
+        balances: HashMap<String, u64>,
+    }
+
+    type Node = SerdeNodeSmt<Account, blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let mut list: Vec<(TreeIndex, Node)> = Vec::new();
+    for (i, idx) in
+        generate_sorted_index_value_pairs::<HashNodeSmt<blake3::Hasher>>(TREE_HEIGHT, 10)
+            .into_iter()
+            .map(|(idx, _)| idx)
+            .enumerate()
+    {
+        let balances = (0..5)
+            .map(|j| (format!("coin{}", j), (i * j) as u64))
+            .collect();
+        let account = Account {
+            name: format!("user{}", i),
+            balances,
+        };
+        list.push((idx, Node::new(account)));
+    }
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+
+    // The tree has the same root as the tree of the hashes of the canonical bytes.
+    let hashes: Vec<(TreeIndex, HashNodeSmt<blake3::Hasher>)> = list
+        .iter()
+        .map(|(idx, value)| (*idx, HashNodeSmt::new(value.get_hash().to_vec())))
+        .collect();
+    let mut hash_tree = Smt::<HashNodeSmt<blake3::Hasher>>::new(TREE_HEIGHT);
+    hash_tree.build(&hashes, secret);
+    assert_eq!(tree.get_root(), hash_tree.get_root());
+
+    let (idx, value) = &list[0];
+    let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[*idx]).unwrap();
+    assert!(proof.verify(&value.get_proof_node(), &tree.get_root()));
+
+    // The canonical bytes don't depend on the insertion order of the maps.
+    let account = value.get_value().unwrap().clone();
+    let mut reordered = account.clone();
+    let mut entries: Vec<(String, u64)> = account.balances.clone().into_iter().collect();
+    entries.reverse();
+    reordered.balances = entries.into_iter().collect();
+    assert_eq!(
+        to_canonical_bytes(&account).unwrap(),
+        to_canonical_bytes(&reordered).unwrap()
+    );
+    assert_eq!(*value, Node::new(reordered));
+
+    // Leaves and internal nodes roundtrip.
+    let decoded = Node::deserialize(&value.serialize()).unwrap();
+    assert_eq!(decoded.get_value(), Some(&account));
+    assert_eq!(decoded, *value);
+    let root = tree.get_root_raw();
+    assert_eq!(Node::deserialize(&root.serialize()).unwrap(), *root);
+
+    // Non-canonical encodings are rejected: here the integer 1 encoded on two bytes.
+    let mut begin = 0;
+    assert_eq!(from_canonical_bytes::<u64>(&[0x01], &mut begin), Ok(1));
+    assert!(from_canonical_bytes::<u64>(&[0x18, 0x01], &mut 0).is_err());
+}
+
+#[test]
+fn test_tree_builder() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = Secret::from_bytes(&[1u8; 32]).unwrap();
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+
+    // The builder builds the same tree as new() + build().
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, &secret);
+    let built = TreeBuilder::new(TREE_HEIGHT)
+        .secret(&secret)
+        .parallelism(4)
+        .build_from(&list)
+        .unwrap();
+    assert_eq!(built.get_root(), tree.get_root());
+    assert_eq!(built.get_nodes_num(), tree.get_nodes_num());
+
+    // The padding policies ignore the secret of the builder.
+    let mut zero_tree = Smt::<Node>::new(TREE_HEIGHT);
+    zero_tree.build(&list, &ALL_ZEROS_SECRET);
+    let builder = TreeBuilder::new(TREE_HEIGHT).secret(&secret);
+    let zero = builder
+        .clone()
+        .padding_policy(PaddingPolicy::Zero)
+        .build_from(&list)
+        .unwrap();
+    assert_eq!(zero.get_root(), zero_tree.get_root());
+    let random = builder
+        .padding_policy(PaddingPolicy::Random)
+        .build_from(&list)
+        .unwrap();
+    assert_ne!(random.get_root(), tree.get_root());
+    assert_ne!(random.get_root(), zero_tree.get_root());
+
+    // Errors are returned instead of panicking.
+    let mut unsorted = list.clone();
+    unsorted.swap(0, 1);
+    let result = TreeBuilder::new(TREE_HEIGHT).build_from(&unsorted);
+    assert_eq!(result.unwrap_err().get_kind(), TreeError::IndexNotSorted);
+    let result = TreeBuilder::new(MAX_HEIGHT + 1).build_from::<Node>(&[]);
+    assert_eq!(result.unwrap_err(), TreeError::ExceedMaxHeight);
+}
+
+#[test]
+fn test_tree_clone_eq() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+
+    let mut cloned = tree.clone();
+    assert_eq!(cloned, tree);
+    let (idx, _) = &list[0];
+    let mut value = Node::default();
+    value.randomize();
+    cloned.update(idx, value.clone(), secret);
+    assert_ne!(cloned, tree);
+
+    // The comparison doesn't depend on the order of the nodes in memory.
+    let mut updated = list.clone();
+    updated[0].1 = value;
+    let mut rebuilt = Smt::<Node>::new(TREE_HEIGHT);
+    rebuilt.build(&updated, secret);
+    assert_eq!(cloned, rebuilt);
+    assert_ne!(
+        Smt::<Node>::new(TREE_HEIGHT),
+        Smt::<Node>::new(TREE_HEIGHT - 1)
+    );
+}
+
+#[test]
+fn test_tree_capacity() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    for leaf_num in [1, 10, LEAF_NUM, 1 << TREE_HEIGHT] {
+        let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, leaf_num);
+        let mut tree = Smt::<Node>::with_capacity(TREE_HEIGHT, leaf_num);
+        let capacity = tree.nodes.capacity();
+        tree.build(&list, secret);
+        // The build doesn't reallocate the node list.
+        assert!(tree.get_nodes_num() <= capacity);
+        assert_eq!(tree.nodes.capacity(), capacity);
+    }
+
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.reserve(LEAF_NUM);
+    assert!(tree.nodes.capacity() > LEAF_NUM);
+    let mut deep = Smt::<Node>::new(MAX_HEIGHT);
+    deep.reserve(LEAF_NUM);
+    assert!(deep.nodes.capacity() >= 2 * MAX_HEIGHT);
+}
+
+#[test]
+fn test_tree_rebuild() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+    let capacity = tree.nodes.capacity();
+
+    // Rebuilding with another list reuses the node list.
+    let other: Vec<(TreeIndex, Node)> =
+        generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM / 2);
+    tree.rebuild(&other, secret).unwrap();
+    let mut expected = Smt::<Node>::new(TREE_HEIGHT);
+    expected.build(&other, secret);
+    assert_eq!(tree, expected);
+    assert_eq!(tree.nodes.capacity(), capacity);
+
+    // An invalid list leaves the tree unchanged.
+    let mut unsorted = list.clone();
+    unsorted.swap(0, 1);
+    assert!(tree.rebuild(&unsorted, secret).is_err());
+    assert_eq!(tree, expected);
+
+    // An empty list empties the tree.
+    tree.rebuild(&[], secret).unwrap();
+    assert_eq!(tree, Smt::<Node>::new(TREE_HEIGHT));
+    assert_eq!(tree.get_nodes_num(), 1);
+}
+
+#[test]
+fn test_tree_from_list() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = Secret::from_bytes(&[1u8; 32]).unwrap();
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, &secret);
+    assert_eq!(Smt::from_list(TREE_HEIGHT, &list, &secret), Ok(tree));
+
+    let result = Smt::from_list(TREE_HEIGHT + 1, &list, &secret);
+    assert_eq!(result.unwrap_err().get_kind(), TreeError::HeightNotMatch);
+    let result = Smt::<Node>::from_list(MAX_HEIGHT + 1, &[], &secret);
+    assert_eq!(result.unwrap_err(), TreeError::ExceedMaxHeight);
+}
+
+#[test]
+fn test_new_with_secret() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = Secret::from_bytes(&[1u8; 32]).unwrap();
+    let padding = Node::padding(&TreeIndex::zero(0), &secret);
+
+    // The empty trees are padded with the secret.
+    let empty = Smt::<Node>::new_with_secret(TREE_HEIGHT, &secret);
+    assert_eq!(*empty.get_root_raw(), padding);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&[], &secret);
+    assert_eq!(tree, empty);
+
+    // Building from an empty list empties the tree.
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, 1);
+    tree.build(&list, &secret);
+    tree.build(&[], &secret);
+    assert_eq!(tree, empty);
+
+    // Inserting a leaf into the empty tree results in the one-leaf tree.
+    let mut updated = empty.clone();
+    updated.update(&list[0].0, list[0].1.clone(), &secret);
+    assert_eq!(Smt::from_list(TREE_HEIGHT, &list, &secret), Ok(updated));
+}
+
+#[test]
+fn test_partial_tree() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+
+    let chosen: Vec<TreeIndex> = list.iter().step_by(10).map(|(idx, _)| *idx).collect();
+    let partial = PartialSmt::from_tree(&tree, &chosen).unwrap();
+    assert_eq!(*partial.get_root(), tree.get_root());
+    assert!(partial.get_nodes_num() < tree.get_nodes_num());
+    assert_eq!(partial.get_leaf_by_index(&chosen[1]), Some(&list[10].1));
+    let mut missing = list[0].0;
+    while tree.get_leaf_by_index(&missing).is_some() {
+        missing.randomize();
+    }
+    assert!(PartialSmt::from_tree(&tree, &[missing]).is_none());
+
+    // The partial tree serves the same proofs as the full tree.
+    for subset in [&chosen[..1], &chosen[2..5]] {
+        let proof = partial.generate_proof(subset).unwrap();
+        let expected = MerkleProof::<Node>::generate_inclusion_proof(&tree, subset).unwrap();
+        assert_eq!(proof.serialize(), expected.serialize());
+    }
+    assert!(partial.generate_proof(&[list[1].0]).is_none());
+
+    // The partial tree constructed from a proof is the same.
+    let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &chosen).unwrap();
+    let leaves: Vec<Node> = list.iter().step_by(10).map(|(_, x)| x.clone()).collect();
+    let mut from_proof = PartialSmt::from_proof(&proof, &leaves).unwrap();
+    assert_eq!(from_proof.get_root(), partial.get_root());
+    assert!(PartialSmt::from_proof(&proof, &leaves[1..]).is_none());
+
+    // Updates of the chosen leaves follow the full tree.
+    let mut value = Node::default();
+    value.randomize();
+    tree.update(&chosen[3], value.clone(), secret);
+    from_proof.update(&chosen[3], value.clone()).unwrap();
+    assert_eq!(*from_proof.get_root(), tree.get_root());
+    assert_eq!(
+        from_proof.update(&list[1].0, value),
+        Err(TreeError::LeafNotFound)
+    );
+}
+
+#[test]
+fn test_compute_updated_root() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+
+    let (idx, old_leaf) = &list[LEAF_NUM / 2];
+    let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[*idx]).unwrap();
+    let root = tree.get_root();
+    let mut new_leaf = Node::default();
+    new_leaf.randomize();
+    let new_root = proof
+        .compute_updated_root(old_leaf, &new_leaf, &root)
+        .unwrap();
+    tree.update(idx, new_leaf.clone(), secret);
+    assert_eq!(new_root, tree.get_root());
+    assert!(proof.verify(&new_leaf, &new_root));
+
+    // The old leaf must be proved against the old root.
+    assert_eq!(
+        proof.compute_updated_root(old_leaf, &new_leaf, &new_root),
+        None
+    );
+    assert_eq!(proof.compute_updated_root(&new_leaf, old_leaf, &root), None);
+}
+
+#[test]
+fn test_compute_updated_root_batch() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+    let root = tree.get_root();
+
+    let chosen: Vec<(TreeIndex, Node)> = list.iter().step_by(7).cloned().collect();
+    let indexes: Vec<TreeIndex> = chosen.iter().map(|(idx, _)| *idx).collect();
+    let old_leaves: Vec<Node> = chosen.iter().map(|(_, x)| x.clone()).collect();
+    let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &indexes).unwrap();
+    let mut partial = PartialSmt::from_proof(&proof, &old_leaves).unwrap();
+
+    // Apply a block of updates to the full tree, the proof and the partial tree.
+    let updates: Vec<(TreeIndex, Node)> = indexes
+        .iter()
+        .map(|idx| {
+            let mut value = Node::default();
+            value.randomize();
+            (*idx, value)
+        })
+        .collect();
+    let new_leaves: Vec<Node> = updates.iter().map(|(_, x)| x.clone()).collect();
+    for (idx, value) in &updates {
+        tree.update(idx, value.clone(), secret);
+    }
+    let new_root = proof
+        .compute_updated_root_batch(&old_leaves, &new_leaves, &root)
+        .unwrap();
+    assert_eq!(new_root, tree.get_root());
+    partial.update_batch(&updates).unwrap();
+    assert_eq!(*partial.get_root(), new_root);
+
+    // The old leaves must be proved against the old root.
+    assert!(proof
+        .compute_updated_root_batch(&old_leaves, &new_leaves, &new_root)
+        .is_none());
+    assert!(proof
+        .compute_updated_root_batch(&old_leaves, &new_leaves[1..], &root)
+        .is_none());
+    assert_eq!(
+        partial.update_batch(&[(list[1].0, Node::default())]),
+        Err(TreeError::LeafNotFound)
+    );
+    assert_eq!(*partial.get_root(), new_root);
+}
+
+#[test]
+fn test_nested_proof() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let mut inners = Vec::new();
+    let mut outer_list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, 3);
+    for item in outer_list.iter_mut() {
+        let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, 10);
+        let mut inner = Smt::<Node>::new(TREE_HEIGHT);
+        inner.build(&list, secret);
+        item.1 = inner.get_root_raw().clone();
+        inners.push((inner, list));
+    }
+    let mut outer = Smt::<Node>::new(TREE_HEIGHT);
+    outer.build(&outer_list, secret);
+
+    let (inner, list) = &inners[1];
+    let indexes = [list[2].0, list[5].0];
+    let leaves = [list[2].1.clone(), list[5].1.clone()];
+    let proof =
+        NestedProof::<Node, Node>::generate(inner, &indexes, &outer, &outer_list[1].0).unwrap();
+    assert!(proof.verify(&leaves, &outer.get_root()));
+    assert!(!proof.verify(&[leaves[1].clone(), leaves[0].clone()], &outer.get_root()));
+    assert!(!proof.verify(&leaves, &inner.get_root()));
+
+    // The proof is one object with one encoding.
+    let decoded = NestedProof::<Node, Node>::deserialize(&proof.serialize()).unwrap();
+    assert!(decoded.verify(&leaves, &outer.get_root()));
+    assert_eq!(decoded.get_inner_root(), proof.get_inner_root());
+
+    // The outer leaf must commit to the root of the inner tree.
+    assert!(
+        NestedProof::<Node, Node>::generate(inner, &indexes, &outer, &outer_list[0].0).is_none()
+    );
+}
+
+#[cfg(feature = "server")]
+#[test]
+fn test_proof_provider() {
+    use crate::error::ProviderError;
+    use crate::server::{ProofProvider, ProofRequest, TreeProofProvider};
+
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+    let root = tree.get_root();
+    let provider = TreeProofProvider::new(tree, secret);
+
+    // The requests are served from their encodings.
+    let request = ProofRequest::Root;
+    let response = provider.handle_bytes(&request.serialize()).unwrap();
+    assert_eq!(Node::deserialize(&response), Ok(root.clone()));
+
+    let request = ProofRequest::Inclusion(vec![list[0].0, list[1].0]);
+    assert_eq!(
+        ProofRequest::deserialize(&request.serialize()),
+        Ok(request.clone())
+    );
+    let response = provider.handle_bytes(&request.serialize()).unwrap();
+    let proof = MerkleProof::<Node>::deserialize(&response).unwrap();
+    assert!(proof.verify_batch(&[list[0].1.clone(), list[1].1.clone()], &root));
+
+    let mut idx = list[0].0;
+    idx.randomize();
+    let request = ProofRequest::RandomSampling(idx);
+    let response = provider.handle_bytes(&request.serialize()).unwrap();
+    let proof = RandomSamplingProof::<Node>::deserialize(&response).unwrap();
+    assert!(proof.verify_random_sampling_proof(&root));
+
+    // Invalid requests are rejected.
+    assert!(matches!(
+        provider.handle_bytes(&[3]),
+        Err(ProviderError::BadRequest { .. })
+    ));
+    let request = ProofRequest::Inclusion(vec![list[1].0, list[0].0]);
+    assert!(matches!(
+        provider.handle(&request),
+        Err(ProviderError::BadRequest { .. })
+    ));
+    let request = ProofRequest::RandomSampling(TreeIndex::zero(TREE_HEIGHT - 1));
+    assert!(matches!(
+        provider.handle(&request),
+        Err(ProviderError::BadRequest { .. })
+    ));
+    let mut missing = list[0].0;
+    while provider.get_tree().get_leaf_by_index(&missing).is_some() {
+        missing.randomize();
+    }
+    let request = ProofRequest::Inclusion(vec![missing]);
+    assert_eq!(provider.handle(&request), Err(ProviderError::NotFound));
+}
+
+#[cfg(feature = "test-vectors")]
+#[test]
+fn test_test_vectors() {
+    use crate::test_vectors::{check_test_vector, compute_test_vector, TEST_VECTORS};
+
+    for vector in TEST_VECTORS {
+        let checked = match vector.digest {
+            "Blake3" => check_test_vector::<blake3::Hasher>(vector),
+            "Blake2b" => check_test_vector::<blake2::Blake2b>(vector),
+            "Sha2" => check_test_vector::<sha2::Sha256>(vector),
+            "Sha3" => check_test_vector::<sha3::Sha3_256>(vector),
+            x => panic!("Unknown digest {}", x),
+        };
+        assert!(checked);
+    }
+    // Every digest is covered in both modes.
+    assert_eq!(TEST_VECTORS.len(), 8);
+
+    // A vector doesn't pass with another digest.
+    assert!(!check_test_vector::<sha2::Sha256>(&TEST_VECTORS[0]));
+    let result = compute_test_vector::<sha2::Sha256>(&TEST_VECTORS[0]).unwrap();
+    assert_ne!(result.root, TEST_VECTORS[0].root);
+}
+
+#[test]
+fn test_export_all_proofs() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+    let root = tree.get_root();
+
+    // The exported proofs are the same as those generated one by one, from left to right.
+    let proofs: Vec<(TreeIndex, MerkleProof<Node>)> = tree.export_all_proofs().collect();
+    assert_eq!(proofs.len(), list.len());
+    for ((idx, proof), (leaf_idx, leaf)) in proofs.iter().zip(list.iter()) {
+        assert_eq!(idx, leaf_idx);
+        let expected = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[*idx]).unwrap();
+        assert_eq!(proof.serialize(), expected.serialize());
+        assert!(proof.verify(leaf, &root));
+    }
+
+    // An empty tree has no proof.
+    assert_eq!(Smt::<Node>::new(TREE_HEIGHT).export_all_proofs().count(), 0);
+}
+
+#[test]
+fn test_serialize_into() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+
+    // The streamed encodings are the serialized ones with the nodes prefixed by their lengths.
+    let indexes: Vec<TreeIndex> = list.iter().step_by(10).map(|x| x.0).collect();
+    let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &indexes).unwrap();
+    let mut bytes = Vec::new();
+    proof.serialize_into(&mut bytes).unwrap();
+    let serialized = proof.serialize();
+    let siblings_len = proof.get_siblings_num() * (UNIT_LEN_BYTE_NUM + 32);
+    assert_eq!(
+        bytes[..bytes.len() - siblings_len],
+        serialized[..serialized.len() - proof.get_siblings_num() * 32]
+    );
+    assert_eq!(
+        bytes[bytes.len() - siblings_len..bytes.len() - siblings_len + UNIT_LEN_BYTE_NUM],
+        u64_to_bytes(32, UNIT_LEN_BYTE_NUM)[..]
+    );
+    let mut bytes = Vec::new();
+    MerkleProof::<Node>::new_batch(&[])
+        .serialize_into(&mut bytes)
+        .unwrap();
+    assert!(bytes.is_empty());
+
+    let mut idx = TreeIndex::zero(TREE_HEIGHT);
+    idx.randomize();
+    let proof = RandomSamplingProof::<Node>::random_sampling(&tree, &idx, secret);
+    let mut bytes = Vec::new();
+    proof.serialize_into(&mut bytes).unwrap();
+    let units_num = proof.get_padding_proofs().len()
+        + proof.get_merkle_proof().get_siblings_num()
+        + proof.get_leaves().len();
+    assert_eq!(
+        bytes.len(),
+        proof.serialize().len() + units_num * UNIT_LEN_BYTE_NUM
+    );
+
+    // A snapshot is the metadata followed by the nodes.
+    let mut bytes = Vec::new();
+    tree.serialize_into(&mut bytes).unwrap();
+    let mut expected = Vec::new();
+    write_unit(&mut expected, &tree.get_metadata().serialize()).unwrap();
+    for i in 0..tree.get_nodes_num() {
+        write_unit(&mut expected, &tree.get_node_by_ref(i).serialize()).unwrap();
+    }
+    assert_eq!(bytes, expected);
+
+    // A full writer fails the streaming.
+    let mut buffer = [0u8; 16];
+    let mut writer: &mut [u8] = &mut buffer;
+    assert!(tree.serialize_into(&mut writer).is_err());
+}
+
+#[test]
+fn test_deserialize_from() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+    let root = tree.get_root();
+
+    // Concatenated proofs are read one after another.
+    let indexes: Vec<TreeIndex> = list.iter().step_by(10).map(|x| x.0).collect();
+    let batch = MerkleProof::<Node>::generate_inclusion_proof(&tree, &indexes).unwrap();
+    let single = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[list[1].0]).unwrap();
+    let mut idx = TreeIndex::zero(TREE_HEIGHT);
+    idx.randomize();
+    let sampling = RandomSamplingProof::<Node>::random_sampling(&tree, &idx, secret);
+    let mut bytes = Vec::new();
+    batch.serialize_into(&mut bytes).unwrap();
+    sampling.serialize_into(&mut bytes).unwrap();
+    single.serialize_into(&mut bytes).unwrap();
+
+    let mut reader: &[u8] = &bytes;
+    let proof = MerkleProof::<Node>::deserialize_from(&mut reader).unwrap();
+    assert_eq!(proof.serialize(), batch.serialize());
+    let proof = RandomSamplingProof::<Node>::deserialize_from(&mut reader).unwrap();
+    assert_eq!(proof.serialize(), sampling.serialize());
+    assert!(proof.verify_random_sampling_proof(&root));
+    let proof = MerkleProof::<Node>::deserialize_from(&mut reader).unwrap();
+    assert!(proof.verify(&list[1].1, &root));
+    // The end of the stream is read as the empty proof.
+    let proof = MerkleProof::<Node>::deserialize_from(&mut reader).unwrap();
+    assert_eq!(proof.get_batch_num(), 0);
+
+    // A truncated proof can't be read.
+    let mut reader: &[u8] = &bytes[..bytes.len() - 1];
+    MerkleProof::<Node>::deserialize_from(&mut reader).unwrap();
+    RandomSamplingProof::<Node>::deserialize_from(&mut reader).unwrap();
+    assert_eq!(
+        MerkleProof::<Node>::deserialize_from(&mut reader).unwrap_err(),
+        DecodingError::BytesNotEnough
+    );
+
+    // A snapshot is read back as the same tree.
+    let mut bytes = Vec::new();
+    tree.serialize_into(&mut bytes).unwrap();
+    bytes.push(0u8);
+    let mut reader: &[u8] = &bytes;
+    assert_eq!(Smt::<Node>::deserialize_from(&mut reader).unwrap(), tree);
+    assert_eq!(reader, &[0u8]);
+
+    // A snapshot whose links don't form a tree can't be read.
+    // The links of the root follow the length and the type of its unit.
+    let node_len = UNIT_LEN_BYTE_NUM + 1 + 3 * 8 + 32;
+    let begin = UNIT_LEN_BYTE_NUM
+        + tree.get_metadata().serialize().len()
+        + tree.get_metadata().root * node_len
+        + UNIT_LEN_BYTE_NUM
+        + 1;
+    let parent = begin..begin + 8;
+    let lch = begin + 8..begin + 16;
+    let mut tampered = bytes.clone();
+    tampered[parent].copy_from_slice(&u64_to_bytes(1, 8));
+    assert!(matches!(
+        Smt::<Node>::deserialize_from(&mut &tampered[..]),
+        Err(DecodingError::ValueDecodingError { .. })
+    ));
+    let mut tampered = bytes.clone();
+    tampered[lch].copy_from_slice(&u64_to_bytes(tree.get_nodes_num() as u64 + 1, 8));
+    assert!(matches!(
+        Smt::<Node>::deserialize_from(&mut &tampered[..]),
+        Err(DecodingError::ValueDecodingError { .. })
+    ));
+
+    // A unit is read by its length, whatever error its decoding returns on truncated bytes.
+    let decode = |bytes: &[u8], begin: &mut usize| match bytes.get(*begin..*begin + 3) {
+        Some(x) => {
+            *begin += 3;
+            Ok(x.to_vec())
+        }
+        None => Err(DecodingError::ValueDecodingError {
+            msg: "Truncated".to_string(),
+        }),
+    };
+    let mut bytes = Vec::new();
+    write_unit(&mut bytes, &[1, 2, 3]).unwrap();
+    write_unit(&mut bytes, &[4, 5, 6, 7]).unwrap();
+    let mut reader: &[u8] = &bytes;
+    assert_eq!(read_unit(&mut reader, decode), Ok(vec![1, 2, 3]));
+    assert_eq!(
+        read_unit(&mut reader, decode),
+        Err(DecodingError::TooManyEncodedBytes)
+    );
+    let mut reader: &[u8] = &bytes[..UNIT_LEN_BYTE_NUM + 2];
+    assert_eq!(
+        read_unit(&mut reader, decode),
+        Err(DecodingError::BytesNotEnough)
+    );
+}
+
+#[test]
+fn test_smt_error() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+
+    // Tree operations and decoding are chained with `?`.
+    fn prove_and_decode(
+        height: usize,
+        list: &[(TreeIndex, Node)],
+        secret: &Secret,
+        suffix: &[u8],
+    ) -> Result<MerkleProof<Node>, SmtError> {
+        let tree = Smt::from_list(height, list, secret)?;
+        let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[list[0].0]).unwrap();
+        let mut bytes = proof.serialize();
+        bytes.extend_from_slice(suffix);
+        Ok(MerkleProof::<Node>::deserialize(&bytes)?)
+    }
+
+    let proof = prove_and_decode(TREE_HEIGHT, &list, secret, &[]).unwrap();
+    assert!(proof.verify(
+        &list[0].1,
+        &Smt::from_list(TREE_HEIGHT, &list, secret)
+            .unwrap()
+            .get_root()
+    ));
+    let err = prove_and_decode(TREE_HEIGHT + 1, &list, secret, &[]).unwrap_err();
+    assert!(matches!(err, SmtError::Tree(ref e) if e.get_kind() == TreeError::HeightNotMatch));
+    let err = prove_and_decode(TREE_HEIGHT, &list, secret, &[0u8]).unwrap_err();
+    assert_eq!(err, SmtError::Decoding(DecodingError::TooManyEncodedBytes));
+    assert!(std::error::Error::source(&err).is_some());
+}
+
+#[test]
+fn test_checked_tree_index() {
+    let idx = TreeIndex::try_from_u32(TREE_HEIGHT, 5).unwrap();
+    assert_eq!(idx, TreeIndex::from_u32(TREE_HEIGHT, 5));
+    assert_eq!(idx.try_get_bit(TREE_HEIGHT - 1), Some(1));
+    assert_eq!(idx.try_get_bit(TREE_HEIGHT), None);
+    assert_eq!(idx.try_get_prefix(3), Some(idx.get_prefix(3)));
+    assert_eq!(idx.try_get_prefix(TREE_HEIGHT + 1), None);
+    assert_eq!(
+        idx.try_get_sibling_index(),
+        Some(TreeIndex::from_u32(TREE_HEIGHT, 4))
+    );
+    assert_eq!(
+        idx.try_get_parent_index(),
+        Some(TreeIndex::from_u32(TREE_HEIGHT - 1, 2))
+    );
+
+    // The root has neither a sibling nor a parent.
+    let root = TreeIndex::zero(0);
+    assert_eq!(root.try_get_bit(0), None);
+    assert_eq!(root.try_get_sibling_index(), None);
+    assert_eq!(root.try_get_parent_index(), None);
+
+    assert_eq!(
+        TreeIndex::try_from_u32(TREE_HEIGHT, 1 << TREE_HEIGHT),
+        Err(DecodingError::IndexOverflow)
+    );
+    assert_eq!(
+        TreeIndex::try_from_u64(MAX_HEIGHT + 1, 0),
+        Err(DecodingError::ExceedMaxHeight)
+    );
+}
+
+#[test]
+fn test_serializable_tree_index() {
+    // Generic code serializes a single index.
+    fn round_trip<T: Serializable>(x: &T) -> T {
+        T::deserialize(&x.serialize()).unwrap()
+    }
+
+    let mut idx = TreeIndex::zero(TREE_HEIGHT + 3);
+    idx.randomize();
+    assert_eq!(idx.serialize(), TreeIndex::serialize(&[idx]));
+    assert_eq!(idx.serialize().len(), 2 + 2);
+    assert_eq!(round_trip(&idx), idx);
+    let small = TreeIndex16::from_u32(16, 12345);
+    assert_eq!(round_trip(&small), small);
+    assert_eq!(round_trip(&TreeIndex::zero(0)), TreeIndex::zero(0));
+
+    let bytes = idx.serialize();
+    assert_eq!(
+        <TreeIndex as Serializable>::deserialize(&bytes[..bytes.len() - 1]),
+        Err(DecodingError::BytesNotEnough)
+    );
+
+    // An untrusted number of indexes doesn't overflow or loop without consuming bytes.
+    let bytes = TreeIndex::serialize(&[TreeIndex::zero(256)]);
+    assert_eq!(
+        TreeIndex::deserialize_as_a_unit(&bytes, usize::MAX, &mut 0),
+        Err(DecodingError::BytesNotEnough)
+    );
+    let bytes = TreeIndex::serialize(&[TreeIndex::zero(0)]);
+    assert_eq!(
+        TreeIndex::deserialize_as_a_unit(&bytes, usize::MAX, &mut 0),
+        Err(DecodingError::IndexOverflow)
+    );
+    assert_eq!(
+        TreeIndex::deserialize_from(&mut &bytes[..], usize::MAX),
+        Err(DecodingError::IndexOverflow)
+    );
+    assert_eq!(
+        TreeIndex::deserialize_as_a_unit(&bytes, 1, &mut 0),
+        Ok(vec![TreeIndex::zero(0)])
+    );
+
+    // The path bytes of an index don't have set bits beyond its height.
+    assert_eq!(
+        TreeIndex::from_path_bytes(idx.get_height(), &idx.get_path_bytes()),
+        Ok(idx)
+    );
+    assert_eq!(
+        TreeIndex::from_path_bytes(3, &[0b101]),
+        Ok(TreeIndex::from_u64(3, 0b101))
+    );
+    assert_eq!(
+        TreeIndex::from_path_bytes(3, &[0b1101]),
+        Err(DecodingError::NonCanonicalEncoding)
+    );
+    assert_eq!(TreeIndex::from_path_bytes(0, &[]), Ok(TreeIndex::zero(0)));
+}
+
+#[test]
+fn test_u64_wire_helpers() {
+    let bytes = u64_to_bytes(u64::MAX - 1, 8);
+    assert_eq!(bytes, (u64::MAX - 1).to_le_bytes());
+    let mut begin = 0;
+    assert_eq!(bytes_to_u64(&bytes, 8, &mut begin), Ok(u64::MAX - 1));
+    assert_eq!(begin, 8);
+    assert_eq!(
+        bytes_to_u64(&[0u8; 9], 9, &mut 0),
+        Err(DecodingError::TooManyEncodedBytes)
+    );
+    assert_eq!(
+        bytes_to_u64(&bytes, 8, &mut 1),
+        Err(DecodingError::BytesNotEnough)
+    );
+
+    // The counts are the same as the usize encoding on 64-bit platforms.
+    assert_eq!(u64_to_bytes(300, 2), usize_to_bytes(300, 2));
+    assert_eq!(bytes_to_count(&u64_to_bytes(300, 8), 8, &mut 0), Ok(300));
+
+    // A sum node keeps all 64 bits.
+    let node = SumNodeSmt::new(u64::MAX);
+    assert_eq!(SumNodeSmt::deserialize(&node.serialize()), Ok(node));
+}
+
+#[test]
+fn test_canonical_decoding() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let height = 5;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(height, 10);
+    let mut tree = Smt::<Node>::new(height);
+    tree.build(&list, secret);
+    let root = tree.get_root();
+
+    // A generated proof is canonical.
+    let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[list[0].0]).unwrap();
+    let bytes = proof.serialize();
+    assert_eq!(proof.canonical_bytes(), bytes);
+    assert!(MerkleProof::<Node>::deserialize_checked(&bytes, true).is_ok());
+    assert!(MerkleProof::<Node>::deserialize_checked(&[], true).is_ok());
+
+    // Setting a bit beyond the height of the index results in an equally valid proof with other bytes.
+    let mut malleated = bytes.clone();
+    malleated[10] |= 0x80;
+    let decoded = MerkleProof::<Node>::deserialize_checked(&malleated, false).unwrap();
+    assert!(!decoded.get_indexes()[0].is_canonical());
+    assert!(decoded.verify(&list[0].1, &root));
+    assert_eq!(decoded.canonical_bytes(), bytes);
+    assert_eq!(
+        MerkleProof::<Node>::deserialize_checked(&malleated, true).unwrap_err(),
+        DecodingError::NonCanonicalEncoding
+    );
+
+    // An extra sibling is rejected.
+    let mut extra = proof.clone();
+    let mut siblings = proof.get_path_siblings().to_vec();
+    siblings.push(siblings[0].clone());
+    extra.set_siblings(siblings);
+    assert_eq!(
+        MerkleProof::<Node>::deserialize_checked(&extra.serialize(), true).unwrap_err(),
+        DecodingError::NonCanonicalEncoding
+    );
+
+    // Unsorted indexes are rejected.
+    let batch =
+        MerkleProof::<Node>::generate_inclusion_proof(&tree, &[list[0].0, list[1].0]).unwrap();
+    let mut unsorted = batch.serialize();
+    unsorted.swap(10, 11);
+    assert!(MerkleProof::<Node>::deserialize_checked(&unsorted, false).is_ok());
+    assert_eq!(
+        MerkleProof::<Node>::deserialize_checked(&unsorted, true).unwrap_err(),
+        DecodingError::NonCanonicalEncoding
+    );
+
+    // The explicit encoding of the empty batch is rejected.
+    let mut empty = vec![0u8; 8];
+    empty.extend_from_slice(&[0u8; 10]);
+    assert!(MerkleProof::<Node>::deserialize_checked(&empty, true).is_err());
+}
+
+#[test]
+fn test_update_and_prove() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list[1..], secret);
+    let mut expected = tree.clone();
+
+    // Updating a new leaf returns no previous value, and updating it again returns the previous one.
+    assert_eq!(tree.update(&list[0].0, list[1].1.clone(), secret), None);
+    assert_eq!(
+        tree.update(&list[0].0, list[0].1.clone(), secret),
+        Some(list[1].1.clone())
+    );
+    assert_eq!(
+        tree.update(&list[2].0, list[2].1.clone(), secret),
+        Some(list[2].1.clone())
+    );
+
+    // The proof of the updated leaf is the same as that generated afterwards.
+    let proof = expected.update_and_prove(&list[0].0, list[0].1.clone(), secret);
+    assert_eq!(expected, tree);
+    let generated = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[list[0].0]).unwrap();
+    assert_eq!(proof.serialize(), generated.serialize());
+    assert!(proof.verify(&list[0].1, &tree.get_root()));
+    let proof = expected.update_and_prove(&list[5].0, list[0].1.clone(), secret);
+    assert!(proof.verify(&list[0].1, &expected.get_root()));
+}
+
+#[test]
+fn test_get_or_insert_with() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list[1..], secret);
+    let mut expected = tree.clone();
+    expected.update(&list[0].0, list[0].1.clone(), secret);
+
+    // An existing leaf is returned without calling the function, and a missing one is inserted.
+    let nodes_num = tree.get_nodes_num();
+    assert_eq!(
+        *tree.get_or_insert_with(&list[1].0, secret, || panic!("The leaf exists.")),
+        list[1].1
+    );
+    assert_eq!(tree.get_nodes_num(), nodes_num);
+    assert_eq!(
+        *tree.get_or_insert_with(&list[0].0, secret, || list[0].1.clone()),
+        list[0].1
+    );
+    assert_eq!(tree, expected);
+
+    // The entry of a leaf.
+    let entry = tree.entry(&list[0].0);
+    assert!(entry.is_occupied());
+    assert_eq!(entry.get(), Some(&list[0].1));
+    entry.and_modify(secret, |_| list[2].1.clone());
+    expected.update(&list[0].0, list[2].1.clone(), secret);
+    assert_eq!(tree, expected);
+    assert_eq!(
+        tree.entry(&list[0].0).insert(list[0].1.clone(), secret),
+        Some(list[2].1.clone())
+    );
+
+    let mut missing = TreeIndex::zero(TREE_HEIGHT);
+    while tree.get_leaf_by_index(&missing).is_some() {
+        missing.randomize();
+    }
+    let entry = tree.entry(&missing);
+    assert!(!entry.is_occupied());
+    let entry = entry.and_modify(secret, |_| panic!("The leaf doesn't exist."));
+    assert_eq!(
+        *entry.or_insert_with(secret, || list[3].1.clone()),
+        list[3].1
+    );
+    assert_eq!(
+        tree.get_leaf_by_index(&missing).unwrap().get_value(),
+        &list[3].1
+    );
+}
+
+#[test]
+fn test_buffered_writer() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut expected = Smt::<Node>::new(TREE_HEIGHT);
+    expected.build(&list, secret);
+
+    // A sorted batch update results in the same tree as building from the leaves.
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list[..LEAF_NUM / 2], secret);
+    tree.update_sorted_batch(list[LEAF_NUM / 2..].to_vec(), secret);
+    assert_eq!(tree, expected);
+
+    // Unsorted writes are buffered and flushed in batches, the last write to a leaf winning.
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    {
+        let mut writer = BufferedWriter::new(&mut tree, secret, 30);
+        writer.write(list[0].0, list[1].1.clone());
+        for (key, value) in list.iter().rev() {
+            writer.write(*key, value.clone());
+        }
+        assert_eq!(writer.get_threshold(), 30);
+        assert_eq!(writer.get_buffered_num(), (LEAF_NUM + 1) % 30);
+        writer.flush();
+        assert_eq!(writer.get_buffered_num(), 0);
+        writer.write(list[3].0, list[3].1.clone());
+    }
+    assert_eq!(tree, expected);
+}
+
+#[test]
+fn test_snapshot() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+    let root = tree.get_root();
+    let expected = tree.clone();
+
+    // The number of pages of nodes shared by two trees.
+    fn shared_pages_num(a: &Smt<Node>, b: &Smt<Node>) -> usize {
+        a.nodes
+            .pages
+            .iter()
+            .zip(b.nodes.pages.iter())
+            .filter(|(x, y)| Arc::ptr_eq(x, y))
+            .count()
+    }
+
+    let snapshot: SmtSnapshot<Node> = tree.snapshot();
+    let pages_num = tree.nodes.pages.len();
+    assert_eq!(shared_pages_num(&tree, snapshot.get_tree()), pages_num);
+    let cloned = snapshot.clone();
+    assert_eq!(shared_pages_num(&tree, cloned.get_tree()), pages_num);
+
+    // Updating the tree copies the pages it writes, leaving the snapshot at the old version.
+    let mut new_value = Node::default();
+    new_value.randomize();
+    tree.update(&list[0].0, new_value.clone(), secret);
+    assert!(shared_pages_num(&tree, snapshot.get_tree()) < pages_num);
+    assert_ne!(tree.get_root(), root);
+    assert_eq!(snapshot.get_root(), root);
+    assert_eq!(snapshot.get_height(), TREE_HEIGHT);
+    assert_eq!(snapshot.get_tree(), &expected);
+    assert_eq!(
+        snapshot.get_leaf_by_index(&list[0].0).unwrap().get_value(),
+        &list[0].1
+    );
+
+    // The snapshot serves the proofs of the old version.
+    let indexes: Vec<TreeIndex> = list.iter().map(|x| x.0).take(3).collect();
+    let proof = cloned.generate_inclusion_proof(&indexes).unwrap();
+    let leaves: Vec<<Node as ProofExtractable>::ProofNode> =
+        list.iter().take(3).map(|x| x.1.get_proof_node()).collect();
+    assert!(proof.verify_inclusion_proof(&leaves, &root));
+    let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &indexes).unwrap();
+    let mut leaves = leaves;
+    leaves[0] = new_value.get_proof_node();
+    assert!(proof.verify_inclusion_proof(&leaves, &tree.get_root()));
+
+    // Updating a leaf of a larger tree copies at most the pages of the nodes on its path.
+    let height = 20;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(height, 1000);
+    let mut tree = Smt::<Node>::new(height);
+    tree.build(&list, secret);
+    let snapshot = tree.snapshot();
+    tree.update(&list[0].0, new_value, secret);
+    let pages_num = tree.nodes.pages.len();
+    assert!(shared_pages_num(&tree, snapshot.get_tree()) + height + 1 >= pages_num);
+    assert_eq!(
+        snapshot.get_tree().get_leaves()[0].1.get_value(),
+        &list[0].1
+    );
+}
+
+#[test]
+fn test_tree_stats() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let stats: TreeStats = Smt::<Node>::new(TREE_HEIGHT).stats();
+    assert_eq!(stats.paddings_num, 1);
+    assert_eq!(stats.depth_distribution[0], 1);
+    assert_eq!(stats.depth_distribution.len(), TREE_HEIGHT + 1);
+
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::with_capacity(TREE_HEIGHT, LEAF_NUM);
+    tree.build(&list, secret);
+    let stats = tree.stats();
+    assert_eq!(stats.leaves_num, LEAF_NUM);
+    assert_eq!(
+        stats.internals_num + 1,
+        stats.leaves_num + stats.paddings_num
+    );
+    assert_eq!(
+        stats.depth_distribution.iter().sum::<usize>(),
+        stats.leaves_num + stats.paddings_num
+    );
+    assert!(stats.depth_distribution[TREE_HEIGHT] >= LEAF_NUM);
+    assert_eq!(stats.arena_len, tree.get_nodes_num());
+    assert_eq!(stats.reachable_nodes_num, stats.arena_len);
+
+    // The estimation bounds the footprint of the built tree.
+    let estimation = Smt::<Node>::estimate_heap_bytes(TREE_HEIGHT, LEAF_NUM);
+    assert!(estimation >= stats.arena_len * std::mem::size_of::<TreeNode<Node>>());
+    assert!(stats.estimated_heap_bytes >= estimation);
+}
+
+#[test]
+fn test_verify_integrity() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &Secret::from_bytes(&[7u8; 32]).unwrap();
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new_with_secret(TREE_HEIGHT, secret);
+    assert_eq!(tree.verify_integrity(secret), Ok(()));
+    tree.build(&list, secret);
+    let mut new_value = Node::default();
+    new_value.randomize();
+    tree.update(&list[1].0, new_value, secret);
+    assert_eq!(tree.verify_integrity(secret), Ok(()));
+
+    // The padding nodes are checked against the secret.
+    assert!(matches!(
+        tree.verify_integrity(&ALL_ZEROS_SECRET),
+        Err(IntegrityError::PaddingMismatch { .. })
+    ));
+
+    // A patched leaf breaks its parent.
+    let (_, leaf) = tree.get_leaves()[0];
+    let parent = leaf.get_parent().unwrap();
+    let link = tree.get_node_by_ref(parent).get_lch().unwrap();
+    let mut patched = tree.clone();
+    let mut value = Node::default();
+    value.randomize();
+    patched.nodes_mut()[link].set_value(value);
+    assert!(matches!(
+        patched.verify_integrity(secret),
+        Err(IntegrityError::InternalMismatch { link: x, .. }) if x == parent
+    ));
+
+    // Asymmetric links and invalid references are found.
+    let mut patched = tree.clone();
+    patched.links_mut()[link].set_parent(link);
+    assert!(matches!(
+        patched.verify_integrity(secret),
+        Err(IntegrityError::LinkMismatch { link: x, .. }) if x == link
+    ));
+    let mut patched = tree.clone();
+    patched.links_mut()[parent].set_lch(usize::MAX);
+    assert_eq!(
+        patched.verify_integrity(secret),
+        Err(IntegrityError::InvalidReference { link: usize::MAX })
+    );
+    let mut patched = tree.clone();
+    patched.nodes_mut()[link].set_node_type(NodeType::Internal);
+    assert!(matches!(
+        patched.verify_integrity(secret),
+        Err(IntegrityError::NodeTypeMismatch { .. })
+    ));
+}
+
+#[test]
+fn test_recompute_dirty() {
+    type Node = SumNodeSmt;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+    let mut expected = tree.clone();
+    let root = tree.get_root();
+
+    // Staged leaves don't change the root until recomputed.
+    let recorder = Arc::new(CountingRecorder::new());
+    tree.set_metrics_recorder(recorder.clone());
+    for i in [0, 1, LEAF_NUM / 2] {
+        let value = Node::new(i as u64 + 1000);
+        tree.set_leaf_raw(&list[i].0, value.clone()).unwrap();
+        expected.update(&list[i].0, value, secret);
+    }
+    assert_eq!(tree.get_dirty_num(), 3);
+    assert_eq!(tree.get_root(), root);
+    assert!(tree.verify_integrity(secret).is_err());
+    tree.recompute_dirty();
+    assert_eq!(tree.get_dirty_num(), 0);
+    assert_eq!(tree.get_root(), expected.get_root());
+    assert_eq!(tree, expected);
+    assert_eq!(tree.verify_integrity(secret), Ok(()));
+    // The shared ancestors are merged once.
+    assert!(recorder.get_totals(Operation::Update).merges < 3 * TREE_HEIGHT as u64);
+
+    let mut missing = TreeIndex::zero(TREE_HEIGHT);
+    while tree.get_leaf_by_index(&missing).is_some() {
+        missing.randomize();
+    }
+    assert_eq!(
+        tree.set_leaf_raw(&missing, Node::new(1)),
+        Err(TreeError::LeafNotFound)
+    );
+    assert_eq!(
+        tree.set_leaf_raw(&TreeIndex::zero(TREE_HEIGHT - 1), Node::new(1)),
+        Err(TreeError::HeightNotMatch)
+    );
+}
+
+#[test]
+fn test_prove_position() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let mut list: Vec<Node> = Vec::new();
+    for _i in 0..13 {
+        let mut value = Node::default();
+        value.randomize();
+        list.push(value);
+    }
+    let tree = Smt::<Node>::new_merkle_tree(&list);
+    let root = tree.get_root();
+    let height = tree.get_height();
+    for (pos, leaf) in list.iter().enumerate() {
+        let proof = tree.prove_position(pos as u64).unwrap();
+        assert!(Smt::<Node>::verify_position(
+            &root, height, pos as u64, leaf, &proof
+        ));
+        // The proof doesn't verify at another position or for another leaf.
+        assert!(!Smt::<Node>::verify_position(
+            &root,
+            height,
+            pos as u64 + 1,
+            leaf,
+            &proof
+        ));
+        assert!(!Smt::<Node>::verify_position(
+            &root,
+            height,
+            pos as u64,
+            &list[(pos + 1) % list.len()],
+            &proof
+        ));
+    }
+    // There is no leaf beyond the items, nor position beyond the tree.
+    assert!(tree.prove_position(13).is_none());
+    assert!(tree.prove_position(1 << tree.get_height()).is_none());
+    assert!(tree.prove_position(u64::MAX).is_none());
+
+    // A leaf of the tree is an internal node of a shorter tree of the same root,
+    // whose proof doesn't verify against the expected height.
+    let proof = tree.prove_position(0).unwrap();
+    let mut short = MerkleProof::<Node>::new(TreeIndex::zero(height - 1));
+    short.set_siblings(proof.get_path_siblings()[..height - 1].to_vec());
+    let inner = Node::merge(&list[0], &list[1]);
+    assert!(short.verify_inclusion_proof(std::slice::from_ref(&inner), &root));
+    assert!(!Smt::<Node>::verify_position(
+        &root, height, 0, &inner, &short
+    ));
+}
+
+#[test]
+fn test_try_new_merkle_tree() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let mut list: Vec<Node> = Vec::new();
+    for _i in 0..13 {
+        let mut value = Node::default();
+        value.randomize();
+        list.push(value);
+    }
+    let tree =
+        Smt::<Node>::try_new_merkle_tree(list.iter().cloned(), &ALL_ZEROS_SECRET, None).unwrap();
+    assert_eq!(tree.get_height(), 4);
+    assert_eq!(tree, Smt::<Node>::new_merkle_tree(&list));
+
+    // The height and the secret can be chosen.
+    let secret = &Secret::from_bytes(&[3u8; 32]).unwrap();
+    let tree = Smt::<Node>::try_new_merkle_tree(list.clone(), secret, Some(6)).unwrap();
+    assert_eq!(tree.get_height(), 6);
+    assert_eq!(tree.verify_integrity(secret), Ok(()));
+    let proof = tree.prove_position(12).unwrap();
+    assert!(Smt::<Node>::verify_position(
+        &tree.get_root(),
+        6,
+        12,
+        &list[12],
+        &proof
+    ));
+
+    // A single item is the root of a tree of height 0.
+    let tree = Smt::<Node>::try_new_merkle_tree(vec![list[0].clone()], secret, None).unwrap();
+    assert_eq!(tree.get_height(), 0);
+    assert_eq!(tree.get_root(), list[0].get_proof_node());
+    let tree = Smt::<Node>::try_new_merkle_tree(Vec::new(), secret, None).unwrap();
+    assert_eq!(tree, Smt::<Node>::new_with_secret(0, secret));
+
+    assert_eq!(
+        Smt::<Node>::try_new_merkle_tree(list.clone(), secret, Some(3)),
+        Err(TreeError::TreeFull)
+    );
+    assert_eq!(
+        Smt::<Node>::try_new_merkle_tree(list, secret, Some(MAX_HEIGHT + 1)),
+        Err(TreeError::ExceedMaxHeight)
+    );
+}
+
+#[test]
+fn test_first_last_leaf() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    assert!(tree.first_leaf().is_none());
+    assert!(tree.last_leaf().is_none());
+
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    tree.build(&list[1..LEAF_NUM - 1], secret);
+    assert_eq!(tree.first_leaf(), Some((list[1].0, &list[1].1)));
+    assert_eq!(
+        tree.last_leaf(),
+        Some((list[LEAF_NUM - 2].0, &list[LEAF_NUM - 2].1))
+    );
+
+    // The extremes follow the updates.
+    tree.update(&list[0].0, list[0].1.clone(), secret);
+    tree.update(&list[LEAF_NUM - 1].0, list[LEAF_NUM - 1].1.clone(), secret);
+    assert_eq!(tree.first_leaf(), Some((list[0].0, &list[0].1)));
+    assert_eq!(
+        tree.last_leaf(),
+        Some((list[LEAF_NUM - 1].0, &list[LEAF_NUM - 1].1))
+    );
+
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.update(&list[3].0, list[3].1.clone(), secret);
+    assert_eq!(tree.first_leaf(), tree.last_leaf());
+    assert_eq!(tree.first_leaf(), Some((list[3].0, &list[3].1)));
+}
+
+#[test]
+fn test_count_leaves_in_range() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+
+    let first = TreeIndex::zero(TREE_HEIGHT);
+    let mut last = TreeIndex::zero(0);
+    while last.get_height() < TREE_HEIGHT {
+        last = last.get_rch_index();
+    }
+    assert_eq!(tree.count_leaves_in_range(&first, &last), LEAF_NUM);
+    for (i, j) in [
+        (0, 0),
+        (0, LEAF_NUM - 1),
+        (3, 17),
+        (LEAF_NUM / 2, LEAF_NUM - 1),
+    ] {
+        assert_eq!(
+            tree.count_leaves_in_range(&list[i].0, &list[j].0),
+            j - i + 1
+        );
+    }
+    assert_eq!(tree.count_leaves_in_range(&list[5].0, &list[4].0), 0);
+
+    // The ends of the range don't need to be leaves.
+    for _i in 0..20 {
+        let mut a = TreeIndex::zero(TREE_HEIGHT);
+        a.randomize();
+        let mut b = TreeIndex::zero(TREE_HEIGHT);
+        b.randomize();
+        let (a, b) = if a <= b { (a, b) } else { (b, a) };
+        let expected = list.iter().filter(|x| x.0 >= a && x.0 <= b).count();
+        assert_eq!(tree.count_leaves_in_range(&a, &b), expected);
+    }
+}
+
+#[test]
+fn test_epoch_log() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    let mut log = EpochLog::<Node>::new(2);
+    assert!(log.prove_epoch(0).is_none());
+
+    // Publish the root of the tree every epoch.
+    let mut roots = Vec::new();
+    for (i, (key, value)) in list.iter().take(4).enumerate() {
+        tree.update(key, value.clone(), secret);
+        roots.push(tree.get_root());
+        assert_eq!(log.append(tree.get_root()), Ok(i as u64));
+    }
+    assert_eq!(log.append(tree.get_root()), Err(TreeError::TreeFull));
+    assert_eq!(log.get_epochs_num(), 4);
+
+    let head = log.get_head();
+    for (epoch, root) in roots.iter().enumerate() {
+        let epoch = epoch as u64;
+        assert_eq!(log.get_epoch_root(epoch), Some(root));
+        let proof = log.prove_epoch(epoch).unwrap();
+        assert!(EpochLog::verify_epoch(&head, 2, epoch, root, &proof));
+        // The proof doesn't verify for another root or epoch.
+        assert!(!EpochLog::verify_epoch(
+            &head,
+            2,
+            epoch,
+            &roots[(epoch as usize + 1) % 4],
+            &proof
+        ));
+        assert!(!EpochLog::verify_epoch(
+            &head,
+            2,
+            (epoch + 1) % 4,
+            root,
+            &proof
+        ));
+    }
+    assert!(log.get_epoch_root(4).is_none());
+}
+
+#[test]
+fn test_epoch_log_consistency() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let height = 3;
+    let mut log = EpochLog::<Node>::new(height);
+    let mut heads = vec![log.get_head()];
+    let mut roots = Vec::new();
+    for _i in 0..(1 << height) {
+        let mut root = Node::default();
+        root.randomize();
+        log.append(root.clone()).unwrap();
+        heads.push(log.get_head());
+        roots.push(root);
+    }
+
+    // Every prefix of every log is consistent with it.
+    for new_num in 0..heads.len() {
+        let mut prefix = EpochLog::<Node>::new(height);
+        for root in roots.iter().take(new_num) {
+            prefix.append(root.clone()).unwrap();
+        }
+        assert_eq!(prefix.get_head(), heads[new_num]);
+        for (old_num, old_head) in heads.iter().enumerate().take(new_num + 1) {
+            let proof = prefix.prove_consistency(old_num as u64).unwrap();
+            assert!(EpochLog::verify_consistency(
+                old_head,
+                old_num as u64,
+                &heads[new_num],
+                new_num as u64,
+                height,
+                &proof
+            ));
+        }
+        assert!(prefix.prove_consistency(new_num as u64 + 1).is_none());
+    }
+
+    // A log rewriting an old epoch isn't consistent with the old head.
+    let mut forked = EpochLog::<Node>::new(height);
+    for (i, root) in roots.iter().enumerate().take(5) {
+        if i == 1 {
+            forked.append(Node::default()).unwrap();
+        } else {
+            forked.append(root.clone()).unwrap();
+        }
+    }
+    for old_num in 2..5 {
+        let proof = forked.prove_consistency(old_num).unwrap();
+        assert!(!EpochLog::verify_consistency(
+            &heads[old_num as usize],
+            old_num,
+            &forked.get_head(),
+            5,
+            height,
+            &proof
+        ));
+    }
+    // The proof doesn't verify for another number of old epochs or another height.
+    let proof = log.prove_consistency(3).unwrap();
+    let head = log.get_head();
+    assert!(!EpochLog::verify_consistency(
+        &heads[3], 4, &head, 8, height, &proof
+    ));
+    assert!(!EpochLog::verify_consistency(
+        &heads[3],
+        3,
+        &head,
+        8,
+        height + 1,
+        &proof
+    ));
+}
+
+#[test]
+fn test_delta() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list[..LEAF_NUM / 2], secret);
+    let mut snapshot: Vec<u8> = Vec::new();
+    tree.serialize_into(&mut snapshot).unwrap();
+    let mut replica: Smt<Node> = Smt::deserialize_from(&mut &snapshot[..]).unwrap();
+    let base = tree.snapshot();
+
+    // Update existing leaves and add new ones.
+    for (key, _) in list.iter().take(3) {
+        let mut value = Node::default();
+        value.randomize();
+        tree.update(key, value, secret);
+    }
+    for (key, value) in list.iter().skip(LEAF_NUM / 2).take(5) {
+        tree.update(key, value.clone(), secret);
+    }
+    let delta = tree.export_delta(&base);
+    assert!(delta.len() < snapshot.len() / 4);
+
+    // A truncated delta leaves the replica unchanged.
+    assert!(matches!(
+        replica.apply_delta(&delta[..delta.len() - 1]),
+        Err(SmtError::Decoding(_))
+    ));
+    assert_eq!(replica.get_root(), base.get_root());
+
+    // A tampered entry or target root is rejected and leaves the replica unchanged.
+    let root_len = base.get_root().serialize().len();
+    for position in [root_len, delta.len() - 1] {
+        let mut tampered = delta.clone();
+        tampered[position] ^= 1;
+        assert_eq!(
+            replica.apply_delta(&tampered),
+            Err(SmtError::Tree(TreeError::TargetNotMatch))
+        );
+        assert_eq!(replica.get_root(), base.get_root());
+    }
+
+    replica.apply_delta(&delta).unwrap();
+    assert_eq!(replica, tree);
+    assert_eq!(replica.verify_integrity(secret), Ok(()));
+    assert_eq!(
+        replica.apply_delta(&delta),
+        Err(SmtError::Tree(TreeError::BaseNotMatch))
+    );
+
+    // A delta without change applies to the same version.
+    let delta = tree.export_delta(&tree.snapshot());
+    replica.apply_delta(&delta).unwrap();
+    assert_eq!(replica, tree);
+}
+
+#[test]
+fn test_from_index_node_pairs() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+
+    let pairs = tree.get_index_node_pairs();
+    let restored = Smt::<Node>::from_index_node_pairs(TREE_HEIGHT, pairs.iter().copied()).unwrap();
+    assert_eq!(restored, tree);
+    assert_eq!(restored.verify_integrity(secret), Ok(()));
+
+    // The pairs can be owned and in any order.
+    let mut owned: Vec<(TreeIndex, TreeNode<Node>)> = pairs
+        .iter()
+        .map(|(idx, node)| (*idx, (*node).clone()))
+        .collect();
+    owned.reverse();
+    let restored = Smt::<Node>::from_index_node_pairs(TREE_HEIGHT, owned.clone()).unwrap();
+    assert_eq!(restored, tree);
+    assert_eq!(restored.get_root(), tree.get_root());
+
+    // Inconsistent dumps are rejected.
+    assert_eq!(
+        Smt::<Node>::from_index_node_pairs(MAX_HEIGHT + 1, owned.clone()).unwrap_err(),
+        DecodingError::ExceedMaxHeight
+    );
+    assert!(Smt::<Node>::from_index_node_pairs(TREE_HEIGHT + 1, owned.clone()).is_err());
+    let mut missing = owned.clone();
+    missing.remove(0);
+    assert!(Smt::<Node>::from_index_node_pairs(TREE_HEIGHT, missing).is_err());
+    let mut duplicated = owned.clone();
+    duplicated.push(owned[0].clone());
+    assert!(Smt::<Node>::from_index_node_pairs(TREE_HEIGHT, duplicated).is_err());
+    let without_root: Vec<(TreeIndex, TreeNode<Node>)> = owned
+        .into_iter()
+        .filter(|(idx, _)| idx.get_height() > 0)
+        .collect();
+    assert!(Smt::<Node>::from_index_node_pairs(TREE_HEIGHT, without_root).is_err());
+}
+
+#[test]
+fn test_padding_context() {
+    use crate::compressed::CompressedSmt;
+    use crate::nary::NaryMerkleTree;
+
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &Secret::from_bytes(&[5u8; 32]).unwrap();
+    let idx = TreeIndex::from_u64(TREE_HEIGHT, 7);
+
+    // Without epoch and tag, the padding nodes are the same as before.
+    let ctx = PaddingContext::new(TREE_HEIGHT, secret);
+    assert_eq!(ctx.get_domain_secret().as_bytes(), secret.as_bytes());
+    assert_eq!(
+        Node::padding_with_context(&idx, &ctx),
+        Node::padding(&idx, secret)
+    );
+
+    // The epochs and the tags separate the padding nodes.
+    let paddings: Vec<Node> = [
+        ctx.epoch(1),
+        ctx.epoch(2),
+        ctx.tag(b"app"),
+        ctx.epoch(1).tag(b"app"),
+        PaddingContext::new(TREE_HEIGHT + 1, secret).epoch(1),
+    ]
+    .iter()
+    .map(|x| Node::padding_with_context(&idx, x))
+    .collect();
+    for i in 0..paddings.len() {
+        assert_ne!(paddings[i], Node::padding(&idx, secret));
+        for j in 0..i {
+            assert_ne!(paddings[i], paddings[j]);
+        }
+    }
+
+    // A tree with the domain secret proves its padding nodes.
+    let domain_secret = ctx.epoch(3).tag(b"app").get_domain_secret();
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new_with_secret(TREE_HEIGHT, &domain_secret);
+    tree.build(&list, &domain_secret);
+    assert_eq!(tree.verify_integrity(&domain_secret), Ok(()));
+    assert!(tree.verify_integrity(secret).is_err());
+    let mut missing = TreeIndex::zero(TREE_HEIGHT);
+    while tree.get_leaf_by_index(&missing).is_some() {
+        missing.randomize();
+    }
+    let proof = RandomSamplingProof::<Node>::random_sampling(&tree, &missing, &domain_secret);
+    assert!(proof.verify_random_sampling_proof(&tree.get_root()));
+
+    // A tree keeping the padding domain pads and proves in the context of the secret.
+    let domain = PaddingDomain::new().epoch(3).tag(b"app");
+    let mut domain_tree = Smt::<Node>::new(TREE_HEIGHT);
+    domain_tree.set_padding_domain(domain.clone());
+    domain_tree.build(&list, secret);
+    assert_eq!(domain_tree.get_root(), tree.get_root());
+    assert_eq!(domain_tree.verify_integrity(secret), Ok(()));
+    let proof = RandomSamplingProof::<Node>::random_sampling(&domain_tree, &missing, secret);
+    assert!(proof.verify_random_sampling_proof(&tree.get_root()));
+    domain_tree.update(&missing, list[0].1.clone(), secret);
+    tree.update(&missing, list[0].1.clone(), &domain_secret);
+    assert_eq!(domain_tree.get_root(), tree.get_root());
+
+    // So do the compressed and the N-ary trees.
+    let mut list = list;
+    list.push((missing, list[0].1.clone()));
+    list.sort_by_key(|x| x.0);
+    let mut compressed = CompressedSmt::<Node>::new(TREE_HEIGHT);
+    compressed.set_padding_domain(domain.clone());
+    compressed.build(&list, secret);
+    assert_eq!(compressed.get_root(), tree.get_root());
+    let mut nary = NaryMerkleTree::<Node, 4>::new(TREE_HEIGHT / 2);
+    nary.set_padding_domain(domain);
+    nary.build(&list, secret);
+    let mut expected = NaryMerkleTree::<Node, 4>::new(TREE_HEIGHT / 2);
+    expected.build(&list, &domain_secret);
+    assert_eq!(nary.get_root(), expected.get_root());
+}
+
+// A sum node adding the height of the parent plus one at every merge.
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+struct LevelSumNode(u64);
+
+impl Mergeable for LevelSumNode {
+    const MERGES_WITH_INDEX: bool = true;
+
+    fn merge(lch: &LevelSumNode, rch: &LevelSumNode) -> LevelSumNode {
+        LevelSumNode(lch.0 + rch.0)
+    }
+
+    fn merge_with_index(lch: &Self, rch: &Self, parent: &TreeIndex) -> Self {
+        LevelSumNode(lch.0 + rch.0 + parent.get_height() as u64 + 1)
+    }
+}
+
+impl Paddable for LevelSumNode {
+    fn padding(_idx: &TreeIndex, _secret: &Secret) -> LevelSumNode {
+        LevelSumNode(0)
+    }
+}
+
+impl ProofExtractable for LevelSumNode {
+    type ProofNode = LevelSumNode;
+    fn get_proof_node(&self) -> LevelSumNode {
+        self.clone()
+    }
+}
+
+impl PaddingProvable for LevelSumNode {
+    type PaddingProof = LevelSumNode;
+    fn prove_padding_node(&self, _idx: &TreeIndex, _secret: &Secret) -> LevelSumNode {
+        LevelSumNode(0)
+    }
+    fn verify_padding_node(node: &LevelSumNode, proof: &LevelSumNode, _idx: &TreeIndex) -> bool {
+        node.0 == 0 && proof.0 == 0
+    }
+}
+
+impl Serializable for LevelSumNode {
+    fn serialize(&self) -> Vec<u8> {
+        SumNodeSmt::new(self.0).serialize()
+    }
+
+    fn deserialize_as_a_unit(bytes: &[u8], begin: &mut usize) -> Result<Self, DecodingError> {
+        Ok(LevelSumNode(
+            SumNodeSmt::deserialize_as_a_unit(bytes, begin)?.get_value(),
+        ))
+    }
+}
+
+#[test]
+fn test_merge_with_index() {
+    use crate::incremental::IncrementalMerkleTree;
+    use crate::proof::SubtreeCompletenessProof;
+
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, SumNodeSmt)> =
+        generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let level_list: Vec<(TreeIndex, LevelSumNode)> = list
+        .iter()
+        .map(|(idx, value)| (*idx, LevelSumNode(value.get_value())))
+        .collect();
+    let mut plain = Smt::<SumNodeSmt>::new(TREE_HEIGHT);
+    plain.build(&list, secret);
+    let mut tree = Smt::<LevelSumNode>::new(TREE_HEIGHT);
+    tree.build(&level_list, secret);
+
+    // The internal nodes receive their positions.
+    assert_ne!(tree.get_root().0, plain.get_root().get_value());
+    assert_eq!(tree.verify_integrity(secret), Ok(()));
+
+    // The proofs merge the nodes at the same positions.
+    let proof =
+        MerkleProof::<LevelSumNode>::generate_inclusion_proof(&tree, &[level_list[3].0]).unwrap();
+    assert!(proof.verify(&level_list[3].1, &tree.get_root()));
+    let chosen: Vec<TreeIndex> = level_list.iter().step_by(10).map(|(idx, _)| *idx).collect();
+    let leaves: Vec<LevelSumNode> = level_list
+        .iter()
+        .step_by(10)
+        .map(|(_, x)| x.clone())
+        .collect();
+    let proof = MerkleProof::<LevelSumNode>::generate_inclusion_proof(&tree, &chosen).unwrap();
+    assert!(proof.verify_batch(&leaves, &tree.get_root()));
+    let partial = PartialSmt::from_tree(&tree, &chosen).unwrap();
+    assert_eq!(*partial.get_root(), tree.get_root());
+
+    // The subtree completeness proofs merge the leaves at their absolute positions.
+    let idx = level_list[3].0.get_prefix(3);
+    let proof = SubtreeCompletenessProof::generate(&tree, &idx, secret).unwrap();
+    let in_subtree: Vec<(TreeIndex, LevelSumNode)> = level_list
+        .iter()
+        .filter(|(index, _)| index.is_descendant_of(&idx))
+        .cloned()
+        .collect();
+    assert!(proof.verify(&in_subtree, &tree.get_root()));
+
+    // The incremental tree merges the nodes at their positions.
+    let height = 3;
+    let mut incremental = IncrementalMerkleTree::<LevelSumNode>::new(height);
+    for i in 0..5 {
+        incremental.append(LevelSumNode(i + 1)).unwrap();
+    }
+    let mut layer: Vec<LevelSumNode> = (1..=8).map(|x| LevelSumNode(x * (x <= 5) as u64)).collect();
+    for level in (0..height).rev() {
+        layer = layer
+            .chunks(2)
+            .enumerate()
+            .map(|(i, pair)| {
+                let parent = TreeIndex::from_u64(level, i as u64);
+                LevelSumNode::merge_with_index(&pair[0], &pair[1], &parent)
+            })
+            .collect();
+    }
+    assert_eq!(incremental.get_root(), layer[0]);
+    assert!(incremental
+        .prove_last()
+        .unwrap()
+        .verify(&LevelSumNode(5), &layer[0]));
+
+    // The subtree completeness proofs merge the leaves at their absolute positions.
+    let idx = level_list[3].0.get_prefix(3);
+    let proof = SubtreeCompletenessProof::generate(&tree, &idx, secret).unwrap();
+    let in_subtree: Vec<(TreeIndex, LevelSumNode)> = level_list
+        .iter()
+        .filter(|(index, _)| index.is_descendant_of(&idx))
+        .cloned()
+        .collect();
+    assert!(proof.verify(&in_subtree, &tree.get_root()));
+
+    // The incremental tree merges the nodes at their positions.
+    let height = 3;
+    let mut incremental = IncrementalMerkleTree::<LevelSumNode>::new(height);
+    for i in 0..5 {
+        incremental.append(LevelSumNode(i + 1)).unwrap();
+    }
+    let mut layer: Vec<LevelSumNode> = (1..=8).map(|x| LevelSumNode(x * (x <= 5) as u64)).collect();
+    for level in (0..height).rev() {
+        layer = layer
+            .chunks(2)
+            .enumerate()
+            .map(|(i, pair)| {
+                let parent = TreeIndex::from_u64(level, i as u64);
+                LevelSumNode::merge_with_index(&pair[0], &pair[1], &parent)
+            })
+            .collect();
+    }
+    assert_eq!(incremental.get_root(), layer[0]);
+    assert!(incremental
+        .prove_last()
+        .unwrap()
+        .verify(&LevelSumNode(5), &layer[0]));
+
+    // The updates merge the nodes at the same positions as the build.
+    let mut updated = level_list.clone();
+    updated[3].1 = LevelSumNode(7);
+    tree.update(&updated[3].0, updated[3].1.clone(), secret);
+    let mut rebuilt = Smt::<LevelSumNode>::new(TREE_HEIGHT);
+    rebuilt.build(&updated, secret);
+    assert_eq!(tree.get_root(), rebuilt.get_root());
+}
+
+#[test]
+fn test_dyn_smt() {
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, SumNodeSmt)> =
+        generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<SumNodeSmt>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+    let encoded: Vec<(TreeIndex, Vec<u8>)> = list
+        .iter()
+        .map(|(idx, value)| (*idx, value.serialize()))
+        .collect();
+
+    // The template is chosen by name at runtime.
+    assert!(new_dyn_smt("Unknown", TREE_HEIGHT).is_none());
+    let mut trees: Vec<Box<dyn DynSmt>> = vec![
+        new_dyn_smt(&SumNodeSmt::get_name(), TREE_HEIGHT).unwrap(),
+        Box::new(Smt::<SumNodeSmt>::new(TREE_HEIGHT)),
+    ];
+    for dyn_tree in trees.iter_mut() {
+        assert_eq!(dyn_tree.get_type_name(), SumNodeSmt::get_name());
+        assert_eq!(dyn_tree.get_height(), TREE_HEIGHT);
+        dyn_tree.build(&encoded, secret).unwrap();
+        assert_eq!(dyn_tree.get_root(), tree.get_root().serialize());
+        assert_eq!(dyn_tree.get_nodes_num(), tree.get_nodes_num());
+        assert_eq!(dyn_tree.get_leaf(&list[3].0), Some(encoded[3].1.clone()));
+
+        // The proofs are exchanged as bytes.
+        let proof = dyn_tree
+            .generate_inclusion_proof(&[list[3].0, list[5].0])
+            .unwrap();
+        let root = dyn_tree.get_root();
+        let leaves = vec![encoded[3].1.clone(), encoded[5].1.clone()];
+        assert_eq!(
+            dyn_tree.verify_inclusion_proof(&proof, &leaves, &root),
+            Ok(true)
+        );
+        assert!(dyn_tree
+            .generate_inclusion_proof(&[list[5].0, list[3].0])
+            .is_none());
+        assert!(dyn_tree
+            .verify_inclusion_proof(&proof[1..], &leaves, &root)
+            .is_err());
+
+        // Invalid inputs are rejected without changing the tree.
+        assert!(dyn_tree.update(&list[3].0, &[1u8], secret).is_err());
+        assert_eq!(
+            dyn_tree.update(&TreeIndex::zero(TREE_HEIGHT + 1), &encoded[3].1, secret),
+            Err(SmtError::Tree(TreeError::HeightNotMatch))
+        );
+        let mut unsorted = encoded.clone();
+        unsorted.swap(0, 1);
+        assert!(dyn_tree.build(&unsorted, secret).is_err());
+        assert_eq!(dyn_tree.get_root(), root);
+
+        dyn_tree
+            .update(&list[3].0, &SumNodeSmt::new(7).serialize(), secret)
+            .unwrap();
+        assert_ne!(dyn_tree.get_root(), root);
+    }
+
+    // Different templates are used through the same interface.
+    let mut hash_tree =
+        new_dyn_smt(&HashNodeSmt::<blake3::Hasher>::get_name(), TREE_HEIGHT).unwrap();
+    let mut value = HashNodeSmt::<blake3::Hasher>::default();
+    value.randomize();
+    hash_tree
+        .update(&list[0].0, &value.serialize(), secret)
+        .unwrap();
+    assert_eq!(hash_tree.get_leaf(&list[0].0), Some(value.serialize()));
+}
+
+#[test]
+fn test_fixed_hash_node() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    type FixedNode = FixedHashNodeSmt<blake3::Hasher, 32>;
+    let secret = &Secret::from_bytes(&[3u8; 32]).unwrap();
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let fixed_list: Vec<(TreeIndex, FixedNode)> = list
+        .iter()
+        .map(|(idx, value)| (*idx, FixedNode::deserialize(&value.serialize()).unwrap()))
+        .collect();
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+    let mut fixed_tree = Smt::<FixedNode>::new(TREE_HEIGHT);
+    fixed_tree.build(&fixed_list, secret);
+
+    // The nodes are the same as those of the hash template.
+    assert_eq!(FixedNode::ENCODED_LEN, 32);
+    assert_eq!(
+        fixed_tree.get_root().to_bytes().to_vec(),
+        tree.get_root().serialize()
+    );
+    assert_eq!(fixed_tree.verify_integrity(secret), Ok(()));
+
+    // The encoded proofs are the same, and decode back to verifying proofs.
+    let chosen: Vec<TreeIndex> = list.iter().step_by(10).map(|(idx, _)| *idx).collect();
+    let leaves: Vec<FixedNode> = fixed_list
+        .iter()
+        .step_by(10)
+        .map(|(_, x)| x.clone())
+        .collect();
+    let proof = MerkleProof::<FixedNode>::generate_inclusion_proof(&fixed_tree, &chosen).unwrap();
+    let expected = MerkleProof::<Node>::generate_inclusion_proof(&tree, &chosen).unwrap();
+    assert_eq!(proof.serialize(), expected.serialize());
+    let decoded = MerkleProof::<FixedNode>::deserialize(&proof.serialize()).unwrap();
+    assert!(decoded.verify_batch(&leaves, &fixed_tree.get_root()));
+    assert!(FixedNode::deserialize(&[0u8; 31]).is_err());
+
+    // The padding nodes are proved as with the hash template.
+    let mut missing = TreeIndex::zero(TREE_HEIGHT);
+    while tree.get_leaf_by_index(&missing).is_some() {
+        missing.randomize();
+    }
+    let proof = RandomSamplingProof::<FixedNode>::random_sampling(&fixed_tree, &missing, secret);
+    assert!(proof.verify_random_sampling_proof(&fixed_tree.get_root()));
+}
+
+#[test]
+fn test_borrowed_proof() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+
+    // The templates lend their proof nodes, while the default extraction clones them.
+    assert!(list[0].1.get_proof_node_ref().is_some());
+    assert!(BatchCountingNode::default().get_proof_node_ref().is_none());
+
+    for chosen in [
+        vec![list[3].0],
+        list.iter().step_by(10).map(|(idx, _)| *idx).collect(),
+    ] {
+        let borrowed = BorrowedMerkleProof::generate(&tree, &chosen).unwrap();
+        assert_eq!(borrowed.get_indexes(), &chosen[..]);
+        assert!(borrowed
+            .get_path_siblings()
+            .iter()
+            .all(|x| matches!(x, std::borrow::Cow::Borrowed(_))));
+        let expected = MerkleProof::<Node>::generate_inclusion_proof(&tree, &chosen).unwrap();
+        assert_eq!(borrowed.serialize(), expected.serialize());
+        assert_eq!(
+            borrowed.into_owned().get_path_siblings(),
+            expected.get_path_siblings()
+        );
+    }
+    let mut missing = list[0].0;
+    while tree.get_leaf_by_index(&missing).is_some() {
+        missing.randomize();
+    }
+    assert!(BorrowedMerkleProof::generate(&tree, &[missing]).is_none());
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_parallel_proof_generation() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+
+    let mut missing = list[0].0;
+    while tree.get_leaf_by_index(&missing).is_some() {
+        missing.randomize();
+    }
+    let mut requests: Vec<Vec<TreeIndex>> = list.iter().map(|(idx, _)| vec![*idx]).collect();
+    requests.push(list.iter().step_by(7).map(|(idx, _)| *idx).collect());
+    requests.push(vec![missing]);
+    let proofs = MerkleProof::<Node>::generate_inclusion_proofs_parallel(&tree, &requests);
+    assert_eq!(proofs.len(), requests.len());
+    for (request, proof) in requests[..requests.len() - 1].iter().zip(proofs.iter()) {
+        let expected = MerkleProof::<Node>::generate_inclusion_proof(&tree, request).unwrap();
+        assert_eq!(proof.as_ref().unwrap().serialize(), expected.serialize());
+    }
+    assert!(proofs[requests.len() - 1].is_none());
+}
+
+thread_local! {
+    // The number of merges of MergeCountingNode in the current thread.
+    static MERGES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+// A sum node counting its merges.
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+struct MergeCountingNode(SumNodeSmt);
+
+impl Mergeable for MergeCountingNode {
+    fn merge(lch: &MergeCountingNode, rch: &MergeCountingNode) -> MergeCountingNode {
+        MERGES.with(|x| x.set(x.get() + 1));
+        MergeCountingNode(Mergeable::merge(&lch.0, &rch.0))
+    }
+}
+
+impl Paddable for MergeCountingNode {
+    fn padding(idx: &TreeIndex, secret: &Secret) -> MergeCountingNode {
+        MergeCountingNode(SumNodeSmt::padding(idx, secret))
+    }
+}
+
+impl ProofExtractable for MergeCountingNode {
+    type ProofNode = MergeCountingNode;
+    fn get_proof_node(&self) -> MergeCountingNode {
+        self.clone()
+    }
+}
+
+impl Serializable for MergeCountingNode {
+    fn serialize(&self) -> Vec<u8> {
+        self.0.serialize()
+    }
+
+    fn deserialize_as_a_unit(bytes: &[u8], begin: &mut usize) -> Result<Self, DecodingError> {
+        Ok(MergeCountingNode(SumNodeSmt::deserialize_as_a_unit(
+            bytes, begin,
+        )?))
+    }
+}
+
+#[test]
+fn test_verify_many() {
+    type Node = MergeCountingNode;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> =
+        generate_sorted_index_value_pairs::<SumNodeSmt>(TREE_HEIGHT, LEAF_NUM)
+            .into_iter()
+            .map(|(idx, value)| (idx, MergeCountingNode(value)))
+            .collect();
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+    let root = tree.get_root();
+
+    let mut proofs: Vec<(MerkleProof<Node>, Node)> = list
+        .iter()
+        .map(|(idx, value)| {
+            let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[*idx]).unwrap();
+            (proof, value.clone())
+        })
+        .collect();
+    // A wrong leaf, a wrong sibling, and a batched proof.
+    proofs[5].1 = MergeCountingNode(SumNodeSmt::new(list[5].1 .0.get_value() + 1));
+    let mut forged = proofs[7].0.clone();
+    let mut siblings = forged.get_path_siblings().to_vec();
+    siblings[0] = MergeCountingNode(SumNodeSmt::new(1));
+    forged.set_siblings(siblings);
+    proofs.push((forged, list[7].1.clone()));
+    let batch = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[list[0].0, list[1].0]);
+    proofs.push((batch.unwrap(), list[0].1.clone()));
+
+    MERGES.with(|x| x.set(0));
+    let expected: Vec<bool> = proofs
+        .iter()
+        .map(|(proof, leaf)| proof.verify(leaf, &root))
+        .collect();
+    let separate = MERGES.with(|x| x.replace(0));
+    let results = MerkleProof::verify_many(&proofs, &root);
+    let amortized = MERGES.with(|x| x.get());
+
+    assert_eq!(results, expected);
+    assert_eq!(results.iter().filter(|x| !**x).count(), 3);
+    assert!(amortized < separate / 2);
+}
+
+#[test]
+fn test_verify_against_any() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+
+    // The roots of the last epochs, each updating a leaf that is not proved.
+    let mut roots: Vec<Node> = Vec::new();
+    for i in 0..4 {
+        let mut value = Node::default();
+        value.randomize();
+        tree.update(&list[10 + i].0, value, secret);
+        roots.push(tree.get_root());
+    }
+    let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[list[3].0]).unwrap();
+    assert_eq!(
+        proof.verify_against_any(&[list[3].1.clone()], &roots),
+        Some(3)
+    );
+    assert_eq!(
+        proof.verify_against_any(&[list[3].1.clone()], &roots[..3]),
+        None
+    );
+    assert_eq!(proof.verify_against_any(&[list[4].1.clone()], &roots), None);
+
+    let chosen = [list[3].0, list[5].0];
+    let leaves = [list[3].1.clone(), list[5].1.clone()];
+    let mut old_tree = Smt::<Node>::new(TREE_HEIGHT);
+    old_tree.build(&list, secret);
+    let old_proof = MerkleProof::<Node>::generate_inclusion_proof(&old_tree, &chosen).unwrap();
+    roots.insert(0, old_tree.get_root());
+    assert_eq!(old_proof.verify_against_any(&leaves, &roots), Some(0));
+    assert_eq!(old_proof.verify_against_any(&leaves[..1], &roots), None);
+    assert_eq!(old_proof.verify_against_any(&leaves, &[]), None);
+}
+
+#[test]
+fn test_proof_cache() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+    let mut cache = ProofCache::new(tree);
+
+    // Repeated requests are served from the cache.
+    for _ in 0..3 {
+        for (idx, _) in &list[..4] {
+            assert!(cache.get_proof(idx).is_some());
+        }
+    }
+    let mut missing = list[0].0;
+    while cache.get_tree().get_leaf_by_index(&missing).is_some() {
+        missing.randomize();
+    }
+    assert!(cache.get_proof(&missing).is_none());
+    assert_eq!(cache.get_cached_num(), 4);
+    assert_eq!(cache.get_stats().hits, 8);
+    assert_eq!(cache.get_stats().misses, 5);
+    assert!((cache.get_stats().get_hit_rate() - 8.0 / 13.0).abs() < 1e-9);
+
+    // An update patches the cached proofs, which still verify and equal the generated ones.
+    let mut value = Node::default();
+    value.randomize();
+    cache.update(&list[1].0, value.clone(), secret);
+    assert_eq!(cache.get_cached_num(), 4);
+    assert_eq!(cache.get_stats().invalidations, 0);
+    let root = cache.get_tree().get_root();
+    assert!(cache.get_proof(&list[1].0).unwrap().verify(&value, &root));
+    assert!(cache
+        .get_proof(&list[2].0)
+        .unwrap()
+        .verify(&list[2].1, &root));
+    assert_eq!(cache.get_stats().hits, 10);
+
+    // Inserting a new leaf patches the cached proofs as well.
+    cache.update(&missing, value.clone(), secret);
+    assert_eq!(cache.get_cached_num(), 4);
+    for (idx, _) in &list[..4] {
+        let expected =
+            MerkleProof::<Node>::generate_inclusion_proof(cache.get_tree(), &[*idx]).unwrap();
+        assert_eq!(
+            cache.get_proof(idx).unwrap().serialize(),
+            expected.serialize()
+        );
+    }
+
+    // A batch update drops all cached proofs.
+    let updates: Vec<(TreeIndex, Node)> = vec![(list[5].0, value.clone()), (list[6].0, value)];
+    cache.update_batch(updates, secret, &DuplicatePolicy::Error);
+    assert_eq!(cache.get_cached_num(), 0);
+    assert_eq!(cache.get_stats().invalidations, 4);
+    let root = cache.get_tree().get_root();
+    assert!(cache
+        .get_proof(&list[2].0)
+        .unwrap()
+        .verify(&list[2].1, &root));
+    cache.reset_stats();
+    assert_eq!(cache.get_stats().get_hit_rate(), 0.0);
+}
+
+#[test]
+fn test_dense_top_levels() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    // The trees are deeper and shallower than the dense top levels.
+    for height in [6, 20] {
+        let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(height, 10);
+        let mut tree = Smt::<Node>::new(height);
+        tree.build(&list, secret);
+        let old = tree.snapshot();
+        for (idx, value) in &list {
+            assert_eq!(tree.get_leaf_by_index(idx).unwrap().get_value(), value);
+            let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[*idx]).unwrap();
+            assert!(proof.verify(value, &tree.get_root()));
+        }
+
+        // The lookups follow the updates changing the top levels.
+        let mut missing = TreeIndex::zero(height);
+        while tree.get_leaf_by_index(&missing).is_some() {
+            missing.randomize();
+        }
+        assert!(MerkleProof::<Node>::generate_inclusion_proof(&tree, &[missing]).is_none());
+        let mut value = Node::default();
+        value.randomize();
+        tree.update(&missing, value.clone(), secret);
+        assert_eq!(
+            tree.get_leaf_by_index(&missing).unwrap().get_value(),
+            &value
+        );
+        let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[missing]).unwrap();
+        assert!(proof.verify(&value, &tree.get_root()));
+        let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[list[0].0]).unwrap();
+        assert!(proof.verify(&list[0].1, &tree.get_root()));
+
+        // The dense index patched by the update is the one rebuilt from the links.
+        let mut rebuilt = tree.clone();
+        rebuilt.top = std::sync::OnceLock::new();
+        assert!(rebuilt.get_leaf_by_index(&missing).is_some());
+        assert_eq!(rebuilt.top.get(), tree.top.get());
+
+        // The snapshot keeps the lookups of its version.
+        assert!(old.get_leaf_by_index(&missing).is_none());
+        assert!(old.generate_inclusion_proof(&[list[0].0]).is_some());
+    }
+}
+
+#[test]
+fn test_anchored_proof() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+
+    let anchor = list[3].0.get_prefix(3);
+    let (chosen, leaves): (Vec<TreeIndex>, Vec<Node>) = list
+        .iter()
+        .filter(|(idx, _)| idx.is_descendant_of(&anchor))
+        .take(3)
+        .map(|(idx, value)| (*idx, value.get_proof_node()))
+        .unzip();
+    let anchor_proof = AnchoredProof::<Node>::generate_anchor_proof(&tree, &anchor).unwrap();
+    for num in 1..=chosen.len() {
+        let proof = AnchoredProof::<Node>::generate(&tree, &anchor, &chosen[..num]).unwrap();
+        assert_eq!(proof.get_indexes(), &chosen[..num]);
+        let full = MerkleProof::<Node>::generate_inclusion_proof(&tree, &chosen[..num]).unwrap();
+        assert_eq!(
+            proof.get_relative_proof().get_siblings_num() + anchor.get_height(),
+            full.get_siblings_num()
+        );
+
+        // The anchor computed from the proof is authenticated by the anchor proof.
+        let value = proof.compute_anchor(&leaves[..num]).unwrap();
+        assert!(proof.verify(&leaves[..num], &value));
+        assert!(anchor_proof.verify(&value, &tree.get_root()));
+        assert!(proof.verify_with_anchor_proof(&leaves[..num], &anchor_proof, &tree.get_root()));
+        let mut forged = leaves[..num].to_vec();
+        forged[0].randomize();
+        assert!(!proof.verify(&forged, &value));
+
+        // The composed proof is the proof against the root.
+        let composed = proof.compose(&anchor_proof).unwrap();
+        assert_eq!(composed.serialize(), full.serialize());
+
+        let decoded = AnchoredProof::<Node>::deserialize(&proof.serialize()).unwrap();
+        assert_eq!(decoded.get_anchor(), &anchor);
+        assert!(decoded.verify(&leaves[..num], &value));
+    }
+
+    // The anchor is the leaf itself, or the root.
+    let proof = AnchoredProof::<Node>::generate(&tree, &chosen[0], &chosen[..1]).unwrap();
+    assert!(proof.verify(&leaves[..1], &leaves[0]));
+    let proof = AnchoredProof::<Node>::generate(&tree, &TreeIndex::zero(0), &chosen).unwrap();
+    assert!(proof.verify(&leaves, &tree.get_root()));
+
+    // The leaves must be under the anchor.
+    let outside = list
+        .iter()
+        .find(|(idx, _)| !idx.is_descendant_of(&anchor))
+        .unwrap()
+        .0;
+    assert!(AnchoredProof::<Node>::generate(&tree, &anchor, &[outside]).is_none());
+    let other_proof =
+        AnchoredProof::<Node>::generate_anchor_proof(&tree, &outside.get_prefix(3)).unwrap();
+    let proof = AnchoredProof::<Node>::generate(&tree, &anchor, &chosen).unwrap();
+    assert!(proof.compose(&other_proof).is_none());
+    assert!(!proof.verify_with_anchor_proof(&leaves, &other_proof, &tree.get_root()));
+
+    // The merges below the anchor are given their positions in the tree.
+    let level_list: Vec<(TreeIndex, LevelSumNode)> = list
+        .iter()
+        .enumerate()
+        .map(|(i, (idx, _))| (*idx, LevelSumNode(i as u64)))
+        .collect();
+    let mut level_tree = Smt::<LevelSumNode>::new(TREE_HEIGHT);
+    level_tree.build(&level_list, secret);
+    let level_leaves: Vec<LevelSumNode> = level_list
+        .iter()
+        .filter(|(idx, _)| chosen.contains(idx))
+        .map(|(_, value)| value.clone())
+        .collect();
+    let proof = AnchoredProof::<LevelSumNode>::generate(&level_tree, &anchor, &chosen).unwrap();
+    let anchor_proof =
+        AnchoredProof::<LevelSumNode>::generate_anchor_proof(&level_tree, &anchor).unwrap();
+    assert!(proof.verify_with_anchor_proof(&level_leaves, &anchor_proof, &level_tree.get_root()));
+}
+
+#[test]
+fn test_leaf_filter() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let height = 32;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(height, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(height);
+    tree.build(&list[..LEAF_NUM / 2], secret);
+    tree.enable_leaf_filter(LEAF_NUM, 0.01);
+    assert_eq!(
+        tree.get_leaf_filter().unwrap().get_inserted_num(),
+        LEAF_NUM / 2
+    );
+
+    // The filter is maintained by the updates, and never reports an existing leaf as absent.
+    tree.update(&list[LEAF_NUM / 2].0, list[LEAF_NUM / 2].1.clone(), secret);
+    tree.update_batch(
+        list[LEAF_NUM / 2 + 1..].to_vec(),
+        secret,
+        &DuplicatePolicy::Error,
+    );
+    let filter = tree.get_leaf_filter().unwrap();
+    assert_eq!(filter.get_inserted_num(), LEAF_NUM);
+    assert!(filter.get_false_positive_rate() < 0.02);
+    for (idx, _) in &list {
+        assert!(filter.may_contain(idx));
+        assert!(tree.contains_leaf(idx));
+    }
+
+    // Most missing leaves are rejected by the filter, and none is reported as present.
+    let mut missing = TreeIndex::zero(height);
+    let mut rejected = 0;
+    for _ in 0..1000 {
+        missing.randomize();
+        if list.iter().any(|(idx, _)| *idx == missing) {
+            continue;
+        }
+        if !tree.get_leaf_filter().unwrap().may_contain(&missing) {
+            rejected += 1;
+        }
+        assert!(!tree.contains_leaf(&missing));
+    }
+    assert!(rejected > 900);
+
+    // A rebuild refills the filter with the new leaves.
+    tree.build(&list[..10], secret);
+    assert_eq!(tree.get_leaf_filter().unwrap().get_inserted_num(), 10);
+    assert!(!tree.contains_leaf(&list[10].0));
+    assert!(tree.snapshot().get_tree().get_leaf_filter().is_none());
+    tree.disable_leaf_filter();
+    assert!(tree.contains_leaf(&list[0].0));
+    assert!(!tree.contains_leaf(&list[10].0));
+}
+
+#[test]
+fn test_tree_iterators() {
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, SumNodeSmt)> =
+        generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<SumNodeSmt>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+
+    // The BFS iterator yields the nodes in the same order as the collected pairs.
+    let pairs = tree.get_index_ref_pairs();
+    let bfs: Vec<(TreeIndex, &TreeNode<SumNodeSmt>)> = tree.bfs_iter().collect();
+    assert_eq!(bfs.len(), pairs.len());
+    for ((idx, node), (index, link)) in bfs.iter().zip(pairs.iter()) {
+        assert_eq!(idx, index);
+        assert!(std::ptr::eq(*node, tree.get_node_by_ref(*link)));
+    }
+    assert_eq!((&tree).into_iter().count(), pairs.len());
+    let mut internals = 0;
+    for (_, node) in &tree {
+        if *node.get_node_type() == NodeType::Internal {
+            internals += 1;
+        }
+    }
+    assert_eq!(internals, tree.get_internals().len());
+
+    // The DFS pre-order visits each node right before its subtree, from left to right.
+    let dfs: Vec<TreeIndex> = tree.dfs_preorder_iter().map(|(idx, _)| idx).collect();
+    assert_eq!(dfs.len(), pairs.len());
+    for pair in dfs.windows(2) {
+        if pair[1].get_height() > pair[0].get_height() {
+            assert_eq!(pair[1], pair[0].get_lch_index());
+        } else {
+            assert!(pair[1] > pair[0]);
+            assert_eq!(pair[1].get_last_bit(), 1);
+        }
+    }
+
+    // The leaves are yielded in increasing order of index.
+    let leaves: Vec<(TreeIndex, u64)> = tree
+        .leaves_in_order_iter()
+        .map(|(idx, node)| (idx, node.get_value().get_value()))
+        .collect();
+    let expected: Vec<(TreeIndex, u64)> = list
+        .iter()
+        .map(|(idx, value)| (*idx, value.get_value()))
+        .collect();
+    assert_eq!(leaves, expected);
+    assert_eq!(tree.get_leaves().len(), LEAF_NUM);
+    assert_eq!(
+        Smt::<SumNodeSmt>::new(TREE_HEIGHT)
+            .leaves_in_order_iter()
+            .count(),
+        0
+    );
+}
+
+#[test]
+fn test_parallel_build() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = Secret::from_bytes(&[7u8; 32]).unwrap();
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let expected = TreeBuilder::new(TREE_HEIGHT)
+        .secret(&secret)
+        .build_from(&list)
+        .unwrap();
+    for threads in [1, 2, 3, 8, 64] {
+        let tree = TreeBuilder::new(TREE_HEIGHT)
+            .secret(&secret)
+            .parallelism(threads)
+            .build_from_parallel(&list)
+            .unwrap();
+        assert!(tree == expected);
+        assert_eq!(tree.get_root(), expected.get_root());
+        assert_eq!(tree.get_nodes_num(), expected.get_nodes_num());
+        assert_eq!(tree.verify_integrity(&secret), Ok(()));
+        let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[list[5].0]).unwrap();
+        assert!(proof.verify(&list[5].1, &expected.get_root()));
+    }
+
+    // The padding nodes of the subtrees and of the top levels are in the padding domain of the tree.
+    let domain = PaddingDomain::new().epoch(3).tag(b"app");
+    let mut expected = Smt::<Node>::new(TREE_HEIGHT);
+    expected.set_padding_domain(domain.clone());
+    expected.build(&list, &secret);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.set_padding_domain(domain);
+    assert_eq!(tree.construct_smt_nodes_parallel(&list, &secret, 4), None);
+    assert_eq!(tree.get_root(), expected.get_root());
+    assert_eq!(tree.verify_integrity(&secret), Ok(()));
+
+    // The merges of the subtrees and of the top levels receive their positions.
+    let level_list: Vec<(TreeIndex, LevelSumNode)> = list
+        .iter()
+        .enumerate()
+        .map(|(i, (idx, _))| (*idx, LevelSumNode(i as u64)))
+        .collect();
+    let builder = TreeBuilder::new(TREE_HEIGHT).parallelism(4);
+    assert!(
+        builder.build_from_parallel(&level_list).unwrap()
+            == builder.build_from(&level_list).unwrap()
+    );
+
+    // A single leaf, a tree not higher than the top levels, and an invalid list.
+    let tree = builder.build_from_parallel(&level_list[..1]).unwrap();
+    assert!(tree == builder.build_from(&level_list[..1]).unwrap());
+    let short: Vec<(TreeIndex, SumNodeSmt)> = generate_sorted_index_value_pairs(2, 3);
+    let tree = TreeBuilder::new(2)
+        .parallelism(4)
+        .build_from_parallel(&short)
+        .unwrap();
+    assert_eq!(tree.get_leaves().len(), 3);
+    let mut unsorted = level_list.clone();
+    unsorted.swap(3, 4);
+    assert_eq!(
+        builder
+            .build_from_parallel(&unsorted)
+            .err()
+            .map(|x| x.get_kind()),
+        Some(TreeError::IndexNotSorted)
+    );
+}
+
+#[test]
+fn test_namespace_proof() {
+    type Node = NamespaceNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    // The leaves are sorted by namespace, with the even namespaces 2 to 20 of 10 leaves each.
+    let indexes: Vec<(TreeIndex, SumNodeSmt)> =
+        generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let list: Vec<(TreeIndex, Node)> = indexes
+        .iter()
+        .enumerate()
+        .map(|(i, (idx, _))| {
+            let namespace = (i / 10 + 1) as u64 * 2;
+            (*idx, Node::from_data(namespace, &u64_to_bytes(i as u64, 8)))
+        })
+        .collect();
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+    let root = tree.get_root();
+    assert_eq!(root.get_min_namespace(), 2);
+    assert_eq!(root.get_max_namespace(), 20);
+
+    for namespace in 0..=22 {
+        let proof = NamespaceProof::generate(&tree, namespace);
+        assert!(proof.verify(&root));
+        let expected: Vec<(TreeIndex, &Node)> = list
+            .iter()
+            .filter(|(_, leaf)| leaf.contains_namespace(namespace))
+            .map(|(idx, leaf)| (*idx, leaf))
+            .collect();
+        assert_eq!(proof.get_leaves(), expected);
+        let leaves: Vec<Node> = expected.iter().map(|(_, leaf)| (*leaf).clone()).collect();
+        assert!(proof.verify_leaves(&leaves, &root));
+        if leaves.len() > 1 {
+            assert!(!proof.verify_leaves(&leaves[1..], &root));
+        }
+
+        let decoded = NamespaceProof::<blake3::Hasher>::deserialize(&proof.serialize()).unwrap();
+        assert_eq!(decoded.get_namespace(), namespace);
+        assert!(decoded.verify_leaves(&leaves, &root));
+    }
+
+    // A proof is bound to the root of its tree.
+    let proof = NamespaceProof::generate(&tree, 4);
+    tree.update(&list[15].0, Node::from_data(4, b"other"), secret);
+    assert!(!proof.verify(&tree.get_root()));
+    assert!(NamespaceProof::generate(&tree, 4).verify(&tree.get_root()));
+
+    // The empty tree has no leaf of any namespace.
+    let empty = Smt::<Node>::new(TREE_HEIGHT);
+    let proof = NamespaceProof::generate(&empty, 4);
+    assert!(proof.verify(&empty.get_root()));
+    assert!(proof.get_leaves().is_empty());
+}
+
+#[test]
+fn test_circuit_witness() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+
+    // Merging the siblings decoded from the limbs along the path bits, from the leaf up, gives the root.
+    let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[list[7].0]).unwrap();
+    let witness = proof.to_circuit_witness().unwrap();
+    assert_eq!(witness.limbs_num, 4);
+    assert_eq!(witness.path_bits.len(), TREE_HEIGHT);
+    assert_eq!(witness.siblings.len(), TREE_HEIGHT);
+    let mut value = list[7].1.clone();
+    for (bit, limbs) in witness.path_bits.iter().zip(&witness.siblings) {
+        assert_eq!(limbs.len(), witness.limbs_num);
+        let bytes: Vec<u8> = limbs.iter().flat_map(|x| x.to_le_bytes()).collect();
+        let sibling = Node::deserialize(&bytes).unwrap();
+        value = if *bit == 0 {
+            Node::merge(&value, &sibling)
+        } else {
+            Node::merge(&sibling, &value)
+        };
+    }
+    assert_eq!(value, tree.get_root());
+    assert_eq!(
+        CircuitWitness::to_limbs(&tree.get_root().serialize(), witness.limbs_num)
+            .iter()
+            .flat_map(|x| x.to_le_bytes())
+            .collect::<Vec<u8>>(),
+        tree.get_root().serialize()
+    );
+
+    // The values of sum nodes fit in a single limb.
+    let sums: Vec<(TreeIndex, SumNodeSmt)> = generate_sorted_index_value_pairs(TREE_HEIGHT, 10);
+    let mut sum_tree = Smt::<SumNodeSmt>::new(TREE_HEIGHT);
+    sum_tree.build(&sums, secret);
+    let proof =
+        MerkleProof::<SumNodeSmt>::generate_inclusion_proof(&sum_tree, &[sums[0].0]).unwrap();
+    let witness = proof.to_circuit_witness().unwrap();
+    assert_eq!(witness.limbs_num, 1);
+    let total: u64 = witness.siblings.iter().map(|x| x[0]).sum();
+    assert_eq!(
+        total + sums[0].1.get_value(),
+        sum_tree.get_root().get_value()
+    );
+
+    // Batched proofs have no single path.
+    let proof =
+        MerkleProof::<Node>::generate_inclusion_proof(&tree, &[list[1].0, list[2].0]).unwrap();
+    assert!(proof.to_circuit_witness().is_none());
+}
+
+#[cfg(feature = "ark")]
+#[test]
+fn test_ark_node() {
+    use std::convert::TryInto;
+    use std::sync::OnceLock;
+
+    use ark_crypto_primitives::{
+        crh::poseidon::{TwoToOneCRH, CRH},
+        merkle_tree::{Config, IdentityDigestConverter},
+        sponge::poseidon::{find_poseidon_ark_and_mds, PoseidonConfig},
+    };
+    use ark_ff::fields::{Fp64, MontBackend, MontConfig};
+
+    use crate::ark::{ArkCrhConfig, ArkNodeSmt};
+
+    #[derive(MontConfig)]
+    #[modulus = "18446744069414584321"]
+    #[generator = "7"]
+    pub struct FqConfig;
+    type Fq = Fp64<MontBackend<FqConfig, 1>>;
+
+    fn get_poseidon_config() -> &'static PoseidonConfig<Fq> {
+        static CONFIG: OnceLock<PoseidonConfig<Fq>> = OnceLock::new();
+        CONFIG.get_or_init(|| {
+            let (ark, mds) = find_poseidon_ark_and_mds::<Fq>(64, 2, 8, 31, 0);
+            PoseidonConfig::new(8, 31, 7, mds, ark, 2, 1)
+        })
+    }
+
+    struct Poseidon;
+    impl ArkCrhConfig for Poseidon {
+        type Field = Fq;
+        type Parameters = PoseidonConfig<Fq>;
+        type TwoToOneHash = TwoToOneCRH<Fq>;
+        fn get_parameters() -> &'static PoseidonConfig<Fq> {
+            get_poseidon_config()
+        }
+    }
+
+    struct PoseidonTree;
+    impl Config for PoseidonTree {
+        type Leaf = [Fq];
+        type LeafDigest = Fq;
+        type LeafInnerDigestConverter = IdentityDigestConverter<Fq>;
+        type InnerDigest = Fq;
+        type LeafHash = CRH<Fq>;
+        type TwoToOneHash = TwoToOneCRH<Fq>;
+    }
+
+    type Node = ArkNodeSmt<Poseidon>;
+    let secret = &ALL_ZEROS_SECRET;
+    let params = get_poseidon_config();
+    let indexes: Vec<(TreeIndex, SumNodeSmt)> =
+        generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let list: Vec<(TreeIndex, Node)> = indexes
+        .iter()
+        .enumerate()
+        .map(|(i, (idx, _))| {
+            let leaf = [Fq::from(i as u64), Fq::from(7u64)];
+            (
+                *idx,
+                Node::from_leaf_hash::<CRH<Fq>>(params, &leaf[..]).unwrap(),
+            )
+        })
+        .collect();
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+    let root = tree.get_root();
+
+    // The SMT proof and the converted arkworks path both verify under the same Poseidon parameters.
+    let pos = 3;
+    let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[list[pos].0]).unwrap();
+    assert!(proof.verify(&list[pos].1, &root));
+    let path = Node::to_ark_path::<PoseidonTree>(&proof).unwrap();
+    assert_eq!(path.auth_path.len(), TREE_HEIGHT - 1);
+    let leaf = [Fq::from(pos as u64), Fq::from(7u64)];
+    assert!(path
+        .verify(params, params, root.get_value(), &leaf[..])
+        .unwrap());
+    let forged = [Fq::from(pos as u64), Fq::from(8u64)];
+    assert!(!path
+        .verify(params, params, root.get_value(), &forged[..])
+        .unwrap());
+
+    // Only single-leaf proofs convert.
+    let batch =
+        MerkleProof::<Node>::generate_inclusion_proof(&tree, &[list[0].0, list[1].0]).unwrap();
+    assert!(Node::to_ark_path::<PoseidonTree>(&batch).is_none());
+
+    // The nodes and the proofs round-trip through the compressed encoding of the field.
+    let bytes = root.serialize();
+    assert_eq!(bytes.len(), 8);
+    assert_eq!(Node::deserialize(&bytes).unwrap(), root);
+    assert!(Node::deserialize(&[0xff; 8]).is_err());
+    let decoded = MerkleProof::<Node>::deserialize(&proof.serialize()).unwrap();
+    assert!(decoded.verify(&list[pos].1, &root));
+
+    // Padding proofs reveal the pre-images of the padding nodes.
+    let idx = TreeIndex::zero(TREE_HEIGHT);
+    if tree.get_leaf_by_index(&idx).is_none() {
+        let padding = Node::padding(&idx, secret);
+        let padding_proof = padding.prove_padding_node(&idx, secret);
+        assert!(Node::verify_padding_node(&padding, &padding_proof, &idx));
+        assert!(!Node::verify_padding_node(&root, &padding_proof, &idx));
+    }
+}
+
+#[test]
+fn test_num_sum_node() {
+    let secret = &ALL_ZEROS_SECRET;
+    let pairs: Vec<(TreeIndex, SumNodeSmt)> =
+        generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    // Leaves of a quarter of u64::MAX each, whose total overflows a u64.
+    let list: Vec<(TreeIndex, SumNodeSmt128)> = pairs
+        .iter()
+        .map(|(idx, _)| (*idx, SumNodeSmt128::new((u64::MAX / 4) as u128)))
+        .collect();
+    let mut tree = Smt::<SumNodeSmt128>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+    let root = tree.get_root();
+    assert_eq!(
+        root.get_value(),
+        (u64::MAX / 4) as u128 * list.len() as u128
+    );
+    assert!(root.get_value() > u64::MAX as u128);
+
+    let proof =
+        MerkleProof::<SumNodeSmt128>::generate_inclusion_proof(&tree, &[list[5].0]).unwrap();
+    assert!(proof.verify(&list[5].1, &root));
+    let decoded = MerkleProof::<SumNodeSmt128>::deserialize(&proof.serialize()).unwrap();
+    assert!(decoded.verify(&list[5].1, &root));
+
+    // The encoding width follows the value type, the same as SumNodeSmt for u64.
+    assert_eq!(root.serialize().len(), 16);
+    assert_eq!(SumNodeSmt128::deserialize(&root.serialize()).unwrap(), root);
+    assert_eq!(
+        NumSumNode::new(42u64).serialize(),
+        SumNodeSmt::new(42).serialize()
+    );
+    assert_eq!(NumSumNode::new(42u16).serialize(), vec![42, 0]);
+    assert_eq!(
+        NumSumNode::<u32>::deserialize(&[1, 0]).unwrap_err(),
+        DecodingError::BytesNotEnough
+    );
+
+    // Overflowing sums are detected rather than wrapped.
+    let max = NumSumNode::new(u8::MAX);
+    assert_eq!(
+        NumSumNode::checked_merge(&max, &NumSumNode::new(0)),
+        Some(max.clone())
+    );
+    assert_eq!(NumSumNode::checked_merge(&max, &NumSumNode::new(1)), None);
+    assert!(std::panic::catch_unwind(|| NumSumNode::merge(&max, &max)).is_err());
+}
+
+#[test]
+fn test_timestamp_node() {
+    type Node = MaxTimestampNodeSmt<blake3::Hasher>;
+    type MinNode = MinTimestampNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+    let root = tree.get_root();
+    let latest = list.iter().map(|(_, x)| x.get_timestamp()).max().unwrap();
+    assert_eq!(root.get_timestamp(), latest);
+
+    // No leaf is newer than the root timestamp, and a leaf proof checks the bound of the leaf.
+    let proof = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[list[3].0]).unwrap();
+    let leaf = &list[3].1;
+    assert!(proof.verify_timestamp_bound(leaf, &root, leaf.get_timestamp()));
+    assert!(!proof.verify_timestamp_bound(leaf, &root, leaf.get_timestamp() - 1));
+    assert!(root.is_within_bound(latest) && !root.is_within_bound(latest - 1));
+
+    // A subtree proves the bound of all its leaves, and a forged timestamp doesn't verify.
+    let (anchor, node) = tree
+        .bfs_iter()
+        .find(|(idx, node)| idx.get_height() == 3 && *node.get_node_type() == NodeType::Internal)
+        .unwrap();
+    let subtree = node.get_value().clone();
+    let anchor_proof = AnchoredProof::<Node>::generate_anchor_proof(&tree, &anchor).unwrap();
+    let subtree_latest = list
+        .iter()
+        .filter(|(idx, _)| idx.is_descendant_of(&anchor))
+        .map(|(_, x)| x.get_timestamp())
+        .max()
+        .unwrap();
+    assert_eq!(subtree.get_timestamp(), subtree_latest);
+    assert!(anchor_proof.verify_timestamp_bound(&subtree, &root, subtree_latest));
+    let forged = Node::new(subtree_latest - 1, subtree.get_hash().to_vec());
+    assert!(!anchor_proof.verify_timestamp_bound(&forged, &root, subtree_latest));
+
+    // The min variant bounds the earliest timestamp, and padding doesn't lower it.
+    let min_list: Vec<(TreeIndex, MinNode)> = list
+        .iter()
+        .map(|(idx, x)| (*idx, MinNode::new(x.get_timestamp(), x.get_hash().to_vec())))
+        .collect();
+    let mut min_tree = Smt::<MinNode>::new(TREE_HEIGHT);
+    min_tree.build(&min_list, secret);
+    let min_root = min_tree.get_root();
+    let earliest = list.iter().map(|(_, x)| x.get_timestamp()).min().unwrap();
+    assert_eq!(min_root.get_timestamp(), earliest);
+    assert!(min_root.is_within_bound(earliest) && !min_root.is_within_bound(earliest + 1));
+    assert_eq!(MinNode::PADDING_TIMESTAMP, u64::MAX);
+
+    // Padding proofs and the encoding.
+    let idx = TreeIndex::zero(TREE_HEIGHT);
+    let padding = Node::padding(&idx, secret);
+    let padding_proof = padding.prove_padding_node(&idx, secret);
+    assert!(Node::verify_padding_node(&padding, &padding_proof, &idx));
+    assert!(!Node::verify_padding_node(&root, &padding_proof, &idx));
+    assert_eq!(Node::deserialize(&root.serialize()).unwrap(), root);
+    let decoded = MerkleProof::<Node>::deserialize(&proof.serialize()).unwrap();
+    assert!(decoded.verify_timestamp_bound(leaf, &root, latest));
+}
+
+#[test]
+fn test_derive_subsecret() {
+    let master = Secret::from_bytes(&[7u8; 32]).unwrap();
+    let accounts = master.derive_subsecret(b"accounts");
+    assert_eq!(
+        accounts.as_bytes(),
+        master.derive_subsecret(b"accounts").as_bytes()
+    );
+    assert_ne!(
+        accounts.as_bytes(),
+        master.derive_subsecret(b"balances").as_bytes()
+    );
+    assert_ne!(accounts.as_bytes(), master.as_bytes());
+    let other = Secret::from_bytes(&[8u8; 32]).unwrap();
+    assert_ne!(
+        accounts.as_bytes(),
+        other.derive_subsecret(b"accounts").as_bytes()
+    );
+
+    // Trees padded with different sub-secrets have different roots.
+    let list: Vec<(TreeIndex, HashNodeSmt<blake3::Hasher>)> =
+        generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let mut tree = Smt::<HashNodeSmt<blake3::Hasher>>::new(TREE_HEIGHT);
+    tree.build(&list, &accounts);
+    let mut other_tree = Smt::<HashNodeSmt<blake3::Hasher>>::new(TREE_HEIGHT);
+    other_tree.build(&list, &master.derive_subsecret(b"balances"));
+    assert_ne!(tree.get_root(), other_tree.get_root());
+}
+
+#[cfg(feature = "argon2")]
+#[test]
+fn test_derive_from_passphrase() {
+    let params = argon2::Params::new(256, 1, 1, None).unwrap();
+    let derive = |passphrase: &[u8], salt: &[u8]| {
+        Secret::derive_from_passphrase_with_params(passphrase, salt, params.clone())
+    };
+    let secret = derive(b"passphrase", b"deployment-1").unwrap();
+    assert_eq!(
+        secret.as_bytes(),
+        derive(b"passphrase", b"deployment-1").unwrap().as_bytes()
+    );
+    assert_ne!(
+        secret.as_bytes(),
+        derive(b"passphrase", b"deployment-2").unwrap().as_bytes()
+    );
+    assert_ne!(
+        secret.as_bytes(),
+        derive(b"Passphrase", b"deployment-1").unwrap().as_bytes()
+    );
+    assert_eq!(
+        derive(b"passphrase", b"short").err(),
+        Some(TreeError::SecretError)
+    );
+
+    // The default parameters differ from the explicit ones.
+    let default = Secret::derive_from_passphrase(b"passphrase", b"deployment-1").unwrap();
+    assert_ne!(secret.as_bytes(), default.as_bytes());
+}
+
+#[test]
+fn test_leaf_cursor() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    let secret = &ALL_ZEROS_SECRET;
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(TREE_HEIGHT, LEAF_NUM);
+    let indexes: Vec<TreeIndex> = list.iter().map(|(idx, _)| *idx).collect();
+    let mut tree = Smt::<Node>::new(TREE_HEIGHT);
+    tree.build(&list, secret);
+
+    // Going forward from the leftmost index yields all leaves in order, and backward from there yields nothing.
+    let cursor = tree.leaf_cursor_at(&TreeIndex::zero(TREE_HEIGHT));
+    assert!(cursor.peek_prev().is_none());
+    let forward: Vec<TreeIndex> = cursor.map(|(idx, _)| idx).collect();
+    assert_eq!(forward, indexes);
+
+    // Going backward from the end yields all leaves in reverse order.
+    let mut cursor = tree.leaf_cursor_at(indexes.last().unwrap());
+    assert_eq!(cursor.next().unwrap().0, *indexes.last().unwrap());
+    let mut backward = Vec::new();
+    while let Some((idx, node)) = cursor.prev() {
+        assert_eq!(
+            node.get_value(),
+            &list[indexes.binary_search(&idx).unwrap()].1
+        );
+        backward.push(idx);
+    }
+    backward.reverse();
+    assert_eq!(backward, indexes);
+
+    // Seeking to any index finds the adjacent leaves, and moving back and forth yields the same leaf.
+    for pos in 0..(1u32 << TREE_HEIGHT) {
+        let target = TreeIndex::from_u32(TREE_HEIGHT, pos);
+        let mut cursor = tree.leaf_cursor_at(&target);
+        let after = indexes.iter().find(|x| **x >= target).copied();
+        let before = indexes.iter().rev().find(|x| **x < target).copied();
+        assert_eq!(cursor.peek_next().map(|(idx, _)| idx), after);
+        assert_eq!(cursor.peek_prev().map(|(idx, _)| idx), before);
+        if let Some(after) = after {
+            assert_eq!(cursor.next().unwrap().0, after);
+            assert_eq!(cursor.prev().unwrap().0, after);
+            assert_eq!(cursor.get_position(), Some(after));
+        }
+    }
+
+    // Yielding the rightmost possible leaf moves the cursor after all indexes.
+    let rightmost = TreeIndex::from_u32(TREE_HEIGHT, (1u32 << TREE_HEIGHT) - 1);
+    tree.update(&rightmost, Node::default(), secret);
+    let mut cursor = tree.leaf_cursor_at(&rightmost);
+    assert_eq!(cursor.next().unwrap().0, rightmost);
+    assert_eq!(cursor.get_position(), None);
+    assert!(cursor.next().is_none());
+    assert_eq!(cursor.prev().unwrap().0, rightmost);
+
+    // An empty tree has no leaf in either direction.
+    let empty = Smt::<Node>::new(TREE_HEIGHT);
+    let mut cursor = empty.leaf_cursor_at(&rightmost);
+    assert!(cursor.next().is_none() && cursor.prev().is_none());
+}
+
+#[test]
+fn test_padding_omitted_proof() {
+    type Node = HashNodeSmt<blake3::Hasher>;
+    const HEIGHT: usize = 64;
+    let secret = Secret::from_bytes(&[7u8; 32]).unwrap();
+    let list: Vec<(TreeIndex, Node)> = generate_sorted_index_value_pairs(HEIGHT, 20);
+    let mut tree = Smt::<Node>::new(HEIGHT);
+    tree.build(&list, &secret);
+    let root = tree.get_root();
+
+    // In a sparse tree, a single-leaf proof keeps only the few siblings that are not padding nodes.
+    let proof = PaddingOmittedProof::<Node>::generate(&tree, &[list[4].0], &secret).unwrap();
+    let full = MerkleProof::<Node>::generate_inclusion_proof(&tree, &[list[4].0]).unwrap();
+    assert_eq!(
+        proof.get_omitted_num() + proof.get_kept_siblings().len(),
+        HEIGHT
+    );
+    assert!(proof.get_omitted_num() > HEIGHT / 2);
+    assert!(proof.serialize().len() < full.serialize().len() / 2);
+    assert!(proof.verify(&list[4].1, &root, &secret));
+    assert_eq!(
+        proof.to_merkle_proof(&secret).unwrap().serialize(),
+        full.serialize()
+    );
+
+    // The omitted siblings can't be recomputed without the secret.
+    assert!(!proof.verify(&list[4].1, &root, &ALL_ZEROS_SECRET));
+    assert!(!proof.verify(&list[5].1, &root, &secret));
+
+    // Batched proofs omit the padding siblings as well, and the proofs round-trip through the encoding.
+    let indexes: Vec<TreeIndex> = list.iter().step_by(3).map(|(idx, _)| *idx).collect();
+    let leaves: Vec<_> = list.iter().step_by(3).map(|(_, x)| x.clone()).collect();
+    let batch = PaddingOmittedProof::<Node>::generate(&tree, &indexes, &secret).unwrap();
+    assert!(batch.get_omitted_num() > 0);
+    assert!(batch.verify_batch(&leaves, &root, &secret));
+    let decoded = PaddingOmittedProof::<Node>::deserialize(&batch.serialize()).unwrap();
+    assert!(decoded.verify_batch(&leaves, &root, &secret));
+    assert_eq!(decoded.get_indexes(), &indexes[..]);
+
+    // Flipping an omitted bit breaks the count of kept siblings.
+    let mut bytes = proof.serialize();
+    bytes[8] ^= 1;
+    assert!(PaddingOmittedProof::<Node>::deserialize(&bytes).is_err());
 }
@@ -9,13 +9,246 @@
 //! For examples on how to use these traits,
 //! see the implementations of the [example](../example/index.html) module.
 
+use digest::Digest;
+use rand::Rng;
+
 use crate::pad_secret::Secret;
-use crate::{error::DecodingError, index::TreeIndex};
+use crate::{error::DecodingError, index::TreeIndex, utils::u64_to_bytes};
 
 /// Trait for merging two child nodes to extract the parent node in the SMT.
 pub trait Mergeable {
     /// A function to merge two child nodes as the parent node in the SMT.
     fn merge(lch: &Self, rch: &Self) -> Self;
+
+    /// Merge a batch of independent child pairs, e.g., all pairs in one layer of the SMT during building,
+    /// returning the parent nodes in the same order.
+    ///
+    /// The default implementation calls [merge](#tymethod.merge) on each pair in turn.
+    /// Node types with a vectorized or multi-threaded hash function can override it to hash the whole batch at once.
+    fn merge_many(pairs: &[(&Self, &Self)]) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        pairs
+            .iter()
+            .map(|(lch, rch)| Self::merge(lch, rch))
+            .collect()
+    }
+
+    /// Whether the merging depends on the tree index of the parent,
+    /// i.e., whether the node type overrides [merge_with_index](#method.merge_with_index).
+    ///
+    /// When it's ```false```, [merge_many_with_index](#method.merge_many_with_index) merges the batch
+    /// with [merge_many](#method.merge_many), so node types hashing a whole batch at once keep doing so.
+    /// A node type overriding [merge_with_index](#method.merge_with_index) must set it to ```true```,
+    /// otherwise the batches merged during building ignore the indexes.
+    const MERGES_WITH_INDEX: bool = false;
+
+    /// Merge two child nodes as the parent node at the input tree index,
+    /// for constructions hashing the level or the position into the internal nodes,
+    /// e.g., to be compatible with other SMT implementations.
+    ///
+    /// The SMT, its Merkle proofs, compressed trees and partial trees merge the nodes with this method,
+    /// whose default implementation ignores the index and calls [merge](#tymethod.merge).
+    /// Note that the encoded merging of [MergeableEncoded] doesn't receive the index,
+    /// so a node type overriding this method shouldn't be used with proofs merging encoded siblings.
+    fn merge_with_index(lch: &Self, rch: &Self, parent: &TreeIndex) -> Self
+    where
+        Self: Sized,
+    {
+        let _ = parent;
+        Self::merge(lch, rch)
+    }
+
+    /// Merge a batch of independent child pairs as the parent nodes at the input tree indexes,
+    /// returning the parent nodes in the same order.
+    ///
+    /// The default implementation merges the batch with [merge_many](#method.merge_many)
+    /// if [MERGES_WITH_INDEX](#associatedconstant.MERGES_WITH_INDEX) is ```false```,
+    /// and otherwise calls [merge_with_index](#method.merge_with_index) on each pair in turn.
+    fn merge_many_with_index(pairs: &[(&Self, &Self)], parents: &[TreeIndex]) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        if !Self::MERGES_WITH_INDEX {
+            return Self::merge_many(pairs);
+        }
+        pairs
+            .iter()
+            .zip(parents.iter())
+            .map(|((lch, rch), parent)| Self::merge_with_index(lch, rch, parent))
+            .collect()
+    }
+
+    /// Merge the children of a node in an N-ary tree, from left to right, as the parent node.
+    ///
+    /// The default implementation merges the children as a balanced binary tree by [merge](#tymethod.merge),
+    /// so it agrees with [merge](#tymethod.merge) on two children.
+    /// Node types with a hash function of cheap wide inputs can override it to hash all children at once.
+    ///
+    /// Panics if ```children``` is empty.
+    fn merge_n(children: &[Self]) -> Self
+    where
+        Self: Sized + Clone,
+    {
+        assert!(!children.is_empty(), "No children to merge.");
+        let mut layer = children.to_vec();
+        while layer.len() > 1 {
+            layer = layer
+                .chunks(2)
+                .map(|pair| match pair {
+                    [lch, rch] => Self::merge(lch, rch),
+                    [single] => single.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+        }
+        layer.remove(0)
+    }
+}
+
+/// The string prefixed to the input of the domain secret of a padding context.
+const PADDING_DOMAIN_STRING: &str = "SMTree padding domain";
+
+/// The context of constructing a padding node:
+/// the height of the tree, the secret, and optionally an epoch and an application tag.
+///
+/// The epoch and the tag separate the padding nodes of different epochs and deployments sharing a secret.
+#[derive(Clone, Copy)]
+pub struct PaddingContext<'a> {
+    height: usize,
+    secret: &'a Secret,
+    epoch: Option<u64>,
+    tag: Option<&'a [u8]>,
+}
+
+impl<'a> PaddingContext<'a> {
+    /// The constructor, without epoch or tag.
+    pub fn new(height: usize, secret: &'a Secret) -> PaddingContext<'a> {
+        PaddingContext {
+            height,
+            secret,
+            epoch: None,
+            tag: None,
+        }
+    }
+
+    /// Set the epoch.
+    pub fn epoch(mut self, epoch: u64) -> PaddingContext<'a> {
+        self.epoch = Some(epoch);
+        self
+    }
+
+    /// Set the application tag.
+    pub fn tag(mut self, tag: &'a [u8]) -> PaddingContext<'a> {
+        self.tag = Some(tag);
+        self
+    }
+
+    /// Returns the height of the tree.
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the secret.
+    pub fn get_secret(&self) -> &Secret {
+        self.secret
+    }
+
+    /// Returns the epoch if any.
+    pub fn get_epoch(&self) -> Option<u64> {
+        self.epoch
+    }
+
+    /// Returns the application tag if any.
+    pub fn get_tag(&self) -> Option<&[u8]> {
+        self.tag
+    }
+
+    /// Returns the secret separated by the domain of the context.
+    ///
+    /// Without epoch and tag, it is the secret itself.
+    /// Otherwise, it is the SHA-256 hash of
+    /// ```domain_string || secret || height || epoch_flag || epoch || tag_flag || tag_length || tag```.
+    ///
+    /// Using the domain secret as the secret of a tree, including for proof generation,
+    /// domain-separates the padding nodes and their proofs with any node template.
+    pub fn get_domain_secret(&self) -> Secret {
+        if self.epoch.is_none() && self.tag.is_none() {
+            // The secret has been checked at its construction.
+            return Secret::from_bytes(self.secret.as_bytes()).unwrap();
+        }
+        let mut hasher = sha2::Sha256::new();
+        hasher.update(PADDING_DOMAIN_STRING.as_bytes());
+        hasher.update(self.secret.as_bytes());
+        hasher.update(u64_to_bytes(self.height as u64, 2));
+        match self.epoch {
+            Some(x) => {
+                hasher.update([1u8]);
+                hasher.update(x.to_be_bytes());
+            }
+            None => hasher.update([0u8]),
+        }
+        match self.tag {
+            Some(x) => {
+                hasher.update([1u8]);
+                hasher.update((x.len() as u64).to_be_bytes());
+                hasher.update(x);
+            }
+            None => hasher.update([0u8]),
+        }
+        // A SHA-256 hash has the length of a secret.
+        Secret::from_bytes(&hasher.finalize()).unwrap()
+    }
+}
+
+/// The optional epoch and application tag of the padding nodes of a tree,
+/// kept by the tree to construct the [PaddingContext] of its padding nodes.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct PaddingDomain {
+    epoch: Option<u64>,
+    tag: Option<Vec<u8>>,
+}
+
+impl PaddingDomain {
+    /// The constructor, without epoch or tag.
+    pub fn new() -> PaddingDomain {
+        PaddingDomain::default()
+    }
+
+    /// Set the epoch.
+    pub fn epoch(mut self, epoch: u64) -> PaddingDomain {
+        self.epoch = Some(epoch);
+        self
+    }
+
+    /// Set the application tag.
+    pub fn tag(mut self, tag: &[u8]) -> PaddingDomain {
+        self.tag = Some(tag.to_vec());
+        self
+    }
+
+    /// Returns the epoch if any.
+    pub fn get_epoch(&self) -> Option<u64> {
+        self.epoch
+    }
+
+    /// Returns the application tag if any.
+    pub fn get_tag(&self) -> Option<&[u8]> {
+        self.tag.as_deref()
+    }
+
+    /// Returns the padding context of a tree of the input height and secret in the domain.
+    pub fn get_context<'a>(&'a self, height: usize, secret: &'a Secret) -> PaddingContext<'a> {
+        let mut ctx = PaddingContext::new(height, secret);
+        if let Some(x) = self.epoch {
+            ctx = ctx.epoch(x);
+        }
+        if let Some(x) = &self.tag {
+            ctx = ctx.tag(x);
+        }
+        ctx
+    }
 }
 
 /// Trait for generating a padding node in the SMT.
@@ -23,6 +256,19 @@ pub trait Paddable {
     /// When the tree node of the input index doesn't exist,
     /// we need to construct a padding node at that position.
     fn padding(idx: &TreeIndex, secret: &Secret) -> Self;
+
+    /// Construct the padding node at the input index in the input context.
+    ///
+    /// The default implementation is [padding](#tymethod.padding) with the
+    /// [domain secret](struct.PaddingContext.html#method.get_domain_secret) of the context,
+    /// so every existing node type supports the context, with the same padding nodes as before without epoch and tag.
+    /// A node type overriding it needs to keep its padding proofs consistent.
+    fn padding_with_context(idx: &TreeIndex, ctx: &PaddingContext) -> Self
+    where
+        Self: Sized,
+    {
+        Self::padding(idx, &ctx.get_domain_secret())
+    }
 }
 
 /// Trait for getting the type name of tree nodes in the SMT.
@@ -33,10 +279,25 @@ pub trait TypeName {
     }
 }
 
+/// Trait for nodes carrying a sum over the leaves of their subtree,
+/// e.g., a sum of balances or a count of leaves,
+/// where the sum of a parent is the total of its children and a padding node sums to 0.
+pub trait Summable {
+    /// Returns the sum of the subtree rooted at the node.
+    fn get_sum(&self) -> u64;
+}
+
 /// Trait for generating a random value.
 pub trait Rand {
-    /// A function returning a random value of the corresponding type.
-    fn randomize(&mut self) {}
+    /// A function sampling a random value of the corresponding type from the input random number generator.
+    ///
+    /// Passing a seeded generator, e.g., ```rand::rngs::StdRng::seed_from_u64(seed)```, makes the value reproducible.
+    fn randomize_with<R: Rng + ?Sized>(&mut self, rng: &mut R);
+
+    /// A function returning a random value of the corresponding type, sampled from the thread-local generator.
+    fn randomize(&mut self) {
+        self.randomize_with(&mut rand::thread_rng());
+    }
 }
 
 /// Trait for extracting a node with necessary information in Merkle proofs from a tree node.
@@ -46,6 +307,14 @@ pub trait ProofExtractable {
 
     /// Extracting a proof node from a tree node.
     fn get_proof_node(&self) -> Self::ProofNode;
+
+    /// Returns the proof node by reference, if the tree node holds it, e.g., when it is its own proof node,
+    /// so that proofs can borrow the nodes of the tree instead of cloning them.
+    ///
+    /// The default implementation returns None, i.e., the proof node is extracted by [get_proof_node](#tymethod.get_proof_node).
+    fn get_proof_node_ref(&self) -> Option<&Self::ProofNode> {
+        None
+    }
 }
 
 /// Trait for prove and verify padding nodes at random sampling.
@@ -106,13 +375,29 @@ pub trait Serializable {
         }
         // Check if all input bytes are used for decoding.
         if begin != bytes.len() {
-            println!("{}, {}", begin, bytes.len());
+            log_debug!("Decoding failed: {} of {} bytes used", begin, bytes.len());
             return Err(DecodingError::TooManyEncodedBytes);
         }
         res
     }
 }
 
+/// Trait for proof nodes with a fixed-size encoding that can be merged with the encoding of a sibling,
+/// which allows verifying a serialized proof without decoding its siblings,
+/// e.g., with a [MerkleProofRef](../proof/struct.MerkleProofRef.html).
+///
+/// Merging with an encoding must give the same result as merging with the decoded node.
+pub trait MergeableEncoded: Serializable {
+    /// Returns the number of bytes of an encoded node.
+    fn get_encoded_len() -> usize;
+
+    /// Merges a node with the encoding of its right sibling.
+    fn merge_encoded_rch(lch: &Self, rch: &[u8]) -> Self;
+
+    /// Merges the encoding of the left sibling with a node.
+    fn merge_encoded_lch(lch: &[u8], rch: &Self) -> Self;
+}
+
 /// Trait for generating and verifying inclusion proofs.
 pub trait InclusionProvable {
     /// The data type of a node with necessary information in Merkle proofs.
@@ -6,17 +6,34 @@
 //! This module provides definitions of the tree node and the paddable sparse Merkle tree,
 //! together with methods of tree generation/update, Merkle proof generation, and random sampling.
 
+use std::borrow::{Borrow, Cow};
+use std::collections::{HashMap, VecDeque};
 use std::fmt::Debug;
+use std::sync::{Arc, OnceLock};
+
+use rand::thread_rng;
 
 use crate::pad_secret::{Secret, ALL_ZEROS_SECRET};
 use crate::utils::tree_index_from_u64;
 use crate::{
-    error::{DecodingError, TreeError},
+    arena::NodeArena,
+    error::{DecodingError, IntegrityError, TreeError},
+    filter::LeafFilter,
     index::{TreeIndex, MAX_HEIGHT},
-    traits::{Mergeable, Paddable, ProofExtractable, Serializable},
+    metrics::{MetricsHook, MetricsRecorder, Operation, OperationMetrics},
+    proof::MerkleProof,
+    traits::{
+        InclusionProvable, Mergeable, Paddable, PaddingContext, PaddingDomain, ProofExtractable,
+        Serializable, Summable,
+    },
     utils::{log_2, Nil},
 };
 
+/// The number of top levels of the tree stored in the dense index of a tree.
+const DENSE_TOP_LEVELS: usize = 10;
+/// The entry of an absent node in the dense index of a tree.
+const DENSE_NONE: usize = usize::MAX;
+
 /// The direction of a child node, either left or right.
 #[derive(Debug, Clone, PartialEq, Copy)]
 pub enum ChildDir {
@@ -138,39 +155,284 @@ impl<V: Clone + Default + Mergeable + Paddable> TreeNode<V> {
     }
 }
 
+/// The policy of resolving duplicated indexes in the input list of tree construction or batched update.
+pub enum DuplicatePolicy<'a, P> {
+    /// Reject the list with [TreeError::IndexDuplicated](../error/enum.TreeError.html#variant.IndexDuplicated).
+    Error,
+    /// Keep the first value of a duplicated index in the input list.
+    KeepFirst,
+    /// Keep the last value of a duplicated index in the input list.
+    KeepLast,
+    /// Combine the values of a duplicated index in the order of the input list,
+    /// e.g., summing up balances of the same account index.
+    Combine(&'a dyn Fn(&P, &P) -> P),
+}
+
+impl<'a, P> Default for DuplicatePolicy<'a, P> {
+    /// The default policy is [DuplicatePolicy::Error](../tree/enum.DuplicatePolicy.html#variant.Error),
+    /// the same as [build](struct.SparseMerkleTree.html#method.build).
+    fn default() -> DuplicatePolicy<'a, P> {
+        DuplicatePolicy::Error
+    }
+}
+
+/// The statistics of a tree, returned by [stats](struct.SparseMerkleTree.html#method.stats).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeStats {
+    /// The number of leaf nodes.
+    pub leaves_num: usize,
+    /// The number of padding nodes.
+    pub paddings_num: usize,
+    /// The number of internal nodes.
+    pub internals_num: usize,
+    /// The number of nodes without child, i.e., leaves and padding nodes, at each depth from 0 to the height.
+    pub depth_distribution: Vec<usize>,
+    /// The number of nodes stored in the node list, including those not reachable from the root.
+    pub arena_len: usize,
+    /// The number of nodes reachable from the root.
+    pub reachable_nodes_num: usize,
+    /// The estimated heap footprint of the node list in bytes,
+    /// excluding the heap memory owned by the node values.
+    pub estimated_heap_bytes: usize,
+}
+
+/// The references to the tree nodes in a (batched) Merkle proof.
+///
+/// It consists of the references to the proved leaves in the order of their indexes,
+/// and the references to the siblings in the order of the siblings in the Merkle proof,
+/// together with the tree index of each sibling.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MerklePathRefs {
+    // The tree indexes of the proved leaves.
+    indexes: Vec<TreeIndex>,
+    // The references to the proved leaves.
+    leaves: Vec<usize>,
+    // The references to the siblings in the Merkle proof.
+    siblings: Vec<usize>,
+    // The tree indexes of the siblings.
+    sibling_indexes: Vec<TreeIndex>,
+}
+
+impl MerklePathRefs {
+    /// Returns the tree indexes of the proved leaves.
+    pub fn indexes(&self) -> &[TreeIndex] {
+        &self.indexes
+    }
+
+    /// Returns the references to the proved leaves, in the same order as the indexes.
+    pub fn leaves(&self) -> &[usize] {
+        &self.leaves
+    }
+
+    /// Returns the references to the siblings, in the same order as the siblings in the Merkle proof.
+    pub fn siblings(&self) -> &[usize] {
+        &self.siblings
+    }
+
+    /// Returns the tree indexes of the siblings, in the same order as [siblings](#method.siblings).
+    pub fn sibling_indexes(&self) -> &[TreeIndex] {
+        &self.sibling_indexes
+    }
+
+    /// Returns the reference to the sibling of the node at height ```level``` on the path from the root to the leaf ```index```,
+    /// where the sibling of the leaf is at level ```index.get_height()```.
+    ///
+    /// If the sibling is not part of the Merkle proof, e.g., when it is on the path of another proved leaf
+    /// or when ```level``` is out of the range ```[1, height]```, return ```None```.
+    pub fn sibling_for(&self, index: &TreeIndex, level: usize) -> Option<usize> {
+        if level == 0 || level > index.get_height() {
+            return None;
+        }
+        let target = index.get_prefix(level).get_sibling_index();
+        self.sibling_indexes
+            .iter()
+            .position(|x| *x == target)
+            .map(|i| self.siblings[i])
+    }
+}
+
+/// The entry of a leaf of a tree, either existing or not, returned by
+/// [SparseMerkleTree::entry](struct.SparseMerkleTree.html#method.entry).
+pub struct LeafEntry<'a, P> {
+    tree: &'a mut SparseMerkleTree<P>,
+    key: TreeIndex,
+    // The reference to the leaf if it exists.
+    leaf: Option<usize>,
+}
+
+impl<'a, P: Clone + Default + Mergeable + Paddable + ProofExtractable> LeafEntry<'a, P>
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// Returns the tree index of the entry.
+    pub fn get_key(&self) -> &TreeIndex {
+        &self.key
+    }
+
+    /// Returns the value of the leaf, or ```None``` if the leaf doesn't exist.
+    pub fn get(&self) -> Option<&P> {
+        self.leaf.map(|x| self.tree.nodes[x].get_value())
+    }
+
+    /// Returns true if the leaf exists.
+    pub fn is_occupied(&self) -> bool {
+        self.leaf.is_some()
+    }
+
+    /// Set the value of the leaf, and returns the previous value, or ```None``` if the leaf didn't exist.
+    pub fn insert(self, value: P, secret: &Secret) -> Option<P> {
+        self.tree.update(&self.key, value, secret)
+    }
+
+    /// Returns the value of the leaf, inserting the value computed by ```f``` if the leaf doesn't exist.
+    pub fn or_insert_with<F: FnOnce() -> P>(self, secret: &Secret, f: F) -> &'a P {
+        match self.leaf {
+            Some(x) => self.tree.nodes[x].get_value(),
+            None => self.tree.get_or_insert_with(&self.key, secret, f),
+        }
+    }
+
+    /// Update the value of the leaf with ```f``` of the current value if the leaf exists.
+    pub fn and_modify<F: FnOnce(&P) -> P>(self, secret: &Secret, f: F) -> LeafEntry<'a, P> {
+        if let Some(x) = self.leaf {
+            let value = f(self.tree.nodes[x].get_value());
+            self.tree.update(&self.key, value, secret);
+        }
+        self
+    }
+}
+
 /// Paddable sparse Merkle tree.
-#[derive(Default, Debug)]
+///
+/// A cloned tree shares the metrics recorder, if any, with the original tree.
+/// It also shares the nodes with the original tree, stored in fixed-size pages:
+/// updating either tree copies only the pages of the nodes it writes (copy-on-write).
+///
+/// The references to the nodes in the top levels are also stored in a dense array in the implicit heap layout,
+/// built at the first lookup after building and patched along the path of every update, so that the first steps of a path walk for lookups,
+/// Merkle proofs and random sampling are index arithmetic instead of pointer chasing.
+#[derive(Default, Debug, Clone)]
 pub struct SparseMerkleTree<P> {
-    height: usize,
+    pub(crate) height: usize,
     // The height of the SMT.
-    root: usize,
+    pub(crate) root: usize,
     // The reference to the root of the SMT.
-    nodes: Vec<TreeNode<P>>, // The values of tree nodes.
+    pub(crate) nodes: NodeArena<P>, // The values of tree nodes, shared page by page with the snapshots.
+    pub(crate) metrics: MetricsHook, // The optional recorder of operation metrics.
+    pub(crate) dirty: Vec<(usize, TreeIndex)>, // The leaves patched by set_leaf_raw.
+    pub(crate) top: OnceLock<Vec<usize>>, // The dense index of the top levels, patched at every update.
+    pub(crate) leaf_filter: Option<LeafFilter>, // The optional filter of the leaf indexes.
+    pub(crate) padding_domain: PaddingDomain, // The epoch and the tag of the padding nodes.
+}
+
+/// Two trees are equal if they have the same height and the same nodes at every tree index,
+/// i.e., the same node types and values, independent of the order of the nodes in memory.
+///
+/// The metrics recorders, the leaf filters and the leaves staged by [set_leaf_raw](#method.set_leaf_raw) are not compared.
+impl<P: PartialEq> PartialEq for SparseMerkleTree<P> {
+    fn eq(&self, other: &Self) -> bool {
+        if self.height != other.height {
+            return false;
+        }
+        // Walk both trees from the roots, comparing the nodes at the same tree index.
+        let mut stack: Vec<(usize, usize)> = vec![(self.root, other.root)];
+        while let Some((a, b)) = stack.pop() {
+            let (x, y) = (&self.nodes[a], &other.nodes[b]);
+            if x.node_type != y.node_type || x.value != y.value {
+                return false;
+            }
+            match ((x.lch, x.rch), (y.lch, y.rch)) {
+                ((Some(xl), Some(xr)), (Some(yl), Some(yr))) => {
+                    stack.push((xl, yl));
+                    stack.push((xr, yr));
+                }
+                ((None, None), (None, None)) => {}
+                _ => return false,
+            }
+        }
+        true
+    }
 }
 
+impl<P: Eq> Eq for SparseMerkleTree<P> {}
+
 impl<P: Clone + Default + Mergeable + Paddable + ProofExtractable> SparseMerkleTree<P>
 where
     <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
 {
-    /// The constructor.
+    /// The constructor of an empty tree, whose root is padded with
+    /// [ALL_ZEROS_SECRET](../pad_secret/constant.ALL_ZEROS_SECRET.html).
     ///
     /// Panics if the input height exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html).
     pub fn new(height: usize) -> SparseMerkleTree<P> {
+        Self::new_with_secret(height, &ALL_ZEROS_SECRET)
+    }
+
+    /// The constructor of an empty tree, whose root is the padding node derived from the input secret,
+    /// the same as the root of a tree built from an empty list with the secret.
+    ///
+    /// Panics if the input height exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html).
+    pub fn new_with_secret(height: usize, secret: &Secret) -> SparseMerkleTree<P> {
         if height > MAX_HEIGHT {
             panic!("{}", DecodingError::ExceedMaxHeight);
         }
         let mut root_node = TreeNode::<P>::new(NodeType::Padding);
-        root_node.set_value(P::padding(&TreeIndex::zero(0), &ALL_ZEROS_SECRET));
+        root_node.set_value(P::padding_with_context(
+            &TreeIndex::zero(0),
+            &PaddingContext::new(height, secret),
+        ));
         SparseMerkleTree {
             height,
             root: 0,
-            nodes: vec![root_node],
+            nodes: NodeArena::from(vec![root_node]),
+            metrics: MetricsHook::default(),
+            dirty: Vec::new(),
+            top: OnceLock::new(),
+            leaf_filter: None,
+            padding_domain: PaddingDomain::default(),
         }
     }
 
+    /// The constructor reserving memory for the nodes of a tree with the expected number of leaves,
+    /// avoiding reallocation of the node list when building a large tree.
+    ///
+    /// Panics if the input height exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html).
+    pub fn with_capacity(height: usize, expected_leaves: usize) -> SparseMerkleTree<P> {
+        let mut tree = Self::new(height);
+        tree.reserve(expected_leaves);
+        tree
+    }
+
+    /// Reserve memory for the nodes of at least ```additional``` more leaves.
+    ///
+    /// The number of nodes is bounded by that of a tree where the leaves share as few ancestors as possible,
+    /// which is close to that of a tree with uniformly random leaf indexes.
+    pub fn reserve(&mut self, additional: usize) {
+        let bound = get_nodes_num_bound(self.height, additional);
+        self.nodes_mut().reserve(bound);
+    }
+
+    /// Construct SMT of the input height from the input list of sorted index-value pairs, index being the sorting key.
+    ///
+    /// If the height exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html),
+    /// return [TreeError::ExceedMaxHeight](../error/enum.TreeError.html#variant.ExceedMaxHeight).
+    ///
+    /// If the input list is not valid, return the error of
+    /// [check_index_list_validity](#method.check_index_list_validity).
+    pub fn from_list(
+        height: usize,
+        list: &[(TreeIndex, P)],
+        secret: &Secret,
+    ) -> Result<SparseMerkleTree<P>, TreeError> {
+        TreeBuilder::new(height).secret(secret).build_from(list)
+    }
+
     /// A simple Merkle tree constructor, where all items are added next to each other from left to
     /// right. Note that zero padding secret is used and the height depends on the input list size.
     /// Use this helper constructor only when simulating a plain Merkle tree.
+    ///
+    /// See [try_new_merkle_tree](#method.try_new_merkle_tree) for building from an iterator
+    /// with a secret and a height, without panicking.
     pub fn new_merkle_tree(list: &[P]) -> SparseMerkleTree<P> {
         let height = log_2(list.len() as u32) as usize;
         let mut smtree = Self::new(height);
@@ -178,6 +440,133 @@ where
         smtree
     }
 
+    /// A plain Merkle tree constructor from the items of an iterator, added next to each other from left to right,
+    /// with the padding nodes derived from the input secret.
+    ///
+    /// The height is the input one if any,
+    /// or otherwise the smallest height with enough leaf positions for the items.
+    ///
+    /// If the height exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html),
+    /// return [TreeError::ExceedMaxHeight](../error/enum.TreeError.html#variant.ExceedMaxHeight).
+    /// If there are more items than leaf positions in the tree of the input height,
+    /// return [TreeError::TreeFull](../error/enum.TreeError.html#variant.TreeFull).
+    pub fn try_new_merkle_tree<I: IntoIterator<Item = P>>(
+        items: I,
+        secret: &Secret,
+        height: Option<usize>,
+    ) -> Result<SparseMerkleTree<P>, TreeError> {
+        let items: Vec<P> = items.into_iter().collect();
+        let height = match height {
+            Some(x) => x,
+            None => (64 - (items.len() as u64).saturating_sub(1).leading_zeros()) as usize,
+        };
+        if height > MAX_HEIGHT {
+            return Err(TreeError::ExceedMaxHeight);
+        }
+        let list: Vec<(TreeIndex, P)> = items
+            .into_iter()
+            .enumerate()
+            .map(|(pos, item)| {
+                TreeIndex::try_from_u64(height, pos as u64)
+                    .map(|idx| (idx, item))
+                    .map_err(|_| TreeError::TreeFull)
+            })
+            .collect::<Result<_, _>>()?;
+        let mut smtree = Self::new_with_secret(height, secret);
+        smtree.build(&list, secret);
+        Ok(smtree)
+    }
+
+    /// Reconstruct a tree from the index-node pairs of all its nodes,
+    /// e.g., the output of [get_index_node_pairs](#method.get_index_node_pairs) restored from a backup.
+    ///
+    /// The pairs can be in any order, and the references in the input nodes are ignored:
+    /// the links are rebuilt from the tree indexes, with the nodes stored in the order of the pairs.
+    /// Only the structure is validated, use [verify_integrity](#method.verify_integrity) to validate the values.
+    ///
+    /// If the height exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html),
+    /// return [DecodingError::ExceedMaxHeight](../error/enum.DecodingError.html#variant.ExceedMaxHeight).
+    /// If the pairs don't form a tree of the input height, e.g., an index is duplicated,
+    /// a node misses its parent or a child, or a node type doesn't fit to the height of its index,
+    /// return [DecodingError::ValueDecodingError](../error/enum.DecodingError.html#variant.ValueDecodingError).
+    pub fn from_index_node_pairs<I, N>(
+        height: usize,
+        pairs: I,
+    ) -> Result<SparseMerkleTree<P>, DecodingError>
+    where
+        I: IntoIterator<Item = (TreeIndex, N)>,
+        N: Borrow<TreeNode<P>>,
+    {
+        if height > MAX_HEIGHT {
+            return Err(DecodingError::ExceedMaxHeight);
+        }
+        let invalid = |msg: &str, idx: &TreeIndex| DecodingError::ValueDecodingError {
+            msg: format!("{} at {:?}", msg, idx),
+        };
+        let mut indexes: Vec<TreeIndex> = Vec::new();
+        let mut nodes: Vec<TreeNode<P>> = Vec::new();
+        let mut links: HashMap<TreeIndex, usize> = HashMap::new();
+        for (idx, node) in pairs {
+            let node = node.borrow();
+            let node_type = node.get_node_type().clone();
+            if idx.get_height() > height || !idx.is_canonical() {
+                return Err(invalid("The index doesn't fit to the tree", &idx));
+            }
+            if (node_type == NodeType::Leaf && idx.get_height() != height)
+                || (node_type == NodeType::Internal && idx.get_height() == height)
+            {
+                return Err(invalid("The node type doesn't fit to the index", &idx));
+            }
+            if links.insert(idx, nodes.len()).is_some() {
+                return Err(invalid("The index is duplicated", &idx));
+            }
+            let mut new_node = TreeNode::new(node_type);
+            new_node.set_value(node.get_value().clone());
+            indexes.push(idx);
+            nodes.push(new_node);
+        }
+        let root = match links.get(&TreeIndex::zero(0)) {
+            Some(&x) => x,
+            None => {
+                return Err(DecodingError::ValueDecodingError {
+                    msg: "The root is missing".to_owned(),
+                })
+            }
+        };
+
+        // Link every node to its parent.
+        for (link, idx) in indexes.iter().enumerate() {
+            if idx.get_height() == 0 {
+                continue;
+            }
+            let parent = match links.get(&idx.get_parent_index()) {
+                Some(&x) if nodes[x].node_type == NodeType::Internal => x,
+                _ => return Err(invalid("The parent is missing or not internal", idx)),
+            };
+            nodes[link].set_parent(parent);
+            if idx.get_last_bit() == 0 {
+                nodes[parent].set_lch(link);
+            } else {
+                nodes[parent].set_rch(link);
+            }
+        }
+        for (node, idx) in nodes.iter().zip(indexes.iter()) {
+            if node.node_type == NodeType::Internal && (node.lch.is_none() || node.rch.is_none()) {
+                return Err(invalid("The internal node misses a child", idx));
+            }
+        }
+        Ok(SparseMerkleTree {
+            height,
+            root,
+            nodes: NodeArena::from(nodes),
+            metrics: MetricsHook::default(),
+            dirty: Vec::new(),
+            top: OnceLock::new(),
+            leaf_filter: None,
+            padding_domain: PaddingDomain::default(),
+        })
+    }
+
     /// Returns the height of the SMT.
     pub fn get_height(&self) -> usize {
         self.height
@@ -188,6 +577,215 @@ where
         self.nodes.len()
     }
 
+    /// Returns an immutable snapshot of the current version of the tree, sharing the nodes with the tree.
+    ///
+    /// Taking and cloning a snapshot doesn't copy the nodes.
+    /// The updates of the tree copy only the pages of the nodes they write, sharing the others with the snapshot,
+    /// so the snapshot keeps serving the proofs of this version while the tree moves on to the next one.
+    /// The snapshot doesn't keep the leaf filter of the tree, if any.
+    pub fn snapshot(&self) -> SmtSnapshot<P> {
+        SmtSnapshot {
+            tree: SparseMerkleTree {
+                height: self.height,
+                root: self.root,
+                nodes: self.nodes.clone(),
+                metrics: MetricsHook::default(),
+                dirty: Vec::new(),
+                top: self.top.clone(),
+                leaf_filter: None,
+                padding_domain: self.padding_domain.clone(),
+            },
+        }
+    }
+
+    /// Returns the statistics of the tree, walking all the nodes reachable from the root.
+    ///
+    /// More stored than reachable nodes indicate fragmentation of the node list.
+    pub fn stats(&self) -> TreeStats {
+        let mut stats = TreeStats {
+            depth_distribution: vec![0; self.height + 1],
+            arena_len: self.nodes.len(),
+            estimated_heap_bytes: self.nodes.capacity() * std::mem::size_of::<TreeNode<P>>(),
+            ..TreeStats::default()
+        };
+        let mut stack: Vec<(usize, usize)> = vec![(self.root, 0)];
+        while let Some((link, depth)) = stack.pop() {
+            let node = &self.nodes[link];
+            stats.reachable_nodes_num += 1;
+            match node.node_type {
+                NodeType::Internal => stats.internals_num += 1,
+                NodeType::Padding => stats.paddings_num += 1,
+                NodeType::Leaf => stats.leaves_num += 1,
+            }
+            match (node.lch, node.rch) {
+                (Some(lch), Some(rch)) => {
+                    stack.push((lch, depth + 1));
+                    stack.push((rch, depth + 1));
+                }
+                _ => stats.depth_distribution[depth] += 1,
+            }
+        }
+        stats
+    }
+
+    /// Audit the integrity of the tree, e.g., after restoring it from a storage or patching raw nodes.
+    ///
+    /// Walking from the root, check that every reference is in the node list,
+    /// that every child refers back to its parent and every node is reached once,
+    /// that the children of every node match with its type,
+    /// that every internal node is the merge of its children,
+    /// and that every padding node is derived from the input secret,
+    /// comparing the proof nodes of the values.
+    ///
+    /// Return the first found [IntegrityError](../error/enum.IntegrityError.html).
+    pub fn verify_integrity(&self, secret: &Secret) -> Result<(), IntegrityError> {
+        if self.root >= self.nodes.len() {
+            return Err(IntegrityError::InvalidReference { link: self.root });
+        }
+        let mut visited = vec![false; self.nodes.len()];
+        let mut stack: Vec<(usize, TreeIndex)> = vec![(self.root, TreeIndex::zero(0))];
+        while let Some((link, index)) = stack.pop() {
+            if visited[link] {
+                return Err(IntegrityError::LinkMismatch { link, index });
+            }
+            visited[link] = true;
+            let node = &self.nodes[link];
+            match (&node.node_type, node.lch, node.rch) {
+                (NodeType::Internal, Some(lch), Some(rch)) if index.get_height() < self.height => {
+                    for (child, child_index) in
+                        [(lch, index.get_lch_index()), (rch, index.get_rch_index())]
+                    {
+                        if child >= self.nodes.len() {
+                            return Err(IntegrityError::InvalidReference { link: child });
+                        }
+                        if self.nodes[child].parent != Some(link) {
+                            return Err(IntegrityError::LinkMismatch {
+                                link: child,
+                                index: child_index,
+                            });
+                        }
+                        stack.push((child, child_index));
+                    }
+                    let merged: P = Mergeable::merge_with_index(
+                        &self.nodes[lch].value,
+                        &self.nodes[rch].value,
+                        &index,
+                    );
+                    if merged.get_proof_node() != node.value.get_proof_node() {
+                        return Err(IntegrityError::InternalMismatch { link, index });
+                    }
+                }
+                (NodeType::Leaf, None, None) if index.get_height() == self.height => {}
+                (NodeType::Padding, None, None) => {
+                    if self.get_padding(&index, secret).get_proof_node()
+                        != node.value.get_proof_node()
+                    {
+                        return Err(IntegrityError::PaddingMismatch { link, index });
+                    }
+                }
+                _ => return Err(IntegrityError::NodeTypeMismatch { link, index }),
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the estimated heap footprint in bytes of the nodes of a tree of the input height
+    /// with the input number of leaves, excluding the heap memory owned by the node values,
+    /// for sizing machines before building a tree.
+    ///
+    /// The estimation is an upper bound on the number of nodes,
+    /// close to that of a tree with uniformly random leaf indexes.
+    pub fn estimate_heap_bytes(height: usize, leaves_num: usize) -> usize {
+        get_nodes_num_bound(height, leaves_num).saturating_mul(std::mem::size_of::<TreeNode<P>>())
+    }
+
+    /// Returns the epoch and the application tag of the padding nodes of the tree.
+    pub fn get_padding_domain(&self) -> &PaddingDomain {
+        &self.padding_domain
+    }
+
+    /// Set the epoch and the application tag of the padding nodes constructed from then on,
+    /// e.g., before building the tree, or to restore those of a decoded tree, as the encoding doesn't carry them.
+    ///
+    /// The proofs of padding nodes generated from the tree are domain-separated by
+    /// the [domain secret](../traits/struct.PaddingContext.html#method.get_domain_secret) of the context.
+    pub fn set_padding_domain(&mut self, domain: PaddingDomain) {
+        self.padding_domain = domain;
+    }
+
+    /// Returns the context of the padding nodes of the tree with the input secret.
+    pub fn get_padding_context<'a>(&'a self, secret: &'a Secret) -> PaddingContext<'a> {
+        self.padding_domain.get_context(self.height, secret)
+    }
+
+    // Returns the padding node at the input index in the context of the tree.
+    fn get_padding(&self, idx: &TreeIndex, secret: &Secret) -> P {
+        P::padding_with_context(idx, &self.get_padding_context(secret))
+    }
+
+    // Returns the mutable nodes, whose pages shared with a clone or a snapshot are copied when written.
+    //
+    // The dense index of the top levels is kept, so the links of the existing nodes in the top levels
+    // must be changed either through links_mut or together with the dense index.
+    pub(crate) fn nodes_mut(&mut self) -> &mut NodeArena<P> {
+        &mut self.nodes
+    }
+
+    // Returns the mutable nodes as nodes_mut does, dropping the dense index of the top levels
+    // to be rebuilt at the next lookup, for changing the links between the nodes.
+    pub(crate) fn links_mut(&mut self) -> &mut NodeArena<P> {
+        self.top.take();
+        &mut self.nodes
+    }
+
+    // Returns the dense index of the top levels, building it if the links have been changed since the last lookup.
+    //
+    // The reference to the node at depth d whose index has the first d bits b_0...b_{d-1}
+    // is at position 2^d + (b_0...b_{d-1} read as a binary number), or DENSE_NONE if the node doesn't exist.
+    fn get_dense_top(&self) -> &[usize] {
+        self.top.get_or_init(|| {
+            let levels = self.height.min(DENSE_TOP_LEVELS);
+            let mut top = vec![DENSE_NONE; 2 << levels];
+            if self.nodes.is_empty() {
+                return top;
+            }
+            top[1] = self.root;
+            for pos in 1..(1 << levels) {
+                if top[pos] == DENSE_NONE {
+                    continue;
+                }
+                if let Some(x) = self.nodes[top[pos]].get_lch() {
+                    top[2 * pos] = x;
+                }
+                if let Some(x) = self.nodes[top[pos]].get_rch() {
+                    top[2 * pos + 1] = x;
+                }
+            }
+            top
+        })
+    }
+
+    /// Set the recorder receiving the metrics of [build](#method.build), [update](#method.update)
+    /// and proof generation on this tree.
+    pub fn set_metrics_recorder(&mut self, recorder: Arc<dyn MetricsRecorder>) {
+        self.metrics = MetricsHook::new(Some(recorder));
+    }
+
+    /// Remove the metrics recorder, if any.
+    pub fn clear_metrics_recorder(&mut self) {
+        self.metrics = MetricsHook::default();
+    }
+
+    /// Returns the metrics recorder, if any.
+    pub fn get_metrics_recorder(&self) -> Option<&Arc<dyn MetricsRecorder>> {
+        self.metrics.get()
+    }
+
+    /// Report the metrics of an operation to the recorder, if any.
+    pub(crate) fn record_metrics(&self, op: Operation, metrics: &OperationMetrics) {
+        self.metrics.record(op, metrics);
+    }
+
     /// Returns the tree node by reference.
     ///
     /// Panics if the reference is out of range.
@@ -220,6 +818,22 @@ where
         vec
     }
 
+    /// Returns the proof nodes by references, borrowing the nodes of the tree
+    /// when they are their own proof nodes, see [get_proof_node_ref](../traits/trait.ProofExtractable.html#method.get_proof_node_ref).
+    ///
+    /// Panics if the reference is out of range.
+    pub fn get_node_proof_cow_by_refs(&self, list: &[usize]) -> Vec<Cow<'_, P::ProofNode>> {
+        list.iter()
+            .map(|link| {
+                let value = self.get_node_by_ref(*link).get_value();
+                match value.get_proof_node_ref() {
+                    Some(node) => Cow::Borrowed(node),
+                    None => Cow::Owned(value.get_proof_node()),
+                }
+            })
+            .collect()
+    }
+
     /// Returns the reference to the root ndoe.
     pub fn get_root_ref(&self) -> usize {
         self.root
@@ -243,10 +857,22 @@ where
             panic!("{}", TreeError::HeightNotMatch);
         }
 
-        let mut ancestor = self.root;
+        // Navigate by the tree index from the root node through the top levels in the dense index.
+        let top = self.get_dense_top();
+        let levels = self.height.min(DENSE_TOP_LEVELS);
+        let mut pos = 1;
+        for i in 0..levels {
+            let child = 2 * pos + idx.get_bit(i) as usize;
+            if top[child] == DENSE_NONE {
+                return (top[pos], idx.get_prefix(i));
+            }
+            pos = child;
+        }
+
+        let mut ancestor = top[pos];
         let mut ancestor_idx = *idx;
-        // Navigate by the tree index from the root node to the queried node.
-        for i in 0..self.height {
+        // Navigate by the tree index from the bottom of the top levels to the queried node.
+        for i in levels..self.height {
             if idx.get_bit(i) == 0 {
                 // The queried index is in the left sub-tree.
                 if self.nodes[ancestor].get_lch().is_none() {
@@ -282,6 +908,50 @@ where
         }
     }
 
+    /// Returns true if the leaf at the input index exists,
+    /// answering absence from the leaf filter without walking the tree if the filter is enabled.
+    ///
+    /// Panics if the the height of the input index doesn't match with the tree height.
+    pub fn contains_leaf(&self, idx: &TreeIndex) -> bool {
+        if let Some(filter) = &self.leaf_filter {
+            if !filter.may_contain(idx) {
+                return false;
+            }
+        }
+        self.get_leaf_by_index(idx)
+            .is_some_and(|node| *node.get_node_type() == NodeType::Leaf)
+    }
+
+    /// Enable the leaf filter sized for the expected number of leaves with the target false positive rate,
+    /// filled with the current leaves and maintained by the later builds and updates,
+    /// replacing the previous filter, if any.
+    ///
+    /// Panics if the rate is not in the range ```(0, 1)```.
+    pub fn enable_leaf_filter(&mut self, expected_leaves: usize, false_positive_rate: f64) {
+        let mut filter = LeafFilter::new(expected_leaves, false_positive_rate);
+        for (idx, _) in self.leaves_in_order_iter() {
+            filter.insert(&idx);
+        }
+        self.leaf_filter = Some(filter);
+    }
+
+    /// Disable the leaf filter, dropping it.
+    pub fn disable_leaf_filter(&mut self) {
+        self.leaf_filter = None;
+    }
+
+    /// Returns the leaf filter, or None if it is not enabled.
+    pub fn get_leaf_filter(&self) -> Option<&LeafFilter> {
+        self.leaf_filter.as_ref()
+    }
+
+    // Insert the input index of a new or updated leaf into the leaf filter, if enabled.
+    pub(crate) fn insert_into_leaf_filter(&mut self, idx: &TreeIndex) {
+        if let Some(filter) = &mut self.leaf_filter {
+            filter.insert(idx);
+        }
+    }
+
     /// Returns the index-reference pairs of all tree nodes in a BFS order.
     pub fn get_index_ref_pairs(&self) -> Vec<(TreeIndex, usize)> {
         // Run a BFS to go through all tree nodes and
@@ -304,31 +974,62 @@ where
         vec
     }
 
-    /// Returns the index-node pairs of all tree nodes.
+    /// Returns the index-node pairs of all tree nodes in a BFS order.
+    ///
+    /// For walking a large tree, [bfs_iter](#method.bfs_iter) yields the same pairs without collecting them.
     pub fn get_index_node_pairs(&self) -> Vec<(TreeIndex, &TreeNode<P>)> {
-        let mut vec: Vec<(TreeIndex, &TreeNode<P>)> = Vec::new();
-        let index_ref = self.get_index_ref_pairs();
-        for (index, refer) in index_ref {
-            vec.push((index, &self.nodes[refer]));
+        self.bfs_iter().collect()
+    }
+
+    /// Returns an iterator over the index-node pairs of all tree nodes in a BFS order, starting from the root,
+    /// which is also the iterator of ```&SparseMerkleTree```.
+    pub fn bfs_iter(&self) -> BfsIter<'_, P> {
+        BfsIter::new(self)
+    }
+
+    /// Returns an iterator over the index-node pairs of all tree nodes in a DFS pre-order,
+    /// i.e., a node before the subtree of its left child, and that before the subtree of its right child.
+    ///
+    /// The iterator only keeps the right children of the nodes on the current path,
+    /// so its memory is linear in the height of the tree instead of the width of a level.
+    pub fn dfs_preorder_iter(&self) -> DfsPreorderIter<'_, P> {
+        DfsPreorderIter {
+            tree: self,
+            stack: vec![(TreeIndex::zero(0), self.root)],
         }
-        vec
+    }
+
+    /// Returns an iterator over the index-node pairs of all leaf nodes in increasing order of index.
+    pub fn leaves_in_order_iter(&self) -> LeavesIter<'_, P> {
+        LeavesIter {
+            inner: self.dfs_preorder_iter(),
+        }
+    }
+
+    /// Returns a cursor over the leaves in the order of index, positioned right before the input index,
+    /// so that it yields the first leaf at or after the index going forward,
+    /// and the last leaf before the index going backward.
+    ///
+    /// Panics if the height of the input index doesn't match with that of the tree.
+    pub fn leaf_cursor_at(&self, idx: &TreeIndex) -> LeafCursor<'_, P> {
+        let mut cursor = LeafCursor {
+            tree: self,
+            position: None,
+        };
+        cursor.seek(idx);
+        cursor
     }
 
     // Returns the index-node pairs of the input node type.
     fn get_nodes_of_type(&self, _node_type: NodeType) -> Vec<(TreeIndex, &TreeNode<P>)> {
-        let mut vec: Vec<(TreeIndex, &TreeNode<P>)> = Vec::new();
-        let nodes = self.get_index_node_pairs();
-        for (key, value) in nodes.iter() {
-            if _node_type == *value.get_node_type() {
-                vec.push((*key, value));
-            }
-        }
-        vec
+        self.bfs_iter()
+            .filter(|(_, node)| *node.get_node_type() == _node_type)
+            .collect()
     }
 
-    /// Returns the index-node pairs of all leaf nodes.
+    /// Returns the index-node pairs of all leaf nodes, in increasing order of index.
     pub fn get_leaves(&self) -> Vec<(TreeIndex, &TreeNode<P>)> {
-        self.get_nodes_of_type(NodeType::Leaf)
+        self.leaves_in_order_iter().collect()
     }
 
     /// Returns the index-node pairs of all padding nodes.
@@ -345,16 +1046,16 @@ where
     fn add_child(&mut self, parent: usize, dir: ChildDir) {
         let mut node: TreeNode<P> = TreeNode::new(NodeType::Internal);
         node.set_parent(parent); // Link the parent to the child node.
-        self.nodes.push(node);
+        self.nodes_mut().push(node);
         let len = self.nodes.len();
 
         // Link the child to the parent node.
         match dir {
             ChildDir::Left => {
-                self.nodes[parent].set_lch(len - 1);
+                self.nodes_mut()[parent].set_lch(len - 1);
             }
             ChildDir::Right => {
-                self.nodes[parent].set_rch(len - 1);
+                self.nodes_mut()[parent].set_rch(len - 1);
             }
         }
     }
@@ -373,19 +1074,35 @@ where
     /// and return the reference to the new node.
     fn add_node(&mut self, node_type: NodeType) -> usize {
         let node = TreeNode::new(node_type);
-        self.nodes.push(node);
+        self.nodes_mut().push(node);
         self.nodes.len() - 1
     }
 
-    /// Set references to child nodes and the value as the merging result of two child nodes.
-    fn set_children(&mut self, parent: &mut TreeNode<P>, lref: usize, rref: usize) {
-        parent.set_lch(lref);
-        parent.set_rch(rref);
-
-        let lch = self.nodes[lref].get_value();
-        let rch = self.nodes[rref].get_value();
-        let value = Mergeable::merge(lch, rch);
-        parent.set_value(value);
+    /// Set the values of the input parent nodes as the merging results of their child nodes,
+    /// merging all pairs of child nodes in one batch, and count the merges in the input metrics.
+    fn merge_children_batch(
+        &mut self,
+        parents: &[(TreeIndex, usize)],
+        metrics: &mut OperationMetrics,
+    ) {
+        let values = {
+            let pairs: Vec<(&P, &P)> = parents
+                .iter()
+                .map(|(_, link)| {
+                    let node = &self.nodes[*link];
+                    (
+                        self.nodes[node.get_lch().unwrap()].get_value(),
+                        self.nodes[node.get_rch().unwrap()].get_value(),
+                    )
+                })
+                .collect();
+            let indexes: Vec<TreeIndex> = parents.iter().map(|(idx, _)| *idx).collect();
+            P::merge_many_with_index(&pairs, &indexes)
+        };
+        metrics.merges += parents.len() as u64;
+        for ((_, link), value) in parents.iter().zip(values) {
+            self.nodes_mut()[*link].set_value(value);
+        }
     }
 
     /// Check if the tree indexes in the list are all valid and sorted.
@@ -398,28 +1115,94 @@ where
     ///
     /// If there are duplicated indexes in the list,
     /// return [TreeError::IndexDuplicated](../error/enum.TreeError.html#variant.IndexDuplicated).
+    ///
+    /// Use [check_index_list_validity_at](#method.check_index_list_validity_at) to locate the offending entry.
     pub fn check_index_list_validity(&self, list: &[(TreeIndex, P)]) -> Option<TreeError> {
+        self.check_index_list_validity_at(list)
+            .map(|x| x.get_kind())
+    }
+
+    /// Check if the tree indexes in the list are all valid and sorted,
+    /// as [check_index_list_validity](#method.check_index_list_validity) does,
+    /// identifying the first offending entry by its position and tree index.
+    ///
+    /// If the height of some index doesn't match with the height of the tree,
+    /// return [TreeError::HeightNotMatchAt](../error/enum.TreeError.html#variant.HeightNotMatchAt).
+    ///
+    /// If the indexes are not in order,
+    /// return [TreeError::IndexNotSortedAt](../error/enum.TreeError.html#variant.IndexNotSortedAt).
+    ///
+    /// If there are duplicated indexes in the list,
+    /// return [TreeError::IndexDuplicatedAt](../error/enum.TreeError.html#variant.IndexDuplicatedAt).
+    pub fn check_index_list_validity_at(&self, list: &[(TreeIndex, P)]) -> Option<TreeError> {
         // Check validity of the input list.
         for (i, item) in list.iter().enumerate() {
             // Panic if any index in the list doesn't match with the height of the SMT.
             if item.0.get_height() != self.height {
-                return Some(TreeError::HeightNotMatch);
+                return Some(TreeError::HeightNotMatchAt {
+                    position: i,
+                    index: item.0,
+                });
             }
             // Panic if two consecutive indexes after sorting are the same.
             if i > 0 {
                 if item.0 < list[i - 1].0 {
-                    return Some(TreeError::IndexNotSorted);
+                    return Some(TreeError::IndexNotSortedAt {
+                        position: i,
+                        index: item.0,
+                    });
                 }
                 if item.0 == list[i - 1].0 {
-                    return Some(TreeError::IndexDuplicated);
+                    return Some(TreeError::IndexDuplicatedAt {
+                        position: i,
+                        index: item.0,
+                    });
                 }
             }
         }
         None
     }
 
+    /// Check if the tree indexes in the list are all valid and sorted,
+    /// and return every violation in the list instead of stopping at the first one.
+    ///
+    /// Each violation is reported as in [check_index_list_validity_at](#method.check_index_list_validity_at),
+    /// identifying the offending entry by its position and tree index.
+    /// An entry is compared with the entry right before it for sorting and duplicates.
+    ///
+    /// If the list is valid, return an empty vector.
+    pub fn check_index_list_validity_all(&self, list: &[(TreeIndex, P)]) -> Vec<TreeError> {
+        let mut errors: Vec<TreeError> = Vec::new();
+        for (i, item) in list.iter().enumerate() {
+            if item.0.get_height() != self.height {
+                errors.push(TreeError::HeightNotMatchAt {
+                    position: i,
+                    index: item.0,
+                });
+                // The order doesn't make sense for indexes of different heights.
+                continue;
+            }
+            if i > 0 && list[i - 1].0.get_height() == self.height {
+                if item.0 < list[i - 1].0 {
+                    errors.push(TreeError::IndexNotSortedAt {
+                        position: i,
+                        index: item.0,
+                    });
+                } else if item.0 == list[i - 1].0 {
+                    errors.push(TreeError::IndexDuplicatedAt {
+                        position: i,
+                        index: item.0,
+                    });
+                }
+            }
+        }
+        errors
+    }
+
     /// Construct SMT from the input list of sorted index-value pairs, index being the sorting key.
     ///
+    /// An empty list results in an empty tree, whose root is the padding node derived from the input secret.
+    ///
     /// If the height of some index in the input list doesn't match with the height of the tree,
     /// return [TreeError::HeightNotMatch](../error/enum.TreeError.html#variant.HeightNotMatch).
     ///
@@ -437,13 +1220,34 @@ where
         if let Some(x) = self.check_index_list_validity(list) {
             return Some(x);
         }
+        // The patched leaves are discarded with the old nodes.
+        self.dirty.clear();
+        if let Some(filter) = &mut self.leaf_filter {
+            filter.clear();
+            for (idx, _) in list {
+                filter.insert(idx);
+            }
+        }
 
-        // If the input list is empty, no change to the tree.
+        // If the input list is empty, the tree is empty with the padding root derived from the secret.
         if list.is_empty() {
+            self.links_mut().clear();
+            self.root = self.add_node(NodeType::Padding);
+            let root = self.root;
+            let padding = self.get_padding(&TreeIndex::zero(0), secret);
+            self.nodes_mut()[root].set_value(padding);
+            self.record_metrics(
+                Operation::Build,
+                &OperationMetrics {
+                    nodes_allocated: 1,
+                    paddings: 1,
+                    ..OperationMetrics::default()
+                },
+            );
             return None;
         }
         // If the input list is not empty, pop out the original padding root node.
-        self.nodes.pop();
+        self.nodes_mut().pop();
 
         let mut layer: Vec<(TreeIndex, usize)> = Vec::new();
         for (i, item) in list.iter().enumerate() {
@@ -451,10 +1255,41 @@ where
         }
 
         // Clear the node list.
-        self.nodes.clear();
+        self.links_mut().clear();
+
+        // A tree of height 0 consists of the single leaf as the root.
+        let mut metrics = OperationMetrics::default();
+        if self.height == 0 {
+            let link = self.add_node(NodeType::Leaf);
+            self.nodes_mut()[link].set_value(list[0].1.clone());
+            metrics.nodes_allocated += 1;
+        }
+
+        self.construct_layers(layer, Some(list), 0, secret, &mut metrics);
+        self.root = self.nodes.len() - 1; // The root is the last node added to the tree.
+        self.record_metrics(Operation::Build, &metrics);
+        None
+    }
 
+    // Build the layers of the tree from the input layer of sorted index-reference pairs
+    // up to the layer at height ```top```, which is returned.
+    //
+    // If the leaves are given, the input layer is the leaf layer referring to the positions of the leaves in the list,
+    // whose nodes are added to the tree when they are reached.
+    // Otherwise the input layer refers to the nodes in the tree.
+    //
+    // The merges, the added nodes and the padding nodes are counted in the input metrics.
+    fn construct_layers(
+        &mut self,
+        mut layer: Vec<(TreeIndex, usize)>,
+        leaves: Option<&[(TreeIndex, P)]>,
+        top: usize,
+        secret: &Secret,
+        metrics: &mut OperationMetrics,
+    ) -> Vec<(TreeIndex, usize)> {
+        let bottom = layer.first().map_or(top, |(idx, _)| idx.get_height());
         // Build the tree layer by layer.
-        for i in (0..self.height).rev() {
+        for i in (top..bottom).rev() {
             let mut upper: Vec<(TreeIndex, usize)> = Vec::new(); // The upper layer to be constructed.
 
             // Build the upper layer starting from the left-most tree index of the current highest existing layer.
@@ -464,11 +1299,13 @@ where
                 // Get the index and instance of the current child node.
                 let node_idx = &layer[head].0;
                 let node_link: usize; // Reference to the current node.
-                if i == self.height - 1 {
+                let list = leaves.filter(|_| i == bottom - 1);
+                if let Some(list) = list {
                     // If the current layer is the leaf layer, the node hasn't been added to the tree.
                     // Add the node and refer to it, the last node in the node vector.
                     node_link = self.add_node(NodeType::Leaf);
-                    self.nodes[node_link].set_value(list[layer[head].1].1.clone());
+                    self.nodes_mut()[node_link].set_value(list[layer[head].1].1.clone());
+                    metrics.nodes_allocated += 1;
                 } else {
                     // If the current layer is above the leaf layer, the node is already in the list,
                     // and the reference is the second element of the ```(TreeIndex, usize)``` pair.
@@ -491,11 +1328,13 @@ where
                     if head < length - 1 && layer[head + 1].0 == sibling_idx {
                         // When the sibling is the next node in the list,
                         // retrieve the node reference, and move the pointer to the next node.
-                        if i == self.height - 1 {
+                        if let Some(list) = list {
                             // If the current layer is the leaf layer, the node hasn't been added to the tree.
                             // Add the node and refer to it, the last node in the node vector.
                             sibling_link = self.add_node(NodeType::Leaf);
-                            self.nodes[sibling_link].set_value(list[layer[head + 1].1].1.clone());
+                            self.nodes_mut()[sibling_link]
+                                .set_value(list[layer[head + 1].1].1.clone());
+                            metrics.nodes_allocated += 1;
                         } else {
                             // If the current layer is above the leaf layer, the node is already in the list,
                             // and the reference is the second element of the (TreeIndex, usize) pair.
@@ -505,31 +1344,41 @@ where
                     } else {
                         // When the sibling doesn't exist, generate a new padding node.
                         sibling_link = self.add_node(NodeType::Padding);
-                        self.nodes[sibling_link].set_value(Paddable::padding(&sibling_idx, secret));
+                        let padding = self.get_padding(&sibling_idx, secret);
+                        self.nodes_mut()[sibling_link].set_value(padding);
+                        metrics.nodes_allocated += 1;
+                        metrics.paddings += 1;
                     }
-                    self.set_children(&mut parent, node_link, sibling_link);
+                    parent.set_lch(node_link);
+                    parent.set_rch(sibling_link);
                 } else {
                     // When the current node is the right node of its parent,
                     // its sibling doesn't exist yet, so need to generate a new padding node.
                     sibling_link = self.add_node(NodeType::Padding);
-                    self.nodes[sibling_link].set_value(Paddable::padding(&sibling_idx, secret));
-                    self.set_children(&mut parent, sibling_link, node_link);
+                    let padding = self.get_padding(&sibling_idx, secret);
+                    self.nodes_mut()[sibling_link].set_value(padding);
+                    metrics.nodes_allocated += 1;
+                    metrics.paddings += 1;
+                    parent.set_lch(sibling_link);
+                    parent.set_rch(node_link);
                 }
 
-                self.nodes.push(parent); // Add the parent node to the node list.
-                                         // Link the child nodes to the parent.
+                self.nodes_mut().push(parent); // Add the parent node to the node list.
+                metrics.nodes_allocated += 1;
+                // Link the child nodes to the parent.
                 let len = self.nodes.len();
-                self.nodes[node_link].set_parent(len - 1);
-                self.nodes[sibling_link].set_parent(len - 1);
+                self.nodes_mut()[node_link].set_parent(len - 1);
+                self.nodes_mut()[sibling_link].set_parent(len - 1);
                 upper.push((parent_idx, len - 1)); // Add the new parent node to the upper layer for generating the next layer.
 
                 head += 1; // Done with the current node, move the pointer to the next node.
             }
+            // Merge the child nodes of the whole upper layer at once.
+            self.merge_children_batch(&upper, metrics);
             layer.clear();
             layer = upper; // Continue to generate the upper layer.
         }
-        self.root = self.nodes.len() - 1; // The root is the last node added to the tree.
-        None
+        layer
     }
 
     /// Build SMT from the input list of sorted index-value pairs, index being the sorting key.
@@ -539,52 +1388,234 @@ where
         if let Some(x) = self.construct_smt_nodes(list, secret) {
             panic!("{}", x);
         }
+        log_debug!(
+            "Built SMT of height {} with {} leaves and {} nodes",
+            self.height,
+            list.len(),
+            self.nodes.len()
+        );
     }
 
-    /// Build simple Merkle tree from the input list with zero padding secret.
+    /// Clear the tree and rebuild it from the input list of sorted index-value pairs, index being the sorting key,
+    /// reusing the memory of the node list, e.g., when regenerating the whole tree every epoch.
     ///
-    /// Panics if the input list is not valid.
-    fn build_merkle_tree_zero_padding(&mut self, list: &[P]) {
-        let tree_list: Vec<(TreeIndex, P)> = list
-            .iter()
-            .enumerate()
-            .map(|(index, p)| (tree_index_from_u64(self.height, index as u64), p.clone()))
-            .collect();
-        if let Some(x) = self.construct_smt_nodes(&tree_list, &ALL_ZEROS_SECRET) {
-            panic!("{}", x);
+    /// If the input list is not valid, return the error of
+    /// [check_index_list_validity](#method.check_index_list_validity), leaving the tree unchanged.
+    pub fn rebuild(&mut self, list: &[(TreeIndex, P)], secret: &Secret) -> Result<(), TreeError> {
+        if let Some(x) = self.check_index_list_validity(list) {
+            return Err(x);
+        }
+        self.links_mut().clear();
+        self.reserve(list.len());
+        match self.construct_smt_nodes(list, secret) {
+            Some(x) => Err(x),
+            None => Ok(()),
         }
     }
 
-    /// Retrieve the path from the root to the input leaf node.
-    /// If there is any node on the path or its sibling not existing yet, add it to the tree.
-    fn retrieve_path(&mut self, key: &TreeIndex) -> Vec<usize> {
-        let mut vec: Vec<usize> = Vec::new();
+    /// Build SMT from the input list of index-value pairs in any order.
+    ///
+    /// The list is sorted by index internally with a stable sort before construction.
+    ///
+    /// Panics if the input list is not valid after sorting,
+    /// i.e., some index height doesn't match with the tree height or there are duplicated indexes.
+    pub fn build_unsorted(&mut self, mut list: Vec<(TreeIndex, P)>, secret: &Secret) {
+        list.sort_by_key(|a| a.0);
+        self.build(&list, secret);
+    }
+
+    /// Sort the input list of index-value pairs by index with a stable sort,
+    /// and resolve duplicated indexes according to the input policy.
+    ///
+    /// If there are duplicated indexes and the policy is [DuplicatePolicy::Error],
+    /// return [TreeError::IndexDuplicatedAt](../error/enum.TreeError.html#variant.IndexDuplicatedAt)
+    /// with the position of the later duplicate in the input list.
+    pub fn resolve_duplicates(
+        list: Vec<(TreeIndex, P)>,
+        policy: &DuplicatePolicy<P>,
+    ) -> Result<Vec<(TreeIndex, P)>, TreeError> {
+        // Keep the positions in the input list for error reporting.
+        let mut list: Vec<(usize, (TreeIndex, P))> = list.into_iter().enumerate().collect();
+        list.sort_by_key(|a| (a.1).0);
+        let mut resolved: Vec<(TreeIndex, P)> = Vec::with_capacity(list.len());
+        for (position, item) in list {
+            match resolved.last_mut() {
+                Some(last) if last.0 == item.0 => match policy {
+                    DuplicatePolicy::Error => {
+                        return Err(TreeError::IndexDuplicatedAt {
+                            position,
+                            index: item.0,
+                        })
+                    }
+                    DuplicatePolicy::KeepFirst => {}
+                    DuplicatePolicy::KeepLast => last.1 = item.1,
+                    DuplicatePolicy::Combine(f) => last.1 = f(&last.1, &item.1),
+                },
+                _ => resolved.push(item),
+            }
+        }
+        Ok(resolved)
+    }
+
+    /// Build SMT from the input list of index-value pairs in any order,
+    /// resolving duplicated indexes according to the input policy.
+    ///
+    /// Panics if the input list is not valid after resolving duplicates.
+    pub fn build_with_policy(
+        &mut self,
+        list: Vec<(TreeIndex, P)>,
+        secret: &Secret,
+        policy: &DuplicatePolicy<P>,
+    ) {
+        match Self::resolve_duplicates(list, policy) {
+            Ok(list) => self.build(&list, secret),
+            Err(x) => panic!("{}", x),
+        }
+    }
+
+    /// Update the tree with the input list of index-value pairs in any order,
+    /// resolving duplicated indexes according to the input policy.
+    ///
+    /// Panics if the height of some input index doesn't match with that of the tree,
+    /// or if there are duplicated indexes with the policy [DuplicatePolicy::Error].
+    pub fn update_batch(
+        &mut self,
+        list: Vec<(TreeIndex, P)>,
+        secret: &Secret,
+        policy: &DuplicatePolicy<P>,
+    ) {
+        match Self::resolve_duplicates(list, policy) {
+            Ok(list) => self.update_sorted_batch(list, secret),
+            Err(x) => panic!("{}", x),
+        }
+    }
+
+    /// Update the tree with the input list of index-value pairs sorted by index without duplicates,
+    /// recomputing each ancestor of the updated leaves once, layer by layer from the leaves to the root,
+    /// instead of once per leaf below it.
+    ///
+    /// Panics if the height of some input index doesn't match with that of the tree.
+    pub fn update_sorted_batch(&mut self, list: Vec<(TreeIndex, P)>, secret: &Secret) {
+        if list.iter().any(|(key, _)| key.get_height() != self.height) {
+            panic!("{}", TreeError::HeightNotMatch)
+        }
+        let nodes_num = self.nodes.len();
+        let mut paddings = 0u64;
+        let mut merges = 0u64;
+
+        // Set the leaves, and collect the ancestors layer by layer, each ancestor once.
+        let mut layers: Vec<Vec<(usize, TreeIndex)>> = vec![Vec::new(); self.height];
+        for (key, value) in list {
+            let vec = self.retrieve_path(&key);
+            let leaf = vec[self.height];
+            self.nodes_mut()[leaf].set_node_type(NodeType::Leaf);
+            self.nodes_mut()[leaf].set_value(value);
+            self.insert_into_leaf_filter(&key);
+            for (i, parent) in vec.into_iter().take(self.height).enumerate() {
+                // The ancestors of sorted leaves are also sorted in each layer.
+                if layers[i].last().map(|x| x.0) != Some(parent) {
+                    layers[i].push((parent, key.get_prefix(i)));
+                }
+            }
+        }
+
+        // Merge the ancestors from the lowest layer to the root.
+        for layer in layers.iter().rev() {
+            for (parent, idx) in layer {
+                self.nodes_mut()[*parent].set_node_type(NodeType::Internal);
+                let lch = self.nodes[*parent].get_lch().unwrap();
+                let rch = self.nodes[*parent].get_rch().unwrap();
+                for (child, child_idx) in [(lch, idx.get_lch_index()), (rch, idx.get_rch_index())] {
+                    // A child without its own child, which is not a leaf, is a padding node.
+                    if *self.nodes[child].get_node_type() != NodeType::Leaf
+                        && self.nodes[child].get_lch().is_none()
+                        && self.nodes[child].get_rch().is_none()
+                    {
+                        self.nodes_mut()[child].set_node_type(NodeType::Padding);
+                        let padding = self.get_padding(&child_idx, secret);
+                        self.nodes_mut()[child].set_value(padding);
+                        paddings += 1;
+                    }
+                }
+                let new_value = Mergeable::merge_with_index(
+                    self.nodes[lch].get_value(),
+                    self.nodes[rch].get_value(),
+                    idx,
+                );
+                self.nodes_mut()[*parent].set_value(new_value);
+                merges += 1;
+            }
+        }
+
+        self.record_metrics(
+            Operation::Update,
+            &OperationMetrics {
+                merges,
+                nodes_allocated: (self.nodes.len() - nodes_num) as u64,
+                paddings,
+            },
+        );
+    }
 
-        // Start from the index of the root.
-        let mut node_idx = TreeIndex::zero(0);
+    /// Build simple Merkle tree from the input list with zero padding secret.
+    ///
+    /// Panics if the input list is not valid.
+    fn build_merkle_tree_zero_padding(&mut self, list: &[P]) {
+        let tree_list: Vec<(TreeIndex, P)> = list
+            .iter()
+            .enumerate()
+            .map(|(index, p)| (tree_index_from_u64(self.height, index as u64), p.clone()))
+            .collect();
+        if let Some(x) = self.construct_smt_nodes(&tree_list, &ALL_ZEROS_SECRET) {
+            panic!("{}", x);
+        }
+    }
+
+    /// Add the missing children of the input node, returning whether any child is added.
+    fn add_missing_children(&mut self, node: usize) -> bool {
+        let mut added = false;
+        if self.nodes[node].get_lch().is_none() {
+            self.add_lch(node);
+            added = true;
+        }
+        if self.nodes[node].get_rch().is_none() {
+            self.add_rch(node);
+            added = true;
+        }
+        added
+    }
+
+    /// Retrieve the path from the root to the input leaf node.
+    /// If there is any node on the path or its sibling not existing yet, add it to the tree.
+    fn retrieve_path(&mut self, key: &TreeIndex) -> Vec<usize> {
+        let mut vec: Vec<usize> = Vec::with_capacity(self.height + 1);
         let mut node: usize = self.root;
         vec.push(node); // Add the root to the path.
 
-        for i in 0..self.height {
-            // Add the left child if not exist.
-            if self.nodes[node].get_lch().is_none() {
-                self.add_lch(node);
-            }
-            // Add the right child if not exist.
-            if self.nodes[node].get_rch().is_none() {
-                self.add_rch(node);
+        // Walk through the top levels in the dense index, patching the slots of the added children.
+        self.get_dense_top();
+        let levels = self.height.min(DENSE_TOP_LEVELS);
+        let mut pos = 1;
+        for i in 0..levels {
+            let added = self.add_missing_children(node);
+            let top = self.top.get_mut().unwrap();
+            if added {
+                top[2 * pos] = self.nodes[node].get_lch().unwrap();
+                top[2 * pos + 1] = self.nodes[node].get_rch().unwrap();
             }
+            pos = 2 * pos + key.get_bit(i) as usize;
+            node = top[pos];
+            vec.push(node);
+        }
 
-            // Move on to the next node in the path.
-            if key.get_bit(i) == 0 {
-                // Go to the left child.
-                node = self.nodes[node].get_lch().unwrap();
-                node_idx = node_idx.get_lch_index();
+        // Walk from the bottom of the top levels to the leaf by the links.
+        for i in levels..self.height {
+            self.add_missing_children(node);
+            node = if key.get_bit(i) == 0 {
+                self.nodes[node].get_lch().unwrap()
             } else {
-                // Go to the right child.
-                node = self.nodes[node].get_rch().unwrap();
-                node_idx = node_idx.get_rch_index();
-            }
+                self.nodes[node].get_rch().unwrap()
+            };
             vec.push(node);
         }
         vec
@@ -592,19 +1623,189 @@ where
 
     /// Update the tree by modifying the leaf node of a certain tree index.
     ///
+    /// Returns the previous value of the leaf, or ```None``` if the leaf didn't exist.
+    ///
+    /// Panics if the height of the input index doesn't match with that of the tree.
+    pub fn update(&mut self, key: &TreeIndex, value: P, secret: &Secret) -> Option<P> {
+        self.update_and_extract_siblings(key, value, secret, false)
+            .0
+    }
+
+    /// Returns the value of the leaf at the input index,
+    /// inserting the value computed by ```f``` if the leaf doesn't exist,
+    /// in one walk from the root to the leaf.
+    ///
     /// Panics if the height of the input index doesn't match with that of the tree.
-    pub fn update(&mut self, key: &TreeIndex, value: P, secret: &Secret) {
+    pub fn get_or_insert_with<F: FnOnce() -> P>(
+        &mut self,
+        key: &TreeIndex,
+        secret: &Secret,
+        f: F,
+    ) -> &P {
+        if key.get_height() != self.height {
+            panic!("{}", TreeError::HeightNotMatch)
+        }
+        // The path to an existing leaf already exists, so nothing is added to the tree.
+        let nodes_num = self.nodes.len();
+        let vec = self.retrieve_path(key);
+        let leaf = vec[vec.len() - 1];
+        if *self.nodes[leaf].get_node_type() != NodeType::Leaf {
+            self.update_retrieved_path(key, &vec, nodes_num, f(), secret, false);
+        }
+        self.nodes[leaf].get_value()
+    }
+
+    /// Returns the entry of the leaf at the input index for in-place inspection and update.
+    ///
+    /// Panics if the height of the input index doesn't match with that of the tree.
+    pub fn entry(&mut self, key: &TreeIndex) -> LeafEntry<'_, P> {
+        let (node, node_idx) = self.get_closest_ancestor_ref_index(key);
+        let leaf = if node_idx.get_height() == self.height
+            && *self.nodes[node].get_node_type() == NodeType::Leaf
+        {
+            Some(node)
+        } else {
+            None
+        };
+        LeafEntry {
+            tree: self,
+            key: *key,
+            leaf,
+        }
+    }
+
+    /// Update the tree by modifying the leaf node of a certain tree index as [update](#method.update) does,
+    /// and returns the Merkle proof of the new leaf, extracted during the same walk along the path.
+    ///
+    /// Panics if the height of the input index doesn't match with that of the tree.
+    pub fn update_and_prove(
+        &mut self,
+        key: &TreeIndex,
+        value: P,
+        secret: &Secret,
+    ) -> MerkleProof<P> {
+        let (_, siblings) = self.update_and_extract_siblings(key, value, secret, true);
+        let mut proof = MerkleProof::<P>::new(*key);
+        self.record_metrics(
+            Operation::ProofGeneration,
+            &OperationMetrics {
+                nodes_allocated: siblings.len() as u64,
+                ..OperationMetrics::default()
+            },
+        );
+        proof.set_siblings(siblings);
+        proof
+    }
+
+    /// Set the raw value of the existing leaf at the input index without updating its ancestors,
+    /// e.g., to adjust auxiliary fields of a composite node type,
+    /// staging the leaf for [recompute_dirty](#method.recompute_dirty).
+    ///
+    /// The root and the proofs are stale until the staged leaves are recomputed.
+    ///
+    /// If the height of the index doesn't match with that of the tree,
+    /// return [TreeError::HeightNotMatch](../error/enum.TreeError.html#variant.HeightNotMatch).
+    /// If the index is not a leaf of the tree,
+    /// return [TreeError::LeafNotFound](../error/enum.TreeError.html#variant.LeafNotFound).
+    pub fn set_leaf_raw(&mut self, idx: &TreeIndex, value: P) -> Result<(), TreeError> {
+        if idx.get_height() != self.height {
+            return Err(TreeError::HeightNotMatch);
+        }
+        let (link, node_idx) = self.get_closest_ancestor_ref_index(idx);
+        if node_idx.get_height() < self.height || self.nodes[link].node_type != NodeType::Leaf {
+            return Err(TreeError::LeafNotFound);
+        }
+        self.nodes_mut()[link].set_value(value);
+        self.dirty.push((link, *idx));
+        Ok(())
+    }
+
+    /// Returns the number of leaves staged by [set_leaf_raw](#method.set_leaf_raw) and not recomputed yet.
+    pub fn get_dirty_num(&self) -> usize {
+        self.dirty.len()
+    }
+
+    /// Re-merge the ancestors of the leaves staged by [set_leaf_raw](#method.set_leaf_raw),
+    /// merging each affected internal node once from the bottom up, and clear the staged leaves.
+    pub fn recompute_dirty(&mut self) {
+        if self.dirty.is_empty() {
+            return;
+        }
+        // Collect the ancestors of the staged leaves with their depths and tree indexes.
+        let mut ancestors: Vec<(usize, usize, TreeIndex)> = Vec::new();
+        let dirty = std::mem::take(&mut self.dirty);
+        for (leaf, key) in dirty {
+            let mut depth = self.height;
+            let mut link = leaf;
+            while let Some(parent) = self.nodes[link].parent {
+                depth -= 1;
+                ancestors.push((depth, parent, key.get_prefix(depth)));
+                link = parent;
+            }
+        }
+        // Merge the deepest nodes first, each node once.
+        ancestors.sort_unstable_by(|a, b| b.cmp(a));
+        ancestors.dedup();
+        let mut metrics = OperationMetrics::default();
+        for (_, link, idx) in ancestors {
+            // An ancestor is an internal node, having both children.
+            let lch = self.nodes[link].lch.unwrap();
+            let rch = self.nodes[link].rch.unwrap();
+            let value =
+                Mergeable::merge_with_index(&self.nodes[lch].value, &self.nodes[rch].value, &idx);
+            self.nodes_mut()[link].set_value(value);
+            metrics.merges += 1;
+        }
+        self.record_metrics(Operation::Update, &metrics);
+    }
+
+    // Updates the leaf, and returns its previous value,
+    // together with the siblings along the path from the root to the leaf if required.
+    fn update_and_extract_siblings(
+        &mut self,
+        key: &TreeIndex,
+        value: P,
+        secret: &Secret,
+        extract_siblings: bool,
+    ) -> (Option<P>, Vec<P::ProofNode>) {
         // Panic if the height of the input tree index doesn't match with that of the tree.
         if key.get_height() != self.height {
             panic!("{}", TreeError::HeightNotMatch)
         }
 
+        let nodes_num = self.nodes.len();
         let vec = self.retrieve_path(key); // Retrieve the path from the root to the input leaf node.
+        self.update_retrieved_path(key, &vec, nodes_num, value, secret, extract_siblings)
+    }
+
+    // Updates the leaf at the end of the retrieved path as update_and_extract_siblings does,
+    // with nodes_num being the number of nodes before retrieving the path.
+    fn update_retrieved_path(
+        &mut self,
+        key: &TreeIndex,
+        vec: &[usize],
+        nodes_num: usize,
+        value: P,
+        secret: &Secret,
+        extract_siblings: bool,
+    ) -> (Option<P>, Vec<P::ProofNode>) {
+        let mut paddings = 0u64;
+        let mut siblings: Vec<P::ProofNode> = Vec::new();
 
         // Update the leaf node.
         let len = vec.len();
-        self.nodes[vec[len - 1]].set_node_type(NodeType::Leaf);
-        self.nodes[vec[len - 1]].set_value(value);
+        let leaf = &mut self.nodes_mut()[vec[len - 1]];
+        let old_value = match leaf.node_type {
+            NodeType::Leaf => Some(std::mem::replace(&mut leaf.value, value)),
+            _ => {
+                leaf.set_value(value);
+                None
+            }
+        };
+        leaf.set_node_type(NodeType::Leaf);
+        if old_value.is_none() {
+            self.insert_into_leaf_filter(key);
+        }
 
         assert_eq!(len - 1, self.height); // Make sure the length of the path matches with the tree height.
 
@@ -612,18 +1813,15 @@ where
         let mut idx = *key; // The node index starting from the leaf node.
         for i in (0..len - 1).rev() {
             let parent = vec[i]; // The link to the parent node.
-            self.nodes[parent].set_node_type(NodeType::Internal);
-
-            let sibling: usize;
-            let sibling_idx: TreeIndex;
+            self.nodes_mut()[parent].set_node_type(NodeType::Internal);
 
             // Get the link to and the index of the sibling node.
-            if idx.get_last_bit() == 0 {
-                sibling = self.nodes[parent].get_rch().unwrap();
+            let sibling = if idx.get_last_bit() == 0 {
+                self.nodes[parent].get_rch().unwrap()
             } else {
-                sibling = self.nodes[parent].get_lch().unwrap();
-            }
-            sibling_idx = idx.get_sibling_index();
+                self.nodes[parent].get_lch().unwrap()
+            };
+            let sibling_idx = idx.get_sibling_index();
 
             // Adjust the node type of the sibling node.
             match *self.nodes[sibling].get_node_type() {
@@ -633,39 +1831,74 @@ where
                     if self.nodes[sibling].get_lch().is_none()
                         && self.nodes[sibling].get_rch().is_none()
                     {
-                        self.nodes[sibling].set_node_type(NodeType::Padding);
-                        self.nodes[sibling].set_value(Paddable::padding(&sibling_idx, secret));
+                        self.nodes_mut()[sibling].set_node_type(NodeType::Padding);
+                        let padding = self.get_padding(&sibling_idx, secret);
+                        self.nodes_mut()[sibling].set_value(padding);
+                        paddings += 1;
                     }
                 }
             }
 
+            // The sibling doesn't change any more in the update.
+            if extract_siblings {
+                siblings.push(self.nodes[sibling].get_value().get_proof_node());
+            }
+
             // Merge the two child nodes and set the value of the parent node.
-            let new_value = Mergeable::merge(
+            let new_value = Mergeable::merge_with_index(
                 self.nodes[self.nodes[parent].get_lch().unwrap()].get_value(),
                 self.nodes[self.nodes[parent].get_rch().unwrap()].get_value(),
+                &idx.get_parent_index(),
             );
-            self.nodes[parent].set_value(new_value);
+            self.nodes_mut()[parent].set_value(new_value);
 
             idx = idx.get_parent_index(); // Move on to the node at the upper layer.
         }
+
+        self.record_metrics(
+            Operation::Update,
+            &OperationMetrics {
+                merges: (len - 1) as u64,
+                nodes_allocated: (self.nodes.len() - nodes_num) as u64,
+                paddings,
+            },
+        );
+        // The siblings were extracted from the leaf to the root.
+        siblings.reverse();
+        (old_value, siblings)
     }
 
-    /// Returns the references to the input leaf node and siblings of nodes long the Merkle path from the root to the leaf.
-    /// The result is a list of references ```[leaf, sibling, ..., sibling]```.
+    /// Returns the references to the input leaf node and siblings of nodes long the Merkle path from the root to the leaf,
+    /// with siblings ordered from the child of the root to the sibling of the leaf.
     ///
     /// If the input leaf node doesn't exist, return ```None```.
     ///
     /// Panics if the height of the input index is different from the height of the tree.
-    pub fn get_merkle_path_ref(&self, idx: &TreeIndex) -> Option<Vec<usize>> {
+    pub fn get_merkle_path_ref(&self, idx: &TreeIndex) -> Option<MerklePathRefs> {
         // Panics if the height of the input index is different from the height of the tree.
         if idx.get_height() != self.height {
             panic!("{}", TreeError::HeightNotMatch);
         }
 
         let mut siblings = Vec::new();
-        let mut node = self.root;
-        // Add references to sibling nodes along the path from the root to the input node.
-        for i in 0..self.height {
+        let mut sibling_indexes = Vec::new();
+        // Add references to sibling nodes along the path from the root through the top levels in the dense index.
+        let top = self.get_dense_top();
+        let levels = self.height.min(DENSE_TOP_LEVELS);
+        let mut pos = 1;
+        for i in 0..levels {
+            let child = 2 * pos + idx.get_bit(i) as usize;
+            if top[child] == DENSE_NONE {
+                return None;
+            }
+            siblings.push(top[child ^ 1]);
+            sibling_indexes.push(idx.get_prefix(i + 1).get_sibling_index());
+            pos = child;
+        }
+
+        let mut node = top[pos];
+        // Add references to sibling nodes along the path from the bottom of the top levels to the input node.
+        for i in levels..self.height {
             if idx.get_bit(i) == 0 {
                 // Add the reference to the right child to the sibling list and move on to the left child.
                 self.nodes[node].get_lch()?;
@@ -677,24 +1910,28 @@ where
                 siblings.push(self.nodes[node].get_lch().unwrap());
                 node = self.nodes[node].get_rch().unwrap();
             }
+            sibling_indexes.push(idx.get_prefix(i + 1).get_sibling_index());
         }
-        let mut path = vec![node];
-        path.append(&mut siblings);
-        Some(path) // Some([leaf, sibling, ..., sibling])
+        Some(MerklePathRefs {
+            indexes: vec![*idx],
+            leaves: vec![node],
+            siblings,
+            sibling_indexes,
+        })
     }
 
-    /// Returns the references to the input leaves and siblings of nodes long the batched Merkle paths from the root to the leaves.
-    /// The result is a list of references ```[leaf, ..., leaf, sibling, ..., sibling]```.
+    /// Returns the references to the input leaves and siblings of nodes long the batched Merkle paths from the root to the leaves,
+    /// with siblings in the BFS order.
     ///
     /// If the root or some input leaf node doesn't exist, return ```None```.
     ///
-    /// If the input list is empty, return an empty vector.
+    /// If the input list is empty, return empty references.
     ///
     /// Panics if the input list is not valid.
-    pub fn get_merkle_path_ref_batch(&self, list: &[TreeIndex]) -> Option<Vec<usize>> {
-        // If the input list is empty, return an empty vector.
+    pub fn get_merkle_path_ref_batch(&self, list: &[TreeIndex]) -> Option<MerklePathRefs> {
+        // If the input list is empty, return empty references.
         if list.is_empty() {
-            return Some(Vec::new());
+            return Some(MerklePathRefs::default());
         }
 
         // Construct an SMT from the input list of indexes with void value.
@@ -712,15 +1949,17 @@ where
         // in the BFS order of all nodes in proof_tree.
         let mut leaves: Vec<usize> = Vec::new();
         let mut siblings: Vec<usize> = Vec::new();
+        let mut sibling_indexes: Vec<TreeIndex> = Vec::new();
         let vec = proof_tree.get_index_ref_pairs(); // Get the index-ref pair in BFS order.
         let mut smt_refs = vec![0usize; vec.len()]; // Map from nodes in proof_tree to nodes in self.
         smt_refs[vec[0].1] = self.root;
-        for (_idx, proof_ref) in vec {
+        for (idx, proof_ref) in vec {
             let smt_ref = smt_refs[proof_ref];
             match &proof_tree.nodes[proof_ref].node_type {
                 // The padding node in proof_tree is a sibling node in the batched proof.
                 NodeType::Padding => {
                     siblings.push(smt_ref);
+                    sibling_indexes.push(idx);
                 }
                 // The leaf node in proof_tree in also a leaf node in the batched proof.
                 NodeType::Leaf => {
@@ -739,8 +1978,85 @@ where
                 smt_refs[x] = self.nodes[smt_ref].get_rch().unwrap();
             }
         }
-        leaves.append(&mut siblings);
-        Some(leaves) // Some([leaf, ..., leaf, sibling, ..., sibling])
+        Some(MerklePathRefs {
+            indexes: list.to_vec(),
+            leaves,
+            siblings,
+            sibling_indexes,
+        })
+    }
+
+    /// Returns the Merkle proof of the leaf at the input position, counting from 0 at the leftmost leaf position,
+    /// e.g., of the ```pos```-th item of a tree built with [new_merkle_tree](#method.new_merkle_tree).
+    ///
+    /// Return ```None``` if the position doesn't fit to the tree or there is no leaf at the position.
+    pub fn prove_position(&self, pos: u64) -> Option<MerkleProof<P>> {
+        let idx = TreeIndex::try_from_u64(self.height, pos).ok()?;
+        if *self.get_leaf_by_index(&idx)?.get_node_type() != NodeType::Leaf {
+            return None;
+        }
+        MerkleProof::<P>::generate_inclusion_proof(self, &[idx])
+    }
+
+    /// Verify that the input leaf is at the input position of the tree of the input height and the input root,
+    /// with a proof generated by [prove_position](#method.prove_position).
+    ///
+    /// Return false if the proof is not of a single leaf at the position of a tree of the input height,
+    /// with one sibling per level.
+    pub fn verify_position(
+        root: &<P as ProofExtractable>::ProofNode,
+        height: usize,
+        pos: u64,
+        leaf: &P,
+        proof: &MerkleProof<P>,
+    ) -> bool {
+        match proof.get_indexes() {
+            [idx] => {
+                idx.get_height() == height
+                    && proof.get_siblings_num() == height
+                    && TreeIndex::try_from_u64(height, pos) == Ok(*idx)
+                    && proof.verify_inclusion_proof(&[leaf.get_proof_node()], root)
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns an iterator over the Merkle proofs of all leaves, from the leftmost leaf to the rightmost one.
+    ///
+    /// The proofs are generated in one top-down pass:
+    /// the siblings on the path from the root are shared by all leaves below,
+    /// so each node is extracted once instead of once per leaf below it.
+    pub fn export_all_proofs(&self) -> impl Iterator<Item = (TreeIndex, MerkleProof<P>)> + '_ {
+        // The siblings of the nodes on the path from the root to the current node.
+        let mut path: Vec<P::ProofNode> = Vec::with_capacity(self.height);
+        // The nodes to be visited, together with their tree indexes and the references to their siblings.
+        let mut stack: Vec<(usize, TreeIndex, Option<usize>)> =
+            vec![(self.root, TreeIndex::zero(0), None)];
+        std::iter::from_fn(move || {
+            while let Some((node, idx, sibling)) = stack.pop() {
+                if let Some(sibling) = sibling {
+                    path.truncate(idx.get_height() - 1);
+                    path.push(self.nodes[sibling].get_value().get_proof_node());
+                }
+                match self.nodes[node].get_node_type() {
+                    NodeType::Leaf => {
+                        let mut proof = MerkleProof::<P>::new(idx);
+                        proof.set_siblings(path.clone());
+                        return Some((idx, proof));
+                    }
+                    NodeType::Internal => {
+                        // Both children of an internal node exist.
+                        let lch = self.nodes[node].get_lch().unwrap();
+                        let rch = self.nodes[node].get_rch().unwrap();
+                        // The left child is visited first.
+                        stack.push((rch, idx.get_rch_index(), Some(lch)));
+                        stack.push((lch, idx.get_lch_index(), Some(rch)));
+                    }
+                    NodeType::Padding => {}
+                }
+            }
+            None
+        })
     }
 
     /// Returns the tree index of closest left/right (depending on input direction) node in the tree.
@@ -784,19 +2100,97 @@ where
         }
 
         // Retrieve the opp_dir most node in the subtree, which is our target.
-        while *self.nodes[closest_ref].get_node_type() == NodeType::Internal {
-            if *self.nodes[self.nodes[closest_ref].get_child_by_dir(opp_dir).unwrap()]
-                .get_node_type()
+        Some(
+            self.get_outermost_by_dir(closest_ref, closest_idx, opp_dir)
+                .1,
+        )
+    }
+
+    // Returns the reference and the tree index of the dir-most non-padding node in the subtree of the input node.
+    fn get_outermost_by_dir(
+        &self,
+        mut node_ref: usize,
+        mut node_idx: TreeIndex,
+        dir: ChildDir,
+    ) -> (usize, TreeIndex) {
+        let mut opp_dir = ChildDir::Left;
+        if dir == ChildDir::Left {
+            opp_dir = ChildDir::Right;
+        }
+        while *self.nodes[node_ref].get_node_type() == NodeType::Internal {
+            if *self.nodes[self.nodes[node_ref].get_child_by_dir(dir).unwrap()].get_node_type()
                 == NodeType::Padding
             {
-                closest_ref = self.nodes[closest_ref].get_child_by_dir(dir).unwrap();
-                closest_idx = closest_idx.get_child_index_by_dir(dir);
+                node_ref = self.nodes[node_ref].get_child_by_dir(opp_dir).unwrap();
+                node_idx = node_idx.get_child_index_by_dir(opp_dir);
             } else {
-                closest_ref = self.nodes[closest_ref].get_child_by_dir(opp_dir).unwrap();
-                closest_idx = closest_idx.get_child_index_by_dir(opp_dir);
+                node_ref = self.nodes[node_ref].get_child_by_dir(dir).unwrap();
+                node_idx = node_idx.get_child_index_by_dir(dir);
+            }
+        }
+        (node_ref, node_idx)
+    }
+
+    /// Returns the tree index and the value of the left most leaf in the tree, or None if the tree is empty.
+    pub fn first_leaf(&self) -> Option<(TreeIndex, &P)> {
+        self.get_extreme_leaf(ChildDir::Left)
+    }
+
+    /// Returns the tree index and the value of the right most leaf in the tree, or None if the tree is empty.
+    pub fn last_leaf(&self) -> Option<(TreeIndex, &P)> {
+        self.get_extreme_leaf(ChildDir::Right)
+    }
+
+    /// Returns the number of leaves with indexes in the range ```[start, end]```, both ends included.
+    ///
+    /// The subtrees out of the range are pruned, and those in the range are walked without comparing indexes,
+    /// so the cost is linear in the height and the number of counted leaves, not in the size of the tree.
+    ///
+    /// Panics if the height of either end doesn't match with that of the tree.
+    pub fn count_leaves_in_range(&self, start: &TreeIndex, end: &TreeIndex) -> usize {
+        if start.get_height() != self.height || end.get_height() != self.height {
+            panic!("{}", TreeError::HeightNotMatch);
+        }
+        if start > end {
+            return 0;
+        }
+        let mut count = 0;
+        // The reference and the depth of a node,
+        // and whether its index is a prefix of the start and the end respectively.
+        let mut stack: Vec<(usize, usize, bool, bool)> = vec![(self.root, 0, true, true)];
+        while let Some((link, depth, at_start, at_end)) = stack.pop() {
+            let node = &self.nodes[link];
+            match node.node_type {
+                NodeType::Padding => {}
+                NodeType::Leaf => count += 1,
+                NodeType::Internal => {
+                    // An internal node has both children.
+                    for (bit, child) in [(0, node.lch.unwrap()), (1, node.rch.unwrap())] {
+                        if (at_start && bit < start.get_bit(depth))
+                            || (at_end && bit > end.get_bit(depth))
+                        {
+                            continue;
+                        }
+                        stack.push((
+                            child,
+                            depth + 1,
+                            at_start && bit == start.get_bit(depth),
+                            at_end && bit == end.get_bit(depth),
+                        ));
+                    }
+                }
             }
         }
-        Some(closest_idx)
+        count
+    }
+
+    // Returns the left/right (depending on the input direction) most leaf in the tree.
+    fn get_extreme_leaf(&self, dir: ChildDir) -> Option<(TreeIndex, &P)> {
+        if *self.nodes[self.root].get_node_type() == NodeType::Padding {
+            return None;
+        }
+        let (node_ref, node_idx) = self.get_outermost_by_dir(self.root, TreeIndex::zero(0), dir);
+        Some((node_idx, self.nodes[node_ref].get_value()))
     }
 
     /// Returns the index-reference pairs to necessary padding nodes to prove that
@@ -871,3 +2265,654 @@ where
         refs
     }
 }
+
+impl<P: Clone + Default + Mergeable + Paddable + ProofExtractable + Send + Sync> SparseMerkleTree<P>
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// Construct the tree as [construct_smt_nodes](#method.construct_smt_nodes) does,
+    /// using up to the input number of threads.
+    ///
+    /// The sorted list is partitioned by the top path bits of the indexes, giving at least one partition per thread,
+    /// and the subtree of each partition is built on its own thread, the partitions being split evenly among the threads.
+    /// The roots of the subtrees are then merged into the top levels on the calling thread,
+    /// so the threads don't synchronize while building.
+    ///
+    /// If there is a single thread, or the tree is not higher than the top levels, the tree is built on the calling thread.
+    pub fn construct_smt_nodes_parallel(
+        &mut self,
+        list: &[(TreeIndex, P)],
+        secret: &Secret,
+        threads: usize,
+    ) -> Option<TreeError> {
+        // The number of the top levels above the partitions.
+        let levels = threads.max(1).next_power_of_two().trailing_zeros() as usize;
+        if levels == 0 || self.height <= levels || list.is_empty() {
+            return self.construct_smt_nodes(list, secret);
+        }
+        if let Some(x) = self.check_index_list_validity(list) {
+            return Some(x);
+        }
+        self.dirty.clear();
+        if let Some(filter) = &mut self.leaf_filter {
+            filter.clear();
+            for (idx, _) in list {
+                filter.insert(idx);
+            }
+        }
+
+        // Build the subtrees of the partitions, each thread building the subtrees of consecutive partitions
+        // into its own node list.
+        let partitions: Vec<&[(TreeIndex, P)]> = list
+            .chunk_by(|a, b| a.0.get_prefix(levels) == b.0.get_prefix(levels))
+            .collect();
+        let per_thread = partitions.len().div_ceil(threads);
+        let height = self.height;
+        let padding_domain = &self.padding_domain;
+        let built = std::thread::scope(|scope| {
+            let handles: Vec<_> = partitions
+                .chunks(per_thread)
+                .map(|group| {
+                    scope.spawn(move || {
+                        let mut subtree = SparseMerkleTree::<P>::new(height);
+                        // The padding nodes of the subtrees are in the padding domain of the tree.
+                        subtree.padding_domain = padding_domain.clone();
+                        subtree.nodes_mut().clear();
+                        let mut roots: Vec<(TreeIndex, usize)> = Vec::with_capacity(group.len());
+                        let mut metrics = OperationMetrics::default();
+                        for part in group {
+                            let layer = part.iter().enumerate().map(|(i, x)| (x.0, i)).collect();
+                            roots.extend(subtree.construct_layers(
+                                layer,
+                                Some(part),
+                                levels,
+                                secret,
+                                &mut metrics,
+                            ));
+                        }
+                        (std::mem::take(subtree.nodes_mut()), roots, metrics)
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|e| std::panic::resume_unwind(e))
+                })
+                .collect::<Vec<_>>()
+        });
+
+        // Move the subtrees into the node list, shifting their references, and merge their roots up to the root.
+        let nodes = self.links_mut();
+        nodes.clear();
+        let mut layer: Vec<(TreeIndex, usize)> = Vec::with_capacity(partitions.len());
+        let mut metrics = OperationMetrics::default();
+        for (subtree, roots, subtree_metrics) in built {
+            metrics.add(&subtree_metrics);
+            let base = nodes.len();
+            nodes.extend(subtree.into_nodes().map(|mut node| {
+                node.parent = node.parent.map(|x| x + base);
+                node.lch = node.lch.map(|x| x + base);
+                node.rch = node.rch.map(|x| x + base);
+                node
+            }));
+            layer.extend(roots.into_iter().map(|(idx, link)| (idx, link + base)));
+        }
+        self.construct_layers(layer, None, 0, secret, &mut metrics);
+        self.root = self.nodes.len() - 1; // The root is the last node added to the tree.
+        self.record_metrics(Operation::Build, &metrics);
+        None
+    }
+}
+
+impl<P: Clone + Default + Mergeable + Paddable + ProofExtractable + Summable> SparseMerkleTree<P>
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// Returns the leaf covering the input offset when the sums of the leaves are laid out from left to right,
+    /// i.e., the leaf whose range ```[offset, offset + sum)``` contains the target,
+    /// by descending to the left or the right child according to the sum of the left child.
+    ///
+    /// If the target is not less than the sum of the root, return ```None```.
+    ///
+    /// The descent can be proved by the Merkle proof of the returned leaf, see
+    /// [MerkleProof::verify_cumulative_sum](../proof/struct.MerkleProof.html#method.verify_cumulative_sum).
+    pub fn select_by_cumulative_sum(&self, target: u64) -> Option<(TreeIndex, &P)> {
+        if target >= self.get_root_raw().get_sum() {
+            return None;
+        }
+        let mut target = target;
+        let mut link = self.root;
+        let mut idx = TreeIndex::zero(0);
+        while let (Some(lch), Some(rch)) = (self.nodes[link].get_lch(), self.nodes[link].get_rch())
+        {
+            let left_sum = self.nodes[lch].get_value().get_sum();
+            if target < left_sum {
+                link = lch;
+                idx = idx.get_lch_index();
+            } else {
+                target -= left_sum;
+                link = rch;
+                idx = idx.get_rch_index();
+            }
+        }
+        if idx.get_height() != self.height {
+            return None;
+        }
+        Some((idx, self.nodes[link].get_value()))
+    }
+}
+
+/// Returns the maximum number of nodes in a tree of the input height with the input number of leaves.
+///
+/// There are at most ```min(2^i, leaves)``` internal nodes at level ```i```,
+/// and every node except the root is a child of an internal node.
+fn get_nodes_num_bound(height: usize, leaves: usize) -> usize {
+    if leaves == 0 {
+        return 0;
+    }
+    let internals = (0..height).fold(0usize, |acc, i| {
+        let level = if i < usize::BITS as usize - 1 {
+            (1usize << i).min(leaves)
+        } else {
+            leaves
+        };
+        acc.saturating_add(level)
+    });
+    internals.saturating_mul(2).saturating_add(1)
+}
+
+/// The policy of deriving the padding nodes when building a tree with [TreeBuilder].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PaddingPolicy {
+    /// Derive the padding nodes from the secret of the builder, the default policy.
+    #[default]
+    Secret,
+    /// Derive the padding nodes from [ALL_ZEROS_SECRET](../pad_secret/constant.ALL_ZEROS_SECRET.html),
+    /// ignoring the secret of the builder, e.g., to simulate a plain Merkle tree.
+    Zero,
+    /// Derive the padding nodes from a fresh random secret that is discarded after the construction,
+    /// when the padding nodes never need to be proved.
+    Random,
+}
+
+/// A builder of [SparseMerkleTree] with configuration options, e.g.,
+/// ```TreeBuilder::new(height).secret(&secret).build_from(&list)```.
+///
+/// An option that is not set takes its default value:
+/// the secret is [ALL_ZEROS_SECRET](../pad_secret/constant.ALL_ZEROS_SECRET.html),
+/// the padding policy is [PaddingPolicy::Secret], and the parallelism is 1.
+#[derive(Clone)]
+pub struct TreeBuilder<'a> {
+    height: usize,
+    // The secret, or None for the all-zeros secret.
+    secret: Option<&'a Secret>,
+    padding_policy: PaddingPolicy,
+    parallelism: usize,
+}
+
+impl<'a> TreeBuilder<'a> {
+    /// The constructor of a builder of trees of the input height.
+    pub fn new(height: usize) -> TreeBuilder<'a> {
+        TreeBuilder {
+            height,
+            secret: None,
+            padding_policy: PaddingPolicy::default(),
+            parallelism: 1,
+        }
+    }
+
+    /// Set the secret for deriving the padding nodes.
+    pub fn secret(mut self, secret: &'a Secret) -> TreeBuilder<'a> {
+        self.secret = Some(secret);
+        self
+    }
+
+    /// Set the policy of deriving the padding nodes.
+    pub fn padding_policy(mut self, policy: PaddingPolicy) -> TreeBuilder<'a> {
+        self.padding_policy = policy;
+        self
+    }
+
+    /// Set the maximum number of threads the construction may use, where 0 is taken as 1.
+    ///
+    /// The threads are used by [build_from_parallel](#method.build_from_parallel),
+    /// while [build_from](#method.build_from) runs on the calling thread.
+    pub fn parallelism(mut self, threads: usize) -> TreeBuilder<'a> {
+        self.parallelism = threads.max(1);
+        self
+    }
+
+    /// Returns the height of the trees to build.
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the policy of deriving the padding nodes.
+    pub fn get_padding_policy(&self) -> PaddingPolicy {
+        self.padding_policy
+    }
+
+    /// Returns the maximum number of threads the construction may use.
+    pub fn get_parallelism(&self) -> usize {
+        self.parallelism
+    }
+
+    /// Build a tree from the input list of sorted index-value pairs, index being the sorting key.
+    ///
+    /// If the height exceeds [MAX_HEIGHT](../index/constant.MAX_HEIGHT.html),
+    /// return [TreeError::ExceedMaxHeight](../error/enum.TreeError.html#variant.ExceedMaxHeight).
+    ///
+    /// If the input list is not valid, return the error of
+    /// [check_index_list_validity](struct.SparseMerkleTree.html#method.check_index_list_validity).
+    pub fn build_from<P: Clone + Default + Mergeable + Paddable + ProofExtractable>(
+        &self,
+        list: &[(TreeIndex, P)],
+    ) -> Result<SparseMerkleTree<P>, TreeError>
+    where
+        <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+    {
+        self.build_with(list, |tree, secret| tree.construct_smt_nodes(list, secret))
+    }
+
+    /// Build a tree from the input list of sorted index-value pairs as [build_from](#method.build_from) does,
+    /// building the subtrees below the top levels on up to [parallelism](#method.parallelism) threads,
+    /// see [construct_smt_nodes_parallel](struct.SparseMerkleTree.html#method.construct_smt_nodes_parallel).
+    ///
+    /// The tree is the same as the one built by [build_from](#method.build_from),
+    /// up to the order of the nodes in memory.
+    pub fn build_from_parallel<
+        P: Clone + Default + Mergeable + Paddable + ProofExtractable + Send + Sync,
+    >(
+        &self,
+        list: &[(TreeIndex, P)],
+    ) -> Result<SparseMerkleTree<P>, TreeError>
+    where
+        <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+    {
+        self.build_with(list, |tree, secret| {
+            tree.construct_smt_nodes_parallel(list, secret, self.parallelism)
+        })
+    }
+
+    // Returns the tree constructed by the input function with the padding secret of the padding policy.
+    fn build_with<P: Clone + Default + Mergeable + Paddable + ProofExtractable>(
+        &self,
+        list: &[(TreeIndex, P)],
+        construct: impl FnOnce(&mut SparseMerkleTree<P>, &Secret) -> Option<TreeError>,
+    ) -> Result<SparseMerkleTree<P>, TreeError>
+    where
+        <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+    {
+        if self.height > MAX_HEIGHT {
+            return Err(TreeError::ExceedMaxHeight);
+        }
+        let mut tree = SparseMerkleTree::with_capacity(self.height, list.len());
+        let error = match self.padding_policy {
+            PaddingPolicy::Secret => construct(&mut tree, self.secret.unwrap_or(&ALL_ZEROS_SECRET)),
+            PaddingPolicy::Zero => construct(&mut tree, &ALL_ZEROS_SECRET),
+            PaddingPolicy::Random => construct(&mut tree, &Secret::generate(&mut thread_rng())),
+        };
+        match error {
+            Some(x) => Err(x),
+            None => Ok(tree),
+        }
+    }
+}
+
+/// An immutable, cheaply clonable snapshot of a version of a sparse Merkle tree,
+/// taken with [snapshot](struct.SparseMerkleTree.html#method.snapshot).
+///
+/// The snapshot shares the nodes with the tree and its other snapshots,
+/// and serves the root, the leaves and the proofs of the version it was taken at.
+#[derive(Debug, Clone)]
+pub struct SmtSnapshot<P> {
+    tree: SparseMerkleTree<P>,
+}
+
+impl<P: Clone + Default + Mergeable + Paddable + ProofExtractable> SmtSnapshot<P>
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// Returns the height of the tree.
+    pub fn get_height(&self) -> usize {
+        self.tree.get_height()
+    }
+
+    /// Returns the root of the tree at the snapshot.
+    pub fn get_root(&self) -> <P as ProofExtractable>::ProofNode {
+        self.tree.get_root()
+    }
+
+    /// Returns the leaf node at the input index, or None if it is not a leaf at the snapshot.
+    pub fn get_leaf_by_index(&self, idx: &TreeIndex) -> Option<&TreeNode<P>> {
+        self.tree.get_leaf_by_index(idx)
+    }
+
+    /// Returns the Merkle proof of the input sorted leaves against the root of the snapshot.
+    ///
+    /// Return ```None``` if any input index is not a leaf at the snapshot.
+    pub fn generate_inclusion_proof(&self, list: &[TreeIndex]) -> Option<MerkleProof<P>> {
+        MerkleProof::<P>::generate_inclusion_proof(&self.tree, list)
+    }
+
+    /// Returns the tree at the snapshot, for the read-only methods of the tree,
+    /// e.g., generating random sampling proofs.
+    pub fn get_tree(&self) -> &SparseMerkleTree<P> {
+        &self.tree
+    }
+}
+
+/// A handle for ingesting leaf writes in any order into a tree,
+/// buffering the writes and flushing them as a sorted batch with
+/// [update_sorted_batch](struct.SparseMerkleTree.html#method.update_sorted_batch)
+/// once the number of buffered writes reaches the threshold.
+///
+/// The tree only reflects the writes after they are flushed.
+/// The remaining writes are flushed when the handle is dropped.
+pub struct BufferedWriter<'a, P: Clone + Default + Mergeable + Paddable + ProofExtractable>
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    tree: &'a mut SparseMerkleTree<P>,
+    secret: &'a Secret,
+    buffer: Vec<(TreeIndex, P)>,
+    threshold: usize,
+}
+
+impl<'a, P: Clone + Default + Mergeable + Paddable + ProofExtractable> BufferedWriter<'a, P>
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    /// The constructor, flushing the writes into the tree once ```threshold``` writes are buffered.
+    /// A threshold of 0 is treated as 1.
+    pub fn new(
+        tree: &'a mut SparseMerkleTree<P>,
+        secret: &'a Secret,
+        threshold: usize,
+    ) -> BufferedWriter<'a, P> {
+        let threshold = threshold.max(1);
+        BufferedWriter {
+            tree,
+            secret,
+            buffer: Vec::with_capacity(threshold),
+            threshold,
+        }
+    }
+
+    /// Returns the number of writes buffered before a flush.
+    pub fn get_threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// Returns the number of writes currently buffered.
+    pub fn get_buffered_num(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Buffer the write of the input value to the leaf at the input index,
+    /// and flush the buffered writes if the threshold is reached.
+    /// Of multiple writes to the same leaf, the last one wins.
+    ///
+    /// Panics if the height of the input index doesn't match with that of the tree.
+    pub fn write(&mut self, key: TreeIndex, value: P) {
+        if key.get_height() != self.tree.height {
+            panic!("{}", TreeError::HeightNotMatch)
+        }
+        self.buffer.push((key, value));
+        if self.buffer.len() >= self.threshold {
+            self.flush();
+        }
+    }
+
+    /// Write the buffered writes into the tree as a sorted batch.
+    pub fn flush(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        let list = std::mem::replace(&mut self.buffer, Vec::with_capacity(self.threshold));
+        // Keeping the last write never fails.
+        let list = SparseMerkleTree::resolve_duplicates(list, &DuplicatePolicy::KeepLast).unwrap();
+        self.tree.update_sorted_batch(list, self.secret);
+    }
+}
+
+impl<'a, P: Clone + Default + Mergeable + Paddable + ProofExtractable> Drop
+    for BufferedWriter<'a, P>
+where
+    <P as ProofExtractable>::ProofNode: Clone + Default + Eq + Mergeable + Serializable,
+{
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// An iterator going through the nodes of a tree in the BFS order,
+/// yielding the tree index of each node together with the node,
+/// returned by [bfs_iter](struct.SparseMerkleTree.html#method.bfs_iter).
+pub struct BfsIter<'a, P> {
+    tree: &'a SparseMerkleTree<P>,
+    // The index-reference pairs of nodes to be visited.
+    queue: VecDeque<(TreeIndex, usize)>,
+}
+
+impl<'a, P> BfsIter<'a, P> {
+    // Returns an iterator starting from the root of the input tree.
+    fn new(tree: &'a SparseMerkleTree<P>) -> BfsIter<'a, P> {
+        let mut queue = VecDeque::new();
+        queue.push_back((TreeIndex::zero(0), tree.root));
+        BfsIter { tree, queue }
+    }
+}
+
+impl<'a, P> Iterator for BfsIter<'a, P> {
+    type Item = (TreeIndex, &'a TreeNode<P>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (idx, link) = self.queue.pop_front()?;
+        let node = &self.tree.nodes[link];
+        if let Some(x) = node.lch {
+            self.queue.push_back((idx.get_lch_index(), x));
+        }
+        if let Some(x) = node.rch {
+            self.queue.push_back((idx.get_rch_index(), x));
+        }
+        Some((idx, node))
+    }
+}
+
+impl<'a, P> IntoIterator for &'a SparseMerkleTree<P> {
+    type Item = (TreeIndex, &'a TreeNode<P>);
+    type IntoIter = BfsIter<'a, P>;
+
+    fn into_iter(self) -> BfsIter<'a, P> {
+        BfsIter::new(self)
+    }
+}
+
+/// An iterator going through the nodes of a tree in the DFS pre-order,
+/// yielding the tree index of each node together with the node,
+/// returned by [dfs_preorder_iter](struct.SparseMerkleTree.html#method.dfs_preorder_iter).
+pub struct DfsPreorderIter<'a, P> {
+    tree: &'a SparseMerkleTree<P>,
+    // The index-reference pairs of nodes to be visited, the next one on the top.
+    stack: Vec<(TreeIndex, usize)>,
+}
+
+impl<'a, P> Iterator for DfsPreorderIter<'a, P> {
+    type Item = (TreeIndex, &'a TreeNode<P>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (idx, link) = self.stack.pop()?;
+        let node = &self.tree.nodes[link];
+        // The left child is visited first.
+        if let Some(x) = node.rch {
+            self.stack.push((idx.get_rch_index(), x));
+        }
+        if let Some(x) = node.lch {
+            self.stack.push((idx.get_lch_index(), x));
+        }
+        Some((idx, node))
+    }
+}
+
+/// An iterator going through the leaves of a tree from the leftmost one to the rightmost one,
+/// yielding the tree index of each leaf together with the node,
+/// returned by [leaves_in_order_iter](struct.SparseMerkleTree.html#method.leaves_in_order_iter).
+pub struct LeavesIter<'a, P> {
+    inner: DfsPreorderIter<'a, P>,
+}
+
+impl<'a, P> Iterator for LeavesIter<'a, P> {
+    type Item = (TreeIndex, &'a TreeNode<P>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner
+            .by_ref()
+            .find(|(_, node)| node.node_type == NodeType::Leaf)
+    }
+}
+
+/// A cursor over the leaves of a tree in the order of index, which can seek to any index
+/// and move forward or backward from there, yielding the tree index of each leaf together with the node,
+/// returned by [leaf_cursor_at](struct.SparseMerkleTree.html#method.leaf_cursor_at),
+/// e.g., for resumable exports or merge-joins with an external sorted dataset.
+///
+/// The cursor is positioned between two leaves and finds the adjacent leaf from the root on each move,
+/// so it takes time linear in the height of the tree per leaf and keeps no state other than the position.
+/// Going forward as an iterator, the cursor yields the leaf after the position and moves past it,
+/// and [prev](#method.prev) yields the leaf before the position and moves before it.
+pub struct LeafCursor<'a, P> {
+    tree: &'a SparseMerkleTree<P>,
+    // The index right after the position, or None if the position is after all indexes.
+    position: Option<TreeIndex>,
+}
+
+impl<'a, P> LeafCursor<'a, P> {
+    /// Move the cursor to the position right before the input index.
+    ///
+    /// Panics if the height of the input index doesn't match with that of the tree.
+    pub fn seek(&mut self, idx: &TreeIndex) {
+        if idx.get_height() != self.tree.height {
+            panic!("{}", TreeError::HeightNotMatch);
+        }
+        self.position = Some(*idx);
+    }
+
+    /// Returns the index right after the position of the cursor,
+    /// or None if the cursor is after all indexes, e.g., after yielding the rightmost possible leaf.
+    pub fn get_position(&self) -> Option<TreeIndex> {
+        self.position
+    }
+
+    /// Returns the leaf after the position without moving the cursor.
+    pub fn peek_next(&self) -> Option<(TreeIndex, &'a TreeNode<P>)> {
+        let (idx, link) = self.find_next()?;
+        Some((idx, &self.tree.nodes[link]))
+    }
+
+    /// Returns the leaf before the position without moving the cursor.
+    pub fn peek_prev(&self) -> Option<(TreeIndex, &'a TreeNode<P>)> {
+        let (idx, link) = self.find_prev()?;
+        Some((idx, &self.tree.nodes[link]))
+    }
+
+    /// Returns the leaf before the position and moves the cursor right before it,
+    /// or returns None and keeps the position if there isn't any.
+    pub fn prev(&mut self) -> Option<(TreeIndex, &'a TreeNode<P>)> {
+        let (idx, link) = self.find_prev()?;
+        self.position = Some(idx);
+        Some((idx, &self.tree.nodes[link]))
+    }
+
+    // Returns the index and the reference of the first leaf at or after the position.
+    fn find_next(&self) -> Option<(TreeIndex, usize)> {
+        let target = self.position?;
+        // The roots of the right subtrees off the path to the target, all after the target, the closest one last.
+        let mut candidates = Vec::new();
+        let mut current = Some((TreeIndex::zero(0), self.tree.root));
+        while let Some((idx, link)) = current {
+            let node = &self.tree.nodes[link];
+            if node.node_type == NodeType::Leaf && idx == target {
+                return Some((idx, link));
+            }
+            if node.node_type != NodeType::Internal || idx.get_height() == target.get_height() {
+                break;
+            }
+            current = if target.get_bit(idx.get_height()) == 0 {
+                if let Some(x) = node.rch {
+                    candidates.push((idx.get_rch_index(), x));
+                }
+                node.lch.map(|x| (idx.get_lch_index(), x))
+            } else {
+                node.rch.map(|x| (idx.get_rch_index(), x))
+            };
+        }
+        candidates
+            .iter()
+            .rev()
+            .find_map(|&(idx, link)| self.find_edge_in(idx, link, false))
+    }
+
+    // Returns the index and the reference of the last leaf before the position.
+    fn find_prev(&self) -> Option<(TreeIndex, usize)> {
+        let target = match self.position {
+            Some(x) => x,
+            None => return self.find_edge_in(TreeIndex::zero(0), self.tree.root, true),
+        };
+        // The roots of the left subtrees off the path to the target, all before the target, the closest one last.
+        let mut candidates = Vec::new();
+        let mut current = Some((TreeIndex::zero(0), self.tree.root));
+        while let Some((idx, link)) = current {
+            let node = &self.tree.nodes[link];
+            if node.node_type != NodeType::Internal || idx.get_height() == target.get_height() {
+                break;
+            }
+            current = if target.get_bit(idx.get_height()) == 1 {
+                if let Some(x) = node.lch {
+                    candidates.push((idx.get_lch_index(), x));
+                }
+                node.rch.map(|x| (idx.get_rch_index(), x))
+            } else {
+                node.lch.map(|x| (idx.get_lch_index(), x))
+            };
+        }
+        candidates
+            .iter()
+            .rev()
+            .find_map(|&(idx, link)| self.find_edge_in(idx, link, true))
+    }
+
+    // Returns the index and the reference of the first leaf in the subtree of the input node,
+    // or the last leaf if ```last``` is true.
+    fn find_edge_in(&self, idx: TreeIndex, link: usize, last: bool) -> Option<(TreeIndex, usize)> {
+        // The child on the side of the edge is on the top of the stack.
+        let mut stack = vec![(idx, link)];
+        while let Some((idx, link)) = stack.pop() {
+            let node = &self.tree.nodes[link];
+            if node.node_type == NodeType::Leaf {
+                return Some((idx, link));
+            }
+            let lch = node.lch.map(|x| (idx.get_lch_index(), x));
+            let rch = node.rch.map(|x| (idx.get_rch_index(), x));
+            let (first, second) = if last { (lch, rch) } else { (rch, lch) };
+            stack.extend(first);
+            stack.extend(second);
+        }
+        None
+    }
+}
+
+impl<'a, P> Iterator for LeafCursor<'a, P> {
+    type Item = (TreeIndex, &'a TreeNode<P>);
+
+    /// Returns the leaf after the position and moves the cursor right after it,
+    /// or returns None and keeps the position if there isn't any.
+    fn next(&mut self) -> Option<Self::Item> {
+        let (idx, link) = self.find_next()?;
+        self.position = idx.get_right_index();
+        Some((idx, &self.tree.nodes[link]))
+    }
+}
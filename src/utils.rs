@@ -7,9 +7,13 @@
 
 use std::collections::BTreeSet;
 use std::collections::HashSet;
+use std::convert::TryFrom;
 use std::fmt::Debug;
+use std::io::{self, ErrorKind, Read, Write};
 use std::mem;
 
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
 use crate::pad_secret::Secret;
 use crate::{
     error::DecodingError,
@@ -52,7 +56,7 @@ impl Serializable for Nil {
         Vec::new()
     }
     fn deserialize_as_a_unit(_bytes: &[u8], _begin: &mut usize) -> Result<Nil, DecodingError> {
-        Ok(Nil::default())
+        Ok(Nil)
     }
 }
 
@@ -68,16 +72,19 @@ impl ProofExtractable for Nil {
 
 /// Converts the provided `num` into the specified number of bytes in little-endian byte order.
 ///
+/// All counts, lengths and heights in the encoding formats are encoded by this function,
+/// so that the formats don't depend on the width of ```usize``` of the platform.
+///
 /// Panics if the specified number of bytes is not sufficient to encode `num`.
-pub fn usize_to_bytes(num: usize, byte_num: usize) -> Vec<u8> {
+pub fn u64_to_bytes(num: u64, byte_num: usize) -> Vec<u8> {
     let mut vec: Vec<u8> = Vec::new();
     let mut tmp = num;
     while tmp > 0 {
-        vec.push((tmp & u8::MAX as usize) as u8);
+        vec.push((tmp & u8::MAX as u64) as u8);
         tmp >>= BYTE_SIZE;
     }
     if vec.len() > byte_num {
-        panic!("Error when encoding usize to bytes: number of bytes exceeds the input limit.");
+        panic!("Error when encoding u64 to bytes: number of bytes exceeds the input limit.");
     } else {
         vec.resize(byte_num, 0u8)
     }
@@ -85,13 +92,16 @@ pub fn usize_to_bytes(num: usize, byte_num: usize) -> Vec<u8> {
 }
 
 /// Reads `byte_num` bytes from `bytes` slice starting at `begin` index and interprets them
-/// as a usize in little-ending byte order.
-pub fn bytes_to_usize(
+/// as a u64 in little-endian byte order.
+///
+/// If `byte_num` exceeds 8,
+/// return [DecodingError::TooManyEncodedBytes](../error/enum.DecodingError.html#variant.TooManyEncodedBytes).
+pub fn bytes_to_u64(
     bytes: &[u8],
     byte_num: usize,
     begin: &mut usize,
-) -> Result<usize, DecodingError> {
-    if byte_num > mem::size_of::<usize>() {
+) -> Result<u64, DecodingError> {
+    if byte_num > mem::size_of::<u64>() {
         return Err(DecodingError::TooManyEncodedBytes);
     }
 
@@ -99,41 +109,190 @@ pub fn bytes_to_usize(
         return Err(DecodingError::BytesNotEnough);
     }
 
-    let mut num = 0usize;
+    let mut num = 0u64;
     for i in (*begin..*begin + byte_num).rev() {
         num <<= BYTE_SIZE;
-        num += bytes[i] as usize;
+        num += bytes[i] as u64;
     }
     *begin += byte_num;
     Ok(num)
 }
 
+/// Reads a count encoded by [u64_to_bytes] as [bytes_to_u64] does, and converts it to a usize.
+///
+/// If the count doesn't fit in a usize of the platform, e.g., on 32-bit targets,
+/// return [DecodingError::ValueDecodingError](../error/enum.DecodingError.html#variant.ValueDecodingError).
+pub fn bytes_to_count(
+    bytes: &[u8],
+    byte_num: usize,
+    begin: &mut usize,
+) -> Result<usize, DecodingError> {
+    let num = bytes_to_u64(bytes, byte_num, begin)?;
+    usize::try_from(num).map_err(|_| DecodingError::ValueDecodingError {
+        msg: format!("The count {} exceeds the maximum usize", num),
+    })
+}
+
+/// Converts the provided `num` into the specified number of bytes in little-endian byte order,
+/// the same as [u64_to_bytes].
+///
+/// Panics if the specified number of bytes is not sufficient to encode `num`.
+pub fn usize_to_bytes(num: usize, byte_num: usize) -> Vec<u8> {
+    u64_to_bytes(num as u64, byte_num)
+}
+
+/// Reads `byte_num` bytes from `bytes` slice starting at `begin` index and interprets them
+/// as a usize in little-endian byte order, the same as [bytes_to_count].
+pub fn bytes_to_usize(
+    bytes: &[u8],
+    byte_num: usize,
+    begin: &mut usize,
+) -> Result<usize, DecodingError> {
+    bytes_to_count(bytes, byte_num, begin)
+}
+
+/// Fills the buffer with the bytes read from the reader.
+///
+/// Return false if the reader ends before the first byte, and
+/// [DecodingError::BytesNotEnough](../error/enum.DecodingError.html#variant.BytesNotEnough)
+/// if it ends after some bytes but before the buffer is full.
+/// If the reader fails otherwise, return
+/// [DecodingError::IoError](../error/enum.DecodingError.html#variant.IoError).
+pub(crate) fn read_bytes_or_end<R: Read + ?Sized>(
+    r: &mut R,
+    buf: &mut [u8],
+) -> Result<bool, DecodingError> {
+    let mut filled = 0usize;
+    while filled < buf.len() {
+        match r.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(DecodingError::BytesNotEnough),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(DecodingError::IoError { msg: e.to_string() }),
+        }
+    }
+    Ok(true)
+}
+
+/// Fills the buffer with the bytes read from the reader,
+/// returning [DecodingError::BytesNotEnough](../error/enum.DecodingError.html#variant.BytesNotEnough)
+/// if the reader ends before the buffer is full.
+pub(crate) fn read_bytes<R: Read + ?Sized>(r: &mut R, buf: &mut [u8]) -> Result<(), DecodingError> {
+    if read_bytes_or_end(r, buf)? || buf.is_empty() {
+        Ok(())
+    } else {
+        Err(DecodingError::BytesNotEnough)
+    }
+}
+
+/// The number of bytes for encoding the length of a unit written by [write_unit].
+pub const UNIT_LEN_BYTE_NUM: usize = 4;
+
+/// Writes the input encoding of one unit into the writer, prefixed with its length: ```unit_len || unit```,
+/// so that [read_unit] reads exactly the bytes of the unit.
+///
+/// If the unit is longer than ```u32::MAX``` bytes, return an I/O error of kind ```InvalidInput```.
+/// If the writer fails, return the I/O error.
+pub fn write_unit<W: Write + ?Sized>(w: &mut W, unit: &[u8]) -> io::Result<()> {
+    if u32::try_from(unit.len()).is_err() {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            "The unit is too long to be written.",
+        ));
+    }
+    w.write_all(&u64_to_bytes(unit.len() as u64, UNIT_LEN_BYTE_NUM))?;
+    w.write_all(unit)
+}
+
+/// Reads the bytes of one unit written by [write_unit] from the reader, ```unit_len || unit```,
+/// and decodes them with the input decoding function,
+/// which decodes a unit from the beginning of the input bytes as ```deserialize_as_a_unit``` does.
+///
+/// The length isn't trusted for allocation: the buffer only grows with the bytes read.
+///
+/// If the reader ends before the unit is complete, return
+/// [DecodingError::BytesNotEnough](../error/enum.DecodingError.html#variant.BytesNotEnough).
+/// If the decoding doesn't consume exactly the bytes of the unit, return
+/// [DecodingError::TooManyEncodedBytes](../error/enum.DecodingError.html#variant.TooManyEncodedBytes)
+/// or the decoding error.
+/// If the reader fails otherwise, return
+/// [DecodingError::IoError](../error/enum.DecodingError.html#variant.IoError).
+pub fn read_unit<R, T, F>(r: &mut R, decode: F) -> Result<T, DecodingError>
+where
+    R: Read + ?Sized,
+    F: Fn(&[u8], &mut usize) -> Result<T, DecodingError>,
+{
+    let mut len = [0u8; UNIT_LEN_BYTE_NUM];
+    read_bytes(r, &mut len)?;
+    let len = bytes_to_count(&len, UNIT_LEN_BYTE_NUM, &mut 0)?;
+
+    let mut bytes: Vec<u8> = Vec::new();
+    r.take(len as u64)
+        .read_to_end(&mut bytes)
+        .map_err(|e| DecodingError::IoError { msg: e.to_string() })?;
+    if bytes.len() < len {
+        return Err(DecodingError::BytesNotEnough);
+    }
+    let mut begin = 0usize;
+    let unit = decode(&bytes, &mut begin)?;
+    if begin != len {
+        return Err(DecodingError::TooManyEncodedBytes);
+    }
+    Ok(unit)
+}
+
 /// Generates a set of random pairs of tree indexes and values. The function intended for use
 /// in testing and benchmarking code.
 pub fn generate_sorted_index_value_pairs<V: Default + Clone + Rand>(
     height: usize,
     leaf_num: usize,
 ) -> Vec<(TreeIndex, V)> {
-    // TODO: check that leaf_num <= 2^height
+    generate_sorted_index_value_pairs_with(height, leaf_num, &mut rand::thread_rng())
+}
 
-    let mut list: Vec<(TreeIndex, V)> = Vec::new();
-    let mut set: BTreeSet<TreeIndex> = BTreeSet::new();
-    for _i in 0..leaf_num {
-        loop {
-            let mut idx = TreeIndex::zero(height);
-            idx.randomize();
-            if !set.contains(&idx) {
-                set.insert(idx);
-                break;
-            }
-        }
-    }
-    let mut value = V::default();
-    for idx in set {
-        value.randomize();
-        list.push((idx, value.clone()));
-    }
-    list
+/// Generates a set of random pairs of tree indexes and values from the input seed,
+/// so that the same seed always gives the same pairs, e.g., to replay a failing test.
+pub fn generate_sorted_index_value_pairs_from_seed<V: Default + Clone + Rand>(
+    height: usize,
+    leaf_num: usize,
+    seed: u64,
+) -> Vec<(TreeIndex, V)> {
+    generate_sorted_index_value_pairs_with(height, leaf_num, &mut StdRng::seed_from_u64(seed))
+}
+
+/// Encode the input bytes as a lowercase hex string.
+#[cfg(feature = "hex")]
+pub fn bytes_to_hex(bytes: &[u8]) -> String {
+    hex::encode(bytes)
+}
+
+/// Decode the input hex string as bytes.
+///
+/// If the input is not a valid hex string,
+/// return [DecodingError::TextDecodingError](../error/enum.DecodingError.html#variant.TextDecodingError).
+#[cfg(feature = "hex")]
+pub fn hex_to_bytes(text: &str) -> Result<Vec<u8>, DecodingError> {
+    hex::decode(text).map_err(|e| DecodingError::TextDecodingError { msg: e.to_string() })
+}
+
+/// Encode the input bytes as a standard base64 string with padding.
+#[cfg(feature = "base64")]
+pub fn bytes_to_base64(bytes: &[u8]) -> String {
+    use base64::Engine;
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+/// Decode the input standard base64 string with padding as bytes.
+///
+/// If the input is not a valid base64 string,
+/// return [DecodingError::TextDecodingError](../error/enum.DecodingError.html#variant.TextDecodingError).
+#[cfg(feature = "base64")]
+pub fn base64_to_bytes(text: &str) -> Result<Vec<u8>, DecodingError> {
+    use base64::Engine;
+    base64::engine::general_purpose::STANDARD
+        .decode(text)
+        .map_err(|e| DecodingError::TextDecodingError { msg: e.to_string() })
 }
 
 /// Convert a u64 to TreeIndex.
@@ -178,17 +337,16 @@ pub fn print_output<P: Clone + Default + Mergeable + Paddable + ProofExtractable
     let mut leaves = Set::new();
     let mut paddings = Set::new();
     let mut internals = Set::new();
-    let nodes = tree.get_index_node_pairs();
-    for (key, node) in nodes.iter() {
+    for (key, node) in tree {
         match node.get_node_type() {
             NodeType::Leaf => {
-                leaves.insert(*key);
+                leaves.insert(key);
             }
             NodeType::Padding => {
-                paddings.insert(*key);
+                paddings.insert(key);
             }
             NodeType::Internal => {
-                internals.insert(*key);
+                internals.insert(key);
             }
         }
     }
@@ -237,6 +395,33 @@ pub fn print_output<P: Clone + Default + Mergeable + Paddable + ProofExtractable
 // HELPER FUNCTIONS
 // ================================================================================================
 
+fn generate_sorted_index_value_pairs_with<V: Default + Clone + Rand, R: Rng + ?Sized>(
+    height: usize,
+    leaf_num: usize,
+    rng: &mut R,
+) -> Vec<(TreeIndex, V)> {
+    // TODO: check that leaf_num <= 2^height
+
+    let mut list: Vec<(TreeIndex, V)> = Vec::new();
+    let mut set: BTreeSet<TreeIndex> = BTreeSet::new();
+    for _i in 0..leaf_num {
+        loop {
+            let mut idx = TreeIndex::zero(height);
+            idx.randomize_with(rng);
+            if !set.contains(&idx) {
+                set.insert(idx);
+                break;
+            }
+        }
+    }
+    let mut value = V::default();
+    for idx in set {
+        value.randomize_with(rng);
+        list.push((idx, value.clone()));
+    }
+    list
+}
+
 fn print_node(spaces: usize, idx: &TreeIndex, leaves: &Set, paddings: &Set, internals: &Set) {
     if leaves.contains(idx) {
         print!("{:>1$}", "*", spaces);